@@ -0,0 +1,98 @@
+//! What a function accepts, in the form both the `Interpreter` and the `Vm`
+//! line a call's arguments up with, and the type checker checks calls
+//! against when it knows the function.
+
+use crate::parser::parameter::Parameter;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Arity {
+    /// The name of each parameter, `None` for the patterns.
+    pub names: Vec<Option<String>>,
+    /// How many parameters must be passed. The ones after them have
+    /// defaults, apart from a rest parameter.
+    pub required: usize,
+    /// Whether the last parameter is `...rest`.
+    pub rest: bool,
+}
+
+/// A call's arguments lined up with the parameters.
+#[derive(Debug)]
+pub struct Arguments<T> {
+    /// The argument for each parameter before the rest parameter, or `None`
+    /// when it was left out for its default.
+    pub slots: Vec<Option<T>>,
+    /// The positional arguments left over for the rest parameter.
+    pub rest: Vec<T>,
+}
+
+impl Arity {
+    pub fn new(parameters: &[Parameter]) -> Self {
+        Arity {
+            names: parameters
+                .iter()
+                .map(|parameter| parameter.name().map(|name| name.lexeme.clone()))
+                .collect(),
+            required: parameters
+                .iter()
+                .take_while(|parameter| parameter.default.is_none() && !parameter.rest)
+                .count(),
+            rest: parameters.last().is_some_and(|parameter| parameter.rest),
+        }
+    }
+
+    /// The parameters that take a single argument, leaving out the rest.
+    pub fn fixed(&self) -> usize {
+        self.names.len() - usize::from(self.rest)
+    }
+
+    /// Lines up `arguments`, whose last `names.len()` were passed by those
+    /// names, with the parameters.
+    pub fn arrange<T>(
+        &self,
+        mut arguments: Vec<T>,
+        names: &[String],
+    ) -> Result<Arguments<T>, String> {
+        let named = arguments.split_off(arguments.len() - names.len());
+        let given = arguments.len() + named.len();
+        let fixed = self.fixed();
+
+        if arguments.len() > fixed && !self.rest {
+            return Err(self.mismatch(given));
+        }
+        let rest = arguments.split_off(arguments.len().min(fixed));
+
+        let mut slots: Vec<Option<T>> = arguments.into_iter().map(Some).collect();
+        slots.resize_with(fixed, || None);
+
+        for (name, value) in names.iter().zip(named) {
+            let index = self.names[..fixed]
+                .iter()
+                .position(|parameter| parameter.as_ref() == Some(name))
+                .ok_or_else(|| format!("No parameter named {}", name))?;
+            if slots[index].is_some() {
+                return Err(format!("Argument {} was passed twice", name));
+            }
+            slots[index] = Some(value);
+        }
+
+        if let Some(index) = slots[..self.required].iter().position(Option::is_none) {
+            return Err(match (&self.names[index], names.is_empty()) {
+                (Some(name), false) => format!("Missing argument {}", name),
+                _ => self.mismatch(given),
+            });
+        }
+
+        Ok(Arguments { slots, rest })
+    }
+
+    fn mismatch(&self, given: usize) -> String {
+        let fixed = self.fixed();
+        let expected = match (self.rest, self.required == fixed) {
+            (true, _) => format!("at least {}", self.required),
+            (false, true) => self.required.to_string(),
+            (false, false) => format!("{} to {}", self.required, fixed),
+        };
+
+        format!("Expected {} arguments but got {}", expected, given)
+    }
+}
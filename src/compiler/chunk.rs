@@ -0,0 +1,252 @@
+use crate::{
+    arity::Arity,
+    constant::Constant,
+    diagnostic::{Diagnostic, Span},
+    shape::Shape,
+};
+
+/// A single VM instruction. Operands are indices into the chunk's constant
+/// pool, frame-relative stack slots, upvalue indices or absolute jump targets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instruction {
+    Constant(usize),
+    Nil,
+    True,
+    False,
+    Pop,
+    /// Pushes copies of the top `count` values, keeping their order.
+    Duplicate(usize),
+
+    /// Pushes the placeholder a block-local variable holds until it is
+    /// declared. Operand is the constant holding the variable's name.
+    Uninitialized(usize),
+    /// Pops the value a declaration gives the local in `slot`.
+    DefineLocal(usize),
+    GetLocal(usize),
+    SetLocal(usize),
+    GetUpvalue(usize),
+    SetUpvalue(usize),
+    /// Operand is the constant holding the variable's name.
+    GetGlobal(usize),
+    SetGlobal(usize),
+    DefineGlobal(usize),
+
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    FloorDivide,
+    Power,
+    BitAnd,
+    BitOr,
+    BitXor,
+    ShiftLeft,
+    ShiftRight,
+    Negate,
+    Not,
+    BitNot,
+
+    Print,
+
+    Jump(usize),
+    /// Conditional jumps leave the condition on the stack.
+    JumpIfFalse(usize),
+    JumpIfTrue(usize),
+    JumpIfNotTrue(usize),
+
+    /// Operand is the argument count; the callee sits below the arguments.
+    Call(usize),
+    /// Like `Call`, but the last arguments were passed by the names in the
+    /// chunk's `keywords[names]`.
+    CallNamed {
+        count: usize,
+        names: usize,
+    },
+    /// Operand is an index into the program's function table.
+    Closure(usize),
+    CloseUpvalue,
+    /// Drops every value above the frame-relative `height`, closing any that
+    /// were captured. Used by `break` and `continue` to leave a loop body.
+    Unwind(usize),
+    Return,
+
+    /// Operand is the element count.
+    List(usize),
+    /// Operand is the field count; keys and values are pushed in pairs.
+    Record(usize),
+    Index,
+    SetIndex,
+    /// Pops `count` values and pushes their printed forms joined into one
+    /// string.
+    Concat(usize),
+    /// Operand is the constant holding the field name.
+    GetField(usize),
+    SetField(usize),
+
+    /// Checks that the value on top of the stack is an `Iter`, which stays
+    /// there for `ForIter`.
+    IterStart,
+    /// Pushes the next value of the iterator held in `slot`, or jumps to
+    /// `exit` once it is exhausted.
+    ForIter {
+        slot: usize,
+        exit: usize,
+    },
+    /// Closes the iterator in `slot` when a `for` loop stops, unless it is
+    /// held elsewhere, and leaves nil in its place.
+    CloseIter(usize),
+    /// Pops a value and hands it to whoever is iterating over the running
+    /// generator, suspending its frame. Closing the generator carries on at
+    /// the operand instead of the next instruction.
+    Yield(usize),
+
+    /// Installs a handler that catches errors until the matching `TryEnd`
+    /// by unwinding to the current height, pushing the error and jumping to
+    /// the operand.
+    TryStart(usize),
+    TryEnd,
+    /// Pops an error or a message and raises it.
+    Throw,
+    /// `?`: unwraps the `ok` or `some` on top of the stack and jumps to
+    /// `exit`, or else moves the `err` or `none` down to `height`, dropping
+    /// the values between, for the `Return` that follows.
+    Propagate {
+        height: usize,
+        exit: usize,
+    },
+    /// Pops a value and matches it against the chunk's `shape`, pushing the
+    /// values it binds, or jumps to `fail` if it doesn't match.
+    Match {
+        shape: usize,
+        fail: usize,
+    },
+    /// Pops the value no arm of a `match` accepted and raises an error.
+    NoMatch,
+    /// Pops a value and pushes the values the chunk's `shape` binds from it,
+    /// or raises an error if it doesn't fit.
+    Destructure(usize),
+    /// Jumps to `skip` unless the call left out the argument for
+    /// `parameter`, whose default the code in between computes.
+    Default {
+        parameter: usize,
+        skip: usize,
+    },
+}
+
+/// Compiled code for one function, with the source span of every instruction
+/// so runtime errors can point back at the program.
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+    pub code: Vec<Instruction>,
+    pub constants: Vec<Constant>,
+    /// The patterns of the chunk's `match` arms and destructuring.
+    pub shapes: Vec<Shape>,
+    /// The names passed by each `CallNamed`.
+    pub keywords: Vec<Vec<String>>,
+    pub spans: Vec<Span>,
+    /// The span of the list, record or function that an index, field or
+    /// call instruction works on, by instruction index. Errors about that
+    /// value point here, and errors about the index or key at the
+    /// instruction's own span.
+    pub targets: Vec<(usize, Span)>,
+}
+
+impl Chunk {
+    pub fn write(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.code.push(instruction);
+        self.spans.push(span);
+        self.code.len() - 1
+    }
+
+    pub fn add_constant(&mut self, constant: Constant) -> usize {
+        if let Some(index) = self.constants.iter().position(|c| *c == constant) {
+            return index;
+        }
+
+        self.constants.push(constant);
+        self.constants.len() - 1
+    }
+
+    pub fn add_shape(&mut self, shape: Shape) -> usize {
+        self.shapes.push(shape);
+        self.shapes.len() - 1
+    }
+
+    pub fn add_keywords(&mut self, names: Vec<String>) -> usize {
+        if let Some(index) = self.keywords.iter().position(|k| *k == names) {
+            return index;
+        }
+
+        self.keywords.push(names);
+        self.keywords.len() - 1
+    }
+
+    /// Writes an instruction that works on the value at `target`.
+    pub fn write_targeted(&mut self, instruction: Instruction, span: Span, target: Span) -> usize {
+        let index = self.write(instruction, span);
+        self.targets.push((index, target));
+        index
+    }
+
+    pub fn error_at(&self, ip: usize, message: impl Into<String>) -> Diagnostic {
+        let diagnostic = Diagnostic::error(message);
+        match self.spans.get(ip) {
+            Some(span) => diagnostic.at(*span),
+            None => diagnostic,
+        }
+    }
+
+    /// An error about the value the instruction at `ip` works on.
+    pub fn target_error_at(&self, ip: usize, message: impl Into<String>) -> Diagnostic {
+        match self.targets.iter().find(|(index, _)| *index == ip) {
+            Some((_, span)) => Diagnostic::error(message).at(*span),
+            None => self.error_at(ip, message),
+        }
+    }
+}
+
+/// How a closure gets hold of a captured variable when it is created: either
+/// a stack slot of the enclosing frame or one of the enclosing closure's own
+/// upvalues.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capture {
+    pub is_local: bool,
+    pub index: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct CompiledFunction {
+    pub name: String,
+    /// The parameters a call's arguments are lined up with, each taking the
+    /// frame slot after the callee in order.
+    pub arity: Arity,
+    /// Calling a generator returns an iterator instead of running the body.
+    pub generator: bool,
+    pub chunk: Chunk,
+    pub captures: Vec<Capture>,
+}
+
+impl CompiledFunction {
+    pub fn new(name: String, arity: Arity) -> Self {
+        CompiledFunction {
+            name,
+            arity,
+            generator: false,
+            chunk: Chunk::default(),
+            captures: vec![],
+        }
+    }
+}
+
+/// The output of the compiler. `functions[0]` is the top level of the script.
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub functions: Vec<CompiledFunction>,
+}
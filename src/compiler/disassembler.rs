@@ -0,0 +1,151 @@
+use std::fmt::Write;
+
+use crate::constant::Constant;
+
+use super::chunk::{CompiledFunction, Instruction, Program};
+
+/// Renders every function in `program` as a readable instruction listing,
+/// with the source line of each instruction (`|` when it repeats the line
+/// above), e.g.
+///
+/// ```text
+/// == fib (arity 1) ==
+/// 0000    2 GetLocal          1
+/// 0001    | Constant          0    '2'
+/// 0002    | Less
+/// 0003    | JumpIfNotTrue     -> 0009
+/// ```
+pub fn disassemble(program: &Program) -> String {
+    let mut output = String::new();
+
+    for (index, function) in program.functions.iter().enumerate() {
+        if index > 0 {
+            output.push('\n');
+        }
+        disassemble_function(&mut output, program, function);
+    }
+
+    output
+}
+
+fn disassemble_function(output: &mut String, program: &Program, function: &CompiledFunction) {
+    let chunk = &function.chunk;
+
+    let kind = if function.generator {
+        ", generator"
+    } else {
+        ""
+    };
+    let _ = writeln!(
+        output,
+        "== {} (arity {}{}) ==",
+        function.name,
+        function.arity.names.len(),
+        kind
+    );
+    for (index, capture) in function.captures.iter().enumerate() {
+        let source = if capture.is_local { "local" } else { "upvalue" };
+        let _ = writeln!(output, "upvalue {} <- {} {}", index, source, capture.index);
+    }
+
+    let constant = |index: usize| match &chunk.constants[index] {
+        Constant::Int(number) => format!("{:<4} '{}'", index, number),
+        Constant::Float(number) => format!("{:<4} '{}'", index, number),
+        Constant::BigInt(number) => format!("{:<4} '{}'", index, number),
+        Constant::Decimal(number) => format!("{:<4} '{}d'", index, number),
+        Constant::String(string) => format!("{:<4} '{}'", index, string),
+    };
+
+    for (offset, instruction) in chunk.code.iter().enumerate() {
+        let line = chunk.spans[offset].line;
+        let line = if offset > 0 && chunk.spans[offset - 1].line == line {
+            "   |".to_string()
+        } else {
+            format!("{:4}", line)
+        };
+
+        let (name, operand) = match *instruction {
+            Instruction::Constant(index) => ("Constant", constant(index)),
+            Instruction::Uninitialized(name) => ("Uninitialized", constant(name)),
+            Instruction::GetLocal(slot) => ("GetLocal", slot.to_string()),
+            Instruction::SetLocal(slot) => ("SetLocal", slot.to_string()),
+            Instruction::DefineLocal(slot) => ("DefineLocal", slot.to_string()),
+            Instruction::CloseIter(slot) => ("CloseIter", slot.to_string()),
+            Instruction::GetUpvalue(index) => ("GetUpvalue", index.to_string()),
+            Instruction::SetUpvalue(index) => ("SetUpvalue", index.to_string()),
+            Instruction::GetGlobal(name) => ("GetGlobal", constant(name)),
+            Instruction::SetGlobal(name) => ("SetGlobal", constant(name)),
+            Instruction::DefineGlobal(name) => ("DefineGlobal", constant(name)),
+            Instruction::Jump(target) => ("Jump", format!("-> {:04}", target)),
+            Instruction::JumpIfFalse(target) => ("JumpIfFalse", format!("-> {:04}", target)),
+            Instruction::JumpIfTrue(target) => ("JumpIfTrue", format!("-> {:04}", target)),
+            Instruction::JumpIfNotTrue(target) => ("JumpIfNotTrue", format!("-> {:04}", target)),
+            Instruction::TryStart(target) => ("TryStart", format!("-> {:04}", target)),
+            Instruction::Yield(close) => ("Yield", format!("-> {:04}", close)),
+            Instruction::Call(count) => ("Call", count.to_string()),
+            Instruction::CallNamed { count, names } => (
+                "CallNamed",
+                format!("{:<4} {}", count, chunk.keywords[names].join(", ")),
+            ),
+            Instruction::Default { parameter, skip } => {
+                ("Default", format!("{:<4} -> {:04}", parameter, skip))
+            }
+            Instruction::Unwind(height) => ("Unwind", height.to_string()),
+            Instruction::Destructure(shape) => ("Destructure", shape.to_string()),
+            Instruction::Duplicate(count) => ("Duplicate", count.to_string()),
+            Instruction::Concat(count) => ("Concat", count.to_string()),
+            Instruction::Closure(index) => (
+                "Closure",
+                format!("{:<4} <fn {}>", index, program.functions[index].name),
+            ),
+            Instruction::List(count) => ("List", count.to_string()),
+            Instruction::Record(count) => ("Record", count.to_string()),
+            Instruction::GetField(key) => ("GetField", constant(key)),
+            Instruction::SetField(key) => ("SetField", constant(key)),
+            Instruction::Propagate { height, exit } => {
+                ("Propagate", format!("{:<4} -> {:04}", height, exit))
+            }
+            Instruction::Match { shape, fail } => ("Match", format!("{:<4} -> {:04}", shape, fail)),
+            Instruction::ForIter { slot, exit } => {
+                ("ForIter", format!("{:<4} -> {:04}", slot, exit))
+            }
+            Instruction::Nil => ("Nil", String::new()),
+            Instruction::True => ("True", String::new()),
+            Instruction::False => ("False", String::new()),
+            Instruction::Pop => ("Pop", String::new()),
+            Instruction::Equal => ("Equal", String::new()),
+            Instruction::NotEqual => ("NotEqual", String::new()),
+            Instruction::Greater => ("Greater", String::new()),
+            Instruction::GreaterEqual => ("GreaterEqual", String::new()),
+            Instruction::Less => ("Less", String::new()),
+            Instruction::LessEqual => ("LessEqual", String::new()),
+            Instruction::Add => ("Add", String::new()),
+            Instruction::Subtract => ("Subtract", String::new()),
+            Instruction::Multiply => ("Multiply", String::new()),
+            Instruction::Divide => ("Divide", String::new()),
+            Instruction::Negate => ("Negate", String::new()),
+            Instruction::Not => ("Not", String::new()),
+            Instruction::Modulo => ("Modulo", String::new()),
+            Instruction::FloorDivide => ("FloorDivide", String::new()),
+            Instruction::Power => ("Power", String::new()),
+            Instruction::BitAnd => ("BitAnd", String::new()),
+            Instruction::BitOr => ("BitOr", String::new()),
+            Instruction::BitXor => ("BitXor", String::new()),
+            Instruction::ShiftLeft => ("ShiftLeft", String::new()),
+            Instruction::ShiftRight => ("ShiftRight", String::new()),
+            Instruction::BitNot => ("BitNot", String::new()),
+            Instruction::Print => ("Print", String::new()),
+            Instruction::CloseUpvalue => ("CloseUpvalue", String::new()),
+            Instruction::Return => ("Return", String::new()),
+            Instruction::Index => ("Index", String::new()),
+            Instruction::SetIndex => ("SetIndex", String::new()),
+            Instruction::IterStart => ("IterStart", String::new()),
+            Instruction::TryEnd => ("TryEnd", String::new()),
+            Instruction::Throw => ("Throw", String::new()),
+            Instruction::NoMatch => ("NoMatch", String::new()),
+        };
+
+        let text = format!("{:04} {} {:<17} {}", offset, line, name, operand);
+        let _ = writeln!(output, "{}", text.trim_end());
+    }
+}
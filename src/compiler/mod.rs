@@ -0,0 +1,1291 @@
+use crate::{
+    arity::Arity,
+    constant::Constant,
+    diagnostic::{Diagnostic, Span},
+    parser::{
+        accept::Accept,
+        expression::{
+            And, Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor, IfExpression,
+            Index, IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment,
+            Match, Or, Power, Primary, Propagate, Record, Term, Unary,
+        },
+        parameter::Parameter,
+        pattern::Pattern,
+        statement::{
+            Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+            PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement,
+            With, Yield,
+        },
+    },
+    shape::Shape,
+    token::{Token, TokenType},
+    visitors::{resolver::Resolution, Visitor},
+};
+
+pub mod chunk;
+pub mod disassembler;
+pub mod module;
+pub mod vm;
+
+use chunk::{Capture, CompiledFunction, Instruction, Program};
+
+struct FunctionState {
+    index: usize,
+    /// Number of stack slots in use by the frame: the callee, reserved
+    /// locals, hidden loop and `match` state, and the operands waiting on
+    /// the expression being compiled.
+    height: usize,
+    /// The loops being compiled in this function, innermost last.
+    loops: Vec<Loop>,
+    /// The `try` bodies and catch clauses being compiled in this function,
+    /// innermost last.
+    tries: Vec<TryBlock>,
+}
+
+/// Where `break` and `continue` inside a loop go.
+struct Loop {
+    label: Option<String>,
+    /// The frame height the loop's body starts at, which both unwind to.
+    height: usize,
+    /// Where `continue` jumps to.
+    start: usize,
+    /// `break` jumps, patched to the loop's exit once it is known.
+    breaks: Vec<usize>,
+    /// How many `try` statements the loop is inside.
+    tries: usize,
+}
+
+/// Code protected by a handler, which `break`, `continue` and `return` must
+/// remove on the way out, running its cleanup.
+struct TryBlock {
+    cleanup: Cleanup,
+    /// How many scopes and loops the `try` statement is inside, which is
+    /// what its finally clause was resolved against.
+    scopes: usize,
+    loops: usize,
+}
+
+/// What leaving a `TryBlock` runs besides removing its handler.
+enum Cleanup {
+    /// A `try` statement's finally clause, if it has one.
+    Finally(Option<Statement>),
+    /// Closing a `for` loop's iterator, held in the slot.
+    CloseIter(usize),
+}
+
+/// Runtime counterpart of one of the resolver's scopes: the frame slots that
+/// hold its variables, in the resolver's slot order.
+struct Scope {
+    level: usize,
+    base: usize,
+    captured: Vec<bool>,
+}
+
+enum Access {
+    Local(usize),
+    Upvalue(usize),
+    Global(usize),
+}
+
+/// Lowers a resolved program to bytecode for the `vm`. Variable slots come
+/// from the resolver, so the resolver must have run over the statements
+/// first. Every block reserves stack space for its declarations up front and
+/// declares its functions before running anything else, exactly like the
+/// tree-walking `Interpreter`.
+pub struct Compiler {
+    functions: Vec<CompiledFunction>,
+    states: Vec<FunctionState>,
+    scopes: Vec<Scope>,
+}
+
+impl Compiler {
+    pub fn new() -> Self {
+        Compiler {
+            functions: vec![],
+            states: vec![],
+            scopes: vec![],
+        }
+    }
+
+    pub fn compile(&mut self, statements: &[Statement]) -> Result<Program, Diagnostic> {
+        self.functions = vec![CompiledFunction::new(
+            "script".to_string(),
+            Arity::default(),
+        )];
+        self.states = vec![FunctionState {
+            index: 0,
+            height: 1,
+            loops: vec![],
+            tries: vec![],
+        }];
+        self.scopes.clear();
+
+        for statement in statements {
+            statement.accept(self)?;
+        }
+
+        let span = statements
+            .last()
+            .map(|statement| statement.span())
+            .unwrap_or_default();
+        self.emit(Instruction::Nil, span);
+        self.emit(Instruction::Return, span);
+
+        Ok(Program {
+            functions: std::mem::take(&mut self.functions),
+        })
+    }
+
+    fn level(&self) -> usize {
+        self.states.len() - 1
+    }
+
+    fn state(&mut self) -> &mut FunctionState {
+        self.states.last_mut().expect("No function being compiled")
+    }
+
+    fn function(&mut self) -> &mut CompiledFunction {
+        let index = self
+            .states
+            .last()
+            .expect("No function being compiled")
+            .index;
+        &mut self.functions[index]
+    }
+
+    fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.function().chunk.write(instruction, span)
+    }
+
+    fn emit_targeted(&mut self, instruction: Instruction, span: Span, target: Span) -> usize {
+        self.function()
+            .chunk
+            .write_targeted(instruction, span, target)
+    }
+
+    fn constant(&mut self, constant: Constant) -> usize {
+        self.function().chunk.add_constant(constant)
+    }
+
+    fn name(&mut self, identifier: &Token) -> usize {
+        self.constant(Constant::String(identifier.lexeme.clone()))
+    }
+
+    /// Points the jump at `at` to the next instruction to be emitted.
+    fn patch_jump(&mut self, at: usize) {
+        let chunk = &mut self.function().chunk;
+        let target = chunk.code.len();
+        chunk.code[at] = match chunk.code[at] {
+            Instruction::Jump(_) => Instruction::Jump(target),
+            Instruction::JumpIfFalse(_) => Instruction::JumpIfFalse(target),
+            Instruction::JumpIfTrue(_) => Instruction::JumpIfTrue(target),
+            Instruction::JumpIfNotTrue(_) => Instruction::JumpIfNotTrue(target),
+            Instruction::ForIter { slot, .. } => Instruction::ForIter { slot, exit: target },
+            Instruction::TryStart(_) => Instruction::TryStart(target),
+            Instruction::Yield(_) => Instruction::Yield(target),
+            Instruction::Propagate { height, .. } => Instruction::Propagate {
+                height,
+                exit: target,
+            },
+            Instruction::Match { shape, .. } => Instruction::Match {
+                shape,
+                fail: target,
+            },
+            Instruction::Default { parameter, .. } => Instruction::Default {
+                parameter,
+                skip: target,
+            },
+            instruction => instruction,
+        };
+    }
+
+    fn begin_loop(&mut self, label: &Option<Token>, start: usize) {
+        let state = self.state();
+        let height = state.height;
+        let tries = state.tries.len();
+        state.loops.push(Loop {
+            label: label.as_ref().map(|label| label.lexeme.clone()),
+            height,
+            start,
+            breaks: vec![],
+            tries,
+        });
+    }
+
+    /// Returns the `break` jumps of the loop, for the caller to patch.
+    fn end_loop(&mut self) -> Vec<usize> {
+        let state = self.state();
+        state.loops.pop().expect("No loop to end").breaks
+    }
+
+    /// Drops the locals declared inside the targeted loop's body and jumps
+    /// out of it, to its start for `continue` or its exit for `break`.
+    fn loop_control(
+        &mut self,
+        label: &Option<Token>,
+        is_break: bool,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        let state = self.states.last().expect("No function being compiled");
+        let target = state.loops.iter().rposition(|target| match label {
+            Some(label) => target.label.as_ref() == Some(&label.lexeme),
+            None => true,
+        });
+        let target = match target {
+            Some(target) => target,
+            None => return Err(Diagnostic::error("Loop control outside of a loop").at(span)),
+        };
+
+        let (height, start, tries) = (
+            state.loops[target].height,
+            state.loops[target].start,
+            state.loops[target].tries,
+        );
+        self.leave_tries(tries, 0, span)?;
+        if self.state().height > height {
+            self.emit(Instruction::Unwind(height), span);
+        }
+
+        if is_break {
+            let jump = self.emit(Instruction::Jump(0), span);
+            self.state().loops[target].breaks.push(jump);
+        } else {
+            self.emit(Instruction::Jump(start), span);
+        }
+
+        Ok(())
+    }
+
+    /// Marks the start of code protected by a handler.
+    fn begin_try(&mut self, cleanup: Cleanup) {
+        let scopes = self.scopes.len();
+        let state = self.state();
+        let loops = state.loops.len();
+        state.tries.push(TryBlock {
+            cleanup,
+            scopes,
+            loops,
+        });
+    }
+
+    /// Removes the handlers of the `try` statements and `for` loops from
+    /// `from` on, innermost first, for a jump out of them, and runs their
+    /// cleanup in place. `extra` values, such as a return value, sit above the frame
+    /// meanwhile.
+    fn leave_tries(&mut self, from: usize, extra: usize, span: Span) -> Result<(), Diagnostic> {
+        for index in (from..self.state().tries.len()).rev() {
+            self.emit(Instruction::TryEnd, span);
+
+            let try_block = &self.state().tries[index];
+            let (finally, scopes, loops) = match &try_block.cleanup {
+                Cleanup::Finally(Some(finally)) => {
+                    (finally.clone(), try_block.scopes, try_block.loops)
+                }
+                Cleanup::Finally(None) => continue,
+                Cleanup::CloseIter(slot) => {
+                    let slot = *slot;
+                    self.emit(Instruction::CloseIter(slot), span);
+                    continue;
+                }
+            };
+
+            // Compile the clause as if it were where the `try` statement is,
+            // so its variables and any `break` in it resolve from there.
+            let scopes = self.scopes.split_off(scopes);
+            let state = self.state();
+            let loops = state.loops.split_off(loops);
+            let tries = state.tries.split_off(index);
+            state.height += extra;
+
+            let result = finally.accept(self);
+
+            let state = self.state();
+            state.height -= extra;
+            state.tries.extend(tries);
+            state.loops.extend(loops);
+            self.scopes.extend(scopes);
+            result?;
+        }
+
+        Ok(())
+    }
+
+    /// Opens a scope over the `size` values on top of the frame.
+    fn begin_scope(&mut self, size: usize) {
+        let level = self.level();
+        let state = self.state();
+        let base = state.height;
+        state.height += size;
+
+        self.scopes.push(Scope {
+            level,
+            base,
+            captured: vec![false; size],
+        });
+    }
+
+    fn end_scope(&mut self, span: Span) {
+        let scope = self.scopes.pop().expect("No scope to end");
+        self.state().height -= scope.captured.len();
+
+        for captured in scope.captured.into_iter().rev() {
+            if captured {
+                self.emit(Instruction::CloseUpvalue, span);
+            } else {
+                self.emit(Instruction::Pop, span);
+            }
+        }
+    }
+
+    fn access(&mut self, identifier: &Token, resolution: Resolution) -> Result<Access, Diagnostic> {
+        let (depth, slot) = match resolution {
+            Resolution::Global => return Ok(Access::Global(self.name(identifier))),
+            Resolution::Local { depth, slot } => (depth, slot),
+        };
+
+        let level = self.level();
+        let scope = match self
+            .scopes
+            .len()
+            .checked_sub(depth + 1)
+            .and_then(|index| self.scopes.get_mut(index))
+        {
+            Some(scope) => scope,
+            None => {
+                return Err(
+                    Diagnostic::error(format!("Unresolved variable {}", identifier.lexeme))
+                        .at(identifier.span()),
+                )
+            }
+        };
+
+        let stack_slot = scope.base + slot;
+        if scope.level == level {
+            return Ok(Access::Local(stack_slot));
+        }
+
+        if let Some(captured) = scope.captured.get_mut(slot) {
+            *captured = true;
+        }
+        let target = scope.level;
+
+        Ok(Access::Upvalue(self.capture(level, target, stack_slot)))
+    }
+
+    /// Makes the variable in `stack_slot` of the function at `target` level
+    /// available to the function at `level`, threading it through every
+    /// function in between. Returns the upvalue index at `level`.
+    fn capture(&mut self, level: usize, target: usize, stack_slot: usize) -> usize {
+        let capture = if target == level - 1 {
+            Capture {
+                is_local: true,
+                index: stack_slot,
+            }
+        } else {
+            Capture {
+                is_local: false,
+                index: self.capture(level - 1, target, stack_slot),
+            }
+        };
+
+        let function = &mut self.functions[self.states[level].index];
+        if let Some(index) = function.captures.iter().position(|c| *c == capture) {
+            return index;
+        }
+
+        function.captures.push(capture);
+        function.captures.len() - 1
+    }
+
+    fn get_variable(
+        &mut self,
+        identifier: &Token,
+        resolution: Resolution,
+    ) -> Result<(), Diagnostic> {
+        let instruction = match self.access(identifier, resolution)? {
+            Access::Local(slot) => Instruction::GetLocal(slot),
+            Access::Upvalue(index) => Instruction::GetUpvalue(index),
+            Access::Global(name) => Instruction::GetGlobal(name),
+        };
+        self.emit(instruction, identifier.span());
+
+        Ok(())
+    }
+
+    fn set_variable(
+        &mut self,
+        identifier: &Token,
+        resolution: Resolution,
+    ) -> Result<(), Diagnostic> {
+        let instruction = match self.access(identifier, resolution)? {
+            Access::Local(slot) => Instruction::SetLocal(slot),
+            Access::Upvalue(index) => Instruction::SetUpvalue(index),
+            Access::Global(name) => Instruction::SetGlobal(name),
+        };
+        self.emit(instruction, identifier.span());
+
+        Ok(())
+    }
+
+    /// Stores the value on top of the stack in a newly declared variable and
+    /// pops it.
+    fn declare_variable(&mut self, identifier: &Token, resolution: Resolution, span: Span) {
+        match resolution {
+            Resolution::Global => {
+                let name = self.name(identifier);
+                self.emit(Instruction::DefineGlobal(name), span);
+            }
+            Resolution::Local { slot, .. } => {
+                let base = self.scopes.last().map(|scope| scope.base).unwrap_or(0);
+                self.emit(Instruction::DefineLocal(base + slot), span);
+            }
+        }
+    }
+
+    /// Compiles a function body into a new `CompiledFunction` and leaves a
+    /// closure over it on the stack.
+    fn closure(
+        &mut self,
+        name: String,
+        parameters: &[Parameter],
+        generator: bool,
+        body: &Statement,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        let mut function = CompiledFunction::new(name, Arity::new(parameters));
+        function.generator = generator;
+        self.functions.push(function);
+        let index = self.functions.len() - 1;
+        self.states.push(FunctionState {
+            index,
+            height: 1,
+            loops: vec![],
+            tries: vec![],
+        });
+        self.begin_scope(parameters.len());
+
+        let result = self
+            .parameters(parameters, span)
+            .and_then(|_| body.accept(self));
+        self.emit(Instruction::Nil, span);
+        self.emit(Instruction::Return, span);
+
+        // The frame is discarded by `Return`, so the parameters aren't popped.
+        self.scopes.pop();
+        self.states.pop();
+        result?;
+
+        self.emit(Instruction::Closure(index), span);
+
+        Ok(())
+    }
+
+    /// The prologue that finishes binding the parameters once the `Vm` has
+    /// put the arguments in their slots: it fills in the defaults of the ones
+    /// left out and destructures the arguments passed for pattern parameters.
+    /// The names those bind join the function's scope after the arguments,
+    /// in the resolver's order.
+    fn parameters(&mut self, parameters: &[Parameter], span: Span) -> Result<(), Diagnostic> {
+        let base = self.scopes.last().map(|scope| scope.base).unwrap_or(0);
+        for (index, parameter) in parameters.iter().enumerate() {
+            let pattern = &parameter.pattern;
+
+            if let Some(default) = &parameter.default {
+                let skip = self.emit(
+                    Instruction::Default {
+                        parameter: index,
+                        skip: 0,
+                    },
+                    pattern.span(),
+                );
+                default.accept(self)?;
+                self.emit(Instruction::SetLocal(base + index), default.span());
+                self.emit(Instruction::Pop, default.span());
+                self.patch_jump(skip);
+            }
+
+            if let Pattern::Binding(_) = pattern {
+                continue;
+            }
+
+            self.emit(Instruction::GetLocal(base + index), pattern.span());
+            self.destructure(pattern)?;
+
+            let bindings = pattern.bindings().len();
+            self.state().height += bindings;
+            if let Some(scope) = self.scopes.last_mut() {
+                scope.captured.extend(vec![false; bindings]);
+            }
+        }
+
+        // Calling a generator runs it this far and pauses it, so the
+        // arguments are checked at the call.
+        if self.function().generator {
+            self.emit(Instruction::Nil, span);
+            self.emit_yield(span)?;
+        }
+
+        Ok(())
+    }
+
+    /// Emits a `Yield` and, skipped over on resume, the code that closing
+    /// the generator runs instead: a `return` from where it paused.
+    fn emit_yield(&mut self, span: Span) -> Result<(), Diagnostic> {
+        let close = self.emit(Instruction::Yield(0), span);
+        let resume = self.emit(Instruction::Jump(0), span);
+        self.patch_jump(close);
+        self.emit(Instruction::Nil, span);
+        self.leave_tries(0, 1, span)?;
+        self.emit(Instruction::Return, span);
+        self.patch_jump(resume);
+
+        Ok(())
+    }
+
+    /// Replaces the value on top of the stack with the values `pattern`
+    /// binds from it.
+    fn destructure(&mut self, pattern: &Pattern) -> Result<(), Diagnostic> {
+        let shape = Shape::new(pattern)?;
+        let shape = self.function().chunk.add_shape(shape);
+        self.emit(Instruction::Destructure(shape), pattern.span());
+
+        Ok(())
+    }
+
+    /// Emits the arithmetic of a compound assignment, combining the target's
+    /// current value with the assigned value on top of it.
+    fn compound(&mut self, operator: &Option<Token>, span: Span) -> Result<(), Diagnostic> {
+        let instruction = match operator.as_ref().map(|operator| &operator.token_type) {
+            None => return Ok(()),
+            Some(TokenType::Plus) => Instruction::Add,
+            Some(TokenType::Minus) => Instruction::Subtract,
+            Some(TokenType::Star) => Instruction::Multiply,
+            Some(TokenType::Slash) => Instruction::Divide,
+            Some(TokenType::Percent) => Instruction::Modulo,
+            Some(_) => return Err(Diagnostic::error("Unexpected token type").at(span)),
+        };
+        self.emit(instruction, span);
+
+        Ok(())
+    }
+
+    fn binary(
+        &mut self,
+        left: &Expression,
+        right: &Expression,
+        instruction: Instruction,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        left.accept(self)?;
+        self.operand(right, 1)?;
+        self.emit(instruction, span);
+
+        Ok(())
+    }
+
+    /// Compiles `expression` while `held` values pushed before it wait on the
+    /// stack, so any locals it opens sit above them.
+    fn operand(&mut self, expression: &Expression, held: usize) -> Result<(), Diagnostic> {
+        self.state().height += held;
+        let result = expression.accept(self);
+        self.state().height -= held;
+        result
+    }
+}
+
+impl Visitor for Compiler {
+    type Output = Result<(), Diagnostic>;
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        if assignment.operator.is_some() {
+            self.get_variable(&assignment.identifier, assignment.resolution.get())?;
+        }
+        let held = usize::from(assignment.operator.is_some());
+        self.operand(&assignment.value, held)?;
+        self.compound(&assignment.operator, assignment.span)?;
+        self.set_variable(&assignment.identifier, assignment.resolution.get())
+    }
+
+    fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
+        let instruction = match equality.operator.token_type {
+            TokenType::EqualEqual => Instruction::Equal,
+            TokenType::BangEqual => Instruction::NotEqual,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(equality.span)),
+        };
+
+        self.binary(&equality.left, &equality.right, instruction, equality.span)
+    }
+
+    fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
+        let instruction = match comparison.operator.token_type {
+            TokenType::Greater => Instruction::Greater,
+            TokenType::GreaterEqual => Instruction::GreaterEqual,
+            TokenType::Less => Instruction::Less,
+            TokenType::LessEqual => Instruction::LessEqual,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(comparison.span)),
+        };
+
+        self.binary(
+            &comparison.left,
+            &comparison.right,
+            instruction,
+            comparison.span,
+        )
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Self::Output {
+        let instruction = match term.operator.token_type {
+            TokenType::Plus => Instruction::Add,
+            TokenType::Minus => Instruction::Subtract,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(term.span)),
+        };
+
+        self.binary(&term.left, &term.right, instruction, term.span)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
+        let instruction = match factor.operator.token_type {
+            TokenType::Star => Instruction::Multiply,
+            TokenType::Slash => Instruction::Divide,
+            TokenType::Percent => Instruction::Modulo,
+            TokenType::SlashSlash => Instruction::FloorDivide,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(factor.span)),
+        };
+
+        self.binary(&factor.left, &factor.right, instruction, factor.span)
+    }
+
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        let instruction = match bitwise.operator.token_type {
+            TokenType::Ampersand => Instruction::BitAnd,
+            TokenType::Pipe => Instruction::BitOr,
+            TokenType::Caret => Instruction::BitXor,
+            TokenType::LessLess => Instruction::ShiftLeft,
+            TokenType::GreaterGreater => Instruction::ShiftRight,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(bitwise.span)),
+        };
+
+        self.binary(&bitwise.left, &bitwise.right, instruction, bitwise.span)
+    }
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        self.binary(&power.left, &power.right, Instruction::Power, power.span)
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        let instruction = match unary.operator.token_type {
+            TokenType::Minus => Instruction::Negate,
+            TokenType::Bang => Instruction::Not,
+            TokenType::Tilde => Instruction::BitNot,
+            _ => return Err(Diagnostic::error("Unexpected token type").at(unary.span)),
+        };
+
+        unary.right.accept(self)?;
+        self.emit(instruction, unary.span);
+
+        Ok(())
+    }
+
+    fn visit_primary(&mut self, primary: &Primary) -> Self::Output {
+        let token = &primary.value;
+        let instruction = match token.token_type {
+            TokenType::Int
+            | TokenType::BigInt
+            | TokenType::Float
+            | TokenType::Decimal
+            | TokenType::String => Instruction::Constant(self.constant(Constant::literal(token)?)),
+            TokenType::Boolean if token.lexeme == "true" => Instruction::True,
+            TokenType::Boolean => Instruction::False,
+            TokenType::Nil => Instruction::Nil,
+            TokenType::Identifier => return self.get_variable(token, primary.resolution.get()),
+            _ => return Err(Diagnostic::error("Unexpected token type").at(token.span())),
+        };
+        self.emit(instruction, token.span());
+
+        Ok(())
+    }
+
+    fn visit_expression_statement(
+        &mut self,
+        expression_statement: &ExpressionStatement,
+    ) -> Self::Output {
+        expression_statement.expression.accept(self)?;
+        self.emit(Instruction::Pop, expression_statement.span);
+
+        Ok(())
+    }
+
+    fn visit_print_statement(&mut self, print_statement: &PrintStatement) -> Self::Output {
+        print_statement.expression.accept(self)?;
+        self.emit(Instruction::Print, print_statement.span);
+
+        Ok(())
+    }
+
+    fn visit_variable_declaration(
+        &mut self,
+        variable_declaration: &VariableDeclaration,
+    ) -> Self::Output {
+        match &variable_declaration.expression {
+            Some(expression) => expression.accept(self)?,
+            None => {
+                self.emit(Instruction::Nil, variable_declaration.span);
+            }
+        }
+
+        let pattern = &variable_declaration.pattern;
+        let resolution = variable_declaration.resolution.get();
+        if let Pattern::Binding(identifier) = pattern {
+            self.declare_variable(identifier, resolution, variable_declaration.span);
+            return Ok(());
+        }
+
+        self.destructure(pattern)?;
+        for (index, binding) in pattern.bindings().into_iter().enumerate().rev() {
+            self.declare_variable(binding, resolution.nth(index), variable_declaration.span);
+        }
+
+        Ok(())
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Output {
+        // Hoisted functions can run before the variables they capture are
+        // declared, so each slot starts out holding a placeholder that makes
+        // reading it an error, as it is in the interpreter.
+        let mut names: Vec<Option<&Token>> = vec![];
+        let mut name = |resolution: Resolution, identifier| {
+            if let Resolution::Local { slot, .. } = resolution {
+                if slot >= names.len() {
+                    names.resize(slot + 1, None);
+                }
+                names[slot] = Some(identifier);
+            }
+        };
+        for statement in &block.statements {
+            match statement {
+                Statement::VariableDeclaration(declaration) => {
+                    let resolution = declaration.resolution.get();
+                    for (index, binding) in declaration.pattern.bindings().into_iter().enumerate() {
+                        name(resolution.nth(index), binding);
+                    }
+                }
+                Statement::FunctionDeclaration(declaration) => {
+                    name(declaration.resolution.get(), &declaration.identifier)
+                }
+                _ => {}
+            }
+        }
+
+        let size = names.len();
+        for identifier in names {
+            match identifier {
+                Some(identifier) => {
+                    let name = self.name(identifier);
+                    self.emit(Instruction::Uninitialized(name), block.span);
+                }
+                None => {
+                    self.emit(Instruction::Nil, block.span);
+                }
+            }
+        }
+        self.begin_scope(size);
+
+        let (functions, statements): (Vec<_>, Vec<_>) = block
+            .statements
+            .iter()
+            .partition(|statement| matches!(statement, Statement::FunctionDeclaration(_)));
+
+        for statement in functions.into_iter().chain(statements) {
+            statement.accept(self)?;
+        }
+
+        self.end_scope(block.span);
+
+        Ok(())
+    }
+
+    fn visit_if_statement(&mut self, if_statement: &IfStatement) -> Self::Output {
+        let span = if_statement.span;
+
+        if_statement.condition.accept(self)?;
+        let else_jump = self.emit(Instruction::JumpIfNotTrue(0), span);
+        self.emit(Instruction::Pop, span);
+        if_statement.then_branch.accept(self)?;
+        let end_jump = self.emit(Instruction::Jump(0), span);
+
+        self.patch_jump(else_jump);
+        self.emit(Instruction::Pop, span);
+        if let Some(else_branch) = &if_statement.else_branch {
+            else_branch.accept(self)?;
+        }
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_if_expression(&mut self, if_expression: &IfExpression) -> Self::Output {
+        let span = if_expression.span;
+
+        if_expression.condition.accept(self)?;
+        let else_jump = self.emit(Instruction::JumpIfNotTrue(0), span);
+        self.emit(Instruction::Pop, span);
+        if_expression.then_branch.accept(self)?;
+        let end_jump = self.emit(Instruction::Jump(0), span);
+
+        self.patch_jump(else_jump);
+        self.emit(Instruction::Pop, span);
+        if_expression.else_branch.accept(self)?;
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_and(&mut self, and: &And) -> Self::Output {
+        and.left.accept(self)?;
+        let end_jump = self.emit(Instruction::JumpIfFalse(0), and.span);
+        self.emit(Instruction::Pop, and.span);
+        and.right.accept(self)?;
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_or(&mut self, or: &Or) -> Self::Output {
+        or.left.accept(self)?;
+        let end_jump = self.emit(Instruction::JumpIfTrue(0), or.span);
+        self.emit(Instruction::Pop, or.span);
+        or.right.accept(self)?;
+        self.patch_jump(end_jump);
+
+        Ok(())
+    }
+
+    fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
+        let span = while_statement.span;
+        let start = self.function().chunk.code.len();
+
+        while_statement.condition.accept(self)?;
+        let exit_jump = self.emit(Instruction::JumpIfFalse(0), span);
+        self.emit(Instruction::Pop, span);
+        self.begin_loop(&while_statement.label, start);
+        let result = while_statement.body.accept(self);
+        let breaks = self.end_loop();
+        result?;
+        self.emit(Instruction::Jump(start), span);
+
+        self.patch_jump(exit_jump);
+        self.emit(Instruction::Pop, span);
+        // `break` has already dropped the condition.
+        for jump in breaks {
+            self.patch_jump(jump);
+        }
+
+        Ok(())
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        call.identifier.accept(self)?;
+        for (index, argument) in call.arguments.iter().enumerate() {
+            self.operand(&argument.value, index + 1)?;
+        }
+
+        let count = call.arguments.len();
+        let names = call.names();
+        let instruction = if names.is_empty() {
+            Instruction::Call(count)
+        } else {
+            let names = self.function().chunk.add_keywords(names);
+            Instruction::CallNamed { count, names }
+        };
+        self.emit_targeted(instruction, call.span, call.identifier.span());
+
+        Ok(())
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        function_declaration: &FunctionDeclaration,
+    ) -> Self::Output {
+        let identifier = &function_declaration.identifier;
+        let span = function_declaration.span;
+
+        self.closure(
+            identifier.lexeme.clone(),
+            &function_declaration.parameters,
+            function_declaration.generator,
+            &function_declaration.body,
+            span,
+        )?;
+        self.declare_variable(identifier, function_declaration.resolution.get(), span);
+
+        Ok(())
+    }
+
+    fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
+        if self.level() == 0 {
+            return Err(Diagnostic::error(
+                "Return keyword should not be used outside of a function body",
+            )
+            .at(return_statement.span));
+        }
+
+        match &return_statement.value {
+            Some(value) => value.accept(self)?,
+            None => {
+                self.emit(Instruction::Nil, return_statement.span);
+            }
+        }
+        self.leave_tries(0, 1, return_statement.span)?;
+        self.emit(Instruction::Return, return_statement.span);
+
+        Ok(())
+    }
+
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        if !self.function().generator {
+            return Err(Diagnostic::error(
+                "Can only yield inside a generator function, declared with 'funk*'",
+            )
+            .at(yield_statement.span));
+        }
+
+        yield_statement.value.accept(self)?;
+        self.emit_yield(yield_statement.span)
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        throw_statement.value.accept(self)?;
+        self.emit(Instruction::Throw, throw_statement.span);
+
+        Ok(())
+    }
+
+    /// The finally clause is compiled once for each way out: after the body
+    /// or catch clause finishes, before rethrowing an error neither caught,
+    /// and by `leave_tries` for every jump out of them.
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        let span = try_statement.span;
+        let finally = &try_statement.finally;
+
+        let handler = self.emit(Instruction::TryStart(0), span);
+        self.begin_try(Cleanup::Finally(finally.clone()));
+        let result = try_statement.body.accept(self);
+        self.state().tries.pop();
+        result?;
+        self.emit(Instruction::TryEnd, span);
+        let mut exits = vec![self.emit(Instruction::Jump(0), span)];
+
+        // The error is on top of the stack.
+        self.patch_jump(handler);
+        let mut failed = 1;
+        if let Some(catch) = &try_statement.catch {
+            let handler = finally
+                .as_ref()
+                .map(|_| self.emit(Instruction::TryStart(0), span));
+            if handler.is_some() {
+                self.begin_try(Cleanup::Finally(finally.clone()));
+            }
+
+            self.begin_scope(1);
+            let result = catch.body.accept(self);
+            self.end_scope(span);
+            if handler.is_some() {
+                self.state().tries.pop();
+            }
+            result?;
+
+            if let Some(handler) = handler {
+                self.emit(Instruction::TryEnd, span);
+                exits.push(self.emit(Instruction::Jump(0), span));
+                // Both the caught error and the one the catch clause raised.
+                self.patch_jump(handler);
+                failed = 2;
+            }
+        }
+
+        if let Some(finally) = finally {
+            self.state().height += failed;
+            let result = finally.accept(self);
+            self.state().height -= failed;
+            result?;
+            self.emit(Instruction::Throw, span);
+        }
+
+        for exit in exits {
+            self.patch_jump(exit);
+        }
+        if let Some(finally) = finally {
+            finally.accept(self)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output {
+        self.loop_control(&break_statement.label, true, break_statement.span)
+    }
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output {
+        self.loop_control(&continue_statement.label, false, continue_statement.span)
+    }
+
+    fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
+        with_statement.value.accept(self)?;
+
+        self.begin_scope(1);
+        with_statement.body.accept(self)?;
+        self.end_scope(with_statement.span);
+
+        Ok(())
+    }
+
+    fn visit_list(&mut self, list: &List) -> Self::Output {
+        for (index, value) in list.values.iter().enumerate() {
+            self.operand(value, index)?;
+        }
+        self.emit(Instruction::List(list.values.len()), list.span);
+
+        Ok(())
+    }
+
+    fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
+        let span = for_statement.span;
+
+        for_statement.iter.accept(self)?;
+        self.emit(Instruction::IterStart, for_statement.iter.span());
+        let slot = self.state().height;
+        self.state().height += 1;
+
+        // However the loop is left, its iterator is closed: by the handler
+        // on an error, and like a finally clause on the way out otherwise.
+        let handler = self.emit(Instruction::TryStart(0), span);
+        self.begin_try(Cleanup::CloseIter(slot));
+
+        let start = self.function().chunk.code.len();
+        let exit_jump = self.emit(Instruction::ForIter { slot, exit: 0 }, span);
+        self.begin_loop(&for_statement.label, start);
+        let pattern = &for_statement.pattern;
+        let result = match pattern {
+            Pattern::Binding(_) => Ok(()),
+            _ => self.destructure(pattern),
+        };
+        self.begin_scope(pattern.bindings().len());
+        let result = result.and_then(|_| for_statement.body.accept(self));
+        let breaks = self.end_loop();
+        self.state().tries.pop();
+        result?;
+        self.end_scope(span);
+        self.emit(Instruction::Jump(start), span);
+
+        self.patch_jump(exit_jump);
+        for jump in breaks {
+            self.patch_jump(jump);
+        }
+        self.emit(Instruction::TryEnd, span);
+        self.emit(Instruction::CloseIter(slot), span);
+        let exit = self.emit(Instruction::Jump(0), span);
+
+        // The error is on top of the stack.
+        self.patch_jump(handler);
+        self.emit(Instruction::CloseIter(slot), span);
+        self.emit(Instruction::Throw, span);
+
+        self.patch_jump(exit);
+        self.emit(Instruction::Pop, span);
+        self.state().height -= 1;
+
+        Ok(())
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        index.list.accept(self)?;
+        self.operand(&index.expression, 1)?;
+        self.emit_targeted(
+            Instruction::Index,
+            index.expression.span(),
+            index.list.span(),
+        );
+
+        Ok(())
+    }
+
+    fn visit_record(&mut self, record: &Record) -> Self::Output {
+        for (index, (key, value)) in record.key_values.iter().enumerate() {
+            let key = self.name(key);
+            self.emit(Instruction::Constant(key), record.span);
+            self.operand(value, index * 2 + 1)?;
+        }
+        self.emit(Instruction::Record(record.key_values.len()), record.span);
+
+        Ok(())
+    }
+
+    fn visit_map_index(&mut self, map_index: &MapIndex) -> Self::Output {
+        map_index.map.accept(self)?;
+        let key = self.name(&map_index.key);
+        self.emit_targeted(
+            Instruction::GetField(key),
+            map_index.key.span(),
+            map_index.map.span(),
+        );
+
+        Ok(())
+    }
+
+    fn visit_map_index_assignment(
+        &mut self,
+        map_index_assignment: &MapIndexAssignment,
+    ) -> Self::Output {
+        map_index_assignment.map.accept(self)?;
+        let key = self.name(&map_index_assignment.key);
+        if map_index_assignment.operator.is_some() {
+            self.emit(Instruction::Duplicate(1), map_index_assignment.span);
+            self.emit_targeted(
+                Instruction::GetField(key),
+                map_index_assignment.key.span(),
+                map_index_assignment.map.span(),
+            );
+        }
+        let held = 1 + usize::from(map_index_assignment.operator.is_some());
+        self.operand(&map_index_assignment.value, held)?;
+        self.compound(&map_index_assignment.operator, map_index_assignment.span)?;
+        self.emit_targeted(
+            Instruction::SetField(key),
+            map_index_assignment.key.span(),
+            map_index_assignment.map.span(),
+        );
+
+        Ok(())
+    }
+
+    fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
+        index_assignment.list.accept(self)?;
+        self.operand(&index_assignment.expression, 1)?;
+        if index_assignment.operator.is_some() {
+            self.emit(Instruction::Duplicate(2), index_assignment.span);
+            self.emit_targeted(
+                Instruction::Index,
+                index_assignment.expression.span(),
+                index_assignment.list.span(),
+            );
+        }
+        let held = 2 + usize::from(index_assignment.operator.is_some());
+        self.operand(&index_assignment.value, held)?;
+        self.compound(&index_assignment.operator, index_assignment.span)?;
+        self.emit_targeted(
+            Instruction::SetIndex,
+            index_assignment.expression.span(),
+            index_assignment.list.span(),
+        );
+
+        Ok(())
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        self.closure(
+            "lambda".to_string(),
+            &lambda.parameters,
+            lambda.generator,
+            &lambda.body,
+            lambda.span,
+        )
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        let mut count = 0;
+        for (index, part) in interpolation.parts.iter().enumerate() {
+            if index > 0 {
+                self.operand(&interpolation.expressions[index - 1], count)?;
+                count += 1;
+            }
+            if !part.lexeme.is_empty() {
+                let constant = self.constant(Constant::String(part.lexeme.clone()));
+                self.emit(Instruction::Constant(constant), part.span());
+                count += 1;
+            }
+        }
+        self.emit(Instruction::Concat(count), interpolation.span);
+
+        Ok(())
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        if self.level() == 0 {
+            return Err(
+                Diagnostic::error("Can only use '?' inside a function body").at(propagate.span)
+            );
+        }
+
+        propagate.expression.accept(self)?;
+        let height = self.state().height;
+        let exit = self.emit(Instruction::Propagate { height, exit: 0 }, propagate.span);
+        self.leave_tries(0, 1, propagate.span)?;
+        if self.function().generator {
+            // Nothing receives a generator's return value, so the failure
+            // is thrown to whoever is iterating instead.
+            let message = Constant::String(" propagated out of a generator".to_string());
+            let message = self.constant(message);
+            self.emit(Instruction::Constant(message), propagate.span);
+            self.emit(Instruction::Concat(2), propagate.span);
+            self.emit(Instruction::Throw, propagate.span);
+        } else {
+            self.emit(Instruction::Return, propagate.span);
+        }
+        self.patch_jump(exit);
+
+        Ok(())
+    }
+
+    /// The value sits in a hidden slot while the arms are tried, each one
+    /// opening a scope over the values its pattern binds. The arm that
+    /// matches leaves its result in the hidden slot.
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        let span = match_expression.span;
+
+        match_expression.value.accept(self)?;
+        let hidden = self.state().height;
+        self.state().height += 1;
+
+        let mut exits = vec![];
+        for arm in &match_expression.arms {
+            let shape = Shape::new(&arm.pattern)?;
+            let bindings = arm.pattern.bindings().len();
+
+            self.emit(Instruction::GetLocal(hidden), span);
+            let shape = self.function().chunk.add_shape(shape);
+            let fail = self.emit(Instruction::Match { shape, fail: 0 }, arm.pattern.span());
+            self.begin_scope(bindings);
+
+            let guard = match &arm.guard {
+                Some(guard) => {
+                    guard.accept(self)?;
+                    let jump = self.emit(Instruction::JumpIfNotTrue(0), guard.span());
+                    self.emit(Instruction::Pop, span);
+                    Some(jump)
+                }
+                None => None,
+            };
+
+            arm.body.accept(self)?;
+            self.emit(Instruction::SetLocal(hidden), span);
+            self.emit(Instruction::Pop, span);
+            self.end_scope(span);
+            exits.push(self.emit(Instruction::Jump(0), span));
+
+            if let Some(guard) = guard {
+                self.patch_jump(guard);
+                self.emit(Instruction::Unwind(hidden + 1), span);
+            }
+            self.patch_jump(fail);
+        }
+
+        self.emit(Instruction::GetLocal(hidden), span);
+        self.emit(Instruction::NoMatch, match_expression.value.span());
+        for exit in exits {
+            self.patch_jump(exit);
+        }
+        self.state().height -= 1;
+
+        Ok(())
+    }
+}
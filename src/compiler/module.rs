@@ -0,0 +1,938 @@
+//! The `.jvc` compiled module format. All integers are little endian.
+//!
+//! ```text
+//! module    := magic "JVC\0", version u16, function count u32, function*
+//! function  := name string, arity, generator u8,
+//!              capture count u32, (is_local u8, index u32)*,
+//!              constant count u32, constant*,
+//!              shape count u32, shape*,
+//!              keyword list count u32, (name count u32, string*)*,
+//!              instruction count u32, instruction*,
+//!              span run count u32, (line u32, column u32, instruction count u32)*,
+//!              target count u32, (instruction u32, line u32, column u32)*
+//! arity     := parameter count u32, (has name u8, string?)*,
+//!              required u32, rest u8
+//! constant  := 0 f64 | 1 string | 2 i64 | 3 bigint string | 4 decimal string
+//! shape     := 0 | 1 | 2 | 3 boolean u8 | 4 constant
+//!            | 5 item count u32, shape*, has rest u8, shape?
+//!            | 6 field count u32, (key string, shape)*
+//!            | 7 shape | 8 shape | 9 shape | 10
+//!            | 11 alternative count u32, shape*
+//! string    := byte length u32, utf-8 bytes
+//! instruction := opcode u8, operand u32*
+//! ```
+//!
+//! Spans keep their line and column but not their byte offsets, so errors
+//! raised by a loaded module name the same location as the source would but
+//! can't underline it.
+
+use crate::{
+    arity::Arity,
+    constant::Constant,
+    diagnostic::{Diagnostic, Span},
+    shape::Shape,
+};
+
+use super::chunk::{Capture, Chunk, CompiledFunction, Instruction, Program};
+
+pub const MAGIC: &[u8; 4] = b"JVC\0";
+/// Bumped whenever existing module data would decode differently, so
+/// modules written by an older build are rejected instead of misread.
+pub const VERSION: u16 = 12;
+
+impl Program {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer { bytes: vec![] };
+
+        writer.bytes.extend_from_slice(MAGIC);
+        writer.bytes.extend_from_slice(&VERSION.to_le_bytes());
+        writer.usize(self.functions.len());
+
+        for function in &self.functions {
+            writer.string(&function.name);
+            writer.arity(&function.arity);
+            writer.bytes.push(function.generator as u8);
+
+            writer.usize(function.captures.len());
+            for capture in &function.captures {
+                writer.bytes.push(capture.is_local as u8);
+                writer.usize(capture.index);
+            }
+
+            writer.usize(function.chunk.constants.len());
+            for constant in &function.chunk.constants {
+                writer.constant(constant);
+            }
+
+            writer.shapes(&function.chunk.shapes);
+
+            writer.usize(function.chunk.keywords.len());
+            for names in &function.chunk.keywords {
+                writer.usize(names.len());
+                for name in names {
+                    writer.string(name);
+                }
+            }
+
+            writer.usize(function.chunk.code.len());
+            for instruction in &function.chunk.code {
+                writer.instruction(instruction);
+            }
+
+            let mut runs: Vec<(u32, u32, usize)> = vec![];
+            for span in &function.chunk.spans {
+                match runs.last_mut() {
+                    Some((line, column, count)) if *line == span.line && *column == span.column => {
+                        *count += 1
+                    }
+                    _ => runs.push((span.line, span.column, 1)),
+                }
+            }
+            writer.usize(runs.len());
+            for (line, column, count) in runs {
+                writer.u32(line);
+                writer.u32(column);
+                writer.usize(count);
+            }
+
+            writer.usize(function.chunk.targets.len());
+            for (index, span) in &function.chunk.targets {
+                writer.usize(*index);
+                writer.u32(span.line);
+                writer.u32(span.column);
+            }
+        }
+
+        writer.bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, Diagnostic> {
+        let mut reader = Reader { bytes, position: 0 };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(Diagnostic::error("Not a compiled jive module"));
+        }
+
+        let version = u16::from_le_bytes([reader.u8()?, reader.u8()?]);
+        if version != VERSION {
+            return Err(Diagnostic::error(format!(
+                "Unsupported module version {} (expected {})",
+                version, VERSION
+            )));
+        }
+
+        let mut functions = vec![];
+        for _ in 0..reader.usize()? {
+            let name = reader.string()?;
+            let arity = reader.arity()?;
+            let generator = reader.u8()? != 0;
+
+            let mut captures = vec![];
+            for _ in 0..reader.usize()? {
+                captures.push(Capture {
+                    is_local: reader.u8()? != 0,
+                    index: reader.usize()?,
+                });
+            }
+
+            let mut chunk = Chunk::default();
+            for _ in 0..reader.usize()? {
+                chunk.constants.push(reader.constant()?);
+            }
+
+            chunk.shapes = reader.shapes()?;
+
+            for _ in 0..reader.usize()? {
+                let names = (0..reader.usize()?)
+                    .map(|_| reader.string())
+                    .collect::<Result<_, _>>()?;
+                chunk.keywords.push(names);
+            }
+
+            for _ in 0..reader.usize()? {
+                chunk.code.push(reader.instruction()?);
+            }
+
+            for _ in 0..reader.usize()? {
+                let line = reader.u32()?;
+                let column = reader.u32()?;
+                let count = reader.usize()?;
+                if count > chunk.code.len() - chunk.spans.len() {
+                    return Err(reader.error("Span table does not match code"));
+                }
+                chunk
+                    .spans
+                    .extend(std::iter::repeat_n(Span::new(0, 0, line, column), count));
+            }
+            if chunk.spans.len() != chunk.code.len() {
+                return Err(reader.error("Span table does not match code"));
+            }
+
+            for _ in 0..reader.usize()? {
+                let index = reader.usize()?;
+                if index >= chunk.code.len() {
+                    return Err(reader.error("Span table does not match code"));
+                }
+                let span = Span::new(0, 0, reader.u32()?, reader.u32()?);
+                chunk.targets.push((index, span));
+            }
+
+            let mut function = CompiledFunction::new(name, arity);
+            function.generator = generator;
+            function.captures = captures;
+            function.chunk = chunk;
+            functions.push(function);
+        }
+
+        if functions.is_empty() {
+            return Err(reader.error("Module has no functions"));
+        }
+
+        let program = Program { functions };
+        program
+            .validate()
+            .map_err(|message| reader.error(message))?;
+
+        Ok(program)
+    }
+
+    /// Checks the operands the VM trusts without bounds checks of its own,
+    /// and that every path through each function leaves it the stack values,
+    /// locals, upvalues and handlers its instructions use.
+    fn validate(&self) -> Result<(), String> {
+        let script = &self.functions[0];
+        if !script.arity.names.is_empty() || !script.captures.is_empty() {
+            return Err(format!("{}: script cannot take parameters", script.name));
+        }
+
+        for function in &self.functions {
+            let chunk = &function.chunk;
+            let name = |index: usize| match chunk.constants.get(index) {
+                Some(Constant::String(_)) => Ok(()),
+                _ => Err(format!(
+                    "{}: invalid name constant {}",
+                    function.name, index
+                )),
+            };
+            let target = |target: usize| match target < chunk.code.len() {
+                true => Ok(()),
+                false => Err(format!("{}: jump out of bounds", function.name)),
+            };
+
+            let arity = &function.arity;
+            if arity.required + usize::from(arity.rest) > arity.names.len() {
+                return Err(format!("{}: invalid parameters", function.name));
+            }
+
+            if chunk.code.last() != Some(&Instruction::Return) {
+                return Err(format!("{}: code does not end in a return", function.name));
+            }
+
+            for instruction in &chunk.code {
+                match *instruction {
+                    Instruction::Constant(index) if index >= chunk.constants.len() => {
+                        return Err(format!("{}: invalid constant {}", function.name, index))
+                    }
+                    Instruction::Uninitialized(index)
+                    | Instruction::GetGlobal(index)
+                    | Instruction::SetGlobal(index)
+                    | Instruction::DefineGlobal(index)
+                    | Instruction::GetField(index)
+                    | Instruction::SetField(index) => name(index)?,
+                    Instruction::Jump(to)
+                    | Instruction::JumpIfFalse(to)
+                    | Instruction::JumpIfTrue(to)
+                    | Instruction::JumpIfNotTrue(to)
+                    | Instruction::TryStart(to)
+                    | Instruction::Yield(to)
+                    | Instruction::ForIter { exit: to, .. }
+                    | Instruction::Propagate { exit: to, .. } => target(to)?,
+                    Instruction::Match { shape, fail } => {
+                        if shape >= chunk.shapes.len() {
+                            return Err(format!("{}: invalid shape {}", function.name, shape));
+                        }
+                        target(fail)?
+                    }
+                    Instruction::Destructure(shape) if shape >= chunk.shapes.len() => {
+                        return Err(format!("{}: invalid shape {}", function.name, shape))
+                    }
+                    Instruction::CallNamed { count, names } => match chunk.keywords.get(names) {
+                        Some(names) if names.len() <= count => {}
+                        _ => return Err(format!("{}: invalid keywords {}", function.name, names)),
+                    },
+                    Instruction::Default { parameter, skip } => {
+                        if parameter >= arity.names.len() {
+                            return Err(format!(
+                                "{}: invalid parameter {}",
+                                function.name, parameter
+                            ));
+                        }
+                        target(skip)?
+                    }
+                    Instruction::Closure(index) if index >= self.functions.len() => {
+                        return Err(format!("{}: invalid function {}", function.name, index))
+                    }
+                    _ => {}
+                }
+            }
+
+            self.check_stack(function)?;
+        }
+
+        Ok(())
+    }
+
+    /// Follows every path through `function`, tracking the fewest values
+    /// each instruction can find on the frame and how many handlers are
+    /// installed, which must agree wherever paths meet.
+    fn check_stack(&self, function: &CompiledFunction) -> Result<(), String> {
+        let code = &function.chunk.code;
+        let mut states: Vec<Option<FrameState>> = vec![None; code.len()];
+        states[0] = Some(FrameState {
+            height: 1 + function.arity.names.len(),
+            handlers: 0,
+        });
+        let mut pending = vec![0];
+
+        while let Some(ip) = pending.pop() {
+            let state = states[ip].expect("Pending instructions have a state");
+            let successors = self
+                .successors(function, ip, state)
+                .map_err(|problem| format!("{}: {} at {}", function.name, problem, ip))?;
+
+            for (target, next) in successors {
+                let known = match states.get_mut(target) {
+                    Some(known) => known,
+                    None => return Err(format!("{}: runs past its code", function.name)),
+                };
+                match known {
+                    Some(known) if known.handlers != next.handlers => {
+                        return Err(format!(
+                            "{}: unbalanced handlers at {}",
+                            function.name, target
+                        ))
+                    }
+                    Some(known) if known.height <= next.height => {}
+                    _ => {
+                        *known = Some(next);
+                        pending.push(target);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where execution can go after the instruction at `ip`, and the frame
+    /// it finds there.
+    fn successors(
+        &self,
+        function: &CompiledFunction,
+        ip: usize,
+        state: FrameState,
+    ) -> Result<Vec<(usize, FrameState)>, String> {
+        let chunk = &function.chunk;
+        let FrameState { height, handlers } = state;
+        let next = ip + 1;
+
+        let at = |height: usize| FrameState { height, handlers };
+        let take = |count: usize| {
+            height
+                .checked_sub(count)
+                .ok_or_else(|| "stack underflow".to_string())
+        };
+        let slot = |slot: usize, height: usize| match slot < height {
+            true => Ok(()),
+            false => Err(format!("invalid slot {}", slot)),
+        };
+        let unwind = |to: usize, limit: usize| match to <= limit {
+            true => Ok(()),
+            false => Err(format!("invalid height {}", to)),
+        };
+        let upvalue = |index: usize| match index < function.captures.len() {
+            true => Ok(()),
+            false => Err(format!("invalid upvalue {}", index)),
+        };
+        let bound = |shape: usize| {
+            chunk.shapes[shape]
+                .bound()
+                .ok_or_else(|| format!("alternatives bind in shape {}", shape))
+        };
+
+        Ok(match chunk.code[ip] {
+            Instruction::Constant(_)
+            | Instruction::Nil
+            | Instruction::True
+            | Instruction::False
+            | Instruction::Uninitialized(_)
+            | Instruction::GetGlobal(_) => vec![(next, at(height + 1))],
+            Instruction::GetLocal(index) => {
+                slot(index, height)?;
+                vec![(next, at(height + 1))]
+            }
+            Instruction::SetLocal(index) => {
+                take(1)?;
+                slot(index, height)?;
+                vec![(next, state)]
+            }
+            Instruction::DefineLocal(index) => {
+                let below = take(1)?;
+                slot(index, below)?;
+                vec![(next, at(below))]
+            }
+            Instruction::CloseIter(index) => {
+                slot(index, height)?;
+                vec![(next, state)]
+            }
+            Instruction::GetUpvalue(index) => {
+                upvalue(index)?;
+                vec![(next, at(height + 1))]
+            }
+            Instruction::SetUpvalue(index) => {
+                take(1)?;
+                upvalue(index)?;
+                vec![(next, state)]
+            }
+            Instruction::SetGlobal(_)
+            | Instruction::GetField(_)
+            | Instruction::Negate
+            | Instruction::Not
+            | Instruction::BitNot
+            | Instruction::IterStart => {
+                take(1)?;
+                vec![(next, state)]
+            }
+            Instruction::Pop
+            | Instruction::DefineGlobal(_)
+            | Instruction::Print
+            | Instruction::CloseUpvalue => vec![(next, at(take(1)?))],
+            Instruction::Yield(close) => {
+                let below = take(1)?;
+                vec![(next, at(below)), (close, at(below))]
+            }
+            Instruction::Equal
+            | Instruction::NotEqual
+            | Instruction::Greater
+            | Instruction::GreaterEqual
+            | Instruction::Less
+            | Instruction::LessEqual
+            | Instruction::Add
+            | Instruction::Subtract
+            | Instruction::Multiply
+            | Instruction::Divide
+            | Instruction::Modulo
+            | Instruction::FloorDivide
+            | Instruction::Power
+            | Instruction::BitAnd
+            | Instruction::BitOr
+            | Instruction::BitXor
+            | Instruction::ShiftLeft
+            | Instruction::ShiftRight
+            | Instruction::Index
+            | Instruction::SetField(_) => vec![(next, at(take(2)? + 1))],
+            Instruction::SetIndex => vec![(next, at(take(3)? + 1))],
+            Instruction::Duplicate(count) => {
+                take(count)?;
+                vec![(next, at(height + count))]
+            }
+            Instruction::List(count) | Instruction::Concat(count) => {
+                vec![(next, at(take(count)? + 1))]
+            }
+            Instruction::Record(count) => vec![(next, at(take(count.saturating_mul(2))? + 1))],
+            Instruction::Call(count) | Instruction::CallNamed { count, .. } => {
+                vec![(next, at(take(count.saturating_add(1))? + 1))]
+            }
+            Instruction::Closure(index) => {
+                for capture in &self.functions[index].captures {
+                    match capture.is_local {
+                        true => slot(capture.index, height)?,
+                        false => upvalue(capture.index)?,
+                    }
+                }
+                vec![(next, at(height + 1))]
+            }
+            Instruction::Jump(target) => vec![(target, state)],
+            Instruction::JumpIfFalse(target)
+            | Instruction::JumpIfTrue(target)
+            | Instruction::JumpIfNotTrue(target) => {
+                take(1)?;
+                vec![(next, state), (target, state)]
+            }
+            Instruction::Default { skip, .. } => vec![(next, state), (skip, state)],
+            Instruction::Unwind(to) => {
+                unwind(to, height)?;
+                vec![(next, at(to))]
+            }
+            Instruction::Return => {
+                take(1)?;
+                if handlers > 0 {
+                    return Err("return with a handler installed".to_string());
+                }
+                vec![]
+            }
+            Instruction::Throw | Instruction::NoMatch => {
+                take(1)?;
+                vec![]
+            }
+            Instruction::ForIter { slot: index, exit } => {
+                slot(index, height)?;
+                vec![(next, at(height + 1)), (exit, state)]
+            }
+            Instruction::TryStart(catch) => vec![
+                (
+                    next,
+                    FrameState {
+                        height,
+                        handlers: handlers + 1,
+                    },
+                ),
+                (catch, at(height + 1)),
+            ],
+            Instruction::TryEnd => match handlers.checked_sub(1) {
+                Some(handlers) => vec![(next, FrameState { height, handlers })],
+                None => return Err("no handler to remove".to_string()),
+            },
+            Instruction::Propagate {
+                height: failed,
+                exit,
+            } => {
+                unwind(failed, take(1)?)?;
+                vec![(next, at(failed + 1)), (exit, state)]
+            }
+            Instruction::Match { shape, fail } => {
+                let below = take(1)?;
+                vec![(next, at(below + bound(shape)?)), (fail, at(below))]
+            }
+            Instruction::Destructure(shape) => vec![(next, at(take(1)? + bound(shape)?))],
+        })
+    }
+}
+
+/// What `Program::check_stack` knows about a frame before an instruction.
+#[derive(Debug, Clone, Copy)]
+struct FrameState {
+    /// The fewest values the frame can hold, counting its callee.
+    height: usize,
+    /// How many `try` handlers the frame has installed.
+    handlers: usize,
+}
+
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn usize(&mut self, value: usize) {
+        self.u32(value as u32);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.usize(string.len());
+        self.bytes.extend_from_slice(string.as_bytes());
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match constant {
+            Constant::Float(number) => {
+                self.bytes.push(0);
+                self.bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            Constant::Int(number) => {
+                self.bytes.push(2);
+                self.bytes.extend_from_slice(&number.to_le_bytes());
+            }
+            Constant::String(string) => {
+                self.bytes.push(1);
+                self.string(string);
+            }
+            Constant::BigInt(number) => {
+                self.bytes.push(3);
+                self.string(&number.to_string());
+            }
+            Constant::Decimal(number) => {
+                self.bytes.push(4);
+                self.string(&number.to_string());
+            }
+        }
+    }
+
+    fn arity(&mut self, arity: &Arity) {
+        self.usize(arity.names.len());
+        for name in &arity.names {
+            self.bytes.push(name.is_some() as u8);
+            if let Some(name) = name {
+                self.string(name);
+            }
+        }
+        self.usize(arity.required);
+        self.bytes.push(arity.rest as u8);
+    }
+
+    fn shapes(&mut self, shapes: &[Shape]) {
+        self.usize(shapes.len());
+        for shape in shapes {
+            self.shape(shape);
+        }
+    }
+
+    fn shape(&mut self, shape: &Shape) {
+        match shape {
+            Shape::Any => self.bytes.push(0),
+            Shape::Bind => self.bytes.push(1),
+            Shape::Nil => self.bytes.push(2),
+            Shape::Boolean(value) => {
+                self.bytes.push(3);
+                self.bytes.push(*value as u8);
+            }
+            Shape::Constant(constant) => {
+                self.bytes.push(4);
+                self.constant(constant);
+            }
+            Shape::List { items, rest } => {
+                self.bytes.push(5);
+                self.shapes(items);
+                self.bytes.push(rest.is_some() as u8);
+                if let Some(rest) = rest {
+                    self.shape(rest);
+                }
+            }
+            Shape::Record(fields) => {
+                self.bytes.push(6);
+                self.usize(fields.len());
+                for (key, shape) in fields {
+                    self.string(key);
+                    self.shape(shape);
+                }
+            }
+            Shape::Ok(shape) => {
+                self.bytes.push(7);
+                self.shape(shape);
+            }
+            Shape::Err(shape) => {
+                self.bytes.push(8);
+                self.shape(shape);
+            }
+            Shape::Some(shape) => {
+                self.bytes.push(9);
+                self.shape(shape);
+            }
+            Shape::None => self.bytes.push(10),
+            Shape::Either(alternatives) => {
+                self.bytes.push(11);
+                self.shapes(alternatives);
+            }
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let (opcode, operands): (u8, &[usize]) = match *instruction {
+            Instruction::Constant(index) => (0, &[index]),
+            Instruction::Nil => (1, &[]),
+            Instruction::True => (2, &[]),
+            Instruction::False => (3, &[]),
+            Instruction::Pop => (4, &[]),
+            Instruction::GetLocal(slot) => (5, &[slot]),
+            Instruction::SetLocal(slot) => (6, &[slot]),
+            Instruction::GetUpvalue(index) => (7, &[index]),
+            Instruction::SetUpvalue(index) => (8, &[index]),
+            Instruction::GetGlobal(name) => (9, &[name]),
+            Instruction::SetGlobal(name) => (10, &[name]),
+            Instruction::DefineGlobal(name) => (11, &[name]),
+            Instruction::Equal => (12, &[]),
+            Instruction::NotEqual => (13, &[]),
+            Instruction::Greater => (14, &[]),
+            Instruction::GreaterEqual => (15, &[]),
+            Instruction::Less => (16, &[]),
+            Instruction::LessEqual => (17, &[]),
+            Instruction::Add => (18, &[]),
+            Instruction::Subtract => (19, &[]),
+            Instruction::Multiply => (20, &[]),
+            Instruction::Divide => (21, &[]),
+            Instruction::Negate => (22, &[]),
+            Instruction::Not => (23, &[]),
+            Instruction::Print => (24, &[]),
+            Instruction::Jump(target) => (25, &[target]),
+            Instruction::JumpIfFalse(target) => (26, &[target]),
+            Instruction::JumpIfTrue(target) => (27, &[target]),
+            Instruction::JumpIfNotTrue(target) => (28, &[target]),
+            Instruction::Call(count) => (29, &[count]),
+            Instruction::Closure(index) => (30, &[index]),
+            Instruction::CloseUpvalue => (31, &[]),
+            Instruction::Return => (32, &[]),
+            Instruction::List(count) => (33, &[count]),
+            Instruction::Record(count) => (34, &[count]),
+            Instruction::Index => (35, &[]),
+            Instruction::SetIndex => (36, &[]),
+            Instruction::GetField(key) => (37, &[key]),
+            Instruction::SetField(key) => (38, &[key]),
+            Instruction::IterStart => (39, &[]),
+            Instruction::ForIter { slot, exit } => {
+                self.bytes.push(40);
+                self.usize(slot);
+                self.usize(exit);
+                return;
+            }
+            Instruction::Unwind(height) => (41, &[height]),
+            Instruction::Modulo => (42, &[]),
+            Instruction::FloorDivide => (43, &[]),
+            Instruction::Power => (44, &[]),
+            Instruction::BitAnd => (45, &[]),
+            Instruction::BitOr => (46, &[]),
+            Instruction::BitXor => (47, &[]),
+            Instruction::ShiftLeft => (48, &[]),
+            Instruction::ShiftRight => (49, &[]),
+            Instruction::BitNot => (50, &[]),
+            Instruction::Duplicate(count) => (51, &[count]),
+            Instruction::Concat(count) => (52, &[count]),
+            Instruction::Yield(close) => (53, &[close]),
+            Instruction::TryStart(target) => (54, &[target]),
+            Instruction::TryEnd => (55, &[]),
+            Instruction::Throw => (56, &[]),
+            Instruction::Propagate { height, exit } => {
+                self.bytes.push(57);
+                self.usize(height);
+                self.usize(exit);
+                return;
+            }
+            Instruction::Match { shape, fail } => {
+                self.bytes.push(58);
+                self.usize(shape);
+                self.usize(fail);
+                return;
+            }
+            Instruction::NoMatch => (59, &[]),
+            Instruction::Destructure(shape) => (60, &[shape]),
+            Instruction::CallNamed { count, names } => {
+                self.bytes.push(61);
+                self.usize(count);
+                self.usize(names);
+                return;
+            }
+            Instruction::Default { parameter, skip } => {
+                self.bytes.push(62);
+                self.usize(parameter);
+                self.usize(skip);
+                return;
+            }
+            Instruction::Uninitialized(name) => (63, &[name]),
+            Instruction::DefineLocal(slot) => (64, &[slot]),
+            Instruction::CloseIter(slot) => (65, &[slot]),
+        };
+
+        self.bytes.push(opcode);
+        for operand in operands {
+            self.usize(*operand);
+        }
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(format!(
+            "Corrupt module at byte {}: {}",
+            self.position,
+            message.into()
+        ))
+    }
+
+    fn take(&mut self, count: usize) -> Result<&[u8], Diagnostic> {
+        let end = self.position + count;
+        if end > self.bytes.len() {
+            return Err(self.error("Unexpected end of file"));
+        }
+
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, Diagnostic> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, Diagnostic> {
+        Ok(u32::from_le_bytes(
+            self.take(4)?.try_into().expect("Took 4 bytes"),
+        ))
+    }
+
+    fn usize(&mut self) -> Result<usize, Diagnostic> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn string(&mut self) -> Result<String, Diagnostic> {
+        let length = self.usize()?;
+        let bytes = self.take(length)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| self.error("Invalid utf-8 in string"))
+    }
+
+    fn constant(&mut self) -> Result<Constant, Diagnostic> {
+        Ok(match self.u8()? {
+            0 => Constant::Float(f64::from_le_bytes(
+                self.take(8)?.try_into().expect("Took 8 bytes"),
+            )),
+            1 => Constant::String(self.string()?),
+            2 => Constant::Int(i64::from_le_bytes(
+                self.take(8)?.try_into().expect("Took 8 bytes"),
+            )),
+            3 => Constant::BigInt(
+                self.string()?
+                    .parse()
+                    .map_err(|_| self.error("Invalid bigint constant"))?,
+            ),
+            4 => Constant::Decimal(
+                self.string()?
+                    .parse()
+                    .map_err(|_| self.error("Invalid decimal constant"))?,
+            ),
+            tag => return Err(self.error(format!("Unknown constant tag {}", tag))),
+        })
+    }
+
+    fn arity(&mut self) -> Result<Arity, Diagnostic> {
+        let mut names = vec![];
+        for _ in 0..self.usize()? {
+            names.push(match self.u8()? {
+                0 => None,
+                _ => Some(self.string()?),
+            });
+        }
+
+        Ok(Arity {
+            names,
+            required: self.usize()?,
+            rest: self.u8()? != 0,
+        })
+    }
+
+    fn shapes(&mut self) -> Result<Vec<Shape>, Diagnostic> {
+        (0..self.usize()?).map(|_| self.shape()).collect()
+    }
+
+    fn shape(&mut self) -> Result<Shape, Diagnostic> {
+        Ok(match self.u8()? {
+            0 => Shape::Any,
+            1 => Shape::Bind,
+            2 => Shape::Nil,
+            3 => Shape::Boolean(self.u8()? != 0),
+            4 => Shape::Constant(self.constant()?),
+            5 => Shape::List {
+                items: self.shapes()?,
+                rest: match self.u8()? != 0 {
+                    true => Some(Box::new(self.shape()?)),
+                    false => None,
+                },
+            },
+            6 => {
+                let mut fields = vec![];
+                for _ in 0..self.usize()? {
+                    fields.push((self.string()?, self.shape()?));
+                }
+                Shape::Record(fields)
+            }
+            7 => Shape::Ok(Box::new(self.shape()?)),
+            8 => Shape::Err(Box::new(self.shape()?)),
+            9 => Shape::Some(Box::new(self.shape()?)),
+            10 => Shape::None,
+            11 => Shape::Either(self.shapes()?),
+            tag => return Err(self.error(format!("Unknown shape tag {}", tag))),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, Diagnostic> {
+        Ok(match self.u8()? {
+            0 => Instruction::Constant(self.usize()?),
+            1 => Instruction::Nil,
+            2 => Instruction::True,
+            3 => Instruction::False,
+            4 => Instruction::Pop,
+            5 => Instruction::GetLocal(self.usize()?),
+            6 => Instruction::SetLocal(self.usize()?),
+            7 => Instruction::GetUpvalue(self.usize()?),
+            8 => Instruction::SetUpvalue(self.usize()?),
+            9 => Instruction::GetGlobal(self.usize()?),
+            10 => Instruction::SetGlobal(self.usize()?),
+            11 => Instruction::DefineGlobal(self.usize()?),
+            12 => Instruction::Equal,
+            13 => Instruction::NotEqual,
+            14 => Instruction::Greater,
+            15 => Instruction::GreaterEqual,
+            16 => Instruction::Less,
+            17 => Instruction::LessEqual,
+            18 => Instruction::Add,
+            19 => Instruction::Subtract,
+            20 => Instruction::Multiply,
+            21 => Instruction::Divide,
+            22 => Instruction::Negate,
+            23 => Instruction::Not,
+            24 => Instruction::Print,
+            25 => Instruction::Jump(self.usize()?),
+            26 => Instruction::JumpIfFalse(self.usize()?),
+            27 => Instruction::JumpIfTrue(self.usize()?),
+            28 => Instruction::JumpIfNotTrue(self.usize()?),
+            29 => Instruction::Call(self.usize()?),
+            30 => Instruction::Closure(self.usize()?),
+            31 => Instruction::CloseUpvalue,
+            32 => Instruction::Return,
+            33 => Instruction::List(self.usize()?),
+            34 => Instruction::Record(self.usize()?),
+            35 => Instruction::Index,
+            36 => Instruction::SetIndex,
+            37 => Instruction::GetField(self.usize()?),
+            38 => Instruction::SetField(self.usize()?),
+            39 => Instruction::IterStart,
+            40 => Instruction::ForIter {
+                slot: self.usize()?,
+                exit: self.usize()?,
+            },
+            41 => Instruction::Unwind(self.usize()?),
+            42 => Instruction::Modulo,
+            43 => Instruction::FloorDivide,
+            44 => Instruction::Power,
+            45 => Instruction::BitAnd,
+            46 => Instruction::BitOr,
+            47 => Instruction::BitXor,
+            48 => Instruction::ShiftLeft,
+            49 => Instruction::ShiftRight,
+            50 => Instruction::BitNot,
+            51 => Instruction::Duplicate(self.usize()?),
+            52 => Instruction::Concat(self.usize()?),
+            53 => Instruction::Yield(self.usize()?),
+            54 => Instruction::TryStart(self.usize()?),
+            55 => Instruction::TryEnd,
+            56 => Instruction::Throw,
+            57 => Instruction::Propagate {
+                height: self.usize()?,
+                exit: self.usize()?,
+            },
+            58 => Instruction::Match {
+                shape: self.usize()?,
+                fail: self.usize()?,
+            },
+            59 => Instruction::NoMatch,
+            60 => Instruction::Destructure(self.usize()?),
+            61 => Instruction::CallNamed {
+                count: self.usize()?,
+                names: self.usize()?,
+            },
+            62 => Instruction::Default {
+                parameter: self.usize()?,
+                skip: self.usize()?,
+            },
+            63 => Instruction::Uninitialized(self.usize()?),
+            64 => Instruction::DefineLocal(self.usize()?),
+            65 => Instruction::CloseIter(self.usize()?),
+            opcode => return Err(self.error(format!("Unknown opcode {}", opcode))),
+        })
+    }
+}
@@ -0,0 +1,934 @@
+use std::{cell::RefCell, collections::HashMap, fmt, io, rc::Rc};
+
+use crate::{
+    arity::Arguments,
+    constant::Constant,
+    diagnostic::Diagnostic,
+    shape,
+    visitors::interpreter::{
+        callable::{self, BuiltIn, MAX_CALL_DEPTH},
+        error::{self, ErrorValue},
+        iter::{Caller, Generator, Iter, Suspended},
+        value::Value,
+        Output,
+    },
+};
+
+use super::chunk::{Instruction, Program};
+
+/// A variable captured by a closure. It points into the stack while the
+/// declaring frame is live and is moved into the upvalue once that scope ends.
+#[derive(Debug)]
+pub enum Upvalue {
+    Open(usize),
+    Closed(Value),
+}
+
+pub struct Closure {
+    pub program: Rc<Program>,
+    pub function: usize,
+    pub upvalues: Vec<Rc<RefCell<Upvalue>>>,
+}
+
+// Upvalues may hold the closure itself, so they're left out.
+impl fmt::Debug for Closure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<fn {}>", self.program.functions[self.function].name)
+    }
+}
+
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base: usize,
+    /// The parameters the call left out, which `Default` fills in.
+    missing: Vec<usize>,
+}
+
+/// Where an error raised inside a `try` is caught: the frame to carry on in,
+/// with the stack unwound to `height` and the error pushed, at `catch`.
+struct Handler {
+    /// How many frames there were below the one that installed it.
+    frames: usize,
+    closure: Rc<Closure>,
+    base: usize,
+    height: usize,
+    catch: usize,
+}
+
+/// A generator's frame, lifted off the stack while it is paused.
+pub struct VmGenerator {
+    closure: Rc<Closure>,
+    ip: usize,
+    /// Where to carry on instead of `ip` when the generator is closed.
+    close: usize,
+    /// The frame's slots, from the callee up.
+    stack: Vec<Value>,
+    /// Variables captured from the frame, closed over while it is off the
+    /// stack, with the slot each one is reopened at.
+    upvalues: Vec<(usize, Rc<RefCell<Upvalue>>)>,
+    /// Handlers the frame installed, with heights counted from its base.
+    handlers: Vec<Handler>,
+}
+
+/// Stack-based virtual machine that runs programs produced by the `Compiler`.
+/// Globals persist across calls to `run`.
+pub struct Vm {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: HashMap<String, Value>,
+    open_upvalues: Vec<Rc<RefCell<Upvalue>>>,
+    handlers: Vec<Handler>,
+    output: Output,
+    /// Set by `Yield` for `resume` to pick up.
+    yielded: Option<VmGenerator>,
+    /// How many `dispatch` loops are running, each holding its frame apart
+    /// from `frames`. Iterators that call back into the program nest them.
+    running: usize,
+}
+
+impl Vm {
+    pub fn new() -> Self {
+        let mut globals: HashMap<String, Value> = callable::builtins()
+            .into_iter()
+            .map(|(name, builtin)| (name.to_string(), Value::BuiltIn(Box::new(builtin))))
+            .collect();
+        globals.insert("args".to_string(), callable::script_args(&[]));
+        globals.insert("none".to_string(), Value::Option(None));
+
+        Vm {
+            stack: vec![],
+            frames: vec![],
+            globals,
+            open_upvalues: vec![],
+            handlers: vec![],
+            output: Rc::new(RefCell::new(io::stdout())),
+            yielded: None,
+            running: 0,
+        }
+    }
+
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
+    }
+
+    pub fn set_args(&mut self, args: &[String]) {
+        self.globals
+            .insert("args".to_string(), callable::script_args(args));
+    }
+
+    pub fn run(&mut self, program: Program) -> Result<Value, Diagnostic> {
+        let closure = Rc::new(Closure {
+            program: Rc::new(program),
+            function: 0,
+            upvalues: vec![],
+        });
+
+        self.stack.push(Value::Closure(closure.clone()));
+        self.frames.push(Frame {
+            closure,
+            ip: 0,
+            base: 0,
+            missing: vec![],
+        });
+
+        let result = self.execute(0, 0);
+        if result.is_err() {
+            self.stack.clear();
+            self.frames.clear();
+            self.open_upvalues.clear();
+            self.handlers.clear();
+        }
+
+        result
+    }
+
+    /// Runs the topmost frame until it returns with `depth` frames left
+    /// below it, which is 0 for a whole program. Errors are caught by the
+    /// handlers above `floor`; the ones below belong to whoever is waiting on
+    /// this run.
+    fn execute(&mut self, depth: usize, floor: usize) -> Result<Value, Diagnostic> {
+        loop {
+            self.running += 1;
+            let result = self.dispatch(depth, floor);
+            self.running -= 1;
+
+            let diagnostic = match result {
+                Ok(value) => return Ok(value),
+                Err(diagnostic) => diagnostic,
+            };
+            if self.handlers.len() <= floor {
+                return Err(diagnostic);
+            }
+
+            let handler = self.handlers.pop().expect("A handler above the floor");
+            self.frames.truncate(handler.frames);
+            self.close_upvalues(handler.height);
+            self.stack.truncate(handler.height);
+            self.stack
+                .push(Value::Error(Box::new(ErrorValue::caught(&diagnostic))));
+            self.frames.push(Frame {
+                closure: handler.closure,
+                ip: handler.catch,
+                base: handler.base,
+                missing: vec![],
+            });
+        }
+    }
+
+    fn dispatch(&mut self, depth: usize, floor: usize) -> Result<Value, Diagnostic> {
+        let mut frame = self.frames.pop().expect("No frame to run");
+
+        loop {
+            let program = frame.closure.program.clone();
+            let chunk = &program.functions[frame.closure.function].chunk;
+            let instruction = chunk.code[frame.ip];
+            let ip = frame.ip;
+            frame.ip += 1;
+
+            let error = |message: String| chunk.error_at(ip, message);
+            let target_error = |message: String| chunk.target_error_at(ip, message);
+
+            match instruction {
+                Instruction::Constant(index) => self.stack.push(chunk.constants[index].value()),
+                Instruction::Nil => self.stack.push(Value::Nil),
+                Instruction::True => self.stack.push(Value::Boolean(true)),
+                Instruction::False => self.stack.push(Value::Boolean(false)),
+                Instruction::Pop => {
+                    self.stack.pop();
+                }
+                Instruction::Duplicate(count) => {
+                    let start = self.stack.len() - count;
+                    self.stack.extend_from_within(start..);
+                }
+
+                Instruction::Uninitialized(name) => {
+                    let name = Self::name(&chunk.constants[name]).to_string();
+                    self.stack.push(Value::Uninitialized(Box::new(name)));
+                }
+                Instruction::DefineLocal(slot) => {
+                    let value = self.pop();
+                    self.stack[frame.base + slot] = value;
+                }
+                Instruction::GetLocal(slot) => {
+                    let value = Self::initialized(&self.stack[frame.base + slot]).map_err(error)?;
+                    self.stack.push(value.clone());
+                }
+                Instruction::SetLocal(slot) => {
+                    Self::initialized(&self.stack[frame.base + slot]).map_err(error)?;
+                    let value = self.peek(0).clone();
+                    self.stack[frame.base + slot] = value;
+                }
+                Instruction::GetUpvalue(index) => {
+                    let value = match &*frame.closure.upvalues[index].borrow() {
+                        Upvalue::Open(slot) => Self::initialized(&self.stack[*slot]).cloned(),
+                        Upvalue::Closed(value) => Self::initialized(value).cloned(),
+                    };
+                    self.stack.push(value.map_err(error)?);
+                }
+                Instruction::SetUpvalue(index) => {
+                    let value = self.peek(0).clone();
+                    let upvalue = &mut *frame.closure.upvalues[index].borrow_mut();
+                    let current = match upvalue {
+                        Upvalue::Open(slot) => &mut self.stack[*slot],
+                        Upvalue::Closed(closed) => closed,
+                    };
+                    Self::initialized(current).map_err(error)?;
+                    *current = value;
+                }
+                Instruction::GetGlobal(name) => {
+                    let name = Self::name(&chunk.constants[name]);
+                    match self.globals.get(name) {
+                        Some(value) => self.stack.push(value.clone()),
+                        None => return Err(error(format!("Undefined variable {}", name))),
+                    }
+                }
+                Instruction::SetGlobal(name) => {
+                    let name = Self::name(&chunk.constants[name]);
+                    let value = self.peek(0).clone();
+                    match self.globals.get_mut(name) {
+                        Some(slot) => *slot = value,
+                        None => return Err(error(format!("Undefined variable {}", name))),
+                    }
+                }
+                Instruction::DefineGlobal(name) => {
+                    let name = Self::name(&chunk.constants[name]).to_string();
+                    let value = self.pop();
+                    self.globals.insert(name, value);
+                }
+
+                Instruction::Equal => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Boolean(left == right));
+                }
+                Instruction::NotEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    self.stack.push(Value::Boolean(left != right));
+                }
+                Instruction::Greater
+                | Instruction::GreaterEqual
+                | Instruction::Less
+                | Instruction::LessEqual => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    if left.partial_cmp(&right).is_none() {
+                        return Err(error("Operands must be numbers".to_string()));
+                    }
+
+                    self.stack.push(Value::Boolean(match instruction {
+                        Instruction::Greater => left > right,
+                        Instruction::GreaterEqual => left >= right,
+                        Instruction::Less => left < right,
+                        _ => left <= right,
+                    }));
+                }
+                Instruction::Add
+                | Instruction::Subtract
+                | Instruction::Multiply
+                | Instruction::Divide
+                | Instruction::Modulo
+                | Instruction::FloorDivide
+                | Instruction::Power
+                | Instruction::BitAnd
+                | Instruction::BitOr
+                | Instruction::BitXor
+                | Instruction::ShiftLeft
+                | Instruction::ShiftRight => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = match instruction {
+                        Instruction::Add => left + right,
+                        Instruction::Subtract => left - right,
+                        Instruction::Multiply => left * right,
+                        Instruction::Modulo => left % right,
+                        Instruction::FloorDivide => left.floor_div(right),
+                        Instruction::Power => left.pow(right),
+                        Instruction::BitAnd => left & right,
+                        Instruction::BitOr => left | right,
+                        Instruction::BitXor => left ^ right,
+                        Instruction::ShiftLeft => left << right,
+                        Instruction::ShiftRight => left >> right,
+                        _ => left / right,
+                    };
+                    self.stack.push(value.map_err(error)?);
+                }
+                Instruction::Negate => {
+                    let value = self.pop();
+                    self.stack.push((-value).map_err(error)?);
+                }
+                Instruction::BitNot => {
+                    let value = self.pop();
+                    self.stack.push(value.bit_not().map_err(error)?);
+                }
+                Instruction::Not => {
+                    let value = self.pop();
+                    self.stack
+                        .push(Value::Boolean(value == Value::Boolean(false)));
+                }
+
+                Instruction::Print => {
+                    let value = self.pop();
+                    writeln!(self.output.borrow_mut(), "{}", value)
+                        .map_err(|io_error| error(format!("Could not print: {}", io_error)))?;
+                }
+
+                Instruction::Jump(target) => frame.ip = target,
+                Instruction::JumpIfFalse(target) => {
+                    if *self.peek(0) == Value::Boolean(false) {
+                        frame.ip = target;
+                    }
+                }
+                Instruction::JumpIfTrue(target) => {
+                    if *self.peek(0) == Value::Boolean(true) {
+                        frame.ip = target;
+                    }
+                }
+                Instruction::JumpIfNotTrue(target) => {
+                    if *self.peek(0) != Value::Boolean(true) {
+                        frame.ip = target;
+                    }
+                }
+
+                Instruction::Call(count) => {
+                    self.call_value(&mut frame, count, &[], error, target_error)?
+                }
+                Instruction::CallNamed { count, names } => self.call_value(
+                    &mut frame,
+                    count,
+                    &chunk.keywords[names],
+                    error,
+                    target_error,
+                )?,
+                Instruction::Default { parameter, skip } => {
+                    if !frame.missing.contains(&parameter) {
+                        frame.ip = skip;
+                    }
+                }
+                Instruction::Closure(index) => {
+                    let function = &frame.closure.program.functions[index];
+                    let upvalues = function
+                        .captures
+                        .iter()
+                        .map(|capture| match capture.is_local {
+                            true => self.capture_upvalue(frame.base + capture.index),
+                            false => frame.closure.upvalues[capture.index].clone(),
+                        })
+                        .collect();
+
+                    self.stack.push(Value::Closure(Rc::new(Closure {
+                        program: frame.closure.program.clone(),
+                        function: index,
+                        upvalues,
+                    })));
+                }
+                Instruction::CloseUpvalue => {
+                    self.close_upvalues(self.stack.len() - 1);
+                    self.stack.pop();
+                }
+                Instruction::Match { shape, fail } => {
+                    let value = self.pop();
+                    match chunk.shapes[shape].bind(&value) {
+                        Some(bindings) => self.stack.extend(bindings),
+                        None => frame.ip = fail,
+                    }
+                }
+                Instruction::NoMatch => {
+                    let value = self.pop();
+                    return Err(error(shape::unmatched(&value)));
+                }
+                Instruction::Destructure(shape) => {
+                    let value = self.pop();
+                    match chunk.shapes[shape].bind(&value) {
+                        Some(bindings) => self.stack.extend(bindings),
+                        None => return Err(error(shape::mismatched(&value))),
+                    }
+                }
+                Instruction::Unwind(height) => {
+                    self.close_upvalues(frame.base + height);
+                    self.stack.truncate(frame.base + height);
+                }
+                Instruction::Return => {
+                    let value = self.pop();
+                    self.close_upvalues(frame.base);
+                    self.stack.truncate(frame.base);
+
+                    if self.frames.len() == depth {
+                        return Ok(value);
+                    }
+                    frame = self.frames.pop().expect("A caller below the depth");
+                    self.stack.push(value);
+                }
+
+                Instruction::List(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::List(Rc::new(RefCell::new(values))));
+                }
+                Instruction::Record(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count * 2);
+                    let mut map = HashMap::new();
+                    for pair in values.chunks(2) {
+                        map.insert(pair[0].to_string(), pair[1].clone());
+                    }
+                    self.stack.push(Value::Record(Rc::new(RefCell::new(map))));
+                }
+                Instruction::Index => {
+                    let index = self.pop();
+                    let indexable = self.pop();
+                    self.stack
+                        .push(Self::index(indexable, index, error, target_error)?);
+                }
+                Instruction::Concat(count) => {
+                    let start = self.stack.len() - count;
+                    let string: String = self
+                        .stack
+                        .drain(start..)
+                        .map(|value| value.to_string())
+                        .collect();
+                    self.stack.push(Value::String(Box::new(string)));
+                }
+                Instruction::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let list = self.pop();
+                    Self::set_index(list, index, value.clone(), error, target_error)?;
+                    self.stack.push(value);
+                }
+                Instruction::GetField(key) => {
+                    let key = Self::name(&chunk.constants[key]);
+                    let value = match self.pop() {
+                        Value::Record(record) => record
+                            .borrow()
+                            .get(key)
+                            .cloned()
+                            .ok_or_else(|| format!("key {} does not exist in record", key)),
+                        Value::Error(error) => error
+                            .field(key)
+                            .ok_or_else(|| format!("Errors have no field {}", key)),
+                        _ => {
+                            return Err(target_error(
+                                "Cannot dot index into non record type".to_string(),
+                            ))
+                        }
+                    };
+                    self.stack.push(value.map_err(error)?);
+                }
+                Instruction::SetField(key) => {
+                    let key = Self::name(&chunk.constants[key]);
+                    let value = self.pop();
+                    let record = match self.pop() {
+                        Value::Record(record) => record,
+                        _ => {
+                            return Err(target_error(
+                                "Cannot dot index into no record type".to_string(),
+                            ))
+                        }
+                    };
+                    if !record.borrow().contains_key(key) {
+                        return Err(error(format!("key {} does not exist in record", key)));
+                    }
+                    record.borrow_mut().insert(key.to_string(), value.clone());
+                    self.stack.push(value);
+                }
+
+                Instruction::IterStart => {
+                    if !matches!(self.peek(0), Value::Iter(_)) {
+                        return Err(error("Must use an Iter in the 'for' statement".to_string()));
+                    }
+                }
+                Instruction::ForIter { slot, exit } => {
+                    let iter = match &self.stack[frame.base + slot] {
+                        Value::Iter(iter) => iter.clone(),
+                        _ => return Err(error("Corrupt iterator state".to_string())),
+                    };
+
+                    let next = iter
+                        .next(self)
+                        .map_err(|diagnostic| diagnostic.or_at(chunk.spans[ip]))?;
+                    match next {
+                        Some(value) => self.stack.push(value),
+                        None => frame.ip = exit,
+                    }
+                }
+                Instruction::Yield(close) => {
+                    // A generator only ever runs as the bottom frame of
+                    // `start` or `resume`, so its whole frame is lifted off
+                    // the stack.
+                    let value = self.pop();
+                    let stack = self.stack.split_off(frame.base);
+                    let mut upvalues = vec![];
+                    self.open_upvalues.retain(|upvalue| {
+                        let slot = match *upvalue.borrow() {
+                            Upvalue::Open(slot) if slot >= frame.base => slot,
+                            _ => return true,
+                        };
+                        let offset = slot - frame.base;
+                        *upvalue.borrow_mut() = Upvalue::Closed(stack[offset].clone());
+                        upvalues.push((offset, upvalue.clone()));
+                        false
+                    });
+
+                    let handlers = self
+                        .handlers
+                        .split_off(floor)
+                        .into_iter()
+                        .map(|handler| Handler {
+                            height: handler.height - frame.base,
+                            ..handler
+                        })
+                        .collect();
+
+                    self.yielded = Some(VmGenerator {
+                        closure: frame.closure.clone(),
+                        ip: frame.ip,
+                        close,
+                        stack,
+                        upvalues,
+                        handlers,
+                    });
+                    return Ok(value);
+                }
+                Instruction::CloseIter(slot) => {
+                    let iter = std::mem::replace(&mut self.stack[frame.base + slot], Value::Nil);
+                    if let Value::Iter(iter) = iter {
+                        iter.close(self)
+                            .map_err(|diagnostic| diagnostic.or_at(chunk.spans[ip]))?;
+                    }
+                }
+                Instruction::TryStart(catch) => self.handlers.push(Handler {
+                    frames: self.frames.len(),
+                    closure: frame.closure.clone(),
+                    base: frame.base,
+                    height: self.stack.len(),
+                    catch,
+                }),
+                Instruction::TryEnd => {
+                    self.handlers.pop();
+                }
+                Instruction::Propagate { height, exit } => {
+                    let value = self.pop();
+                    match value.unwrapped() {
+                        Some(Ok(value)) => {
+                            self.stack.push(value);
+                            frame.ip = exit;
+                        }
+                        Some(Err(failed)) => {
+                            self.stack.truncate(frame.base + height);
+                            self.stack.push(failed);
+                        }
+                        None => {
+                            return Err(error(format!(
+                                "Can only use '?' on a result or an option, not {}",
+                                value.type_name()
+                            )))
+                        }
+                    }
+                }
+                Instruction::Throw => {
+                    let value = self.pop();
+                    return Err(error::throw(value, chunk.spans[ip]));
+                }
+            }
+        }
+    }
+
+    /// Calls the callee below the top `count` values, the last
+    /// `names.len()` of which were passed by those names. A closure's frame
+    /// takes over from `frame`, which waits for it to return; anything else
+    /// leaves its result in place of the callee.
+    fn call_value(
+        &mut self,
+        frame: &mut Frame,
+        count: usize,
+        names: &[String],
+        error: impl Fn(String) -> Diagnostic,
+        target_error: impl Fn(String) -> Diagnostic,
+    ) -> Result<(), Diagnostic> {
+        let callee_slot = self.stack.len() - count - 1;
+        match self.stack[callee_slot].clone() {
+            Value::Closure(closure) => {
+                let generator = closure.program.functions[closure.function].generator;
+                let callee = self.enter(closure, callee_slot, names).map_err(error)?;
+                if generator {
+                    let value = self.start(callee)?;
+                    self.stack.push(value);
+                } else {
+                    self.frames.push(std::mem::replace(frame, callee));
+                }
+            }
+            Value::BuiltIn(_) if !names.is_empty() => {
+                return Err(error(
+                    "Native functions do not take named arguments".to_string(),
+                ))
+            }
+            Value::BuiltIn(builtin) => {
+                let arguments = self.stack.split_off(callee_slot + 1);
+                self.stack.pop();
+                let value = self.call_builtin(&builtin, arguments).map_err(error)?;
+                self.stack.push(value);
+            }
+            _ => return Err(target_error("Can only call functions".to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Lines the arguments above `callee_slot` up with the closure's
+    /// parameters, one slot each, and returns the frame that runs it.
+    fn enter(
+        &mut self,
+        closure: Rc<Closure>,
+        callee_slot: usize,
+        names: &[String],
+    ) -> Result<Frame, String> {
+        self.check_depth()?;
+        let arity = &closure.program.functions[closure.function].arity;
+        let mut missing = vec![];
+
+        let given = self.stack.len() - callee_slot - 1;
+        if !names.is_empty() || arity.rest || given != arity.names.len() {
+            let arguments = self.stack.split_off(callee_slot + 1);
+            let Arguments { slots, rest } = arity.arrange(arguments, names)?;
+            for (parameter, argument) in slots.into_iter().enumerate() {
+                self.stack.push(argument.unwrap_or_else(|| {
+                    missing.push(parameter);
+                    Value::Nil
+                }));
+            }
+            if arity.rest {
+                self.stack.push(Value::List(Rc::new(RefCell::new(rest))));
+            }
+        }
+
+        Ok(Frame {
+            closure,
+            ip: 0,
+            base: callee_slot,
+            missing,
+        })
+    }
+
+    /// Runs a generator's frame as far as the pause at the end of its
+    /// prologue and returns the paused generator, or what it returned if a
+    /// `?` in a default left early.
+    fn start(&mut self, frame: Frame) -> Result<Value, Diagnostic> {
+        let depth = self.frames.len();
+        self.frames.push(frame);
+        let value = self.execute(depth, self.handlers.len())?;
+
+        Ok(match self.yielded.take() {
+            Some(generator) => Value::Iter(Iter::new(Generator::new(Suspended::Vm(Box::new(
+                generator,
+            ))))),
+            None => value,
+        })
+    }
+
+    fn call_builtin(
+        &mut self,
+        builtin: &BuiltIn,
+        mut arguments: Vec<Value>,
+    ) -> Result<Value, String> {
+        if let Some(arity) = builtin.arity {
+            if arguments.len() != arity {
+                return Err(format!(
+                    "Expected {} arguments but got {}",
+                    arity,
+                    arguments.len()
+                ));
+            }
+        }
+
+        (builtin.function)(&mut arguments, &mut *self.output.borrow_mut())
+    }
+
+    /// Fails once calls are nested `MAX_CALL_DEPTH` deep, so runaway
+    /// recursion is an error rather than a stack that grows until memory
+    /// runs out. The script's own frame doesn't count as a call.
+    fn check_depth(&self) -> Result<(), String> {
+        match self.frames.len() + self.running > MAX_CALL_DEPTH {
+            true => Err("Stack overflow".to_string()),
+            false => Ok(()),
+        }
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("Stack underflow")
+    }
+
+    fn peek(&self, distance: usize) -> &Value {
+        &self.stack[self.stack.len() - 1 - distance]
+    }
+
+    /// The value of a local, or an error if its declaration hasn't run yet.
+    fn initialized(value: &Value) -> Result<&Value, String> {
+        match value {
+            Value::Uninitialized(name) => Err(format!("Undefined variable {}", name)),
+            value => Ok(value),
+        }
+    }
+
+    fn name(constant: &Constant) -> &str {
+        match constant {
+            Constant::String(name) => name,
+            _ => panic!("Expected a name constant"),
+        }
+    }
+
+    fn capture_upvalue(&mut self, slot: usize) -> Rc<RefCell<Upvalue>> {
+        let existing = self
+            .open_upvalues
+            .iter()
+            .find(|upvalue| matches!(*upvalue.borrow(), Upvalue::Open(open) if open == slot));
+        if let Some(upvalue) = existing {
+            return upvalue.clone();
+        }
+
+        let upvalue = Rc::new(RefCell::new(Upvalue::Open(slot)));
+        self.open_upvalues.push(upvalue.clone());
+        upvalue
+    }
+
+    /// Moves every captured variable at or above `from` off the stack.
+    fn close_upvalues(&mut self, from: usize) {
+        let stack = &self.stack;
+        self.open_upvalues.retain(|upvalue| {
+            let slot = match *upvalue.borrow() {
+                Upvalue::Open(slot) if slot >= from => slot,
+                _ => return true,
+            };
+            *upvalue.borrow_mut() = Upvalue::Closed(stack[slot].clone());
+            false
+        });
+    }
+
+    /// `indexable[index]`, with errors about `indexable` raised by
+    /// `target_error` and the rest by `error`.
+    fn index(
+        indexable: Value,
+        index: Value,
+        error: impl Fn(String) -> Diagnostic,
+        target_error: impl Fn(String) -> Diagnostic,
+    ) -> Result<Value, Diagnostic> {
+        match (indexable, index) {
+            (Value::List(list), Value::Int(number)) => {
+                let list = list.borrow();
+                match usize::try_from(number).ok().and_then(|i| list.get(i)) {
+                    Some(value) => Ok(value.clone()),
+                    None => Err(error(format!(
+                        "Index {} out of bounds for list of length {}",
+                        number,
+                        list.len()
+                    ))),
+                }
+            }
+            (Value::String(string), Value::Int(number)) => {
+                match usize::try_from(number)
+                    .ok()
+                    .and_then(|i| string.as_bytes().get(i))
+                {
+                    Some(byte) => Ok(Value::String(Box::new(byte.to_string()))),
+                    None => Err(error(format!(
+                        "Index {} out of bounds for string of length {}",
+                        number,
+                        string.len()
+                    ))),
+                }
+            }
+            (Value::List(_) | Value::String(_), _) => Err(error(
+                "Must use integer to index into list or string".to_string(),
+            )),
+            _ => Err(target_error(
+                "Can only index into list or string".to_string(),
+            )),
+        }
+    }
+
+    fn set_index(
+        list: Value,
+        index: Value,
+        value: Value,
+        error: impl Fn(String) -> Diagnostic,
+        target_error: impl Fn(String) -> Diagnostic,
+    ) -> Result<(), Diagnostic> {
+        let list = match list {
+            Value::List(list) => list,
+            _ => return Err(target_error("Must index into list type".to_string())),
+        };
+        let number = match index {
+            Value::Int(number) => number,
+            _ => {
+                return Err(error(
+                    "Must use integer to index into list type".to_string(),
+                ))
+            }
+        };
+
+        let mut list = list.borrow_mut();
+        let length = list.len();
+        match usize::try_from(number).ok().and_then(|i| list.get_mut(i)) {
+            Some(slot) => {
+                *slot = value;
+                Ok(())
+            }
+            None => Err(error(format!(
+                "Index {} out of bounds for list of length {}",
+                number, length
+            ))),
+        }
+    }
+}
+
+/// Runs a jive function to completion on top of whatever is executing, for
+/// iterators that call back into the program.
+impl Caller for Vm {
+    fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, Diagnostic> {
+        match callee {
+            Value::Closure(closure) => {
+                let base = self.stack.len();
+                self.stack.push(callee.clone());
+                self.stack.extend(arguments);
+                let frame = match self.enter(closure.clone(), base, &[]) {
+                    Ok(frame) => frame,
+                    Err(message) => {
+                        self.stack.truncate(base);
+                        return Err(Diagnostic::error(message));
+                    }
+                };
+
+                if closure.program.functions[closure.function].generator {
+                    return self.start(frame);
+                }
+                let depth = self.frames.len();
+                self.frames.push(frame);
+                self.execute(depth, self.handlers.len())
+            }
+            Value::BuiltIn(builtin) => self
+                .call_builtin(builtin, arguments)
+                .map_err(Diagnostic::error),
+            _ => Err(Diagnostic::error("Can only call functions")),
+        }
+    }
+
+    fn resume(&mut self, generator: &mut Suspended) -> Result<Option<Value>, Diagnostic> {
+        let generator = match generator {
+            Suspended::Vm(generator) => generator,
+            Suspended::Tree(_) => {
+                return Err(Diagnostic::error(
+                    "Generator was started by the tree-walking interpreter",
+                ))
+            }
+        };
+
+        self.check_depth().map_err(Diagnostic::error)?;
+
+        let base = self.stack.len();
+        self.stack.append(&mut generator.stack);
+        for (offset, upvalue) in generator.upvalues.drain(..) {
+            let closed =
+                std::mem::replace(&mut *upvalue.borrow_mut(), Upvalue::Open(base + offset));
+            if let Upvalue::Closed(value) = closed {
+                self.stack[base + offset] = value;
+            }
+            self.open_upvalues.push(upvalue);
+        }
+
+        let depth = self.frames.len();
+        let floor = self.handlers.len();
+        self.handlers
+            .extend(generator.handlers.drain(..).map(|handler| Handler {
+                frames: depth,
+                base,
+                height: base + handler.height,
+                ..handler
+            }));
+        self.frames.push(Frame {
+            closure: generator.closure.clone(),
+            ip: generator.ip,
+            base,
+            missing: vec![],
+        });
+        let value = self.execute(depth, floor)?;
+
+        // `execute` returns the yielded value, or the return value once the
+        // body finishes.
+        match self.yielded.take() {
+            Some(paused) => {
+                **generator = paused;
+                Ok(Some(value))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn close(&mut self, generator: &mut Suspended) -> Result<(), Diagnostic> {
+        if let Suspended::Vm(generator) = generator {
+            generator.ip = generator.close;
+        }
+        match self.resume(generator)? {
+            Some(_) => Err(Diagnostic::error("Generator yielded while being closed")),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,73 @@
+//! The values number and string literals stand for, which the compiler
+//! keeps in its constant pools and patterns compare against.
+
+use num_bigint::BigInt;
+
+use crate::{
+    diagnostic::Diagnostic,
+    lexer::{parse_bigint, parse_decimal, parse_float, parse_int},
+    token::{Token, TokenType},
+    visitors::interpreter::{decimal::Decimal, value::Value},
+};
+
+#[derive(Debug, Clone)]
+pub enum Constant {
+    Int(i64),
+    BigInt(BigInt),
+    Float(f64),
+    Decimal(Decimal),
+    String(String),
+}
+
+impl Constant {
+    /// The constant a number or string literal stands for.
+    pub fn literal(token: &Token) -> Result<Constant, Diagnostic> {
+        let invalid = || {
+            Diagnostic::error(format!("Invalid number literal {}", token.lexeme)).at(token.span())
+        };
+
+        match token.token_type {
+            TokenType::Int => parse_int(&token.lexeme)
+                .map(Constant::Int)
+                .ok_or_else(invalid),
+            TokenType::BigInt => parse_bigint(&token.lexeme)
+                .map(Constant::BigInt)
+                .ok_or_else(invalid),
+            TokenType::Float => parse_float(&token.lexeme)
+                .map(Constant::Float)
+                .ok_or_else(invalid),
+            TokenType::Decimal => parse_decimal(&token.lexeme)
+                .map(Constant::Decimal)
+                .ok_or_else(invalid),
+            TokenType::String => Ok(Constant::String(token.lexeme.clone())),
+            _ => Err(Diagnostic::error("Unexpected token type").at(token.span())),
+        }
+    }
+
+    pub fn value(&self) -> Value {
+        match self {
+            Constant::Int(number) => Value::Int(*number),
+            Constant::Float(number) => Value::Float(*number),
+            Constant::BigInt(number) => Value::BigInt(Box::new(number.clone())),
+            Constant::Decimal(number) => Value::Decimal(Box::new(number.clone())),
+            Constant::String(string) => Value::String(Box::new(string.clone())),
+        }
+    }
+}
+
+/// Decimals must match digit for digit, so `1.5d` and `1.50d` stay apart in
+/// the constant pool.
+impl PartialEq for Constant {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Constant::Int(left), Constant::Int(right)) => left == right,
+            (Constant::BigInt(left), Constant::BigInt(right)) => left == right,
+            (Constant::Float(left), Constant::Float(right)) => left == right,
+            (Constant::Decimal(left), Constant::Decimal(right)) => {
+                left == right && left.scale() == right.scale()
+            }
+            (Constant::String(left), Constant::String(right)) => left == right,
+            _ => false,
+        }
+    }
+}
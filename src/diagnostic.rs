@@ -0,0 +1,171 @@
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: u32, column: u32) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// The smallest span covering both `self` and `other`, which must come
+    /// after it in the source.
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end.max(self.end),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A problem found in a jive program by any phase (lexer, parser, type checker
+/// or interpreter). A `line` of 0 means the location is unknown.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: u32,
+    pub column: u32,
+    pub span: Span,
+    /// The kind of error a jive program threw, which `catch` hands back.
+    /// `None` for errors raised by the runtime itself.
+    pub kind: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            message: message.into(),
+            file: None,
+            line: 0,
+            column: 0,
+            span: Span::default(),
+            kind: None,
+        }
+    }
+
+    pub fn warning(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(message)
+        }
+    }
+
+    pub fn at(mut self, span: Span) -> Diagnostic {
+        self.line = span.line;
+        self.column = span.column;
+        self.span = span;
+        self
+    }
+
+    /// Points at `span` unless the diagnostic already has a location, as
+    /// errors raised inside a called function do.
+    pub fn or_at(self, span: Span) -> Diagnostic {
+        match self.line {
+            0 => self.at(span),
+            _ => self,
+        }
+    }
+
+    pub fn with_kind(mut self, kind: impl Into<String>) -> Diagnostic {
+        self.kind = Some(kind.into());
+        self
+    }
+
+    pub fn in_file(mut self, file: impl Into<String>) -> Diagnostic {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn has_location(&self) -> bool {
+        self.line != 0
+    }
+
+    /// Formats a diagnostic with the offending source line and a caret underline
+    /// beneath the reported column, e.g.
+    ///
+    /// ```text
+    /// error: Unexpected character: @
+    ///  --> main.jive:3:6
+    ///   |
+    /// 3 | make @ = 1;
+    ///   |      ^
+    /// ```
+    pub fn render(&self, source: &str) -> String {
+        let mut output = format!("{}\n", self);
+
+        let file = self.file.as_deref().unwrap_or("<input>");
+        if !self.has_location() {
+            output.push_str(&format!(" --> {}\n", file));
+            return output;
+        }
+
+        let location = match self.column {
+            0 => format!("{}:{}", file, self.line),
+            column => format!("{}:{}:{}", file, self.line, column),
+        };
+        let line_text = match source.lines().nth(self.line as usize - 1) {
+            Some(line_text) => line_text,
+            None => {
+                output.push_str(&format!(" --> {}\n", location));
+                return output;
+            }
+        };
+
+        let gutter = " ".repeat(self.line.to_string().len());
+        output.push_str(&format!("{}--> {}\n", gutter, location));
+        output.push_str(&format!("{} |\n", gutter));
+        output.push_str(&format!("{} | {}\n", self.line, line_text));
+
+        if self.column != 0 {
+            let offset = self.column as usize - 1;
+            let remaining = line_text.chars().count().saturating_sub(offset);
+            let length = source
+                .get(self.span.start..self.span.end)
+                .map(|text| text.chars().count())
+                .unwrap_or(1)
+                .clamp(1, remaining.max(1));
+            let padding: String = line_text
+                .chars()
+                .take(offset)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            output.push_str(&format!("{} | {}{}\n", gutter, padding, "^".repeat(length)));
+        }
+
+        output
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
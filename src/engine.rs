@@ -0,0 +1,285 @@
+use std::{cell::RefCell, fmt, io::Write, rc::Rc};
+
+use crate::{
+    diagnostic::Diagnostic,
+    lexer::Lexer,
+    parser::{statement::Statement, Parser},
+    visitors::{
+        interpreter::{
+            callable::BuiltIn,
+            convert::{HostFunction, IntoValue},
+            value::Value,
+            Interpreter,
+        },
+        resolver::Resolver,
+        type_checker::{types::Type, TypeChecker},
+    },
+};
+
+/// Which phase rejected a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// The lexer or parser.
+    Syntax,
+    /// The resolver or type checker.
+    Type,
+    /// The interpreter, while running.
+    Runtime,
+}
+
+/// Everything wrong with a program, as reported by the phase that stopped it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Error {
+    fn new(kind: ErrorKind, diagnostics: Vec<Diagnostic>) -> Error {
+        Error { kind, diagnostics }
+    }
+
+    fn with(kind: ErrorKind) -> impl Fn(Diagnostic) -> Error {
+        move |diagnostic| Error::new(kind, vec![diagnostic])
+    }
+
+    /// Renders every diagnostic against the source that produced them.
+    pub fn render(&self, source: &str) -> String {
+        self.diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.render(source))
+            .collect()
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let messages: Vec<String> = self
+            .diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect();
+        write!(f, "{}", messages.join("\n"))
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Lexes and parses `source`, reporting every syntax error found.
+pub fn parse(source: &str) -> Result<Vec<Statement>, Error> {
+    let tokens = Lexer::new(source.to_string())
+        .lex()
+        .map_err(Error::with(ErrorKind::Syntax))?;
+
+    let (statements, errors) = Parser::new(tokens).parse();
+    if !errors.is_empty() {
+        return Err(Error::new(ErrorKind::Syntax, errors));
+    }
+
+    Ok(statements)
+}
+
+/// Embeds jive in a Rust program. An engine keeps its globals between calls,
+/// so source can be fed to it a piece at a time:
+///
+/// ```
+/// let mut engine = jive::Engine::new();
+/// engine.capture_output();
+/// engine.set_global("name", jive::Value::String(Box::new("world".to_string())));
+///
+/// engine.run("funk greet(who) { print \"hello \" + who; }").unwrap();
+/// engine.run("greet(name);").unwrap();
+///
+/// assert_eq!(engine.take_output(), "hello world\n");
+/// assert_eq!(engine.eval("len(name) * 2").unwrap(), jive::Value::Int(10));
+/// ```
+///
+/// Programs may nest calls `MAX_CALL_DEPTH` deep before failing with "Stack
+/// overflow", which they can catch. Each call uses the host thread's stack,
+/// and that depth fits in the 8 MB most platforms give the main thread.
+pub struct Engine {
+    resolver: Resolver,
+    type_checker: TypeChecker,
+    interpreter: Interpreter,
+    type_checking: bool,
+    warnings: Vec<Diagnostic>,
+    captured: Option<Rc<RefCell<Vec<u8>>>>,
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine {
+            resolver: Resolver::new(),
+            type_checker: TypeChecker::new(),
+            interpreter: Interpreter::new(),
+            type_checking: true,
+            warnings: vec![],
+            captured: None,
+        }
+    }
+
+    /// Turns the type checker on or off for the programs run after this.
+    /// Programs still have their variables resolved either way.
+    pub fn set_type_checking(&mut self, enabled: bool) {
+        self.type_checking = enabled;
+    }
+
+    /// Sends everything programs print to `output` instead of stdout.
+    pub fn set_output(&mut self, output: impl Write + 'static) {
+        self.captured = None;
+        self.interpreter.set_output(Rc::new(RefCell::new(output)));
+    }
+
+    /// Collects everything programs print, for `take_output` to return.
+    pub fn capture_output(&mut self) {
+        let buffer = Rc::new(RefCell::new(vec![]));
+        self.interpreter.set_output(buffer.clone());
+        self.captured = Some(buffer);
+    }
+
+    /// The output printed since the last call, when capturing.
+    pub fn take_output(&mut self) -> String {
+        match &self.captured {
+            Some(buffer) => String::from_utf8_lossy(&buffer.take()).to_string(),
+            None => String::new(),
+        }
+    }
+
+    /// Defines or replaces a global that programs can read and assign.
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        let value = value.into_value();
+        self.resolver.declare_global(name);
+        self.type_checker.declare_global(name, Type::of(&value));
+        self.interpreter.define_global(name, value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.interpreter.get_global(name)
+    }
+
+    /// Sets the `args` list programs see.
+    pub fn set_args(&mut self, args: &[String]) {
+        self.set_global("args", args.to_vec());
+    }
+
+    /// Makes a Rust closure callable from programs. Its arguments are
+    /// converted with `FromValue`, so calls with the wrong number or types of
+    /// arguments are errors, caught by the type checker where it can tell:
+    ///
+    /// ```
+    /// let mut engine = jive::Engine::new();
+    /// engine.register("repeat", |text: String, times: f64| text.repeat(times as usize));
+    ///
+    /// assert_eq!(engine.eval("repeat(\"ab\", 2)").unwrap().to_string(), "abab");
+    /// assert_eq!(engine.eval("repeat(2, 2)").unwrap_err().kind, jive::ErrorKind::Type);
+    /// ```
+    pub fn register<Arguments>(&mut self, name: &str, function: impl HostFunction<Arguments>) {
+        let builtin = function.into_builtin(name);
+        self.set_global(name, Value::BuiltIn(Box::new(builtin)));
+    }
+
+    /// Makes a Rust closure that takes any number of arguments callable from
+    /// programs, passing it the values as they are.
+    pub fn register_variadic(
+        &mut self,
+        name: &str,
+        function: impl Fn(&mut [Value]) -> Result<Value, String> + 'static,
+    ) {
+        let builtin = BuiltIn::new(None, move |arguments, _output| function(arguments));
+        self.set_global(name, Value::BuiltIn(Box::new(builtin)));
+    }
+
+    /// Resolves and type checks a program without running it.
+    pub fn check(&mut self, source: &str) -> Result<Vec<Statement>, Error> {
+        let statements = parse(source)?;
+
+        self.resolver
+            .resolve(&statements)
+            .map_err(Error::with(ErrorKind::Type))?;
+        if self.type_checking {
+            self.type_checker
+                .check(&statements)
+                .map_err(Error::with(ErrorKind::Type))?;
+            self.warnings.extend(self.type_checker.take_warnings());
+        }
+
+        Ok(statements)
+    }
+
+    /// The warnings the type checker found in the programs checked since the
+    /// last call, such as a `match` that misses some values.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    pub fn run(&mut self, source: &str) -> Result<(), Error> {
+        let statements = self.check(source)?;
+        self.execute(&statements)
+    }
+
+    /// Runs a program that has already been through `check`.
+    pub fn execute(&mut self, statements: &[Statement]) -> Result<(), Error> {
+        self.interpreter
+            .evaluate(statements)
+            .map_err(Error::with(ErrorKind::Runtime))?;
+
+        Ok(())
+    }
+
+    /// Runs `source` and returns the value of its last statement when that
+    /// is a bare expression, or nil. The final `;` may be left off.
+    pub fn eval(&mut self, source: &str) -> Result<Value, Error> {
+        let statements = self.check(&Self::terminated(source))?;
+
+        match statements.split_last() {
+            Some((Statement::ExpressionStatement(last), rest)) => {
+                self.interpreter
+                    .evaluate(rest)
+                    .map_err(Error::with(ErrorKind::Runtime))?;
+                self.interpreter
+                    .evaluate_expression(&last.expression)
+                    .map_err(Error::with(ErrorKind::Runtime))
+            }
+            _ => self
+                .interpreter
+                .evaluate(&statements)
+                .map_err(Error::with(ErrorKind::Runtime)),
+        }
+    }
+
+    /// The static type of a single expression, which is not run.
+    pub fn type_of(&mut self, source: &str) -> Result<Type, Error> {
+        let statements = parse(&Self::terminated(source))?;
+        let expression = match statements.as_slice() {
+            [Statement::ExpressionStatement(statement)] => &statement.expression,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::Syntax,
+                    vec![Diagnostic::error("Expected a single expression")],
+                ))
+            }
+        };
+
+        self.resolver
+            .resolve(&statements)
+            .map_err(Error::with(ErrorKind::Type))?;
+        self.type_checker
+            .check_expression(expression)
+            .map_err(Error::with(ErrorKind::Type))
+    }
+
+    /// Adds the `;` a trailing expression statement needs, if the source
+    /// only parses with it. Otherwise the source is left alone, so its
+    /// errors point at what was written.
+    pub fn terminated(source: &str) -> String {
+        if parse(source).is_ok() {
+            return source.to_string();
+        }
+
+        let terminated = format!("{};", source.trim_end());
+        match parse(&terminated) {
+            Ok(_) => terminated,
+            Err(_) => source.to_string(),
+        }
+    }
+}
@@ -1,14 +1,41 @@
-use crate::token::{Token, TokenType};
+use num_bigint::BigInt;
+
+use crate::{
+    diagnostic::{Diagnostic, Span},
+    token::{Token, TokenType},
+    visitors::interpreter::decimal::Decimal,
+};
 
 pub struct Lexer {
-    input: String,
+    input: Vec<char>,
     position: usize,
+    offset: usize,
     line_number: u32,
+    line_start: usize,
+    start: TokenStart,
     pub tokens: Vec<Token>,
+    /// `#` comments, kept apart from `tokens` so the parser never sees them.
+    pub comments: Vec<Token>,
+    /// The `${` expressions inside backtick strings that are still open.
+    interpolations: Vec<OpenInterpolation>,
+}
+
+struct OpenInterpolation {
+    /// Braces opened inside the expression, so its own `}` can be told apart.
+    braces: usize,
+    opening_quote: Span,
+}
+
+/// Where the token currently being lexed began.
+#[derive(Clone, Copy, Default)]
+struct TokenStart {
+    offset: usize,
+    line: u32,
+    column: u32,
 }
 
 impl Lexer {
-    const KEYWORDS: [(&'static str, TokenType); 16] = [
+    const KEYWORDS: [(&'static str, TokenType); 24] = [
         ("true", TokenType::Boolean),
         ("false", TokenType::Boolean),
         ("nil", TokenType::Nil),
@@ -25,20 +52,44 @@ impl Lexer {
         ("as", TokenType::As),
         ("for", TokenType::For),
         ("in", TokenType::In),
+        ("break", TokenType::Break),
+        ("continue", TokenType::Continue),
+        ("yield", TokenType::Yield),
+        ("try", TokenType::Try),
+        ("catch", TokenType::Catch),
+        ("finally", TokenType::Finally),
+        ("throw", TokenType::Throw),
+        ("match", TokenType::Match),
     ];
 
     pub fn new(input: String) -> Lexer {
         Lexer {
-            input,
+            input: input.chars().collect(),
             position: 0,
+            offset: 0,
             tokens: vec![],
-            line_number: 0,
+            comments: vec![],
+            interpolations: vec![],
+            line_number: 1,
+            line_start: 0,
+            start: TokenStart::default(),
         }
     }
 
-    pub fn lex(&mut self) -> Vec<Token> {
+    pub fn lex(&mut self) -> Result<Vec<Token>, Diagnostic> {
         loop {
+            self.start = TokenStart {
+                offset: self.offset,
+                line: self.line_number,
+                column: self.column(),
+            };
+
             if self.is_at_end() {
+                if let Some(interpolation) = self.interpolations.last() {
+                    return Err(
+                        Diagnostic::error("Unterminated string").at(interpolation.opening_quote)
+                    );
+                }
                 self.add_token(TokenType::EOF, "".to_string());
                 break;
             }
@@ -49,7 +100,7 @@ impl Lexer {
                 }
                 '\n' => {
                     self.advance();
-                    self.line_number += 1;
+                    self.new_line();
                 }
                 '(' => {
                     let c = self.advance().to_string();
@@ -60,13 +111,33 @@ impl Lexer {
                     self.add_token(TokenType::RParen, c);
                 }
                 '{' => {
+                    if let Some(interpolation) = self.interpolations.last_mut() {
+                        interpolation.braces += 1;
+                    }
                     let c = self.advance().to_string();
                     self.add_token(TokenType::LBrace, c);
                 }
-                '}' => {
-                    let c = self.advance().to_string();
-                    self.add_token(TokenType::RBrace, c);
-                }
+                '}' => match self.interpolations.last_mut() {
+                    Some(interpolation) if interpolation.braces == 0 => {
+                        let opening_quote = interpolation.opening_quote;
+                        self.interpolations.pop();
+                        self.advance();
+                        if self.tokens.last().map(|token| &token.token_type)
+                            == Some(&TokenType::Interpolation)
+                        {
+                            return Err(Diagnostic::error("Expected expression inside '${}'")
+                                .at(self.current_span()));
+                        }
+                        self.string_part('`', opening_quote)?;
+                    }
+                    open => {
+                        if let Some(interpolation) = open {
+                            interpolation.braces -= 1;
+                        }
+                        let c = self.advance().to_string();
+                        self.add_token(TokenType::RBrace, c);
+                    }
+                },
                 '[' => {
                     let c = self.advance().to_string();
                     self.add_token(TokenType::LBracket, c);
@@ -76,16 +147,65 @@ impl Lexer {
                     self.add_token(TokenType::RBracket, c);
                 }
                 '-' => {
-                    let c = self.advance().to_string();
-                    self.add_token(TokenType::Minus, c);
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '=' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::MinusEqual, c);
+                    } else if self.peek() == '-' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::MinusMinus, c);
+                    } else {
+                        self.add_token(TokenType::Minus, c);
+                    }
                 }
                 '+' => {
-                    let c = self.advance().to_string();
-                    self.add_token(TokenType::Plus, c);
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '=' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::PlusEqual, c);
+                    } else if self.peek() == '+' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::PlusPlus, c);
+                    } else {
+                        self.add_token(TokenType::Plus, c);
+                    }
                 }
                 '*' => {
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '*' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::StarStar, c);
+                    } else if self.peek() == '=' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::StarEqual, c);
+                    } else {
+                        self.add_token(TokenType::Star, c);
+                    }
+                }
+                '%' => {
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '=' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::PercentEqual, c);
+                    } else {
+                        self.add_token(TokenType::Percent, c);
+                    }
+                }
+                '&' => {
+                    let c = self.advance().to_string();
+                    self.add_token(TokenType::Ampersand, c);
+                }
+                '^' => {
+                    let c = self.advance().to_string();
+                    self.add_token(TokenType::Caret, c);
+                }
+                '~' => {
                     let c = self.advance().to_string();
-                    self.add_token(TokenType::Star, c);
+                    self.add_token(TokenType::Tilde, c);
+                }
+                '?' => {
+                    let c = self.advance().to_string();
+                    self.add_token(TokenType::Question, c);
                 }
                 '!' => {
                     let mut c = self.advance().to_string();
@@ -101,6 +221,9 @@ impl Lexer {
                     if self.peek() == '=' {
                         c.push(self.advance());
                         self.add_token(TokenType::EqualEqual, c);
+                    } else if self.peek() == '>' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::FatArrow, c);
                     } else {
                         self.add_token(TokenType::Equal, c);
                     }
@@ -110,6 +233,9 @@ impl Lexer {
                     if self.peek() == '=' {
                         c.push(self.advance());
                         self.add_token(TokenType::LessEqual, c);
+                    } else if self.peek() == '<' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::LessLess, c);
                     } else {
                         self.add_token(TokenType::Less, c);
                     }
@@ -119,22 +245,34 @@ impl Lexer {
                     if self.peek() == '=' {
                         c.push(self.advance());
                         self.add_token(TokenType::GreaterEqual, c);
+                    } else if self.peek() == '>' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::GreaterGreater, c);
                     } else {
                         self.add_token(TokenType::Greater, c);
                     }
                 }
                 '/' => {
-                    if self.peek_next() == '/' {
-                        while self.peek() != '\n' && !self.is_at_end() {
-                            self.advance();
-                        }
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '/' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::SlashSlash, c);
+                    } else if self.peek() == '=' {
+                        c.push(self.advance());
+                        self.add_token(TokenType::SlashEqual, c);
                     } else {
-                        let c = self.advance().to_string();
                         self.add_token(TokenType::Slash, c);
                     }
                 }
+                '#' => {
+                    let mut comment = String::new();
+                    while self.peek() != '\n' && !self.is_at_end() {
+                        comment.push(self.advance());
+                    }
+                    self.add_comment(comment);
+                }
                 '"' | '\'' | '`' => {
-                    self.handle_string(self.peek());
+                    self.handle_string(self.peek())?;
                 }
                 ',' => {
                     let c = self.advance().to_string();
@@ -145,70 +283,274 @@ impl Lexer {
                     self.add_token(TokenType::Semicolon, c);
                 }
                 '.' => {
-                    let c = self.advance().to_string();
-                    self.add_token(TokenType::Dot, c);
+                    let mut c = self.advance().to_string();
+                    if self.peek() == '.' {
+                        c.push(self.advance());
+                        if self.peek() == '.' {
+                            c.push(self.advance());
+                            self.add_token(TokenType::Ellipsis, c);
+                        } else {
+                            self.add_token(TokenType::DotDot, c);
+                        }
+                    } else {
+                        self.add_token(TokenType::Dot, c);
+                    }
                 }
                 ':' => {
                     let c = self.advance().to_string();
                     self.add_token(TokenType::Colon, c);
                 }
+                '|' => {
+                    let c = self.advance().to_string();
+                    self.add_token(TokenType::Pipe, c);
+                }
+                'r' if matches!(self.peek_next(), '"' | '\'' | '`') => {
+                    self.handle_raw_string()?;
+                }
                 _ => {
-                    if self.peek().is_ascii_alphabetic() {
+                    if self.peek().is_ascii_alphabetic() || self.peek() == '_' {
                         self.handle_alpha();
                     } else if self.peek().is_ascii_digit() {
-                        self.handle_number();
+                        self.handle_number()?;
                     } else {
-                        panic!("Unexpected character: {}", self.peek());
+                        let c = self.advance();
+                        return Err(Diagnostic::error(format!("Unexpected character: {}", c))
+                            .at(self.current_span()));
                     }
                 }
             }
         }
 
-        self.tokens.clone()
+        Ok(self.tokens.clone())
     }
 
-    fn handle_string(&mut self, character: char) {
+    /// Lexes a string, replacing escape sequences. Backtick strings stop at
+    /// each `${` so the embedded expression is lexed as ordinary tokens.
+    fn handle_string(&mut self, quote: char) -> Result<(), Diagnostic> {
         self.advance();
+        let opening_quote = self.current_span();
+
+        self.string_part(quote, opening_quote)
+    }
 
+    /// Lexes the rest of a string, or of a backtick string after one of its
+    /// embedded expressions.
+    fn string_part(&mut self, quote: char, opening_quote: Span) -> Result<(), Diagnostic> {
         let mut value = String::new();
 
-        while !self.is_at_end() && self.peek() != character && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line_number += 1;
+        loop {
+            if self.is_at_end() {
+                return Err(Diagnostic::error("Unterminated string").at(opening_quote));
             }
-            value.push(self.advance());
+
+            let c = self.advance();
+            match c {
+                _ if c == quote => break,
+                '\\' => value.push(self.escape()?),
+                '$' if quote == '`' && self.peek() == '{' => {
+                    self.advance();
+                    self.interpolations.push(OpenInterpolation {
+                        braces: 0,
+                        opening_quote,
+                    });
+                    self.add_token(TokenType::Interpolation, value);
+                    return Ok(());
+                }
+                '\n' => {
+                    self.new_line();
+                    value.push(c);
+                }
+                _ => value.push(c),
+            }
+        }
+
+        self.add_token(TokenType::String, value);
+        Ok(())
+    }
+
+    /// Reads the escape sequence after a `\`.
+    fn escape(&mut self) -> Result<char, Diagnostic> {
+        let start = self.offset - 1;
+        let column = self.column() - 1;
+        let span = |lexer: &Lexer| Span::new(start, lexer.offset, lexer.line_number, column);
+
+        if self.is_at_end() {
+            return Err(Diagnostic::error("Unterminated string").at(span(self)));
+        }
+
+        let escaped = match self.advance() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            c @ ('\\' | '"' | '\'' | '`' | '$') => c,
+            'u' => {
+                let mut digits = String::new();
+                if self.peek() == '{' {
+                    self.advance();
+                    while !self.is_at_end() && self.peek().is_ascii_hexdigit() {
+                        digits.push(self.advance());
+                    }
+                }
+                if self.peek() != '}' || digits.is_empty() || digits.len() > 6 {
+                    return Err(
+                        Diagnostic::error("Expected \\u{...} with 1 to 6 hex digits")
+                            .at(span(self)),
+                    );
+                }
+                self.advance();
+
+                match u32::from_str_radix(&digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                {
+                    Some(c) => c,
+                    None => {
+                        return Err(Diagnostic::error(format!(
+                            "Invalid unicode code point {}",
+                            digits
+                        ))
+                        .at(span(self)))
+                    }
+                }
+            }
+            c => {
+                return Err(
+                    Diagnostic::error(format!("Unknown escape sequence \\{}", c)).at(span(self)),
+                )
+            }
+        };
+
+        Ok(escaped)
+    }
+
+    /// `r"..."` keeps every character as written, backslashes included.
+    fn handle_raw_string(&mut self) -> Result<(), Diagnostic> {
+        self.advance();
+        let quote = self.advance();
+        let opening_quote = self.current_span();
+
+        let mut value = String::new();
+        while !self.is_at_end() && self.peek() != quote {
+            let c = self.advance();
+            if c == '\n' {
+                self.new_line();
+            }
+            value.push(c);
         }
 
         if self.is_at_end() {
-            panic!("Unterminated string");
+            return Err(Diagnostic::error("Unterminated string").at(opening_quote));
         }
 
         self.advance();
 
         self.add_token(TokenType::String, value);
+        Ok(())
     }
 
-    fn handle_number(&mut self) {
-        let mut value = self.advance().to_string();
+    /// Lexes a decimal number with an optional fraction and exponent, or a
+    /// `0x`, `0b` or `0o` integer. `_` may separate digits in either. Only
+    /// a fraction or exponent makes a float.
+    fn handle_number(&mut self) -> Result<(), Diagnostic> {
+        let mut value = String::new();
+        let mut token_type = TokenType::Int;
 
-        while !self.is_at_end() && self.peek().is_ascii_digit() {
-            value.push(self.advance());
+        let radix = match (self.peek(), self.peek_next()) {
+            ('0', 'x') => Some((16, "hex")),
+            ('0', 'b') => Some((2, "binary")),
+            ('0', 'o') => Some((8, "octal")),
+            _ => None,
+        };
+
+        match radix {
+            Some((radix, name)) => {
+                value.push(self.advance());
+                value.push(self.advance());
+                if self.digits(radix, &mut value)? == 0 {
+                    return Err(Diagnostic::error(format!(
+                        "Expected {} digits after {}",
+                        name, value
+                    ))
+                    .at(self.current_span()));
+                }
+                if self.peek().is_ascii_digit() {
+                    return Err(Diagnostic::error(format!(
+                        "Invalid digit '{}' in {} literal",
+                        self.peek(),
+                        name
+                    ))
+                    .at(self.char_span()));
+                }
+            }
+            None => {
+                self.digits(10, &mut value)?;
+
+                if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+                    token_type = TokenType::Float;
+                    value.push(self.advance());
+                    self.digits(10, &mut value)?;
+                }
+
+                if matches!(self.peek(), 'e' | 'E') {
+                    token_type = TokenType::Float;
+                    value.push(self.advance());
+                    if matches!(self.peek(), '+' | '-') {
+                        value.push(self.advance());
+                    }
+                    if self.digits(10, &mut value)? == 0 {
+                        return Err(Diagnostic::error("Expected digits in exponent")
+                            .at(self.current_span()));
+                    }
+                }
+
+                if self.peek() == 'd' && !self.peek_next().is_ascii_alphanumeric() {
+                    token_type = TokenType::Decimal;
+                    value.push(self.advance());
+                }
+            }
         }
 
-        if self.is_at_end() {
-            self.add_token(TokenType::Number, value);
-            return;
+        if self.peek().is_ascii_alphanumeric() {
+            return Err(Diagnostic::error(format!(
+                "Invalid character '{}' in number literal",
+                self.peek()
+            ))
+            .at(self.char_span()));
         }
 
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            value.push(self.advance());
+        if token_type == TokenType::Float && !parse_float(&value).is_some_and(f64::is_finite) {
+            return Err(Diagnostic::error("Float literal out of range").at(self.current_span()));
+        }
+
+        if token_type == TokenType::Int && parse_int(&value).is_none() {
+            token_type = TokenType::BigInt;
+        }
+
+        self.add_token(token_type, value);
+        Ok(())
+    }
+
+    /// Reads digits of `radix` onto `value`, along with any `_` between
+    /// them, and returns how many digits there were.
+    fn digits(&mut self, radix: u32, value: &mut String) -> Result<usize, Diagnostic> {
+        let mut count = 0;
 
-            while !self.is_at_end() && self.peek().is_ascii_digit() {
+        loop {
+            if self.peek().is_digit(radix) {
+                value.push(self.advance());
+                count += 1;
+            } else if self.peek() == '_' {
+                if count == 0 || !self.peek_next().is_digit(radix) {
+                    return Err(
+                        Diagnostic::error("'_' must be between digits").at(self.char_span())
+                    );
+                }
                 value.push(self.advance());
+            } else {
+                return Ok(count);
             }
         }
-
-        self.add_token(TokenType::Number, value);
     }
 
     fn handle_alpha(&mut self) {
@@ -233,30 +575,101 @@ impl Lexer {
     fn advance(&mut self) -> char {
         let current_char = self.peek();
         self.position += 1;
+        self.offset += current_char.len_utf8();
         return current_char;
     }
 
     fn peek(&self) -> char {
-        return self
-            .input
-            .chars()
-            .nth(self.position)
-            .expect("Unexpected end of input");
+        return self.input.get(self.position).copied().unwrap_or('\0');
     }
 
     fn peek_next(&self) -> char {
-        return self
-            .input
-            .chars()
-            .nth(self.position + 1)
-            .expect("Unexpected end of input");
+        return self.input.get(self.position + 1).copied().unwrap_or('\0');
+    }
+
+    fn new_line(&mut self) {
+        self.line_number += 1;
+        self.line_start = self.position;
+    }
+
+    fn column(&self) -> u32 {
+        (self.position - self.line_start) as u32 + 1
+    }
+
+    /// The span of the character `peek` returns.
+    fn char_span(&self) -> Span {
+        Span::new(
+            self.offset,
+            self.offset + self.peek().len_utf8(),
+            self.line_number,
+            self.column(),
+        )
+    }
+
+    fn current_span(&self) -> Span {
+        Span::new(
+            self.start.offset,
+            self.offset,
+            self.start.line,
+            self.start.column,
+        )
     }
 
     fn add_token(&mut self, token_type: TokenType, lexeme: String) {
-        self.tokens.push(Token {
+        let token = self.token(token_type, lexeme);
+        self.tokens.push(token);
+    }
+
+    fn add_comment(&mut self, lexeme: String) {
+        let token = self.token(TokenType::Comment, lexeme);
+        self.comments.push(token);
+    }
+
+    fn token(&self, token_type: TokenType, lexeme: String) -> Token {
+        Token {
             token_type,
             lexeme,
-            line: self.line_number,
-        })
+            line: self.start.line,
+            column: self.start.column,
+            start: self.start.offset,
+            end: self.offset,
+        }
     }
 }
+
+/// The value of an int literal the lexer accepted, or `None` if it does not
+/// fit in an `i64`.
+pub fn parse_int(lexeme: &str) -> Option<i64> {
+    let digits = lexeme.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x") => 16,
+        Some("0b") => 2,
+        Some("0o") => 8,
+        _ => return digits.parse().ok(),
+    };
+
+    i64::from_str_radix(&digits[2..], radix).ok()
+}
+
+/// The value of an int literal the lexer accepted, however large.
+pub fn parse_bigint(lexeme: &str) -> Option<BigInt> {
+    let digits = lexeme.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x") => 16,
+        Some("0b") => 2,
+        Some("0o") => 8,
+        _ => return digits.parse().ok(),
+    };
+
+    BigInt::parse_bytes(&digits.as_bytes()[2..], radix)
+}
+
+/// The value of a float literal the lexer accepted.
+pub fn parse_float(lexeme: &str) -> Option<f64> {
+    lexeme.replace('_', "").parse().ok()
+}
+
+/// The value of a decimal literal the lexer accepted.
+pub fn parse_decimal(lexeme: &str) -> Option<Decimal> {
+    lexeme.replace('_', "").strip_suffix('d')?.parse().ok()
+}
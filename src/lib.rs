@@ -0,0 +1,29 @@
+#![allow(
+    clippy::needless_return,
+    clippy::enum_variant_names,
+    clippy::upper_case_acronyms,
+    clippy::match_like_matches_macro,
+    clippy::box_collection,
+    clippy::new_without_default
+)]
+
+//! The jive language as a library. `Engine` is the way in for embedding;
+//! the phases it is built from are public for tools that need them alone.
+
+pub mod arity;
+pub mod compiler;
+pub mod constant;
+pub mod diagnostic;
+mod engine;
+pub mod lexer;
+pub mod parser;
+pub mod shape;
+pub mod token;
+pub mod visitors;
+
+pub use diagnostic::Diagnostic;
+pub use engine::{parse, Engine, Error, ErrorKind};
+pub use visitors::interpreter::convert::{FromValue, HostFunction, IntoReturn, IntoValue};
+pub use visitors::interpreter::decimal::Decimal;
+pub use visitors::interpreter::value::Value;
+pub use visitors::type_checker::types::Type;
@@ -1,34 +1,423 @@
-mod lexer;
-mod parser;
-mod token;
-mod visitors;
+mod repl;
 
-use std::{env, fs};
+use std::{
+    env, fs,
+    io::{self, Read},
+    panic,
+    path::Path,
+    process, thread,
+};
 
-use lexer::Lexer;
-use parser::Parser;
+use jive::{
+    compiler::{self, chunk::Program},
+    lexer::Lexer,
+    parser::{statement::Statement, Parser},
+    token::Token,
+    visitors::{ast_printer::AstPrinter, formatter::Formatter},
+    Diagnostic, Engine, ErrorKind,
+};
+
+// Exit codes, following sysexits(3) where one fits.
+const EXIT_UNFORMATTED: i32 = 1;
+const EXIT_USAGE: i32 = 64;
+const EXIT_SYNTAX: i32 = 65;
+const EXIT_NO_INPUT: i32 = 66;
+const EXIT_TYPE: i32 = 67;
+const EXIT_RUNTIME: i32 = 70;
+const EXIT_CANT_WRITE: i32 = 73;
+
+/// The parser, the checkers and the tree-walking interpreter all recurse, so
+/// programs run on a thread with room for deeply nested source as well as
+/// `MAX_CALL_DEPTH` calls.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+const USAGE: &str = "\
+usage: jive                                      start the repl
+       jive [run] [options] <input> [args...]    run a program
+       jive check [--no-typecheck] <input>       report errors without running
+       jive tokens <input>                       print the tokens
+       jive ast <input>                          print the syntax tree
+       jive fmt [--check | --write] <input>      print the program reformatted
+       jive compile [options] <input> -o <file>  write a .jvc module
+       jive disassemble <input>                  print the compiled bytecode
+
+<input> is a file, `-` for stdin, or `-e <code>`; run reads stdin without one.
+The script sees [args...] as the list `args`.
+
+options:
+  --backend <interpreter|vm>  how to run the program (default interpreter)
+  --no-typecheck              skip the type checker
+  --check                     exit with 1 if the input is not formatted
+  --write                     reformat the input file in place
+
+exit codes: 1 not formatted, 64 bad usage, 65 syntax error, 66 unreadable input,
+            67 type error, 70 runtime error, 73 could not write output";
+
+#[derive(Clone, Copy, PartialEq)]
+enum Backend {
+    Interpreter,
+    Vm,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Run,
+    Check,
+    Tokens,
+    Ast,
+    Fmt,
+    Compile,
+    Disassemble,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FmtMode {
+    Print,
+    Check,
+    Write,
+}
+
+enum Input {
+    File(String),
+    Inline(String),
+    Stdin,
+}
+
+impl Input {
+    /// How diagnostics refer to the input.
+    fn name(&self) -> &str {
+        match self {
+            Input::File(path) => path,
+            Input::Inline(_) => "<eval>",
+            Input::Stdin => "<stdin>",
+        }
+    }
+
+    fn read(&self) -> io::Result<Vec<u8>> {
+        match self {
+            Input::File(path) => fs::read(path),
+            Input::Inline(code) => Ok(code.clone().into_bytes()),
+            Input::Stdin => {
+                let mut bytes = vec![];
+                io::stdin().read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
+struct Options {
+    command: Command,
+    input: Input,
+    backend: Backend,
+    typecheck: bool,
+    fmt: FmtMode,
+    output: Option<String>,
+    args: Vec<String>,
+}
+
+/// Why a command failed: the diagnostics to report and the code to exit with.
+struct Failure {
+    code: i32,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Failure {
+    fn new(code: i32, diagnostics: Vec<Diagnostic>) -> Failure {
+        Failure { code, diagnostics }
+    }
+
+    fn with(code: i32) -> impl Fn(Diagnostic) -> Failure {
+        move |diagnostic| Failure::new(code, vec![diagnostic])
+    }
+}
+
+impl From<jive::Error> for Failure {
+    fn from(error: jive::Error) -> Failure {
+        let code = match error.kind {
+            ErrorKind::Syntax => EXIT_SYNTAX,
+            ErrorKind::Type => EXIT_TYPE,
+            ErrorKind::Runtime => EXIT_RUNTIME,
+        };
+        Failure::new(code, error.diagnostics)
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let cli = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(cli)
+        .expect("Could not start the main thread");
+    if let Err(panic) = cli.join() {
+        panic::resume_unwind(panic);
+    }
+}
+
+fn cli() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        repl::Repl::new().run();
+        return;
+    }
 
-    let filename = &args[1];
-    let code = fs::read_to_string(filename).expect("Should have been able to read the file");
+    let options = match parse_args(&args) {
+        Some(options) => options,
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let name = options.input.name();
 
-    let mut lexer = Lexer::new(code);
-    lexer.lex();
-    for token in lexer.tokens.iter() {
-        println!("{:?}", token);
+    let bytes = match options.input.read() {
+        Ok(bytes) => bytes,
+        Err(error) => {
+            eprintln!("error: could not read {}: {}", name, error);
+            process::exit(EXIT_NO_INPUT);
+        }
+    };
+
+    // Compiled modules have no source to show in diagnostics.
+    let (code, result) = if bytes.starts_with(compiler::module::MAGIC) {
+        let result = Program::from_bytes(&bytes)
+            .map_err(Failure::with(EXIT_SYNTAX))
+            .and_then(|program| execute_module(program, &options));
+        (String::new(), result)
+    } else {
+        let code = match String::from_utf8(bytes) {
+            Ok(code) => code,
+            Err(_) => {
+                eprintln!("error: {} is not valid utf-8", name);
+                process::exit(EXIT_SYNTAX);
+            }
+        };
+        let result = execute(&code, &options);
+        (code, result)
+    };
+
+    if let Err(failure) = result {
+        for diagnostic in failure.diagnostics {
+            eprint!("{}", diagnostic.in_file(name).render(&code));
+        }
+        process::exit(failure.code);
+    }
+}
+
+/// Reads `jive [command] [flags] <input> [args...]`. Without a command the
+/// arguments are run. Only `run` takes arguments after its input; they are
+/// handed to the script.
+fn parse_args(args: &[String]) -> Option<Options> {
+    let (command, mut rest) = match args[0].as_str() {
+        "run" => (Command::Run, &args[1..]),
+        "check" => (Command::Check, &args[1..]),
+        "tokens" => (Command::Tokens, &args[1..]),
+        "ast" => (Command::Ast, &args[1..]),
+        "fmt" => (Command::Fmt, &args[1..]),
+        "compile" => (Command::Compile, &args[1..]),
+        "disassemble" => (Command::Disassemble, &args[1..]),
+        _ => (Command::Run, args),
+    };
+
+    let mut input = None;
+    let mut backend = Backend::Interpreter;
+    let mut typecheck = true;
+    let mut fmt = FmtMode::Print;
+    let mut output = None;
+    let mut script_args = vec![];
+
+    while let Some((arg, tail)) = rest.split_first() {
+        rest = tail;
+
+        if input.is_some() && command == Command::Run {
+            script_args.push(arg.clone());
+            script_args.extend_from_slice(rest);
+            break;
+        }
+
+        match (arg.as_str(), command) {
+            ("--backend", Command::Run) => {
+                let (value, tail) = rest.split_first()?;
+                rest = tail;
+                backend = match value.as_str() {
+                    "interpreter" => Backend::Interpreter,
+                    "vm" => Backend::Vm,
+                    _ => return None,
+                };
+            }
+            ("--no-typecheck", Command::Run | Command::Check | Command::Compile) => {
+                typecheck = false
+            }
+            ("--check", Command::Fmt) => fmt = FmtMode::Check,
+            ("--write", Command::Fmt) => fmt = FmtMode::Write,
+            ("-o", Command::Compile) => {
+                let (value, tail) = rest.split_first()?;
+                rest = tail;
+                output = Some(value.clone());
+            }
+            _ if input.is_some() => return None,
+            ("-e", _) => {
+                let (value, tail) = rest.split_first()?;
+                rest = tail;
+                input = Some(Input::Inline(value.clone()));
+            }
+            ("-", _) => input = Some(Input::Stdin),
+            (flag, _) if flag.starts_with('-') => return None,
+            (path, _) => input = Some(Input::File(path.to_string())),
+        }
+    }
+
+    let input = input.unwrap_or(Input::Stdin);
+    if fmt == FmtMode::Write && !matches!(input, Input::File(_)) {
+        return None;
+    }
+    if command == Command::Compile && output.is_none() && !matches!(input, Input::File(_)) {
+        return None;
+    }
+
+    Some(Options {
+        command,
+        input,
+        backend,
+        typecheck,
+        fmt,
+        output,
+        args: script_args,
+    })
+}
+
+/// Carries out a command on source code.
+fn execute(code: &str, options: &Options) -> Result<(), Failure> {
+    let mut engine = Engine::new();
+    engine.set_type_checking(options.typecheck);
+
+    match options.command {
+        Command::Run if options.backend == Backend::Interpreter => {
+            engine.set_args(&options.args);
+            let statements = check(&mut engine, code, options)?;
+            engine.execute(&statements).map_err(Failure::from)?;
+        }
+        Command::Check => {
+            check(&mut engine, code, options)?;
+        }
+        Command::Tokens => {
+            for token in lex(code)?.tokens {
+                let line = format!(
+                    "{:>4}:{:<4} {:<12} {}",
+                    token.line,
+                    token.column,
+                    format!("{:?}", token.token_type),
+                    token.lexeme
+                );
+                println!("{}", line.trim_end());
+            }
+        }
+        Command::Ast => {
+            let statements = jive::parse(code).map_err(Failure::from)?;
+            AstPrinter::new().print(&statements);
+        }
+        Command::Fmt => format(code, options)?,
+        Command::Run | Command::Compile | Command::Disassemble => {
+            let statements = check(&mut engine, code, options)?;
+            let program = compiler::Compiler::new()
+                .compile(&statements)
+                .map_err(Failure::with(EXIT_TYPE))?;
+            execute_module(program, options)?;
+        }
     }
 
-    let mut parser = Parser::new(lexer.tokens);
-    let statements = parser.parse();
+    Ok(())
+}
+
+/// Checks a program, reporting any warnings on stderr.
+fn check(engine: &mut Engine, code: &str, options: &Options) -> Result<Vec<Statement>, Failure> {
+    let statements = engine.check(code).map_err(Failure::from)?;
+    for warning in engine.take_warnings() {
+        eprint!("{}", warning.in_file(options.input.name()).render(code));
+    }
+
+    Ok(statements)
+}
+
+/// Carries out a command on an already compiled program.
+fn execute_module(program: Program, options: &Options) -> Result<(), Failure> {
+    match options.command {
+        Command::Run => {
+            let mut vm = compiler::vm::Vm::new();
+            vm.set_args(&options.args);
+            vm.run(program).map_err(Failure::with(EXIT_RUNTIME))?;
+        }
+        Command::Compile => {
+            let output = match (&options.output, &options.input) {
+                (Some(output), _) => output.clone(),
+                (None, input) => Path::new(input.name())
+                    .with_extension("jvc")
+                    .display()
+                    .to_string(),
+            };
+            fs::write(&output, program.to_bytes()).map_err(|error| {
+                Failure::new(
+                    EXIT_CANT_WRITE,
+                    vec![Diagnostic::error(format!(
+                        "could not write {}: {}",
+                        output, error
+                    ))],
+                )
+            })?;
+        }
+        Command::Disassemble => print!("{}", compiler::disassembler::disassemble(&program)),
+        Command::Check | Command::Tokens | Command::Ast | Command::Fmt => {
+            return Err(Failure::new(
+                EXIT_USAGE,
+                vec![Diagnostic::error(
+                    "Compiled modules can only be run or disassembled",
+                )],
+            ));
+        }
+    }
+
+    Ok(())
+}
 
-    let mut ast_printer = visitors::ast_printer::AstPrinter::new();
-    ast_printer.print(&statements);
+fn lex(code: &str) -> Result<Lexer, Failure> {
+    let mut lexer = Lexer::new(code.to_string());
+    lexer.lex().map_err(Failure::with(EXIT_SYNTAX))?;
 
-    let mut type_checker = visitors::type_checker::TypeChecker::new();
-    type_checker.check(&statements);
+    Ok(lexer)
+}
+
+fn parse(tokens: Vec<Token>) -> Result<Vec<Statement>, Failure> {
+    let (statements, errors) = Parser::new(tokens).parse();
+    if !errors.is_empty() {
+        return Err(Failure::new(EXIT_SYNTAX, errors));
+    }
+
+    Ok(statements)
+}
+
+fn format(code: &str, options: &Options) -> Result<(), Failure> {
+    let lexer = lex(code)?;
+    let statements = parse(lexer.tokens.clone())?;
+    let formatted = Formatter::new(code, &lexer.comments).format(&statements);
+
+    match (options.fmt, &options.input) {
+        (FmtMode::Print, _) => print!("{}", formatted),
+        (FmtMode::Check, input) if formatted != code => {
+            eprintln!("{} is not formatted", input.name());
+            return Err(Failure::new(EXIT_UNFORMATTED, vec![]));
+        }
+        (FmtMode::Write, Input::File(path)) if formatted != code => {
+            fs::write(path, formatted).map_err(|error| {
+                Failure::new(
+                    EXIT_CANT_WRITE,
+                    vec![Diagnostic::error(format!(
+                        "could not write {}: {}",
+                        path, error
+                    ))],
+                )
+            })?;
+        }
+        _ => {}
+    }
 
-    let mut interpreter = visitors::interpreter::Interpreter::new();
-    interpreter.evaluate(&statements);
+    Ok(())
 }
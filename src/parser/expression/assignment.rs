@@ -1,10 +1,21 @@
+use std::cell::Cell;
+
 use super::Expression;
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use crate::{
+    diagnostic::Span,
+    parser::accept::Accept,
+    token::Token,
+    visitors::{resolver::Resolution, Visitor},
+};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Assignment {
     pub identifier: Token,
+    /// The arithmetic operator of a compound assignment such as `+=`.
+    pub operator: Option<Token>,
     pub value: Expression,
+    pub span: Span,
+    pub resolution: Cell<Resolution>,
 }
 
 impl Accept for Assignment {
@@ -0,0 +1,28 @@
+use super::Expression;
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
+
+/// `&`, `|`, `^`, `<<` and `>>`, which work on whole numbers.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Bitwise {
+    pub left: Expression,
+    pub operator: Token,
+    pub right: Expression,
+    pub span: Span,
+}
+
+impl Bitwise {
+    pub fn new(left: Expression, operator: Token, right: Expression) -> Bitwise {
+        let span = left.span().to(right.span());
+        Bitwise {
+            left,
+            operator,
+            right,
+            span,
+        }
+    }
+}
+impl Accept for Bitwise {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_bitwise(self)
+    }
+}
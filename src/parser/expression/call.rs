@@ -1,10 +1,30 @@
 use super::Expression;
-use crate::{parser::accept::Accept, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Call {
     pub identifier: Expression,
-    pub arguments: Vec<Expression>,
+    /// Positional arguments first, then any named ones.
+    pub arguments: Vec<Argument>,
+    pub span: Span,
+}
+
+impl Call {
+    /// The names of the named arguments, in the order they're passed.
+    pub fn names(&self) -> Vec<String> {
+        self.arguments
+            .iter()
+            .filter_map(|argument| argument.name.as_ref())
+            .map(|name| name.lexeme.clone())
+            .collect()
+    }
+}
+
+/// `value`, or `name: value` to pass the parameter called `name`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Argument {
+    pub name: Option<Token>,
+    pub value: Expression,
 }
 
 impl Accept for Call {
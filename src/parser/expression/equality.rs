@@ -1,19 +1,22 @@
 use super::Expression;
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Equality {
     pub left: Expression,
     pub operator: Token,
     pub right: Expression,
+    pub span: Span,
 }
 
 impl Equality {
     pub fn new(left: Expression, operator: Token, right: Expression) -> Equality {
+        let span = left.span().to(right.span());
         Equality {
             left,
             operator,
             right,
+            span,
         }
     }
 }
@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     visitors::Visitor,
 };
@@ -8,6 +9,7 @@ pub struct IfExpression {
     pub condition: Expression,
     pub then_branch: Expression,
     pub else_branch: Expression,
+    pub span: Span,
 }
 
 impl Accept for IfExpression {
@@ -1,10 +1,11 @@
 use super::Expression;
-use crate::{parser::accept::Accept, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Index {
     pub list: Expression,
     pub expression: Expression,
+    pub span: Span,
 }
 
 impl Accept for Index {
@@ -1,11 +1,14 @@
 use super::Expression;
-use crate::{parser::accept::Accept, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct IndexAssignment {
     pub list: Expression,
     pub expression: Expression,
+    /// The arithmetic operator of a compound assignment such as `+=`.
+    pub operator: Option<Token>,
     pub value: Expression,
+    pub span: Span,
 }
 
 impl Accept for IndexAssignment {
@@ -0,0 +1,22 @@
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, expression::Expression},
+    token::Token,
+    visitors::Visitor,
+};
+
+/// A backtick string with embedded `${...}` expressions. There is one more
+/// string part than there are expressions, and they alternate, starting
+/// with a string part.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Interpolation {
+    pub parts: Vec<Token>,
+    pub expressions: Vec<Expression>,
+    pub span: Span,
+}
+
+impl Accept for Interpolation {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_interpolation(self)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, parameter::Parameter, statement::Statement},
+    visitors::Visitor,
+};
+
+/// An anonymous function. Written `funk (a, b) { ... }`, the body is a block;
+/// written `|a, b| a + b`, it is a `return` of the expression.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Lambda {
+    pub parameters: Vec<Parameter>,
+    pub body: Statement,
+    /// Written `funk* (a) { ... }`.
+    pub generator: bool,
+    pub span: Span,
+}
+
+impl Lambda {
+    /// Whether this was written with the concise `|a| a * 2` syntax.
+    pub fn is_arrow(&self) -> bool {
+        !matches!(self.body, Statement::Block(_))
+    }
+}
+
+impl Accept for Lambda {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_lambda(self)
+    }
+}
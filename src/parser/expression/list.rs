@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     visitors::Visitor,
 };
@@ -6,6 +7,7 @@ use crate::{
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct List {
     pub values: Vec<Expression>,
+    pub span: Span,
 }
 
 impl Accept for List {
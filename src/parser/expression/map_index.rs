@@ -1,10 +1,11 @@
 use super::Expression;
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct MapIndex {
     pub map: Expression,
     pub key: Token,
+    pub span: Span,
 }
 
 impl Accept for MapIndex {
@@ -0,0 +1,30 @@
+use super::Expression;
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, pattern::Pattern},
+    visitors::Visitor,
+};
+
+/// `match value { pattern => expression, ... }`: the expression of the
+/// first arm whose pattern accepts the value and whose guard, if any, holds.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Match {
+    pub value: Expression,
+    pub arms: Vec<Arm>,
+    pub span: Span,
+}
+
+/// `pattern if guard => body`. The pattern's bindings are in scope in both
+/// the guard and the body.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Arm {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Expression,
+}
+
+impl Accept for Match {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_match(self)
+    }
+}
@@ -1,5 +1,6 @@
 pub mod and;
 pub mod assignment;
+pub mod bitwise;
 pub mod call;
 pub mod comparison;
 pub mod equality;
@@ -7,34 +8,45 @@ pub mod factor;
 pub mod if_expression;
 pub mod index;
 pub mod index_assignment;
+pub mod interpolation;
+pub mod lambda;
 pub mod list;
 pub mod map_index;
 pub mod map_index_assignment;
+pub mod match_expression;
 pub mod or;
+pub mod power;
 pub mod primary;
+pub mod propagate;
 pub mod record;
 pub mod term;
 pub mod unary;
 
 pub use and::And;
 pub use assignment::Assignment;
-pub use call::Call;
+pub use bitwise::Bitwise;
+pub use call::{Argument, Call};
 pub use comparison::Comparison;
 pub use equality::Equality;
 pub use factor::Factor;
 pub use if_expression::IfExpression;
 pub use index::Index;
 pub use index_assignment::IndexAssignment;
+pub use interpolation::Interpolation;
+pub use lambda::Lambda;
 pub use list::List;
 pub use map_index::MapIndex;
 pub use map_index_assignment::MapIndexAssignment;
+pub use match_expression::{Arm, Match};
 pub use or::Or;
+pub use power::Power;
 pub use primary::Primary;
+pub use propagate::Propagate;
 pub use record::Record;
 pub use term::Term;
 pub use unary::Unary;
 
-use crate::visitors::Visitor;
+use crate::{diagnostic::Span, visitors::Visitor};
 
 use super::accept::Accept;
 
@@ -57,6 +69,12 @@ pub enum Expression {
     MapIndex(Box<MapIndex>),
     MapIndexAssignment(Box<MapIndexAssignment>),
     IndexAssignment(Box<IndexAssignment>),
+    Lambda(Box<Lambda>),
+    Bitwise(Box<Bitwise>),
+    Power(Box<Power>),
+    Interpolation(Box<Interpolation>),
+    Propagate(Box<Propagate>),
+    Match(Box<Match>),
 }
 
 impl Accept for Expression {
@@ -81,6 +99,42 @@ impl Accept for Expression {
                 map_index_assignment.accept(visitor)
             }
             Expression::IndexAssignment(index_assignment) => index_assignment.accept(visitor),
+            Expression::Lambda(lambda) => lambda.accept(visitor),
+            Expression::Bitwise(bitwise) => bitwise.accept(visitor),
+            Expression::Power(power) => power.accept(visitor),
+            Expression::Interpolation(interpolation) => interpolation.accept(visitor),
+            Expression::Propagate(propagate) => propagate.accept(visitor),
+            Expression::Match(match_expression) => match_expression.accept(visitor),
+        }
+    }
+}
+
+impl Expression {
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Assignment(assignment) => assignment.span,
+            Expression::Equality(equality) => equality.span,
+            Expression::Comparison(comparison) => comparison.span,
+            Expression::Term(term) => term.span,
+            Expression::Factor(factor) => factor.span,
+            Expression::Unary(unary) => unary.span,
+            Expression::Primary(primary) => primary.span,
+            Expression::IfExpression(cond) => cond.span,
+            Expression::Or(or) => or.span,
+            Expression::And(and) => and.span,
+            Expression::Call(call) => call.span,
+            Expression::List(list) => list.span,
+            Expression::Index(index) => index.span,
+            Expression::Record(record) => record.span,
+            Expression::MapIndex(map_index) => map_index.span,
+            Expression::MapIndexAssignment(map_index_assignment) => map_index_assignment.span,
+            Expression::IndexAssignment(index_assignment) => index_assignment.span,
+            Expression::Lambda(lambda) => lambda.span,
+            Expression::Bitwise(bitwise) => bitwise.span,
+            Expression::Power(power) => power.span,
+            Expression::Interpolation(interpolation) => interpolation.span,
+            Expression::Propagate(propagate) => propagate.span,
+            Expression::Match(match_expression) => match_expression.span,
         }
     }
 }
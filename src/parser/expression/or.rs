@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     visitors::Visitor,
 };
@@ -7,6 +8,7 @@ use crate::{
 pub struct Or {
     pub left: Expression,
     pub right: Expression,
+    pub span: Span,
 }
 
 impl Accept for Or {
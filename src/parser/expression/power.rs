@@ -0,0 +1,28 @@
+use super::Expression;
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
+
+/// `**`, which is right associative and binds tighter than unary minus.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Power {
+    pub left: Expression,
+    pub operator: Token,
+    pub right: Expression,
+    pub span: Span,
+}
+
+impl Power {
+    pub fn new(left: Expression, operator: Token, right: Expression) -> Power {
+        let span = left.span().to(right.span());
+        Power {
+            left,
+            operator,
+            right,
+            span,
+        }
+    }
+}
+impl Accept for Power {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_power(self)
+    }
+}
@@ -1,13 +1,27 @@
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use std::cell::Cell;
+
+use crate::{
+    diagnostic::Span,
+    parser::accept::Accept,
+    token::Token,
+    visitors::{resolver::Resolution, Visitor},
+};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Primary {
     pub value: Token,
+    pub span: Span,
+    pub resolution: Cell<Resolution>,
 }
 
 impl Primary {
     pub fn new(value: Token) -> Primary {
-        Primary { value }
+        let span = value.span();
+        Primary {
+            value,
+            span,
+            resolution: Cell::default(),
+        }
     }
 }
 
@@ -0,0 +1,16 @@
+use super::Expression;
+use crate::{diagnostic::Span, parser::accept::Accept, visitors::Visitor};
+
+/// `value?`: the value inside an `ok` or `some`, or else an early return of
+/// the `err` or `none` from the enclosing function.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Propagate {
+    pub expression: Expression,
+    pub span: Span,
+}
+
+impl Accept for Propagate {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_propagate(self)
+    }
+}
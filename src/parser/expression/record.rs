@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     token::Token,
     visitors::Visitor,
@@ -7,6 +8,7 @@ use crate::{
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Record {
     pub key_values: Vec<(Token, Expression)>,
+    pub span: Span,
 }
 
 impl Accept for Record {
@@ -1,15 +1,21 @@
 use super::Expression;
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Unary {
     pub operator: Token,
     pub right: Expression,
+    pub span: Span,
 }
 
 impl Unary {
     pub fn new(operator: Token, right: Expression) -> Unary {
-        Unary { operator, right }
+        let span = operator.span().to(right.span());
+        Unary {
+            operator,
+            right,
+            span,
+        }
     }
 }
 impl Accept for Unary {
@@ -1,49 +1,87 @@
 /*
 program → declaration* EOF ;
-statement → printStatement | expressionStatement | ifStatement | block | whileStatement | returnStatement | wihtStatement | forStatement ;
-forStatement → "for" expression "in" expression statement;
+statement → printStatement | expressionStatement | ifStatement | block | whileStatement | returnStatement | wihtStatement | forStatement | breakStatement | continueStatement | labelledStatement | yieldStatement | tryStatement | throwStatement ;
+labelledStatement → IDENTIFIER ":" ( whileStatement | forStatement ) ;
+breakStatement → "break" IDENTIFIER? ";" ;
+continueStatement → "continue" IDENTIFIER? ";" ;
+forStatement → "for" binding "in" expression statement;
 withStatement → "with" expression "as" expression statement;
+yieldStatement → "yield" expression ";" ;
+tryStatement → "try" block ( "catch" "(" IDENTIFIER ")" block )? ( "finally" block )? ;
+throwStatement → "throw" expression ";" ;
 declaration → functionDeclaration | variableDeclaration |  statement ;
-variableDeclaration → "make" IDENTIFIER ( "=" expression )? ";" ;
-functionDeclaration → "funk" function ;
+variableDeclaration → "make" IDENTIFIER ( "=" expression )? ";" | "make" binding "=" expression ";" ;
+functionDeclaration → "funk" "*"? function ;
 function → IDENTIFIER "(" parameters? ")" block ;
 whileStatement → "while" expression statement ;
 ifStatement → "if"  expression  statement ( "else"  statement )? ;
 block → "{" declaration* "}" ;
 expression → assignment ;
-assignment → (call ("." | "[" NUMBER "]"))? identifier "=" assignment | ifExpression ;
+assignment → (call ("." | "[" NUMBER "]"))? identifier ( "=" | "+=" | "-=" | "*=" | "/=" | "%=" ) assignment | ifExpression ;
 ifExpression → expression "if" expression "else" expression | equality ;
 or → and ( "||" and )* | and;
 and → equality ( "&&" equality )* | equality;
 equality → comparison ( ( "!=" | "==" ) comparison )* ;
-comparison → term ( ( ">" | ">=" | "<" | "<=" ) term )* ;
+comparison → bitOr ( ( ">" | ">=" | "<" | "<=" ) bitOr )* ;
+bitOr → bitXor ( "|" bitXor )* ;
+bitXor → bitAnd ( "^" bitAnd )* ;
+bitAnd → shift ( "&" shift )* ;
+shift → term ( ( "<<" | ">>" ) term )* ;
 term → factor ( ( "-" | "+" ) factor )* ;
-factor → unary ( ( "/" | "*" ) unary )* ;
-unary → ("!"|"-") unary
-        | call ;
-call → primary ( "(" arguments? ")" )* ;
+factor → unary ( ( "/" | "*" | "%" | "//" ) unary )* ;
+unary → ("!"|"-"|"~") unary
+        | power ;
+power → call ( "**" unary )? ;
+call → primary ( "(" arguments? ")" | "[" expression "]" | "." IDENTIFIER | "?" )* ( "++" | "--" )? ;
+arguments → argument ( "," argument )* ;
+argument → ( IDENTIFIER ":" )? expression ;
 primary → NUMBER | STRING | "true" | "false" | "nil"
-        | "(" expression ")" ;
+        | "(" expression ")" | lambda | match | interpolation ;
+interpolation → INTERPOLATION expression ( INTERPOLATION expression )* STRING ;
+match → "match" expression "{" ( arm ( "," arm )* ","? )? "}" ;
+arm → pattern ( "if" expression )? "=>" expression ;
+pattern → singlePattern ( "|" singlePattern )* ;
+singlePattern → "_" | "-"? NUMBER | STRING | "true" | "false" | "nil" | IDENTIFIER
+        | ( "ok" | "err" | "some" ) "(" pattern ")" | "none"
+        | listPattern | recordPattern ;
+listPattern → "[" ( pattern ( "," pattern )* )? ( ","? ".." IDENTIFIER? )? "]" ;
+recordPattern → "{" ( IDENTIFIER ( ":" pattern )? ( "," IDENTIFIER ( ":" pattern )? )* ","? )? "}" ;
+binding → IDENTIFIER | listPattern | recordPattern ;
+parameters → parameter ( "," parameter )* ;
+parameter → binding ( "=" expression )? | "..." IDENTIFIER ;
+lambda → "funk" "*"? "(" parameters? ")" block | "|" parameters? "|" expression ;
  */
 
-use crate::token::{Token, TokenType};
+use std::cell::Cell;
+
+use crate::{
+    diagnostic::{Diagnostic, Span},
+    token::{Token, TokenType},
+};
 
 pub mod accept;
 pub mod expression;
+pub mod parameter;
+pub mod pattern;
 pub mod statement;
 
 use expression::{
-    And, Assignment, Call, Comparison, Equality, Expression, Factor, IfExpression, Index,
-    IndexAssignment, List, MapIndex, MapIndexAssignment, Or, Primary, Record, Term, Unary,
+    And, Argument, Arm, Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor,
+    IfExpression, Index, IndexAssignment, Interpolation, Lambda, List, MapIndex,
+    MapIndexAssignment, Match, Or, Power, Primary, Propagate, Record, Term, Unary,
 };
+use parameter::Parameter;
+use pattern::{Pattern, Rest};
 use statement::{
-    Block, ExpressionStatement, For, FunctionDeclaration, IfStatement, PrintStatement, Return,
-    Statement, VariableDeclaration, WhileStatement, With,
+    Block, Break, Catch, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+    PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement, With,
+    Yield,
 };
 
 pub struct Parser {
     tokens: Vec<Token>,
     position: usize,
+    errors: Vec<Diagnostic>,
 }
 
 impl Parser {
@@ -51,345 +89,776 @@ impl Parser {
         Parser {
             tokens,
             position: 0,
+            errors: vec![],
         }
     }
 
-    pub fn parse(&mut self) -> Vec<Statement> {
+    /// Parses every declaration in the token stream. Malformed declarations
+    /// are left out of the returned statements and reported as errors, so
+    /// one bad statement doesn't hide the ones after it.
+    pub fn parse(&mut self) -> (Vec<Statement>, Vec<Diagnostic>) {
         let mut statements = vec![];
 
         while !self.is_at_end() {
-            statements.push(self.declaration());
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
-        return statements;
+        return (statements, std::mem::take(&mut self.errors));
     }
 
-    fn declaration(&mut self) -> Statement {
-        match self.peek().token_type {
-            TokenType::Make => return self.declaration_statement(),
-            TokenType::Funk => return self.function_declaration(),
-            _ => return self.statement(),
+    fn declaration(&mut self) -> Option<Statement> {
+        let start = self.position;
+
+        let result = match self.peek().token_type {
+            TokenType::Make => self.declaration_statement(),
+            // `funk (` and `funk* (` start a lambda, in an expression statement.
+            TokenType::Funk if !self.is_lambda() => self.function_declaration(),
+            _ => self.statement(),
+        };
+
+        match result {
+            Ok(statement) => Some(statement),
+            Err(diagnostic) => {
+                self.errors.push(diagnostic);
+                self.synchronize(start);
+                None
+            }
+        }
+    }
+
+    /// Skips tokens until the start of the next statement: just past a `;`,
+    /// or at a `}` or a keyword that begins a statement. Braces opened by the
+    /// failed declaration, such as a record or a function body, are skipped
+    /// up to their closing `}` (and a `;` after it) so it isn't mistaken for
+    /// the end of an enclosing block. Always moves past `start` so a
+    /// declaration that fails on its first token can't be retried forever.
+    fn synchronize(&mut self, start: usize) {
+        let mut depth = self.tokens[start..self.position]
+            .iter()
+            .fold(0, |depth: usize, token| match token.token_type {
+                TokenType::LBrace => depth + 1,
+                TokenType::RBrace => depth.saturating_sub(1),
+                _ => depth,
+            });
+
+        while !self.is_at_end() {
+            if depth > 0 {
+                match self.advance().token_type {
+                    TokenType::LBrace => depth += 1,
+                    TokenType::RBrace => depth -= 1,
+                    _ => continue,
+                }
+                if depth == 0 {
+                    if self.peek().token_type == TokenType::Semicolon {
+                        self.advance();
+                    }
+                    return;
+                }
+                continue;
+            }
+
+            if self.position > start {
+                if self.previous().token_type == TokenType::Semicolon {
+                    return;
+                }
+
+                match self.peek().token_type {
+                    TokenType::RBrace
+                    | TokenType::Make
+                    | TokenType::Funk
+                    | TokenType::If
+                    | TokenType::While
+                    | TokenType::For
+                    | TokenType::With
+                    | TokenType::Return
+                    | TokenType::Break
+                    | TokenType::Continue
+                    | TokenType::Yield
+                    | TokenType::Try
+                    | TokenType::Throw
+                    | TokenType::Print => return,
+                    _ => {}
+                }
+            }
+
+            if self.advance().token_type == TokenType::LBrace {
+                depth += 1;
+            }
         }
     }
 
-    fn statement(&mut self) -> Statement {
+    fn statement(&mut self) -> Result<Statement, Diagnostic> {
         match self.peek().token_type {
             TokenType::Print => return self.print_statement(),
             TokenType::LBrace => return self.block(),
             TokenType::If => return self.if_statement(),
             TokenType::While => return self.while_statement(),
             TokenType::Return => return self.return_statement(),
+            TokenType::Yield => return self.yield_statement(),
+            TokenType::Throw => return self.throw_statement(),
+            TokenType::Try => return self.try_statement(),
             TokenType::With => return self.with_statement(),
             TokenType::For => return self.for_statement(),
+            TokenType::Break | TokenType::Continue => return self.loop_control(),
+            TokenType::Identifier if self.peek_next().token_type == TokenType::Colon => {
+                return self.labelled_statement()
+            }
             _ => return self.expression_statement(),
         }
     }
 
-    fn for_statement(&mut self) -> Statement {
+    /// `label: while ...` or `label: for ...`.
+    fn labelled_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let label = self.advance();
         self.advance();
 
-        let identifier = self.peek();
+        let mut statement = match self.peek().token_type {
+            TokenType::While => self.while_statement()?,
+            TokenType::For => self.for_statement()?,
+            _ => return Err(self.expected("Expected a loop after label")),
+        };
+
+        match &mut statement {
+            Statement::WhileStatement(while_statement) => {
+                while_statement.span = label.span().to(while_statement.span);
+                while_statement.label = Some(label);
+            }
+            Statement::For(for_statement) => {
+                for_statement.span = label.span().to(for_statement.span);
+                for_statement.label = Some(label);
+            }
+            _ => {}
+        }
+
+        return Ok(statement);
+    }
+
+    fn loop_control(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        let label = match self.peek().token_type {
+            TokenType::Identifier => Some(self.advance()),
+            _ => None,
+        };
+
+        if self.peek().token_type != TokenType::Semicolon {
+            return Err(self.expected(format!("Expected ';' after '{}'", start.lexeme)));
+        }
+
         self.advance();
 
+        let span = self.span_from(&start);
+        if start.token_type == TokenType::Break {
+            return Ok(Statement::Break(Box::new(Break { label, span })));
+        }
+
+        return Ok(Statement::Continue(Box::new(Continue { label, span })));
+    }
+
+    fn for_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        let pattern = self.binding("Expected a name or a pattern after 'for'")?;
+
         if self.peek().token_type != TokenType::In {
-            panic!("Expected 'as' keyword after 'with' keyword");
+            return Err(self.expected("Expected 'in' keyword after 'for' identifier"));
         }
 
         self.advance();
 
-        return Statement::For(Box::new(For {
-            identifier,
-            iter: self.expression(),
-            body: self.statement(),
-        }));
+        let iter = self.expression()?;
+        let body = self.statement()?;
+
+        return Ok(Statement::For(Box::new(For {
+            label: None,
+            pattern,
+            iter,
+            body,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn with_statement(&mut self) -> Statement {
-        self.advance();
-        let value = self.expression();
+    fn with_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+        let value = self.expression()?;
 
         if self.peek().token_type != TokenType::As {
-            panic!("Expected 'as' keyword after 'with' keyword");
+            return Err(self.expected("Expected 'as' keyword after 'with' keyword"));
         }
         self.advance();
 
-        return Statement::With(Box::new(With {
+        let identifier = self.advance();
+        let body = self.statement()?;
+
+        return Ok(Statement::With(Box::new(With {
             value,
-            identifier: self.advance(),
-            body: self.statement(),
-        }));
+            identifier,
+            body,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn return_statement(&mut self) -> Statement {
-        self.advance();
+    fn return_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
 
         if self.peek().token_type == TokenType::Semicolon {
             self.advance();
-            return Statement::Return(Box::new(Return { value: None }));
+            return Ok(Statement::Return(Box::new(Return {
+                value: None,
+                span: self.span_from(&start),
+            })));
         }
 
-        let value = self.expression();
+        let value = self.expression()?;
 
         if self.peek().token_type != TokenType::Semicolon {
-            panic!("Expected ';' after return statement");
+            return Err(self.expected("Expected ';' after return statement"));
         }
 
         self.advance();
 
-        return Statement::Return(Box::new(Return { value: Some(value) }));
+        return Ok(Statement::Return(Box::new(Return {
+            value: Some(value),
+            span: self.span_from(&start),
+        })));
     }
 
-    fn while_statement(&mut self) -> Statement {
+    fn yield_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        let value = self.expression()?;
+
+        if self.peek().token_type != TokenType::Semicolon {
+            return Err(self.expected("Expected ';' after yield statement"));
+        }
+
         self.advance();
-        return Statement::WhileStatement(Box::new(WhileStatement {
-            condition: self.expression(),
-            body: self.statement(),
-        }));
+
+        return Ok(Statement::Yield(Box::new(Yield {
+            value,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn if_statement(&mut self) -> Statement {
+    fn throw_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        let value = self.expression()?;
+
+        if self.peek().token_type != TokenType::Semicolon {
+            return Err(self.expected("Expected ';' after throw statement"));
+        }
+
         self.advance();
 
-        let condition = self.expression();
-        let then_branch = self.statement();
+        return Ok(Statement::Throw(Box::new(Throw {
+            value,
+            span: self.span_from(&start),
+        })));
+    }
+
+    fn try_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        if self.peek().token_type != TokenType::LBrace {
+            return Err(self.expected("Expected '{' after 'try'"));
+        }
+        let body = self.block()?;
+
+        let mut catch = None;
+        if self.peek().token_type == TokenType::Catch {
+            self.advance();
+
+            if self.peek().token_type != TokenType::LParen {
+                return Err(self.expected("Expected '(' after 'catch'"));
+            }
+            self.advance();
+
+            if self.peek().token_type != TokenType::Identifier {
+                return Err(self.expected("Expected a name for the caught error"));
+            }
+            let identifier = self.advance();
+
+            if self.peek().token_type != TokenType::RParen {
+                return Err(self.expected("Expected ')' after the caught error's name"));
+            }
+            self.advance();
+
+            if self.peek().token_type != TokenType::LBrace {
+                return Err(self.expected("Expected '{' before catch body"));
+            }
+            let body = self.block()?;
+
+            catch = Some(Catch { identifier, body });
+        }
+
+        let mut finally = None;
+        if self.peek().token_type == TokenType::Finally {
+            self.advance();
+
+            if self.peek().token_type != TokenType::LBrace {
+                return Err(self.expected("Expected '{' after 'finally'"));
+            }
+            finally = Some(self.block()?);
+        }
+
+        if catch.is_none() && finally.is_none() {
+            return Err(self.expected("Expected 'catch' or 'finally' after try body"));
+        }
+
+        return Ok(Statement::Try(Box::new(Try {
+            body,
+            catch,
+            finally,
+            span: self.span_from(&start),
+        })));
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+        let condition = self.expression()?;
+        let body = self.statement()?;
+
+        return Ok(Statement::WhileStatement(Box::new(WhileStatement {
+            label: None,
+            condition,
+            body,
+            span: self.span_from(&start),
+        })));
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+
+        let condition = self.expression()?;
+        let then_branch = self.statement()?;
         let else_branch = if self.peek().token_type == TokenType::Else {
             self.advance();
 
-            let else_branch = self.statement();
+            let else_branch = self.statement()?;
 
             Some(Box::new(else_branch))
         } else {
             None
         };
 
-        return Statement::IfStatement(Box::new(IfStatement {
+        return Ok(Statement::IfStatement(Box::new(IfStatement {
             condition,
             then_branch: Box::new(then_branch),
             else_branch,
-        }));
+            span: self.span_from(&start),
+        })));
     }
 
-    fn block(&mut self) -> Statement {
-        self.advance();
+    fn block(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
         let mut statements = vec![];
 
         while self.peek().token_type != TokenType::RBrace && !self.is_at_end() {
-            statements.push(self.declaration());
+            if let Some(statement) = self.declaration() {
+                statements.push(statement);
+            }
         }
 
         if self.peek().token_type != TokenType::RBrace {
-            panic!("Expected '}}' after block");
+            return Err(self.expected("Expected '}' after block"));
         }
 
         self.advance();
 
-        return Statement::Block(Box::new(Block { statements }));
+        return Ok(Statement::Block(Box::new(Block {
+            statements,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn expression_statement(&mut self) -> Statement {
-        let expression = self.expression();
+    fn expression_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.peek();
+        let expression = self.expression()?;
 
         if self.peek().token_type != TokenType::Semicolon {
-            panic!("Expected ';' after expression at line {}", self.peek().line);
+            return Err(self.expected("Expected ';' after expression"));
         }
 
         self.advance();
 
-        return Statement::ExpressionStatement(Box::new(ExpressionStatement { expression }));
+        return Ok(Statement::ExpressionStatement(Box::new(
+            ExpressionStatement {
+                expression,
+                span: self.span_from(&start),
+            },
+        )));
     }
 
-    fn print_statement(&mut self) -> Statement {
-        self.advance();
-        let expression = self.expression();
+    fn print_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+        let expression = self.expression()?;
 
         if self.peek().token_type != TokenType::Semicolon {
-            panic!("Expected ';' after expression");
+            return Err(self.expected("Expected ';' after expression"));
         }
 
         self.advance();
 
-        return Statement::PrintStatement(Box::new(PrintStatement { expression }));
+        return Ok(Statement::PrintStatement(Box::new(PrintStatement {
+            expression,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn function_declaration(&mut self) -> Statement {
-        self.advance();
+    fn function_declaration(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
+        let generator = self.generator_marker();
 
         let identifier = self.advance();
 
         if self.peek().token_type != TokenType::LParen {
-            panic!("Expected '(' after function identifier");
+            return Err(self.expected("Expected '(' after function identifier"));
         }
 
         self.advance();
 
-        let mut parameters = Vec::<Token>::new();
+        let parameters = self.parameters(TokenType::RParen)?;
 
         if self.peek().token_type != TokenType::RParen {
-            parameters.push(self.advance());
+            return Err(self.expected("Expected ')' after parameters"));
+        }
 
-            while self.peek().token_type == TokenType::Comma {
-                self.advance();
-                parameters.push(self.advance());
+        self.advance();
+
+        if self.peek().token_type != TokenType::LBrace {
+            return Err(self.expected("Expected '{' before function body"));
+        }
+
+        let body = self.block()?;
+
+        return Ok(Statement::FunctionDeclaration(Box::new(
+            FunctionDeclaration {
+                identifier,
+                parameters,
+                body,
+                generator,
+                span: self.span_from(&start),
+                resolution: Cell::default(),
+            },
+        )));
+    }
+
+    /// Parses a comma separated parameter list up to, but not including,
+    /// `end`.
+    fn parameters(&mut self, end: TokenType) -> Result<Vec<Parameter>, Diagnostic> {
+        let mut parameters = Vec::<Parameter>::new();
+
+        if self.peek().token_type == end {
+            return Ok(parameters);
+        }
+
+        loop {
+            if parameters.last().is_some_and(|parameter| parameter.rest) {
+                return Err(self.error("A rest parameter must be the last parameter"));
             }
+
+            let parameter = self.parameter(&end)?;
+            if parameter.default.is_none()
+                && !parameter.rest
+                && parameters.iter().any(|other| other.default.is_some())
+            {
+                return Err(Diagnostic::error(
+                    "A parameter without a default cannot follow one with a default",
+                )
+                .at(parameter.pattern.span()));
+            }
+            parameters.push(parameter);
+
+            if self.peek().token_type != TokenType::Comma {
+                return Ok(parameters);
+            }
+            self.advance();
         }
+    }
 
-        if self.peek().token_type != TokenType::RParen {
-            panic!("Expected ')' after parameters");
+    /// A default inside `|...|` stops short of `|`, which would otherwise be
+    /// read as a bitwise or.
+    fn parameter(&mut self, end: &TokenType) -> Result<Parameter, Diagnostic> {
+        if self.peek().token_type == TokenType::Ellipsis {
+            self.advance();
+            if self.peek().token_type != TokenType::Identifier {
+                return Err(self.expected("Expected parameter name after '...'"));
+            }
+            let pattern = Pattern::Binding(self.advance());
+            if self.peek().token_type == TokenType::Equal {
+                return Err(self.error("A rest parameter cannot have a default"));
+            }
+
+            return Ok(Parameter {
+                pattern,
+                default: None,
+                rest: true,
+            });
         }
 
-        self.advance();
+        let pattern = self.binding("Expected parameter name")?;
+        let default = match self.peek().token_type {
+            TokenType::Equal => {
+                self.advance();
+                match end {
+                    TokenType::Pipe => Some(self.bit_xor()?),
+                    _ => Some(self.expression()?),
+                }
+            }
+            _ => None,
+        };
 
-        let body = self.block();
+        Ok(Parameter {
+            pattern,
+            default,
+            rest: false,
+        })
+    }
 
-        return Statement::FunctionDeclaration(Box::new(FunctionDeclaration {
-            identifier,
-            parameters,
-            body,
-        }));
+    /// What `make`, `for` and parameters bind: a name, or a list or record
+    /// pattern to destructure the value with.
+    fn binding(&mut self, message: &str) -> Result<Pattern, Diagnostic> {
+        match self.peek().token_type {
+            TokenType::Identifier => Ok(Pattern::Binding(self.advance())),
+            TokenType::LBracket => self.list_pattern(),
+            TokenType::LBrace => self.record_pattern(),
+            _ => Err(self.expected(message)),
+        }
     }
 
-    fn declaration_statement(&mut self) -> Statement {
-        self.advance();
+    fn declaration_statement(&mut self) -> Result<Statement, Diagnostic> {
+        let start = self.advance();
 
-        let identifier = self.advance();
+        let pattern = self.binding("Expected variable name after 'make'")?;
 
         if self.peek().token_type == TokenType::Semicolon {
+            if !matches!(pattern, Pattern::Binding(_)) {
+                return Err(self.expected("Expected '=' after pattern"));
+            }
             self.advance();
-            return Statement::VariableDeclaration(Box::new(VariableDeclaration {
-                identifier,
-                expression: None,
-            }));
+            return Ok(Statement::VariableDeclaration(Box::new(
+                VariableDeclaration {
+                    pattern,
+                    expression: None,
+                    span: self.span_from(&start),
+                    resolution: Cell::default(),
+                },
+            )));
         }
 
         if self.peek().token_type != TokenType::Equal {
-            panic!("Expected '=' after identifier");
+            return Err(self.expected("Expected '=' after identifier"));
         }
 
         self.advance();
 
-        let expression = self.expression();
+        let expression = self.expression()?;
 
         if self.peek().token_type != TokenType::Semicolon {
-            panic!(
+            return Err(self.expected(format!(
                 "Expected ';' after expression, found {:?}",
                 self.peek().token_type
-            );
+            )));
         }
 
         self.advance();
 
-        return Statement::VariableDeclaration(Box::new(VariableDeclaration {
-            identifier,
-            expression: Some(expression),
-        }));
+        return Ok(Statement::VariableDeclaration(Box::new(
+            VariableDeclaration {
+                pattern,
+                expression: Some(expression),
+                span: self.span_from(&start),
+                resolution: Cell::default(),
+            },
+        )));
     }
 
-    fn expression(&mut self) -> Expression {
+    fn expression(&mut self) -> Result<Expression, Diagnostic> {
         return self.assignment();
     }
 
-    fn assignment(&mut self) -> Expression {
-        let first = self.if_expression();
+    fn assignment(&mut self) -> Result<Expression, Diagnostic> {
+        let first = self.if_expression()?;
+
+        let operator = match self.peek().token_type {
+            TokenType::Equal => None,
+            TokenType::PlusEqual => Some(TokenType::Plus),
+            TokenType::MinusEqual => Some(TokenType::Minus),
+            TokenType::StarEqual => Some(TokenType::Star),
+            TokenType::SlashEqual => Some(TokenType::Slash),
+            TokenType::PercentEqual => Some(TokenType::Percent),
+            _ => return Ok(first),
+        };
 
-        while self.peek().token_type == TokenType::Equal {
-            self.advance();
-            let second = self.assignment();
-
-            match first {
-                Expression::Primary(primary) => {
-                    return Expression::Assignment(Box::new(Assignment {
-                        identifier: primary.value,
-                        value: second,
-                    }));
-                }
-                Expression::MapIndex(map_index) => {
-                    return Expression::MapIndexAssignment(Box::new(MapIndexAssignment {
-                        map: map_index.map,
-                        key: map_index.key,
-                        value: second,
-                    }))
-                }
-                Expression::Index(index) => {
-                    return Expression::IndexAssignment(Box::new(IndexAssignment {
-                        list: index.list,
-                        expression: index.expression,
-                        value: second,
-                    }))
-                }
-                _ => {
-                    panic!("Invalid assignment target");
-                }
+        let equal = self.advance();
+        // `a += b` keeps the `+` as its operator, so the backends can
+        // treat it like the `+` in `a = a + b`.
+        let operator = operator.map(|token_type| {
+            let mut lexeme = equal.lexeme.clone();
+            lexeme.pop();
+            Token {
+                token_type,
+                lexeme,
+                end: equal.end - 1,
+                ..equal.clone()
+            }
+        });
+        let second = self.assignment()?;
+
+        Self::assign(first, operator, second, &equal)
+    }
+
+    /// Builds the assignment of `value` to `target`, which must be a
+    /// variable, a list slot or a record field.
+    fn assign(
+        target: Expression,
+        operator: Option<Token>,
+        value: Expression,
+        equal: &Token,
+    ) -> Result<Expression, Diagnostic> {
+        let span = target.span().to(value.span());
+
+        match target {
+            Expression::Primary(primary) if primary.value.token_type == TokenType::Identifier => {
+                Ok(Expression::Assignment(Box::new(Assignment {
+                    identifier: primary.value,
+                    operator,
+                    value,
+                    span,
+                    resolution: Cell::default(),
+                })))
+            }
+            Expression::MapIndex(map_index) => Ok(Expression::MapIndexAssignment(Box::new(
+                MapIndexAssignment {
+                    map: map_index.map,
+                    key: map_index.key,
+                    operator,
+                    value,
+                    span,
+                },
+            ))),
+            Expression::Index(index) => {
+                Ok(Expression::IndexAssignment(Box::new(IndexAssignment {
+                    list: index.list,
+                    expression: index.expression,
+                    operator,
+                    value,
+                    span,
+                })))
+            }
+            _ => {
+                Err(Diagnostic::error("Invalid assignment target")
+                    .at(target.span().to(equal.span())))
             }
         }
+    }
+
+    /// `target++` and `target--` are `target += 1` and `target -= 1`. The
+    /// operator keeps the `++` lexeme so the formatter can print it back,
+    /// and the `1` is spanned by it.
+    fn increment(&mut self, target: Expression) -> Result<Expression, Diagnostic> {
+        let increment = self.advance();
+        let token_type = match increment.token_type {
+            TokenType::PlusPlus => TokenType::Plus,
+            _ => TokenType::Minus,
+        };
+        let operator = Token {
+            token_type,
+            ..increment.clone()
+        };
+        let one = Token {
+            token_type: TokenType::Int,
+            lexeme: "1".to_string(),
+            ..increment.clone()
+        };
 
-        first
+        Self::assign(
+            target,
+            Some(operator),
+            Expression::Primary(Box::new(Primary::new(one))),
+            &increment,
+        )
     }
 
-    fn if_expression(&mut self) -> Expression {
-        let mut first = self.or();
+    fn if_expression(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.or()?;
 
         while self.peek().token_type == TokenType::If {
             self.advance();
-            let condition = self.or();
+            let condition = self.or()?;
 
             if self.peek().token_type != TokenType::Else {
-                panic!("Expected 'else' after if expression");
+                return Err(self.expected("Expected 'else' after if expression"));
             }
 
             self.advance();
 
-            let else_expression = self.or();
+            let else_expression = self.or()?;
+            let span = first.span().to(else_expression.span());
 
             first = Expression::IfExpression(Box::new(IfExpression {
-                condition: condition,
+                condition,
                 then_branch: first,
                 else_branch: else_expression,
+                span,
             }));
         }
 
-        first
+        Ok(first)
     }
 
-    fn or(&mut self) -> Expression {
-        let mut first = self.and();
+    fn or(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.and()?;
 
         while self.peek().token_type == TokenType::Or {
             self.advance();
-            let right = self.and();
-            first = Expression::Or(Box::new(Or { left: first, right }));
+            let right = self.and()?;
+            let span = first.span().to(right.span());
+            first = Expression::Or(Box::new(Or {
+                left: first,
+                right,
+                span,
+            }));
         }
 
-        first
+        Ok(first)
     }
 
-    fn and(&mut self) -> Expression {
-        let mut first = self.equality();
+    fn and(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.equality()?;
 
         while self.peek().token_type == TokenType::And {
             self.advance();
-            let right = self.equality();
-            first = Expression::And(Box::new(And { left: first, right }));
+            let right = self.equality()?;
+            let span = first.span().to(right.span());
+            first = Expression::And(Box::new(And {
+                left: first,
+                right,
+                span,
+            }));
         }
 
-        first
+        Ok(first)
     }
-
-    fn equality(&mut self) -> Expression {
-        let mut first = self.comparison();
+    fn equality(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.comparison()?;
         while match self.peek().token_type {
             TokenType::BangEqual | TokenType::EqualEqual => true,
             _ => false,
         } {
             let operator = self.advance();
-            let second = self.comparison();
+            let second = self.comparison()?;
             first = Expression::Equality(Box::new(Equality::new(first, operator, second)));
         }
 
-        first
+        Ok(first)
     }
 
-    fn comparison(&mut self) -> Expression {
-        let mut first = self.term();
+    fn comparison(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.bit_or()?;
 
         while match self.peek().token_type {
             TokenType::Greater
@@ -399,83 +868,155 @@ impl Parser {
             _ => false,
         } {
             let operator = self.advance();
-            let second = self.term();
+            let second = self.bit_or()?;
             first = Expression::Comparison(Box::new(Comparison::new(first, operator, second)));
         }
 
-        first
+        Ok(first)
     }
 
-    fn term(&mut self) -> Expression {
-        let mut first = self.factor();
+    fn bit_or(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.bit_xor()?;
+
+        while self.peek().token_type == TokenType::Pipe {
+            let operator = self.advance();
+            let second = self.bit_xor()?;
+            first = Expression::Bitwise(Box::new(Bitwise::new(first, operator, second)));
+        }
+
+        Ok(first)
+    }
+
+    fn bit_xor(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.bit_and()?;
+
+        while self.peek().token_type == TokenType::Caret {
+            let operator = self.advance();
+            let second = self.bit_and()?;
+            first = Expression::Bitwise(Box::new(Bitwise::new(first, operator, second)));
+        }
+
+        Ok(first)
+    }
+
+    fn bit_and(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.shift()?;
+
+        while self.peek().token_type == TokenType::Ampersand {
+            let operator = self.advance();
+            let second = self.shift()?;
+            first = Expression::Bitwise(Box::new(Bitwise::new(first, operator, second)));
+        }
+
+        Ok(first)
+    }
+
+    fn shift(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.term()?;
+
+        while match self.peek().token_type {
+            TokenType::LessLess | TokenType::GreaterGreater => true,
+            _ => false,
+        } {
+            let operator = self.advance();
+            let second = self.term()?;
+            first = Expression::Bitwise(Box::new(Bitwise::new(first, operator, second)));
+        }
+
+        Ok(first)
+    }
+
+    fn term(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.factor()?;
 
         while match self.peek().token_type {
             TokenType::Minus | TokenType::Plus => true,
             _ => false,
         } {
             let operator = self.advance();
-            let second = self.factor();
+            let second = self.factor()?;
             first = Expression::Term(Box::new(Term::new(first, operator, second)));
         }
 
-        first
+        Ok(first)
     }
 
-    fn factor(&mut self) -> Expression {
-        let mut first = self.unary();
+    fn factor(&mut self) -> Result<Expression, Diagnostic> {
+        let mut first = self.unary()?;
 
         while match self.peek().token_type {
-            TokenType::Slash | TokenType::Star => true,
+            TokenType::Slash | TokenType::Star | TokenType::Percent | TokenType::SlashSlash => true,
             _ => false,
         } {
             let operator = self.advance();
-            let second = self.unary();
+            let second = self.unary()?;
             first = Expression::Factor(Box::new(Factor::new(first, operator, second)));
         }
 
-        first
+        Ok(first)
     }
 
-    fn unary(&mut self) -> Expression {
+    fn unary(&mut self) -> Result<Expression, Diagnostic> {
         if match self.peek().token_type {
-            TokenType::Bang | TokenType::Minus => true,
+            TokenType::Bang | TokenType::Minus | TokenType::Tilde => true,
             _ => false,
         } {
             let operator = self.advance();
-            let right = self.unary();
-            return Expression::Unary(Box::new(Unary::new(operator, right)));
+            let right = self.unary()?;
+            return Ok(Expression::Unary(Box::new(Unary::new(operator, right))));
         } else {
-            return self.call();
+            return self.power();
+        }
+    }
+
+    /// The exponent is parsed as a unary, which makes `**` right associative
+    /// and lets it take a negative exponent: `2 ** -1`.
+    fn power(&mut self) -> Result<Expression, Diagnostic> {
+        let base = self.call()?;
+
+        if self.peek().token_type != TokenType::StarStar {
+            return Ok(base);
         }
+
+        let operator = self.advance();
+        let exponent = self.unary()?;
+        Ok(Expression::Power(Box::new(Power::new(
+            base, operator, exponent,
+        ))))
     }
 
-    fn call(&mut self) -> Expression {
-        let mut identifier = self.primary();
+    fn call(&mut self) -> Result<Expression, Diagnostic> {
+        let mut identifier = self.primary()?;
 
         while self.peek().token_type == TokenType::LParen
             || self.peek().token_type == TokenType::LBracket
             || self.peek().token_type == TokenType::Dot
+            || self.peek().token_type == TokenType::Question
         {
             if self.peek().token_type == TokenType::LParen {
                 self.advance();
-                let arguments = self.arguments();
+                let arguments = self.arguments()?;
+                let span = identifier.span().to(self.previous().span());
                 identifier = Expression::Call(Box::new(Call {
                     identifier,
                     arguments,
+                    span,
                 }));
             }
 
             if self.peek().token_type == TokenType::LBracket {
                 self.advance();
-                let expression = self.expression();
+                let expression = self.expression()?;
                 if self.peek().token_type != TokenType::RBracket {
-                    panic!("Expected '] after list index")
+                    return Err(self.expected("Expected ']' after list index"));
                 }
                 self.advance();
 
+                let span = identifier.span().to(self.previous().span());
                 identifier = Expression::Index(Box::new(Index {
                     list: identifier,
                     expression,
+                    span,
                 }))
             }
 
@@ -484,86 +1025,416 @@ impl Parser {
                 let key = self.peek();
                 self.advance();
 
+                let span = identifier.span().to(key.span());
                 identifier = Expression::MapIndex(Box::new(MapIndex {
                     map: identifier,
                     key,
+                    span,
+                }))
+            }
+
+            if self.peek().token_type == TokenType::Question {
+                self.advance();
+                let span = identifier.span().to(self.previous().span());
+                identifier = Expression::Propagate(Box::new(Propagate {
+                    expression: identifier,
+                    span,
                 }))
             }
         }
 
-        identifier
+        if self.peek().token_type == TokenType::PlusPlus
+            || self.peek().token_type == TokenType::MinusMinus
+        {
+            return self.increment(identifier);
+        }
+
+        Ok(identifier)
     }
 
-    fn arguments(&mut self) -> Vec<Expression> {
+    fn arguments(&mut self) -> Result<Vec<Argument>, Diagnostic> {
         let mut arguments = vec![];
 
         if self.peek().token_type != TokenType::RParen {
-            arguments.push(self.expression());
+            arguments.push(self.argument(&arguments)?);
 
             while self.peek().token_type == TokenType::Comma {
                 self.advance();
-                arguments.push(self.expression());
+                arguments.push(self.argument(&arguments)?);
             }
 
             if self.peek().token_type != TokenType::RParen {
-                panic!("Expected ')' after arguments");
+                return Err(self.expected("Expected ')' after arguments"));
             }
         }
 
         self.advance();
-        return arguments;
+        return Ok(arguments);
     }
 
-    fn primary(&mut self) -> Expression {
+    /// An argument following `previous`, which it must come after if it is
+    /// positional and must not share a name with if it is named.
+    fn argument(&mut self, previous: &[Argument]) -> Result<Argument, Diagnostic> {
+        let named = self.peek().token_type == TokenType::Identifier
+            && self.peek_next().token_type == TokenType::Colon;
+        if !named {
+            if previous.iter().any(|argument| argument.name.is_some()) {
+                return Err(self.error("Positional arguments must come before named ones"));
+            }
+
+            return Ok(Argument {
+                name: None,
+                value: self.expression()?,
+            });
+        }
+
+        let name = self.advance();
+        if previous
+            .iter()
+            .filter_map(|argument| argument.name.as_ref())
+            .any(|other| other.lexeme == name.lexeme)
+        {
+            return Err(
+                Diagnostic::error(format!("Duplicate argument {}", name.lexeme)).at(name.span()),
+            );
+        }
+        self.advance();
+
+        Ok(Argument {
+            name: Some(name),
+            value: self.expression()?,
+        })
+    }
+
+    fn primary(&mut self) -> Result<Expression, Diagnostic> {
         match self.peek().token_type {
-            TokenType::Number
+            TokenType::Int
+            | TokenType::BigInt
+            | TokenType::Float
+            | TokenType::Decimal
             | TokenType::String
             | TokenType::Boolean
             | TokenType::Nil
             | TokenType::Identifier => {
                 let value = self.advance();
-                return Expression::Primary(Box::new(Primary::new(value)));
+                return Ok(Expression::Primary(Box::new(Primary::new(value))));
             }
             TokenType::LBracket => {
                 return self.list();
             }
+            TokenType::Interpolation => {
+                return self.interpolation();
+            }
             TokenType::LBrace => {
                 return self.record();
             }
+            TokenType::Funk => {
+                return self.lambda();
+            }
+            TokenType::Pipe => {
+                return self.arrow_lambda();
+            }
+            TokenType::Match => {
+                return self.match_expression();
+            }
             TokenType::LParen => {
                 self.advance();
-                let expression = self.expression();
+                let expression = self.expression()?;
                 if self.peek().token_type != TokenType::RParen {
-                    panic!("Expected ')' after expression");
+                    return Err(self.expected("Expected ')' after expression"));
                 }
                 self.advance();
-                return expression;
+                return Ok(expression);
             }
-            _ => panic!("Unexpected token, {:?}", self.advance().token_type),
+            _ => return Err(self.error(format!("Unexpected token, {:?}", self.peek().token_type))),
         }
     }
 
-    fn record(&mut self) -> Expression {
+    fn lambda(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
+        let generator = self.generator_marker();
+
+        if self.peek().token_type != TokenType::LParen {
+            return Err(self.expected("Expected '(' after 'funk'"));
+        }
+        self.advance();
+
+        let parameters = self.parameters(TokenType::RParen)?;
+
+        if self.peek().token_type != TokenType::RParen {
+            return Err(self.expected("Expected ')' after parameters"));
+        }
+        self.advance();
+
+        if self.peek().token_type != TokenType::LBrace {
+            return Err(self.expected("Expected '{' before function body"));
+        }
+
+        let body = self.block()?;
+
+        return Ok(Expression::Lambda(Box::new(Lambda {
+            parameters,
+            body,
+            generator,
+            span: self.span_from(&start),
+        })));
+    }
+
+    /// `|a, b| a + b`, whose body returns the expression.
+    fn arrow_lambda(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
+
+        let parameters = self.parameters(TokenType::Pipe)?;
+
+        if self.peek().token_type != TokenType::Pipe {
+            return Err(self.expected("Expected '|' after parameters"));
+        }
+        self.advance();
+
+        let value = self.expression()?;
+        let body = Statement::Return(Box::new(Return {
+            span: value.span(),
+            value: Some(value),
+        }));
+
+        return Ok(Expression::Lambda(Box::new(Lambda {
+            parameters,
+            body,
+            generator: false,
+            span: self.span_from(&start),
+        })));
+    }
+
+    fn match_expression(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
+        let value = self.expression()?;
+
+        if self.peek().token_type != TokenType::LBrace {
+            return Err(self.expected("Expected '{' after match value"));
+        }
         self.advance();
 
+        let mut arms = vec![];
+        while self.peek().token_type != TokenType::RBrace {
+            let pattern = self.pattern()?;
+
+            let guard = match self.peek().token_type {
+                TokenType::If => {
+                    self.advance();
+                    Some(self.expression()?)
+                }
+                _ => None,
+            };
+
+            if self.peek().token_type != TokenType::FatArrow {
+                return Err(self.expected("Expected '=>' after pattern"));
+            }
+            self.advance();
+
+            let body = self.expression()?;
+            arms.push(Arm {
+                pattern,
+                guard,
+                body,
+            });
+
+            if self.peek().token_type != TokenType::Comma {
+                break;
+            }
+            self.advance();
+        }
+
+        if self.peek().token_type != TokenType::RBrace {
+            return Err(self.expected("Expected '}' after match arms"));
+        }
+        self.advance();
+
+        if arms.is_empty() {
+            return Err(Diagnostic::error("Match needs at least one arm").at(start.span()));
+        }
+
+        return Ok(Expression::Match(Box::new(Match {
+            value,
+            arms,
+            span: self.span_from(&start),
+        })));
+    }
+
+    /// A pattern, with any `|` alternatives.
+    fn pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let first = self.single_pattern()?;
+        if self.peek().token_type != TokenType::Pipe {
+            return Ok(first);
+        }
+
+        let mut alternatives = vec![first];
+        while self.peek().token_type == TokenType::Pipe {
+            self.advance();
+            alternatives.push(self.single_pattern()?);
+        }
+
+        let span = alternatives[0].span().to(self.previous().span());
+        Ok(Pattern::Either { alternatives, span })
+    }
+
+    fn single_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let token = self.peek();
+        match token.token_type {
+            TokenType::Int
+            | TokenType::BigInt
+            | TokenType::Float
+            | TokenType::Decimal
+            | TokenType::String
+            | TokenType::Boolean
+            | TokenType::Nil => {
+                self.advance();
+                Ok(Pattern::Literal {
+                    span: token.span(),
+                    value: token,
+                    negative: false,
+                })
+            }
+            TokenType::Minus => {
+                self.advance();
+                let value = self.peek();
+                if !matches!(
+                    value.token_type,
+                    TokenType::Int | TokenType::BigInt | TokenType::Float | TokenType::Decimal
+                ) {
+                    return Err(self.expected("Expected a number after '-' in pattern"));
+                }
+                self.advance();
+
+                Ok(Pattern::Literal {
+                    value,
+                    negative: true,
+                    span: self.span_from(&token),
+                })
+            }
+            TokenType::Identifier => {
+                self.advance();
+                match token.lexeme.as_str() {
+                    "_" => Ok(Pattern::Wildcard(token)),
+                    "none" => Ok(Pattern::None(token)),
+                    "ok" | "err" | "some" if self.peek().token_type == TokenType::LParen => {
+                        self.advance();
+                        let pattern = self.pattern()?;
+                        if self.peek().token_type != TokenType::RParen {
+                            return Err(self.expected("Expected ')' after pattern"));
+                        }
+                        self.advance();
+
+                        Ok(Pattern::Variant {
+                            span: self.span_from(&token),
+                            name: token,
+                            pattern: Box::new(pattern),
+                        })
+                    }
+                    _ => Ok(Pattern::Binding(token)),
+                }
+            }
+            TokenType::LBracket => self.list_pattern(),
+            TokenType::LBrace => self.record_pattern(),
+            _ => Err(self.error(format!(
+                "Expected a pattern, found {:?}",
+                self.peek().token_type
+            ))),
+        }
+    }
+
+    fn list_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let start = self.advance();
+
+        let mut items = vec![];
+        let mut rest = None;
+        while self.peek().token_type != TokenType::RBracket {
+            if self.peek().token_type == TokenType::DotDot {
+                let dots = self.advance();
+                let name = match self.peek().token_type {
+                    TokenType::Identifier => Some(self.advance()),
+                    _ => None,
+                };
+                rest = Some(Rest { dots, name });
+                break;
+            }
+
+            items.push(self.pattern()?);
+
+            if self.peek().token_type != TokenType::Comma {
+                break;
+            }
+            self.advance();
+        }
+
+        if self.peek().token_type != TokenType::RBracket {
+            return Err(self.expected("Expected ']' after list pattern"));
+        }
+        self.advance();
+
+        Ok(Pattern::List {
+            items,
+            rest,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn record_pattern(&mut self) -> Result<Pattern, Diagnostic> {
+        let start = self.advance();
+
+        let mut fields = vec![];
+        while self.peek().token_type != TokenType::RBrace {
+            if self.peek().token_type != TokenType::Identifier {
+                return Err(self.expected("Expected field name in record pattern"));
+            }
+            let key = self.advance();
+
+            let pattern = match self.peek().token_type {
+                TokenType::Colon => {
+                    self.advance();
+                    self.pattern()?
+                }
+                _ => Pattern::Binding(key.clone()),
+            };
+            fields.push((key, pattern));
+
+            if self.peek().token_type != TokenType::Comma {
+                break;
+            }
+            self.advance();
+        }
+
+        if self.peek().token_type != TokenType::RBrace {
+            return Err(self.expected("Expected '}' after record pattern"));
+        }
+        self.advance();
+
+        Ok(Pattern::Record {
+            fields,
+            span: self.span_from(&start),
+        })
+    }
+
+    fn record(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
+
         let mut key_values = Vec::<(Token, Expression)>::new();
         while self.peek().token_type != TokenType::RBrace {
             let key = self.peek();
             self.advance();
 
             if self.peek().token_type != TokenType::Colon {
-                panic!(
-                    "expected semicolon after key in record, found {:?}",
+                return Err(self.expected(format!(
+                    "expected ':' after key in record, found {:?}",
                     self.peek().token_type
-                );
+                )));
             }
 
             self.advance();
 
-            let value = self.expression();
+            let value = self.expression()?;
 
             if self.peek().token_type != TokenType::Comma {
-                panic!("expected ',' after key value pair in record");
+                return Err(self.expected("expected ',' after key value pair in record"));
             }
 
             self.advance();
@@ -573,43 +1444,127 @@ impl Parser {
 
         self.advance();
 
-        return Expression::Record(Box::new(Record { key_values }));
+        return Ok(Expression::Record(Box::new(Record {
+            key_values,
+            span: self.span_from(&start),
+        })));
     }
 
-    fn list(&mut self) -> Expression {
-        self.advance();
+    fn list(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
 
         let mut values = Vec::new();
         if self.peek().token_type != TokenType::RBracket {
-            let expression = self.expression();
+            let expression = self.expression()?;
             values.push(expression);
 
             while self.peek().token_type == TokenType::Comma {
                 self.advance();
-                let expression = self.expression();
+                let expression = self.expression()?;
                 values.push(expression);
             }
 
             if self.peek().token_type != TokenType::RBracket {
-                panic!("Unterminated list");
+                return Err(self.error("Unterminated list"));
             }
         }
 
         self.advance();
 
-        return Expression::List(Box::new(List { values }));
+        return Ok(Expression::List(Box::new(List {
+            values,
+            span: self.span_from(&start),
+        })));
+    }
+
+    fn interpolation(&mut self) -> Result<Expression, Diagnostic> {
+        let start = self.advance();
+
+        let mut parts = vec![start.clone()];
+        let mut expressions = Vec::new();
+        loop {
+            expressions.push(self.expression()?);
+
+            match self.peek().token_type {
+                TokenType::Interpolation => parts.push(self.advance()),
+                TokenType::String => {
+                    parts.push(self.advance());
+                    break;
+                }
+                _ => return Err(self.error("Expected '}' after interpolated expression")),
+            }
+        }
+
+        return Ok(Expression::Interpolation(Box::new(Interpolation {
+            parts,
+            expressions,
+            span: self.span_from(&start),
+        })));
     }
 
     fn advance(&mut self) -> Token {
-        self.position += 1;
-        self.tokens[self.position - 1].clone()
+        let token = self.peek();
+        if !self.is_at_end() {
+            self.position += 1;
+        }
+        token
     }
 
     fn peek(&self) -> Token {
         self.tokens[self.position].clone()
     }
 
+    /// Whether the `funk` at the current token starts a lambda rather than
+    /// a declaration.
+    fn is_lambda(&self) -> bool {
+        let after = match self.peek_next().token_type {
+            TokenType::Star => self.position + 2,
+            _ => self.position + 1,
+        };
+        return self.tokens[after.min(self.tokens.len() - 1)].token_type == TokenType::LParen;
+    }
+
+    /// Consumes the `*` of `funk*`, returning whether there was one.
+    fn generator_marker(&mut self) -> bool {
+        if self.peek().token_type != TokenType::Star {
+            return false;
+        }
+        self.advance();
+        return true;
+    }
+
+    fn peek_next(&self) -> Token {
+        self.tokens[(self.position + 1).min(self.tokens.len() - 1)].clone()
+    }
+
+    fn previous(&self) -> &Token {
+        &self.tokens[self.position.saturating_sub(1)]
+    }
+
     fn is_at_end(&self) -> bool {
         self.peek().token_type == TokenType::EOF
     }
+
+    /// The span from `start` through the most recently consumed token.
+    fn span_from(&self, start: &Token) -> Span {
+        start.span().to(self.previous().span())
+    }
+
+    fn error(&self, message: impl Into<String>) -> Diagnostic {
+        Diagnostic::error(message).at(self.peek().span())
+    }
+
+    /// An error for a missing token. Points at the upcoming token, or at the
+    /// previous one when the upcoming token is on a later line (e.g. a
+    /// missing `;` at the end of a line).
+    fn expected(&self, message: impl Into<String>) -> Diagnostic {
+        let next = self.peek();
+        let previous = self.previous();
+
+        if self.position > 0 && next.line > previous.line {
+            return Diagnostic::error(message).at(previous.span());
+        }
+
+        Diagnostic::error(message).at(next.span())
+    }
 }
@@ -0,0 +1,26 @@
+use crate::token::Token;
+
+use super::{expression::Expression, pattern::Pattern};
+
+/// One parameter of a function or lambda.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Parameter {
+    /// A name, or a list or record pattern that destructures the argument.
+    pub pattern: Pattern,
+    /// `b = 10`: what the parameter is when a call leaves it out, evaluated
+    /// at the call after the parameters before it are bound.
+    pub default: Option<Expression>,
+    /// `...rest`: collects the positional arguments left over into a list.
+    pub rest: bool,
+}
+
+impl Parameter {
+    /// The name the parameter can be passed by. Patterns have none, so they
+    /// can only be passed by position.
+    pub fn name(&self) -> Option<&Token> {
+        match &self.pattern {
+            Pattern::Binding(name) => Some(name),
+            _ => None,
+        }
+    }
+}
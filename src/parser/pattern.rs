@@ -0,0 +1,160 @@
+use std::fmt;
+
+use crate::{
+    diagnostic::Span,
+    token::{Token, TokenType},
+};
+
+/// The left side of a `match` arm, describing the values it accepts and the
+/// names it binds parts of them to.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub enum Pattern {
+    /// `_`, which accepts anything and binds nothing.
+    Wildcard(Token),
+    /// A number, string, boolean or `nil`, compared with `==`. Numbers may
+    /// have a leading `-`.
+    Literal {
+        value: Token,
+        negative: bool,
+        span: Span,
+    },
+    /// A name, bound to whatever is there.
+    Binding(Token),
+    /// `[first, second, ..rest]`: lists of exactly as many items, or at least
+    /// as many when there is a rest.
+    List {
+        items: Vec<Pattern>,
+        rest: Option<Rest>,
+        span: Span,
+    },
+    /// `{name: n, age}`: records with at least these fields. A field without
+    /// a pattern binds its value to its own name.
+    Record {
+        fields: Vec<(Token, Pattern)>,
+        span: Span,
+    },
+    /// `ok(p)`, `err(p)` or `some(p)`, matching the wrapped value against `p`.
+    Variant {
+        name: Token,
+        pattern: Box<Pattern>,
+        span: Span,
+    },
+    /// `none`.
+    None(Token),
+    /// `a | b`, which accepts what any of its alternatives does.
+    Either {
+        alternatives: Vec<Pattern>,
+        span: Span,
+    },
+}
+
+/// The `..rest` ending a list pattern, which collects the remaining items
+/// into a list. A bare `..` ignores them.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Rest {
+    pub dots: Token,
+    pub name: Option<Token>,
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            Pattern::Wildcard(token) | Pattern::Binding(token) | Pattern::None(token) => {
+                token.span()
+            }
+            Pattern::Literal { span, .. }
+            | Pattern::List { span, .. }
+            | Pattern::Record { span, .. }
+            | Pattern::Variant { span, .. }
+            | Pattern::Either { span, .. } => *span,
+        }
+    }
+
+    /// The names the pattern binds, in the order their values are bound:
+    /// depth first, left to right.
+    pub fn bindings(&self) -> Vec<&Token> {
+        let mut bindings = vec![];
+        self.collect_bindings(&mut bindings);
+        bindings
+    }
+
+    fn collect_bindings<'a>(&'a self, bindings: &mut Vec<&'a Token>) {
+        match self {
+            Pattern::Binding(token) => bindings.push(token),
+            Pattern::List { items, rest, .. } => {
+                for item in items {
+                    item.collect_bindings(bindings);
+                }
+                if let Some(name) = rest.as_ref().and_then(|rest| rest.name.as_ref()) {
+                    bindings.push(name);
+                }
+            }
+            Pattern::Record { fields, .. } => {
+                for (_, pattern) in fields {
+                    pattern.collect_bindings(bindings);
+                }
+            }
+            Pattern::Variant { pattern, .. } => pattern.collect_bindings(bindings),
+            Pattern::Either { alternatives, .. } => {
+                for alternative in alternatives {
+                    alternative.collect_bindings(bindings);
+                }
+            }
+            Pattern::Wildcard(_) | Pattern::Literal { .. } | Pattern::None(_) => {}
+        }
+    }
+
+    /// Whether the pattern accepts every value.
+    pub fn is_irrefutable(&self) -> bool {
+        match self {
+            Pattern::Wildcard(_) | Pattern::Binding(_) => true,
+            Pattern::Either { alternatives, .. } => alternatives.iter().any(Self::is_irrefutable),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Wildcard(token) | Pattern::Binding(token) | Pattern::None(token) => {
+                write!(f, "{}", token.lexeme)
+            }
+            Pattern::Literal {
+                value, negative, ..
+            } => {
+                let sign = if *negative { "-" } else { "" };
+                match value.token_type {
+                    TokenType::String => write!(f, "{:?}", value.lexeme),
+                    _ => write!(f, "{}{}", sign, value.lexeme),
+                }
+            }
+            Pattern::List { items, rest, .. } => {
+                let mut parts: Vec<String> = items.iter().map(|item| item.to_string()).collect();
+                if let Some(rest) = rest {
+                    let name = rest.name.as_ref().map(|name| name.lexeme.as_str());
+                    parts.push(format!("..{}", name.unwrap_or("")));
+                }
+                write!(f, "[{}]", parts.join(", "))
+            }
+            Pattern::Record { fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(key, pattern)| match pattern {
+                        Pattern::Binding(name) if name.lexeme == key.lexeme => key.lexeme.clone(),
+                        pattern => format!("{}: {}", key.lexeme, pattern),
+                    })
+                    .collect();
+                write!(f, "{{{}}}", fields.join(", "))
+            }
+            Pattern::Variant { name, pattern, .. } => write!(f, "{}({})", name.lexeme, pattern),
+            Pattern::Either { alternatives, .. } => {
+                let alternatives: Vec<String> = alternatives
+                    .iter()
+                    .map(|alternative| alternative.to_string())
+                    .collect();
+                write!(f, "{}", alternatives.join(" | "))
+            }
+        }
+    }
+}
@@ -1,10 +1,11 @@
-use crate::{parser::accept::Accept, visitors::Visitor};
+use crate::{diagnostic::Span, parser::accept::Accept, visitors::Visitor};
 
 use super::Statement;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Block {
     pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
 impl Accept for Block {
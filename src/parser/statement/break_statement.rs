@@ -0,0 +1,14 @@
+use crate::{diagnostic::Span, parser::accept::Accept, token::Token, visitors::Visitor};
+
+/// `break;` or `break label;`. Without a label it applies to the innermost loop.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Break {
+    pub label: Option<Token>,
+    pub span: Span,
+}
+
+impl Accept for Break {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_break(self)
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{
-    parser::{accept::Accept, expression::Expression},
+    diagnostic::Span,
+    parser::{accept::Accept, expression::Expression, pattern::Pattern},
     token::Token,
     visitors::Visitor,
 };
@@ -8,9 +9,13 @@ use super::Statement;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct For {
-    pub identifier: Token,
+    /// Set by `label: for ...`, for `break label;` to name.
+    pub label: Option<Token>,
+    /// A name, or a list or record pattern that destructures each item.
+    pub pattern: Pattern,
     pub iter: Expression,
     pub body: Statement,
+    pub span: Span,
 }
 
 impl Accept for For {
@@ -1,12 +1,24 @@
-use crate::{parser::accept::Accept, token::Token, visitors::Visitor};
+use std::cell::Cell;
+
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, parameter::Parameter},
+    token::Token,
+    visitors::{resolver::Resolution, Visitor},
+};
 
 use super::Statement;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct FunctionDeclaration {
     pub identifier: Token,
-    pub parameters: Vec<Token>,
+    pub parameters: Vec<Parameter>,
     pub body: Statement,
+    /// Declared with `funk*`, so calling it returns an iterator over what
+    /// its body yields.
+    pub generator: bool,
+    pub span: Span,
+    pub resolution: Cell<Resolution>,
 }
 
 impl Accept for FunctionDeclaration {
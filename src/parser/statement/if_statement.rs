@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     visitors::Visitor,
 };
@@ -10,6 +11,7 @@ pub struct IfStatement {
     pub condition: Expression,
     pub then_branch: Box<Statement>,
     pub else_branch: Option<Box<Statement>>,
+    pub span: Span,
 }
 
 impl Accept for IfStatement {
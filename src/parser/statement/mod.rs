@@ -1,28 +1,38 @@
-use crate::visitors::Visitor;
+use crate::{diagnostic::Span, visitors::Visitor};
 
 use super::accept::Accept;
 
 pub mod block;
+pub mod break_statement;
+pub mod continue_statement;
 pub mod expression;
 pub mod for_statement;
 pub mod function_declaration;
 pub mod if_statement;
 pub mod print;
 pub mod return_statement;
+pub mod throw_statement;
+pub mod try_statement;
 pub mod variable_declaration;
 pub mod while_statement;
 pub mod with_statement;
+pub mod yield_statement;
 
 pub use block::Block;
+pub use break_statement::Break;
+pub use continue_statement::Continue;
 pub use expression::ExpressionStatement;
 pub use for_statement::For;
 pub use function_declaration::FunctionDeclaration;
 pub use if_statement::IfStatement;
 pub use print::PrintStatement;
 pub use return_statement::Return;
+pub use throw_statement::Throw;
+pub use try_statement::{Catch, Try};
 pub use variable_declaration::VariableDeclaration;
 pub use while_statement::WhileStatement;
 pub use with_statement::With;
+pub use yield_statement::Yield;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum Statement {
@@ -36,6 +46,11 @@ pub enum Statement {
     Return(Box<Return>),
     With(Box<With>),
     For(Box<For>),
+    Break(Box<Break>),
+    Continue(Box<Continue>),
+    Yield(Box<Yield>),
+    Throw(Box<Throw>),
+    Try(Box<Try>),
 }
 
 impl Accept for Statement {
@@ -61,6 +76,33 @@ impl Accept for Statement {
             Statement::Return(return_statement) => visitor.visit_return(return_statement),
             Statement::With(with_statement) => visitor.visit_with_statement(with_statement),
             Statement::For(for_statement) => visitor.visit_for_statement(for_statement),
+            Statement::Break(break_statement) => visitor.visit_break(break_statement),
+            Statement::Continue(continue_statement) => visitor.visit_continue(continue_statement),
+            Statement::Yield(yield_statement) => visitor.visit_yield(yield_statement),
+            Statement::Throw(throw_statement) => visitor.visit_throw(throw_statement),
+            Statement::Try(try_statement) => visitor.visit_try(try_statement),
+        }
+    }
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::ExpressionStatement(expression_statement) => expression_statement.span,
+            Statement::PrintStatement(print_statement) => print_statement.span,
+            Statement::VariableDeclaration(variable_declaration) => variable_declaration.span,
+            Statement::Block(block) => block.span,
+            Statement::IfStatement(if_statement) => if_statement.span,
+            Statement::WhileStatement(while_statement) => while_statement.span,
+            Statement::FunctionDeclaration(function_declaration) => function_declaration.span,
+            Statement::Return(return_statement) => return_statement.span,
+            Statement::With(with_statement) => with_statement.span,
+            Statement::For(for_statement) => for_statement.span,
+            Statement::Break(break_statement) => break_statement.span,
+            Statement::Continue(continue_statement) => continue_statement.span,
+            Statement::Yield(yield_statement) => yield_statement.span,
+            Statement::Throw(throw_statement) => throw_statement.span,
+            Statement::Try(try_statement) => try_statement.span,
         }
     }
 }
@@ -0,0 +1,18 @@
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, expression::Expression},
+    visitors::Visitor,
+};
+
+/// `throw value;`, where the value is an error or a message for one.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Throw {
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl Accept for Throw {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_throw(self)
+    }
+}
@@ -0,0 +1,29 @@
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, statement::Statement},
+    token::Token,
+    visitors::Visitor,
+};
+
+/// `try { } catch (e) { } finally { }`. At least one of the catch and
+/// finally clauses is present.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Try {
+    pub body: Statement,
+    pub catch: Option<Catch>,
+    pub finally: Option<Statement>,
+    pub span: Span,
+}
+
+/// Runs when the body fails, with the error bound to `identifier`.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Catch {
+    pub identifier: Token,
+    pub body: Statement,
+}
+
+impl Accept for Try {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_try(self)
+    }
+}
@@ -1,13 +1,20 @@
+use std::cell::Cell;
+
 use crate::{
-    parser::{accept::Accept, expression::Expression},
-    token::Token,
-    visitors::Visitor,
+    diagnostic::Span,
+    parser::{accept::Accept, expression::Expression, pattern::Pattern},
+    visitors::{resolver::Resolution, Visitor},
 };
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct VariableDeclaration {
-    pub identifier: Token,
+    /// A name, or a list or record pattern that destructures the value.
+    pub pattern: Pattern,
     pub expression: Option<Expression>,
+    pub span: Span,
+    /// Where the first name the pattern binds lives. Any others follow it,
+    /// in the next slots of the same scope or as globals.
+    pub resolution: Cell<Resolution>,
 }
 
 impl Accept for VariableDeclaration {
@@ -1,5 +1,7 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
+    token::Token,
     visitors::Visitor,
 };
 
@@ -7,8 +9,11 @@ use super::Statement;
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct WhileStatement {
+    /// Set by `label: while ...`, for `break label;` to name.
+    pub label: Option<Token>,
     pub condition: Expression,
     pub body: Statement,
+    pub span: Span,
 }
 
 impl Accept for WhileStatement {
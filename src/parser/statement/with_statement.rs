@@ -1,4 +1,5 @@
 use crate::{
+    diagnostic::Span,
     parser::{accept::Accept, expression::Expression},
     token::Token,
     visitors::Visitor,
@@ -11,6 +12,7 @@ pub struct With {
     pub value: Expression,
     pub identifier: Token,
     pub body: Statement,
+    pub span: Span,
 }
 
 impl Accept for With {
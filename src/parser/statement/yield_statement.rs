@@ -0,0 +1,19 @@
+use crate::{
+    diagnostic::Span,
+    parser::{accept::Accept, expression::Expression},
+    visitors::Visitor,
+};
+
+/// `yield value;`, which hands `value` to whoever is iterating over the
+/// generator and suspends it until the next value is asked for.
+#[derive(Debug, Clone, PartialEq, PartialOrd)]
+pub struct Yield {
+    pub value: Expression,
+    pub span: Span,
+}
+
+impl Accept for Yield {
+    fn accept<V: Visitor>(&self, visitor: &mut V) -> V::Output {
+        visitor.visit_yield(self)
+    }
+}
@@ -0,0 +1,196 @@
+use std::{
+    env, fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use jive::{
+    lexer::Lexer, token::TokenType, visitors::ast_printer::AstPrinter, Diagnostic, Engine, Error,
+    ErrorKind, Value,
+};
+
+const HELP: &str = "\
+:type <expr>   show the static type of an expression
+:ast <code>    show the syntax tree of some code
+:history       list previous entries
+:reset         forget every variable and function
+:help          show this message
+:quit          leave the repl";
+
+/// Interactive session that keeps one set of globals alive across entries.
+/// An entry keeps reading lines while it has unclosed brackets or strings.
+pub struct Repl {
+    engine: Engine,
+    history: Vec<String>,
+    history_file: Option<PathBuf>,
+}
+
+impl Repl {
+    pub fn new() -> Self {
+        let history_file =
+            env::var_os("HOME").map(|home| PathBuf::from(home).join(".jive_history"));
+        let history = history_file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .map(|contents| Self::decode_history(&contents))
+            .unwrap_or_default();
+
+        Repl {
+            engine: Engine::new(),
+            history,
+            history_file,
+        }
+    }
+
+    pub fn run(&mut self) {
+        println!("jive repl, :help for commands");
+
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            let mut entry = String::new();
+            let mut prompt = "> ";
+
+            loop {
+                print!("{}", prompt);
+                let _ = io::stdout().flush();
+
+                match lines.next() {
+                    Some(Ok(line)) => {
+                        entry.push_str(&line);
+                        entry.push('\n');
+                    }
+                    _ => {
+                        println!();
+                        return;
+                    }
+                }
+
+                if !is_incomplete(&entry) {
+                    break;
+                }
+                prompt = "... ";
+            }
+
+            let entry = entry.trim();
+            if entry.is_empty() {
+                continue;
+            }
+
+            self.record(entry);
+            if entry == ":quit" {
+                return;
+            }
+            self.execute(entry);
+        }
+    }
+
+    fn execute(&mut self, entry: &str) {
+        let (command, argument) = match entry.strip_prefix(':') {
+            Some(meta) => match meta.split_once(char::is_whitespace) {
+                Some((command, argument)) => (command, argument.trim()),
+                None => (meta, ""),
+            },
+            None => ("", entry),
+        };
+
+        let result = match command {
+            "" => self.engine.eval(argument).map(|value| {
+                if value != Value::Nil {
+                    println!("{}", value);
+                }
+            }),
+            "type" => self
+                .engine
+                .type_of(argument)
+                .map(|argument_type| println!("{}", argument_type)),
+            "ast" => jive::parse(&Engine::terminated(argument))
+                .map(|statements| AstPrinter::new().print(&statements)),
+            "history" => {
+                for (index, entry) in self.history.iter().enumerate() {
+                    println!("{:4}  {}", index + 1, entry.replace('\n', "\n      "));
+                }
+                Ok(())
+            }
+            "reset" => {
+                self.engine = Engine::new();
+                Ok(())
+            }
+            "help" => {
+                println!("{}", HELP);
+                Ok(())
+            }
+            _ => Err(Error {
+                kind: ErrorKind::Syntax,
+                diagnostics: vec![Diagnostic::error(format!(
+                    "Unknown command :{}, try :help",
+                    command
+                ))],
+            }),
+        };
+
+        if let Err(error) = result {
+            for diagnostic in error.diagnostics {
+                eprint!("{}", diagnostic.in_file("<repl>").render(argument));
+            }
+        }
+    }
+
+    fn record(&mut self, entry: &str) {
+        if self.history.last().map(String::as_str) == Some(entry) {
+            return;
+        }
+        self.history.push(entry.to_string());
+
+        if let Some(file) = &self.history_file {
+            let _ = fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(file)
+                .and_then(|mut file| file.write_all(Self::encode_history(entry).as_bytes()));
+        }
+    }
+
+    /// History is stored one line per line of input, with the continuation
+    /// lines of a multi-line entry indented by a tab.
+    fn encode_history(entry: &str) -> String {
+        let mut encoded = entry.replace('\n', "\n\t");
+        encoded.push('\n');
+        encoded
+    }
+
+    fn decode_history(contents: &str) -> Vec<String> {
+        let mut history: Vec<String> = vec![];
+        for line in contents.lines() {
+            match (line.strip_prefix('\t'), history.last_mut()) {
+                (Some(continuation), Some(entry)) => {
+                    entry.push('\n');
+                    entry.push_str(continuation);
+                }
+                _ => history.push(line.to_string()),
+            }
+        }
+        history
+    }
+}
+
+/// Whether more lines are needed: the entry has an unterminated string or
+/// more opening than closing brackets.
+fn is_incomplete(source: &str) -> bool {
+    let tokens = match Lexer::new(source.to_string()).lex() {
+        Ok(tokens) => tokens,
+        Err(diagnostic) => return diagnostic.message == "Unterminated string",
+    };
+
+    let depth: i64 = tokens
+        .iter()
+        .map(|token| match token.token_type {
+            TokenType::LParen | TokenType::LBrace | TokenType::LBracket => 1,
+            TokenType::RParen | TokenType::RBrace | TokenType::RBracket => -1,
+            _ => 0,
+        })
+        .sum();
+
+    depth > 0
+}
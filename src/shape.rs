@@ -0,0 +1,211 @@
+//! Patterns in the form both the `Interpreter` and the `Vm` match values
+//! against, with their literals already turned into constants.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    constant::Constant, diagnostic::Diagnostic, parser::pattern::Pattern, token::TokenType,
+    visitors::interpreter::value::Value,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    /// Accepts anything.
+    Any,
+    /// Accepts anything, binding it.
+    Bind,
+    Nil,
+    Boolean(bool),
+    /// Accepts values equal to the constant.
+    Constant(Constant),
+    /// Lists of exactly as many items, or at least as many with a `rest`,
+    /// which is matched against the list of the remaining items.
+    List {
+        items: Vec<Shape>,
+        rest: Option<Box<Shape>>,
+    },
+    /// Records, or errors, with at least these fields.
+    Record(Vec<(String, Shape)>),
+    Ok(Box<Shape>),
+    Err(Box<Shape>),
+    Some(Box<Shape>),
+    None,
+    Either(Vec<Shape>),
+}
+
+impl Shape {
+    pub fn new(pattern: &Pattern) -> Result<Shape, Diagnostic> {
+        Ok(match pattern {
+            Pattern::Wildcard(_) => Shape::Any,
+            Pattern::Binding(_) => Shape::Bind,
+            Pattern::Literal {
+                value, negative, ..
+            } => match value.token_type {
+                TokenType::Nil => Shape::Nil,
+                TokenType::Boolean => Shape::Boolean(value.lexeme == "true"),
+                _ => {
+                    let constant = Constant::literal(value)?;
+                    Shape::Constant(match negative {
+                        true => Self::negated(constant),
+                        false => constant,
+                    })
+                }
+            },
+            Pattern::List { items, rest, .. } => Shape::List {
+                items: items.iter().map(Shape::new).collect::<Result<_, _>>()?,
+                rest: rest.as_ref().map(|rest| match rest.name {
+                    Some(_) => Box::new(Shape::Bind),
+                    None => Box::new(Shape::Any),
+                }),
+            },
+            Pattern::Record { fields, .. } => Shape::Record(
+                fields
+                    .iter()
+                    .map(|(key, pattern)| Ok((key.lexeme.clone(), Shape::new(pattern)?)))
+                    .collect::<Result<_, Diagnostic>>()?,
+            ),
+            Pattern::Variant { name, pattern, .. } => {
+                let pattern = Box::new(Shape::new(pattern)?);
+                match name.lexeme.as_str() {
+                    "ok" => Shape::Ok(pattern),
+                    "err" => Shape::Err(pattern),
+                    _ => Shape::Some(pattern),
+                }
+            }
+            Pattern::None(_) => Shape::None,
+            Pattern::Either { alternatives, .. } => Shape::Either(
+                alternatives
+                    .iter()
+                    .map(Shape::new)
+                    .collect::<Result<_, _>>()?,
+            ),
+        })
+    }
+
+    fn negated(constant: Constant) -> Constant {
+        match constant {
+            Constant::Int(number) => match number.checked_neg() {
+                Some(number) => Constant::Int(number),
+                None => Constant::BigInt(-num_bigint::BigInt::from(number)),
+            },
+            Constant::BigInt(number) => Constant::BigInt(-number),
+            Constant::Float(number) => Constant::Float(-number),
+            Constant::Decimal(number) => Constant::Decimal(-number),
+            Constant::String(string) => Constant::String(string),
+        }
+    }
+
+    /// How many values a match binds, or `None` if its alternatives bind
+    /// some, which the compiler never produces, so the count isn't fixed.
+    pub fn bound(&self) -> Option<usize> {
+        match self {
+            Shape::Bind => Some(1),
+            Shape::Any | Shape::Nil | Shape::Boolean(_) | Shape::Constant(_) | Shape::None => {
+                Some(0)
+            }
+            Shape::List { items, rest } => items
+                .iter()
+                .chain(rest.as_deref())
+                .try_fold(0, |count, item| Some(count + item.bound()?)),
+            Shape::Record(fields) => fields
+                .iter()
+                .try_fold(0, |count, (_, shape)| Some(count + shape.bound()?)),
+            Shape::Ok(shape) | Shape::Err(shape) | Shape::Some(shape) => shape.bound(),
+            Shape::Either(alternatives) => alternatives
+                .iter()
+                .all(|alternative| alternative.bound() == Some(0))
+                .then_some(0),
+        }
+    }
+
+    /// The values `value` binds, in order, or `None` if it doesn't match.
+    pub fn bind(&self, value: &Value) -> Option<Vec<Value>> {
+        let mut bindings = vec![];
+        self.collect(value, &mut bindings).then_some(bindings)
+    }
+
+    fn collect(&self, value: &Value, bindings: &mut Vec<Value>) -> bool {
+        match (self, value) {
+            (Shape::Any, _) => true,
+            (Shape::Bind, _) => {
+                bindings.push(value.clone());
+                true
+            }
+            (Shape::Nil, Value::Nil) => true,
+            (Shape::Boolean(expected), Value::Boolean(actual)) => expected == actual,
+            (Shape::Constant(constant), _) => constant.value() == *value,
+            (Shape::List { items, rest }, Value::List(list)) => {
+                let list = list.borrow();
+                let fits = match rest {
+                    Some(_) => list.len() >= items.len(),
+                    None => list.len() == items.len(),
+                };
+                if !fits
+                    || !items
+                        .iter()
+                        .zip(list.iter())
+                        .all(|(item, value)| item.collect(value, bindings))
+                {
+                    return false;
+                }
+
+                match rest {
+                    Some(rest) => {
+                        let remaining = list[items.len()..].to_vec();
+                        rest.collect(&Value::List(Rc::new(RefCell::new(remaining))), bindings)
+                    }
+                    None => true,
+                }
+            }
+            (Shape::Record(fields), Value::Record(record)) => {
+                let record = record.borrow();
+                fields.iter().all(|(key, shape)| match record.get(key) {
+                    Some(value) => shape.collect(value, bindings),
+                    None => false,
+                })
+            }
+            (Shape::Record(fields), Value::Error(error)) => {
+                fields.iter().all(|(key, shape)| match error.field(key) {
+                    Some(value) => shape.collect(&value, bindings),
+                    None => false,
+                })
+            }
+            (Shape::Ok(shape), Value::Result(result)) => match &**result {
+                Ok(value) => shape.collect(value, bindings),
+                Err(_) => false,
+            },
+            (Shape::Err(shape), Value::Result(result)) => match &**result {
+                Err(error) => shape.collect(error, bindings),
+                Ok(_) => false,
+            },
+            (Shape::Some(shape), Value::Option(Some(value))) => shape.collect(value, bindings),
+            (Shape::None, Value::Option(None)) => true,
+            (Shape::Either(alternatives), _) => alternatives
+                .iter()
+                .any(|alternative| alternative.collect(value, bindings)),
+            _ => false,
+        }
+    }
+}
+
+/// The values a `make`, `for` or parameter `pattern` binds from `value`, in
+/// order, or an error if the value has the wrong shape.
+pub fn destructure(pattern: &Pattern, value: Value) -> Result<Vec<Value>, Diagnostic> {
+    if let Pattern::Binding(_) = pattern {
+        return Ok(vec![value]);
+    }
+
+    Shape::new(pattern)?
+        .bind(&value)
+        .ok_or_else(|| Diagnostic::error(mismatched(&value)).at(pattern.span()))
+}
+
+/// The error raised when a destructuring pattern doesn't fit `value`.
+pub fn mismatched(value: &Value) -> String {
+    format!("Cannot destructure the {} {}", value.type_name(), value)
+}
+
+/// The error raised when no arm of a `match` accepts `value`.
+pub fn unmatched(value: &Value) -> String {
+    format!("No match arm accepts the {} {}", value.type_name(), value)
+}
@@ -1,16 +1,35 @@
+use crate::diagnostic::Span;
+
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub struct Token {
     pub token_type: TokenType,
     pub lexeme: String,
     pub line: u32,
+    pub column: u32,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Token {
+    pub fn span(&self) -> Span {
+        Span::new(self.start, self.end, self.line, self.column)
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
 pub enum TokenType {
     // Literals
-    Number,
+    Int,
+    /// An int literal too large for 64 bits.
+    BigInt,
+    Float,
+    /// A number with a `d` suffix, such as `12.50d`.
+    Decimal,
     Identifier,
     String,
+    /// The part of a backtick string before a `${`. The parts after each
+    /// embedded expression follow it, the last one as a `String`.
+    Interpolation,
     Boolean,
     Nil,
 
@@ -28,6 +47,14 @@ pub enum TokenType {
     As,
     For,
     In,
+    Break,
+    Continue,
+    Yield,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Match,
 
     // Single-character tokens
     Plus,
@@ -48,12 +75,38 @@ pub enum TokenType {
     Semicolon,
     Dot,
     Colon,
+    Pipe,
+    Percent,
+    Ampersand,
+    Caret,
+    Tilde,
+    Question,
 
     // double-character tokens
     EqualEqual,
     BangEqual,
     GreaterEqual,
     LessEqual,
+    StarStar,
+    SlashSlash,
+    LessLess,
+    GreaterGreater,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
+    PercentEqual,
+    PlusPlus,
+    MinusMinus,
+    /// `=>`, between a pattern and its arm in a `match`.
+    FatArrow,
+    /// `..`, before the rest of a list pattern.
+    DotDot,
+    /// `...`, before a rest parameter.
+    Ellipsis,
+
+    // Only found in `Lexer::comments`
+    Comment,
 
     // EOF
     EOF,
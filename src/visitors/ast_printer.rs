@@ -1,14 +1,17 @@
 use crate::parser::{
     accept::Accept,
     expression::{
-        Assignment, Call, Comparison, Equality, Factor, IfExpression, Index, IndexAssignment, List,
-        MapIndex, MapIndexAssignment, Primary, Record, Term, Unary,
+        Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor, IfExpression, Index,
+        IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment, Match, Power,
+        Primary, Propagate, Record, Term, Unary,
     },
+    parameter::Parameter,
     statement::{
-        Block, ExpressionStatement, For, IfStatement, PrintStatement, Return, Statement,
-        VariableDeclaration, WhileStatement, With,
+        Block, Break, Continue, ExpressionStatement, For, IfStatement, PrintStatement, Return,
+        Statement, Throw, Try, VariableDeclaration, WhileStatement, With, Yield,
     },
 };
+use crate::token::Token;
 
 pub struct AstPrinter {}
 
@@ -17,6 +20,44 @@ impl AstPrinter {
         AstPrinter {}
     }
 
+    fn label(label: &Option<Token>) -> String {
+        match label {
+            Some(label) => format!("{}: ", label.lexeme),
+            None => String::new(),
+        }
+    }
+
+    fn loop_control(keyword: &str, label: &Option<Token>) -> String {
+        match label {
+            Some(label) => format!("{} {}", keyword, label.lexeme),
+            None => keyword.to_string(),
+        }
+    }
+
+    /// ` = value`, ` += value` and the like for a compound assignment, or
+    /// the `++` or `--` of an increment.
+    fn update(&mut self, operator: &Option<Token>, value: &Expression) -> String {
+        match operator {
+            Some(operator) if operator.lexeme == "++" || operator.lexeme == "--" => {
+                operator.lexeme.clone()
+            }
+            Some(operator) => format!(" {}= {}", operator.lexeme, value.accept(self)),
+            None => format!(" = {}", value.accept(self)),
+        }
+    }
+
+    fn parameters(&mut self, parameters: &[Parameter]) -> String {
+        parameters
+            .iter()
+            .map(|param| match &param.default {
+                _ if param.rest => format!("...{}", param.pattern),
+                Some(default) => format!("{} = {}", param.pattern, default.accept(self)),
+                None => param.pattern.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(",")
+    }
+
     pub fn print(&mut self, statements: &Vec<Statement>) {
         let mut result = String::new();
         for statement in statements {
@@ -33,9 +74,9 @@ impl super::Visitor for AstPrinter {
 
     fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
         format!(
-            "{} = {}",
+            "{}{}",
             assignment.identifier.lexeme,
-            assignment.value.accept(self)
+            self.update(&assignment.operator, &assignment.value)
         )
     }
 
@@ -74,6 +115,24 @@ impl super::Visitor for AstPrinter {
         )
     }
 
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        format!(
+            "({} {} {})",
+            bitwise.left.accept(self),
+            bitwise.operator.lexeme,
+            bitwise.right.accept(self)
+        )
+    }
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        format!(
+            "({} {} {})",
+            power.left.accept(self),
+            power.operator.lexeme,
+            power.right.accept(self)
+        )
+    }
+
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
         format!("({}{})", unary.operator.lexeme, unary.right.accept(self))
     }
@@ -97,7 +156,7 @@ impl super::Visitor for AstPrinter {
         &mut self,
         variable_declaration: &VariableDeclaration,
     ) -> Self::Output {
-        let identifier = variable_declaration.identifier.lexeme.clone();
+        let identifier = variable_declaration.pattern.to_string();
         if let Some(expression) = &variable_declaration.expression {
             format!("make {} = {}", identifier, expression.accept(self))
         } else {
@@ -153,7 +212,8 @@ impl super::Visitor for AstPrinter {
 
     fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
         format!(
-            "while ({}) do {}",
+            "{}while ({}) do {}",
+            Self::label(&while_statement.label),
             while_statement.condition.accept(self),
             while_statement.body.accept(self)
         )
@@ -165,7 +225,10 @@ impl super::Visitor for AstPrinter {
             call.identifier.accept(self),
             call.arguments
                 .iter()
-                .map(|arg| arg.accept(self))
+                .map(|arg| match &arg.name {
+                    Some(name) => format!("{}: {}", name.lexeme, arg.value.accept(self)),
+                    None => arg.value.accept(self),
+                })
                 .collect::<Vec<String>>()
                 .join(", ")
         )
@@ -176,14 +239,14 @@ impl super::Visitor for AstPrinter {
         function_declaration: &crate::parser::statement::FunctionDeclaration,
     ) -> Self::Output {
         format!(
-            "funk {} ({}) {}",
+            "funk{} {} ({}) {}",
+            if function_declaration.generator {
+                "*"
+            } else {
+                ""
+            },
             function_declaration.identifier.lexeme,
-            function_declaration
-                .parameters
-                .iter()
-                .map(|param| param.lexeme.clone())
-                .collect::<Vec<String>>()
-                .join(","),
+            self.parameters(&function_declaration.parameters),
             function_declaration.body.accept(self),
         )
     }
@@ -198,6 +261,36 @@ impl super::Visitor for AstPrinter {
         )
     }
 
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        format!("yield ({})", yield_statement.value.accept(self))
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        format!("throw ({})", throw_statement.value.accept(self))
+    }
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        let mut result = format!("try {}", try_statement.body.accept(self));
+
+        if let Some(catch) = &try_statement.catch {
+            let body = catch.body.accept(self);
+            result.push_str(format!(" catch ({}) {}", catch.identifier.lexeme, body).as_str());
+        }
+        if let Some(finally) = &try_statement.finally {
+            result.push_str(format!(" finally {}", finally.accept(self)).as_str());
+        }
+
+        result
+    }
+
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output {
+        Self::loop_control("break", &break_statement.label)
+    }
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output {
+        Self::loop_control("continue", &continue_statement.label)
+    }
+
     fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
         format!(
             "with {} as ({}) {}",
@@ -220,8 +313,9 @@ impl super::Visitor for AstPrinter {
 
     fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
         format!(
-            "for {} in {} {}",
-            for_statement.identifier.lexeme,
+            "{}for {} in {} {}",
+            Self::label(&for_statement.label),
+            for_statement.pattern,
             for_statement.iter.accept(self),
             for_statement.body.accept(self)
         )
@@ -256,19 +350,67 @@ impl super::Visitor for AstPrinter {
         map_index_assignment: &MapIndexAssignment,
     ) -> Self::Output {
         format!(
-            "{}.{} = {}",
+            "{}.{}{}",
             map_index_assignment.map.accept(self),
             map_index_assignment.key.lexeme,
-            map_index_assignment.value.accept(self)
+            self.update(&map_index_assignment.operator, &map_index_assignment.value)
         )
     }
 
     fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
         format!(
-            "{}[{}] = {}",
+            "{}[{}]{}",
             index_assignment.list.accept(self),
             index_assignment.expression.accept(self),
-            index_assignment.value.accept(self)
+            self.update(&index_assignment.operator, &index_assignment.value)
+        )
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        format!(
+            "funk{} ({}) {}",
+            if lambda.generator { "*" } else { "" },
+            self.parameters(&lambda.parameters),
+            lambda.body.accept(self),
+        )
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        let mut parts = vec![format!("{:?}", interpolation.parts[0].lexeme)];
+        for (expression, part) in interpolation
+            .expressions
+            .iter()
+            .zip(&interpolation.parts[1..])
+        {
+            parts.push(expression.accept(self));
+            parts.push(format!("{:?}", part.lexeme));
+        }
+
+        format!("(interpolate {})", parts.join(" "))
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        format!("({}?)", propagate.expression.accept(self))
+    }
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        let arms: Vec<String> = match_expression
+            .arms
+            .iter()
+            .map(|arm| match &arm.guard {
+                Some(guard) => format!(
+                    "{} if {} => {}",
+                    arm.pattern,
+                    guard.accept(self),
+                    arm.body.accept(self)
+                ),
+                None => format!("{} => {}", arm.pattern, arm.body.accept(self)),
+            })
+            .collect();
+        format!(
+            "match {} {{ {} }}",
+            match_expression.value.accept(self),
+            arms.join(", ")
         )
     }
 }
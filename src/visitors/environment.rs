@@ -23,13 +23,13 @@ impl<T: Clone> Environment<T> {
         self.values.insert(identifier, value);
     }
 
-    pub fn get(&self, identifier: String) -> T {
-        if self.values.contains_key(&identifier) {
-            return self.values.get(&identifier).unwrap().clone();
+    pub fn get(&self, identifier: String) -> Result<T, String> {
+        if let Some(value) = self.values.get(&identifier) {
+            return Ok(value.clone());
         } else {
             match &self.enclosing {
-                Some(enclosing) => enclosing.borrow_mut().get(identifier),
-                None => panic!("Undefined variable {}", identifier),
+                Some(enclosing) => enclosing.borrow().get(identifier),
+                None => Err(format!("Undefined variable {}", identifier)),
             }
         }
     }
@@ -38,13 +38,14 @@ impl<T: Clone> Environment<T> {
         self.values.insert(identifier, value);
     }
 
-    pub fn assign(&mut self, identifier: String, value: T) {
-        if self.values.contains_key(&identifier) {
-            self.values.insert(identifier, value);
+    pub fn assign(&mut self, identifier: String, value: T) -> Result<(), String> {
+        if let Some(slot) = self.values.get_mut(&identifier) {
+            *slot = value;
+            Ok(())
         } else {
             match &mut self.enclosing {
                 Some(enclosing) => enclosing.borrow_mut().assign(identifier, value),
-                None => panic!("Undefined variable {}", identifier),
+                None => Err(format!("Undefined variable {}", identifier)),
             }
         }
     }
@@ -56,8 +57,54 @@ impl<T: Clone> Environment<T> {
     pub fn get_enclosing(&self) -> Rc<RefCell<Environment<T>>> {
         self.enclosing.clone().expect("No enclosing environment")
     }
+}
+
+/// Slot-addressed storage for one lexical scope, laid out by the resolver.
+/// Variables are found by walking `depth` scopes outwards and reading `slot`,
+/// rather than by name.
+#[derive(Debug)]
+pub struct Scope<T: Clone> {
+    slots: Vec<Option<T>>,
+    enclosing: Option<Rc<RefCell<Scope<T>>>>,
+}
+
+impl<T: Clone> Scope<T> {
+    pub fn new(enclosing: Option<Rc<RefCell<Scope<T>>>>) -> Self {
+        Scope {
+            slots: vec![],
+            enclosing,
+        }
+    }
+
+    pub fn declare(&mut self, slot: usize, value: T) {
+        if slot >= self.slots.len() {
+            self.slots.resize(slot + 1, None);
+        }
+        self.slots[slot] = Some(value);
+    }
+
+    pub fn get(&self, depth: usize, slot: usize) -> Option<T> {
+        if depth == 0 {
+            return self.slots.get(slot).cloned().flatten();
+        }
+
+        self.enclosing.as_ref()?.borrow().get(depth - 1, slot)
+    }
+
+    pub fn assign(&mut self, depth: usize, slot: usize, value: T) -> bool {
+        if depth == 0 {
+            return match self.slots.get_mut(slot) {
+                Some(Some(current)) => {
+                    *current = value;
+                    true
+                }
+                _ => false,
+            };
+        }
 
-    pub fn has_enclosing(&self) -> bool {
-        self.enclosing.is_some()
+        match &self.enclosing {
+            Some(enclosing) => enclosing.borrow_mut().assign(depth - 1, slot, value),
+            None => false,
+        }
     }
 }
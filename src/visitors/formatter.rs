@@ -0,0 +1,628 @@
+use crate::{
+    parser::{
+        accept::Accept,
+        expression::{
+            And, Argument, Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor,
+            IfExpression, Index, IndexAssignment, Interpolation, Lambda, List, MapIndex,
+            MapIndexAssignment, Match, Or, Power, Primary, Propagate, Record, Term, Unary,
+        },
+        parameter::Parameter,
+        pattern::Pattern,
+        statement::{
+            Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+            PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement,
+            With, Yield,
+        },
+    },
+    token::{Token, TokenType},
+};
+
+const INDENT: &str = "    ";
+
+/// Pretty-prints a parsed program back to source in the canonical layout:
+/// four space indents, one statement per line, and parentheses only where
+/// precedence needs them. Comments stay on the line they were on, except
+/// those inside a statement, which move to the line after it.
+pub struct Formatter<'a> {
+    source: &'a str,
+    comments: &'a [Token],
+    next_comment: usize,
+    depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    pub fn new(source: &'a str, comments: &'a [Token]) -> Self {
+        Formatter {
+            source,
+            comments,
+            next_comment: 0,
+            depth: 0,
+        }
+    }
+
+    pub fn format(&mut self, statements: &[Statement]) -> String {
+        self.statements(statements, self.source.len())
+    }
+
+    /// Formats `statements` one per line at the current depth, along with
+    /// every comment that starts before `end`. A single blank line is kept
+    /// wherever the source had one or more.
+    fn statements(&mut self, statements: &[Statement], end: usize) -> String {
+        let indent = INDENT.repeat(self.depth);
+        let mut output = String::new();
+        let mut last_line = None;
+
+        for statement in statements {
+            let span = statement.span();
+            self.comments_before(span.start, &indent, &mut output, &mut last_line);
+
+            if last_line.is_some_and(|line| span.line > line + 1) {
+                output.push('\n');
+            }
+            let text = statement.accept(self);
+            output.push_str(&format!("{}{}\n", indent, text));
+            last_line = Some(self.line_of(span.end));
+        }
+        self.comments_before(end, &indent, &mut output, &mut last_line);
+
+        output
+    }
+
+    fn comments_before(
+        &mut self,
+        offset: usize,
+        indent: &str,
+        output: &mut String,
+        last_line: &mut Option<u32>,
+    ) {
+        while let Some(comment) = self.comments.get(self.next_comment) {
+            if comment.start >= offset {
+                break;
+            }
+            self.next_comment += 1;
+
+            match *last_line {
+                Some(line) if comment.line == line => {
+                    output.pop();
+                    output.push_str(&format!(" {}\n", comment.lexeme));
+                }
+                Some(line) if comment.line > line + 1 => {
+                    output.push_str(&format!("\n{}{}\n", indent, comment.lexeme));
+                }
+                _ => output.push_str(&format!("{}{}\n", indent, comment.lexeme)),
+            }
+            *last_line = Some(comment.line);
+        }
+    }
+
+    fn line_of(&self, offset: usize) -> u32 {
+        let offset = offset.min(self.source.len());
+        self.source.as_bytes()[..offset]
+            .iter()
+            .filter(|byte| **byte == b'\n')
+            .count() as u32
+            + 1
+    }
+
+    /// Formats `expression`, wrapping it in parentheses when it binds less
+    /// tightly than `precedence`.
+    fn operand(&mut self, expression: &Expression, precedence: u8) -> String {
+        let text = expression.accept(self);
+        if Self::precedence(expression) < precedence {
+            return format!("({})", text);
+        }
+
+        text
+    }
+
+    /// Mirrors the parser's precedence climbing, from assignment (loosest) to
+    /// calls, indexing and field access (tightest).
+    fn precedence(expression: &Expression) -> u8 {
+        match expression {
+            Expression::Assignment(_)
+            | Expression::IndexAssignment(_)
+            | Expression::MapIndexAssignment(_) => 1,
+            // The body of `|a| a + 1` runs to the end of the expression.
+            Expression::Lambda(lambda) if lambda.is_arrow() => 1,
+            Expression::IfExpression(_) => 2,
+            Expression::Or(_) => 3,
+            Expression::And(_) => 4,
+            Expression::Equality(_) => 5,
+            Expression::Comparison(_) => 6,
+            Expression::Bitwise(bitwise) => Self::bitwise_precedence(&bitwise.operator),
+            Expression::Term(_) => 11,
+            Expression::Factor(_) => 12,
+            Expression::Unary(_) => 13,
+            Expression::Power(_) => 14,
+            Expression::Call(_)
+            | Expression::Index(_)
+            | Expression::MapIndex(_)
+            | Expression::Propagate(_) => 15,
+            Expression::Primary(_)
+            | Expression::List(_)
+            | Expression::Record(_)
+            | Expression::Interpolation(_)
+            | Expression::Lambda(_)
+            | Expression::Match(_) => 16,
+        }
+    }
+
+    fn bitwise_precedence(operator: &Token) -> u8 {
+        match operator.token_type {
+            TokenType::Pipe => 7,
+            TokenType::Caret => 8,
+            TokenType::Ampersand => 9,
+            _ => 10,
+        }
+    }
+
+    fn binary(
+        &mut self,
+        left: &Expression,
+        operator: &str,
+        right: &Expression,
+        precedence: u8,
+    ) -> String {
+        format!(
+            "{} {} {}",
+            self.operand(left, precedence),
+            operator,
+            self.operand(right, precedence + 1)
+        )
+    }
+
+    fn label(label: &Option<Token>) -> String {
+        match label {
+            Some(label) => format!("{}: ", label.lexeme),
+            None => String::new(),
+        }
+    }
+
+    fn loop_control(keyword: &str, label: &Option<Token>) -> String {
+        match label {
+            Some(label) => format!("{} {};", keyword, label.lexeme),
+            None => format!("{};", keyword),
+        }
+    }
+
+    /// ` = value`, ` += value` and the like for a compound assignment, or
+    /// the `++` or `--` of an increment.
+    fn update(&mut self, operator: &Option<Token>, value: &Expression) -> String {
+        match operator {
+            Some(operator) if operator.lexeme == "++" || operator.lexeme == "--" => {
+                operator.lexeme.clone()
+            }
+            Some(operator) => format!(" {}= {}", operator.lexeme, self.operand(value, 1)),
+            None => format!(" = {}", self.operand(value, 1)),
+        }
+    }
+
+    /// The parameters of `|...|` keep their defaults above `|`, which would
+    /// otherwise end the list.
+    fn parameters(&mut self, parameters: &[Parameter], arrow: bool) -> String {
+        parameters
+            .iter()
+            .map(|parameter| match &parameter.default {
+                _ if parameter.rest => format!("...{}", self.pattern(&parameter.pattern)),
+                Some(default) => format!(
+                    "{} = {}",
+                    self.pattern(&parameter.pattern),
+                    self.operand(default, if arrow { 8 } else { 1 })
+                ),
+                None => self.pattern(&parameter.pattern),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    fn arguments(&mut self, arguments: &[Argument]) -> String {
+        arguments
+            .iter()
+            .map(|argument| match &argument.name {
+                Some(name) => format!("{}: {}", name.lexeme, self.operand(&argument.value, 1)),
+                None => self.operand(&argument.value, 1),
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+
+    /// Like the pattern's `Display`, but with string literals copied from
+    /// the source so their escapes survive.
+    fn pattern(&self, pattern: &Pattern) -> String {
+        match pattern {
+            Pattern::Literal {
+                value, negative, ..
+            } => {
+                let sign = if *negative { "-" } else { "" };
+                format!("{}{}", sign, &self.source[value.start..value.end])
+            }
+            Pattern::List { items, rest, .. } => {
+                let mut parts: Vec<String> = items.iter().map(|item| self.pattern(item)).collect();
+                if let Some(rest) = rest {
+                    let name = rest.name.as_ref().map(|name| name.lexeme.as_str());
+                    parts.push(format!("..{}", name.unwrap_or("")));
+                }
+                format!("[{}]", parts.join(", "))
+            }
+            Pattern::Record { fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(key, pattern)| match pattern {
+                        Pattern::Binding(name) if name.lexeme == key.lexeme => key.lexeme.clone(),
+                        pattern => format!("{}: {}", key.lexeme, self.pattern(pattern)),
+                    })
+                    .collect();
+                format!("{{{}}}", fields.join(", "))
+            }
+            Pattern::Variant { name, pattern, .. } => {
+                format!("{}({})", name.lexeme, self.pattern(pattern))
+            }
+            Pattern::Either { alternatives, .. } => alternatives
+                .iter()
+                .map(|alternative| self.pattern(alternative))
+                .collect::<Vec<String>>()
+                .join(" | "),
+            Pattern::Wildcard(_) | Pattern::Binding(_) | Pattern::None(_) => pattern.to_string(),
+        }
+    }
+
+    fn list(&mut self, expressions: &[Expression]) -> String {
+        expressions
+            .iter()
+            .map(|expression| self.operand(expression, 1))
+            .collect::<Vec<String>>()
+            .join(", ")
+    }
+}
+
+impl super::Visitor for Formatter<'_> {
+    type Output = String;
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        format!(
+            "{}{}",
+            assignment.identifier.lexeme,
+            self.update(&assignment.operator, &assignment.value)
+        )
+    }
+
+    fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
+        self.binary(
+            &equality.left,
+            &equality.operator.lexeme,
+            &equality.right,
+            5,
+        )
+    }
+
+    fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
+        self.binary(
+            &comparison.left,
+            &comparison.operator.lexeme,
+            &comparison.right,
+            6,
+        )
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Self::Output {
+        self.binary(&term.left, &term.operator.lexeme, &term.right, 11)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
+        self.binary(&factor.left, &factor.operator.lexeme, &factor.right, 12)
+    }
+
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        self.binary(
+            &bitwise.left,
+            &bitwise.operator.lexeme,
+            &bitwise.right,
+            Self::bitwise_precedence(&bitwise.operator),
+        )
+    }
+
+    /// Right associative, and the exponent may be a unary: `2 ** -x`.
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        format!(
+            "{} ** {}",
+            self.operand(&power.left, 15),
+            self.operand(&power.right, 13)
+        )
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        let right = self.operand(&unary.right, 13);
+        // `- -x` would read back as a decrement without the space.
+        let separator = if unary.operator.lexeme == "-" && right.starts_with('-') {
+            " "
+        } else {
+            ""
+        };
+
+        format!("{}{}{}", unary.operator.lexeme, separator, right)
+    }
+
+    fn visit_primary(&mut self, primary: &Primary) -> Self::Output {
+        match primary.value.token_type {
+            // Strings are copied from the source so their quotes survive.
+            TokenType::String => self.source[primary.span.start..primary.span.end].to_string(),
+            _ => primary.value.lexeme.clone(),
+        }
+    }
+
+    fn visit_expression_statement(
+        &mut self,
+        expression_statement: &ExpressionStatement,
+    ) -> Self::Output {
+        format!("{};", self.operand(&expression_statement.expression, 1))
+    }
+
+    fn visit_print_statement(&mut self, print_statement: &PrintStatement) -> Self::Output {
+        format!("print {};", self.operand(&print_statement.expression, 1))
+    }
+
+    fn visit_variable_declaration(
+        &mut self,
+        variable_declaration: &VariableDeclaration,
+    ) -> Self::Output {
+        let identifier = self.pattern(&variable_declaration.pattern);
+        match &variable_declaration.expression {
+            Some(expression) => format!("make {} = {};", identifier, self.operand(expression, 1)),
+            None => format!("make {};", identifier),
+        }
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Output {
+        let has_comments = self
+            .comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.start < block.span.end);
+        if block.statements.is_empty() && !has_comments {
+            return "{}".to_string();
+        }
+
+        self.depth += 1;
+        let body = self.statements(&block.statements, block.span.end);
+        self.depth -= 1;
+
+        format!("{{\n{}{}}}", body, INDENT.repeat(self.depth))
+    }
+
+    fn visit_if_statement(&mut self, if_statement: &IfStatement) -> Self::Output {
+        let condition = self.operand(&if_statement.condition, 1);
+        let then_branch = if_statement.then_branch.accept(self);
+
+        match &if_statement.else_branch {
+            Some(else_branch) => format!(
+                "if {} {} else {}",
+                condition,
+                then_branch,
+                else_branch.accept(self)
+            ),
+            None => format!("if {} {}", condition, then_branch),
+        }
+    }
+
+    fn visit_if_expression(&mut self, if_expression: &IfExpression) -> Self::Output {
+        format!(
+            "{} if {} else {}",
+            self.operand(&if_expression.then_branch, 2),
+            self.operand(&if_expression.condition, 3),
+            self.operand(&if_expression.else_branch, 3)
+        )
+    }
+
+    fn visit_and(&mut self, and: &And) -> Self::Output {
+        self.binary(&and.left, "and", &and.right, 4)
+    }
+
+    fn visit_or(&mut self, or: &Or) -> Self::Output {
+        self.binary(&or.left, "or", &or.right, 3)
+    }
+
+    fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
+        format!(
+            "{}while {} {}",
+            Self::label(&while_statement.label),
+            self.operand(&while_statement.condition, 1),
+            while_statement.body.accept(self)
+        )
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        format!(
+            "{}({})",
+            self.operand(&call.identifier, 15),
+            self.arguments(&call.arguments)
+        )
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        function_declaration: &FunctionDeclaration,
+    ) -> Self::Output {
+        format!(
+            "funk{} {}({}) {}",
+            if function_declaration.generator {
+                "*"
+            } else {
+                ""
+            },
+            function_declaration.identifier.lexeme,
+            self.parameters(&function_declaration.parameters, false),
+            function_declaration.body.accept(self)
+        )
+    }
+
+    fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
+        match &return_statement.value {
+            Some(value) => format!("return {};", self.operand(value, 1)),
+            None => "return;".to_string(),
+        }
+    }
+
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        format!("yield {};", self.operand(&yield_statement.value, 1))
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        format!("throw {};", self.operand(&throw_statement.value, 1))
+    }
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        let mut result = format!("try {}", try_statement.body.accept(self));
+
+        if let Some(catch) = &try_statement.catch {
+            let body = catch.body.accept(self);
+            result.push_str(&format!(" catch ({}) {}", catch.identifier.lexeme, body));
+        }
+        if let Some(finally) = &try_statement.finally {
+            result.push_str(&format!(" finally {}", finally.accept(self)));
+        }
+
+        result
+    }
+
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output {
+        Self::loop_control("break", &break_statement.label)
+    }
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output {
+        Self::loop_control("continue", &continue_statement.label)
+    }
+
+    fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
+        format!(
+            "with {} as {} {}",
+            self.operand(&with_statement.value, 1),
+            with_statement.identifier.lexeme,
+            with_statement.body.accept(self)
+        )
+    }
+
+    fn visit_list(&mut self, list: &List) -> Self::Output {
+        format!("[{}]", self.list(&list.values))
+    }
+
+    fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
+        format!(
+            "{}for {} in {} {}",
+            Self::label(&for_statement.label),
+            self.pattern(&for_statement.pattern),
+            self.operand(&for_statement.iter, 1),
+            for_statement.body.accept(self)
+        )
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        format!(
+            "{}[{}]",
+            self.operand(&index.list, 15),
+            self.operand(&index.expression, 1)
+        )
+    }
+
+    fn visit_record(&mut self, record: &Record) -> Self::Output {
+        // The parser wants a comma after every field, the last included.
+        let fields = record
+            .key_values
+            .iter()
+            .map(|(key, value)| format!("{}: {},", key.lexeme, self.operand(value, 1)))
+            .collect::<Vec<String>>();
+
+        format!("{{{}}}", fields.join(" "))
+    }
+
+    fn visit_map_index(&mut self, map_index: &MapIndex) -> Self::Output {
+        format!(
+            "{}.{}",
+            self.operand(&map_index.map, 15),
+            map_index.key.lexeme
+        )
+    }
+
+    fn visit_map_index_assignment(
+        &mut self,
+        map_index_assignment: &MapIndexAssignment,
+    ) -> Self::Output {
+        format!(
+            "{}.{}{}",
+            self.operand(&map_index_assignment.map, 15),
+            map_index_assignment.key.lexeme,
+            self.update(&map_index_assignment.operator, &map_index_assignment.value)
+        )
+    }
+
+    fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
+        format!(
+            "{}[{}]{}",
+            self.operand(&index_assignment.list, 15),
+            self.operand(&index_assignment.expression, 1),
+            self.update(&index_assignment.operator, &index_assignment.value)
+        )
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        let parameters = self.parameters(&lambda.parameters, lambda.is_arrow());
+
+        match &lambda.body {
+            Statement::Return(body) if lambda.is_arrow() => match &body.value {
+                Some(value) => format!("|{}| {}", parameters, self.operand(value, 1)),
+                None => format!("|{}| nil", parameters),
+            },
+            body => format!(
+                "funk{} ({}) {}",
+                if lambda.generator { "*" } else { "" },
+                parameters,
+                body.accept(self)
+            ),
+        }
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        // Each part is copied from the source along with its `${` and `}`,
+        // so escapes survive.
+        let mut output = String::new();
+        for (index, part) in interpolation.parts.iter().enumerate() {
+            if index > 0 {
+                output.push_str(&self.operand(&interpolation.expressions[index - 1], 1));
+            }
+            output.push_str(&self.source[part.start..part.end]);
+        }
+
+        output
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        format!("{}?", self.operand(&propagate.expression, 15))
+    }
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        let value = self.operand(&match_expression.value, 1);
+
+        self.depth += 1;
+        let indent = INDENT.repeat(self.depth);
+        let mut arms = String::new();
+        for arm in &match_expression.arms {
+            let guard = match &arm.guard {
+                Some(guard) => format!(" if {}", self.operand(guard, 1)),
+                None => String::new(),
+            };
+            arms.push_str(&format!(
+                "{}{}{} => {},\n",
+                indent,
+                self.pattern(&arm.pattern),
+                guard,
+                self.operand(&arm.body, 1)
+            ));
+        }
+        self.depth -= 1;
+
+        format!(
+            "match {} {{\n{}{}}}",
+            value,
+            arms,
+            INDENT.repeat(self.depth)
+        )
+    }
+}
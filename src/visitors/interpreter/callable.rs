@@ -1,105 +1,284 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fmt, fmt::Write as _, io::Write, rc::Rc};
 
 use crate::{
-    parser::{accept::Accept, statement::FunctionDeclaration},
-    visitors::environment::Environment,
+    arity::{Arguments, Arity},
+    diagnostic::Diagnostic,
+    parser::{accept::Accept, parameter::Parameter, pattern::Pattern, statement::Statement},
+    shape,
+    visitors::{environment::Scope, type_checker::types::Signature},
 };
 
-use super::{value::Value, Interpreter};
+use super::{
+    decimal::{Decimal, Rounding},
+    error::ErrorValue,
+    generator::TreeGenerator,
+    iter::{Chain, Enumerate, Filter, Generator, Iter, Map, Range, Skip, Suspended, Take, Zip},
+    value::Value,
+    Interpreter,
+};
+
+/// How deeply calls to jive functions may nest, on either backend, before
+/// the call that would go deeper fails with "Stack overflow". Low enough
+/// that a debug build reaches it within a main thread's usual 8 MB stack.
+pub const MAX_CALL_DEPTH: usize = 256;
 
 pub trait Callable {
-    fn call(&self, interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value;
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: &mut [Value],
+    ) -> Result<Value, Diagnostic>;
 }
 
-#[derive(Debug, Clone)]
+/// A function declared in the program, or a lambda, which has no name.
+#[derive(Clone)]
 pub struct Function {
-    pub arity: usize,
-    pub declaration: FunctionDeclaration,
-    pub closure: Rc<RefCell<Environment<Value>>>,
+    pub name: Option<String>,
+    pub parameters: Vec<Parameter>,
+    pub arity: Arity,
+    pub body: Statement,
+    pub closure: Option<Rc<RefCell<Scope<Value>>>>,
+    /// Declared with `funk*`, so calling it returns an iterator.
+    pub generator: bool,
 }
 
-impl Callable for Function {
-    fn call(&self, interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
-        let new_environment = Rc::new(RefCell::new(Environment::new()));
-        new_environment.borrow_mut().enclose(self.closure.clone());
-
-        for (parameter, argument) in self.declaration.parameters.iter().zip(arguments.iter()) {
-            new_environment
-                .borrow_mut()
-                .declare(parameter.lexeme.clone(), argument.clone());
+// The closure usually holds the function itself, so it's left out.
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "<fn {}>", name),
+            None => write!(f, "<lambda>"),
         }
+    }
+}
 
-        let previous_environment = interpreter.environment.clone();
-        interpreter.environment = new_environment.clone();
-        let (result, _) = self.declaration.body.accept(interpreter);
+impl Function {
+    /// Calls the function with `arguments`, the last `names.len()` of which
+    /// were passed by those names.
+    pub fn call_with(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Vec<Value>,
+        names: &[String],
+    ) -> Result<Value, Diagnostic> {
+        let arguments = self
+            .arity
+            .arrange(arguments, names)
+            .map_err(Diagnostic::error)?;
+
+        interpreter.nested(|interpreter| {
+            let scope = Rc::new(RefCell::new(Scope::new(self.closure.clone())));
+            if let Err(diagnostic) = self.bind(interpreter, arguments, &scope) {
+                return interpreter.returned(diagnostic);
+            }
 
-        interpreter.environment = previous_environment;
+            if self.generator {
+                let generator = TreeGenerator::new(self.body.clone(), scope);
+                return Ok(Value::Iter(Iter::new(Generator::new(Suspended::Tree(
+                    Box::new(generator),
+                )))));
+            }
 
-        result
+            match interpreter.with_scope(scope, |interpreter| self.body.accept(interpreter)) {
+                Ok((value, _)) => Ok(value),
+                Err(diagnostic) => interpreter.returned(diagnostic),
+            }
+        })
+    }
+
+    /// Fills the call's first scope: each parameter's argument, or its
+    /// default when it was left out, then the names that the parameters
+    /// written as patterns bind from theirs, matching the slots the resolver
+    /// gave them.
+    fn bind(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: Arguments<Value>,
+        scope: &Rc<RefCell<Scope<Value>>>,
+    ) -> Result<(), Diagnostic> {
+        let Arguments { slots, mut rest } = arguments;
+        let mut slots = slots.into_iter();
+        let mut next = self.parameters.len();
+
+        for (slot, parameter) in self.parameters.iter().enumerate() {
+            let value = match (parameter.rest, slots.next().flatten(), &parameter.default) {
+                (true, _, _) => Value::List(Rc::new(RefCell::new(std::mem::take(&mut rest)))),
+                (false, Some(value), _) => value,
+                (false, None, Some(default)) => {
+                    interpreter
+                        .with_scope(scope.clone(), |interpreter| default.accept(interpreter))?
+                        .0
+                }
+                (false, None, None) => Value::Nil,
+            };
+            scope.borrow_mut().declare(slot, value.clone());
+
+            if !matches!(parameter.pattern, Pattern::Binding(_)) {
+                for value in shape::destructure(&parameter.pattern, value)? {
+                    scope.borrow_mut().declare(next, value);
+                    next += 1;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Callable for Function {
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: &mut [Value],
+    ) -> Result<Value, Diagnostic> {
+        self.call_with(interpreter, arguments.to_vec(), &[])
     }
 }
 
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+/// A native function. Anything it prints goes to `output`, the writer the
+/// running program prints to.
+pub type BuiltInFunction = Rc<dyn Fn(&mut [Value], &mut dyn Write) -> Result<Value, String>>;
+
+#[derive(Clone)]
 pub struct BuiltIn {
     pub arity: Option<usize>,
-    pub function: fn(&mut Interpreter, &mut Vec<Value>) -> Value,
+    pub function: BuiltInFunction,
+    /// Parameter and return types for the type checker, when they are known.
+    pub signature: Option<Signature>,
 }
 
 impl BuiltIn {
     pub fn new(
         arity: Option<usize>,
-        function: fn(&mut Interpreter, &mut Vec<Value>) -> Value,
+        function: impl Fn(&mut [Value], &mut dyn Write) -> Result<Value, String> + 'static,
     ) -> Self {
-        BuiltIn { arity, function }
+        BuiltIn {
+            arity,
+            function: Rc::new(function),
+            signature: None,
+        }
+    }
+
+    pub fn with_signature(mut self, signature: Signature) -> Self {
+        self.signature = Some(signature);
+        self
+    }
+}
+
+impl fmt::Debug for BuiltIn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<native funk>")
     }
 }
 
 impl Callable for BuiltIn {
-    fn call(&self, interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
-        (self.function)(interpreter, arguments)
+    fn call(
+        &self,
+        interpreter: &mut Interpreter,
+        arguments: &mut [Value],
+    ) -> Result<Value, Diagnostic> {
+        let output = interpreter.output.clone();
+        let mut output = output.borrow_mut();
+        (self.function)(arguments, &mut *output).map_err(Diagnostic::error)
     }
 }
 
-pub fn clock(_interpreter: &mut Interpreter, _arguments: &mut Vec<Value>) -> Value {
+/// The `args` global: the command line arguments passed to the script.
+pub fn script_args(args: &[String]) -> Value {
+    Value::List(Rc::new(RefCell::new(
+        args.iter()
+            .map(|arg| Value::String(Box::new(arg.clone())))
+            .collect(),
+    )))
+}
+
+/// The native functions every jive program starts with, shared by the
+/// `Interpreter` and the bytecode `Vm`.
+pub fn builtins() -> Vec<(&'static str, BuiltIn)> {
+    vec![
+        ("clock", BuiltIn::new(Some(0), clock)),
+        ("println", BuiltIn::new(None, println)),
+        ("input", BuiltIn::new(Some(0), input)),
+        ("iter", BuiltIn::new(Some(1), iter)),
+        ("range_to", BuiltIn::new(Some(1), range_max)),
+        ("range", BuiltIn::new(Some(2), range_min_max)),
+        ("range_skip", BuiltIn::new(Some(3), range_min_max_skip)),
+        ("range_from", BuiltIn::new(Some(1), range_from)),
+        ("map", BuiltIn::new(Some(2), map)),
+        ("filter", BuiltIn::new(Some(2), filter)),
+        ("take", BuiltIn::new(Some(2), take)),
+        ("skip", BuiltIn::new(Some(2), skip)),
+        ("zip", BuiltIn::new(Some(2), zip)),
+        ("enumerate", BuiltIn::new(Some(1), enumerate)),
+        ("chain", BuiltIn::new(Some(2), chain)),
+        ("len", BuiltIn::new(Some(1), len)),
+        ("push", BuiltIn::new(Some(2), push)),
+        ("to_number", BuiltIn::new(Some(1), to_number)),
+        ("to_bigint", BuiltIn::new(Some(1), to_bigint)),
+        ("to_decimal", BuiltIn::new(Some(1), to_decimal)),
+        ("round", BuiltIn::new(Some(3), round)),
+        ("divide", BuiltIn::new(Some(4), divide)),
+        ("type_of", BuiltIn::new(Some(1), type_of)),
+        ("error", BuiltIn::new(Some(2), error)),
+        ("ok", BuiltIn::new(Some(1), ok)),
+        ("err", BuiltIn::new(Some(1), err)),
+        ("some", BuiltIn::new(Some(1), some)),
+        ("unwrap", BuiltIn::new(Some(1), unwrap)),
+        ("unwrap_or", BuiltIn::new(Some(2), unwrap_or)),
+        ("is_ok", BuiltIn::new(Some(1), is_ok)),
+        ("is_some", BuiltIn::new(Some(1), is_some)),
+        ("try_to_number", BuiltIn::new(Some(1), try_to_number)),
+        ("get", BuiltIn::new(Some(2), get)),
+        ("read_file", BuiltIn::new(Some(1), read_file)),
+        ("write_file", BuiltIn::new(Some(2), write_file)),
+        ("try_read_file", BuiltIn::new(Some(1), try_read_file)),
+        ("try_write_file", BuiltIn::new(Some(2), try_write_file)),
+    ]
+}
+
+pub fn clock(_arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(0)
     use std::time::{SystemTime, UNIX_EPOCH};
 
     let start = SystemTime::now();
     let since_the_epoch = start
         .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
+        .map_err(|_| "Time went backwards".to_string())?;
     let in_ms = since_the_epoch.as_secs() * 1_000 + since_the_epoch.subsec_millis() as u64;
 
-    Value::Number(Box::new(in_ms as f64))
+    Ok(Value::Int(in_ms as i64))
 }
 
-pub fn println(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn println(arguments: &mut [Value], output: &mut dyn Write) -> Result<Value, String> {
     // arity is None
+    let mut line = String::new();
     for argument in arguments {
-        match argument {
-            Value::Number(number) => print!("{}", number),
-            Value::Boolean(boolean) => print!("{}", boolean),
-            Value::String(string) => print!("{}", string),
-            Value::BuiltIn(_) => print!("<native funk>"),
-            Value::Function(_) => print!("<funk>"),
-            Value::Record(record) => print!(
+        let _ = match argument {
+            Value::Int(number) => write!(line, "{}", number),
+            Value::BigInt(number) => write!(line, "{}", number),
+            Value::Float(number) => write!(line, "{}", number),
+            Value::Decimal(number) => write!(line, "{}", number),
+            Value::Boolean(boolean) => write!(line, "{}", boolean),
+            Value::String(string) => write!(line, "{}", string),
+            Value::BuiltIn(_) => write!(line, "<native funk>"),
+            Value::Function(_) | Value::Closure(_) => write!(line, "<funk>"),
+            Value::Record(record) => write!(
+                line,
                 "{{{}}}",
                 record
                     .borrow()
                     .keys()
-                    .map(|k| { format!("{}:{},", k, record.borrow().get(k).unwrap().to_string()) })
+                    .map(|k| { format!("{}:{},", k, record.borrow().get(k).unwrap()) })
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            Value::Iter(iter) => print!(
-                "Iter [{}]",
-                iter.borrow()
-                    .iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ),
-            Value::List(list) => print!(
+            Value::Iter(_) => write!(line, "<iter>"),
+            Value::Error(error) => write!(line, "{}", error),
+            Value::Result(_) | Value::Option(_) | Value::Uninitialized(_) => {
+                write!(line, "{}", argument)
+            }
+            Value::List(list) => write!(
+                line,
                 "[{}]",
                 list.borrow()
                     .iter()
@@ -107,162 +286,481 @@ pub fn println(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Va
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            Value::Nil => println!("nil"),
-        }
+            Value::Nil => writeln!(line, "nil"),
+        };
     }
-    println!();
+    writeln!(output, "{}", line).map_err(|error| error.to_string())?;
 
-    Value::Nil
+    Ok(Value::Nil)
 }
 
-pub fn input(_interpreter: &mut Interpreter, _arguments: &mut Vec<Value>) -> Value {
+pub fn input(_arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(0)
     let mut input = String::new();
-    std::io::stdin().read_line(&mut input).unwrap();
-    Value::String(Box::new(input.trim().to_string()))
+    std::io::stdin()
+        .read_line(&mut input)
+        .map_err(|error| error.to_string())?;
+    Ok(Value::String(Box::new(input.trim().to_string())))
 }
 
-pub fn iter(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn iter(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(1)
-    let value = &arguments[0];
-
-    match value {
-        Value::String(string) => Value::Iter(Rc::new(RefCell::new(
-            string
-                .chars()
-                .map(|c| Value::String(Box::new(c.to_string())))
-                .collect(),
-        ))),
-        Value::List(list) => Value::Iter(list.clone()),
-        _ => panic!("Input must be of type either string or list"),
+    match &arguments[0] {
+        Value::String(_) | Value::List(_) => Ok(Value::Iter(
+            Iter::over(&arguments[0]).expect("Strings and lists are iterable"),
+        )),
+        _ => Err("Input must be of type either string or list".to_string()),
     }
 }
 
-pub fn range_max(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+fn range(next: i64, end: Option<i64>, step: i64) -> Value {
+    Value::Iter(Iter::new(Range {
+        next: Some(next),
+        end,
+        step,
+    }))
+}
+
+pub fn range_max(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(1)
     let max = &arguments[0];
 
     match max {
-        Value::Number(max) => {
-            if max.is_sign_negative() {
-                panic!("Must pass a positive number to range function");
+        Value::Int(max) => {
+            if *max < 0 {
+                Err("Must pass a positive number to range function".to_string())
             } else {
-                Value::Iter(Rc::new(RefCell::new(
-                    (0..(**max as i64))
-                        .map(|v| Value::Number(Box::new(v as f64)))
-                        .collect::<Vec<Value>>(),
-                )))
+                Ok(range(0, Some(*max), 1))
             }
         }
-        _ => panic!("Must pass a number to range function"),
+        _ => Err("Must pass an integer to range function".to_string()),
     }
 }
 
-pub fn range_min_max(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn range_min_max(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(2)
     let min = &arguments[0];
     let max = &arguments[1];
 
-    if let Value::Number(min) = min {
-        if min.is_sign_negative() {
-            panic!("Must pass a positive number to range function");
+    if let Value::Int(min) = min {
+        if *min < 0 {
+            return Err("Must pass a positive number to range function".to_string());
         }
 
-        if let Value::Number(max) = max {
+        if let Value::Int(max) = max {
             if min >= max {
-                panic!("First argument must be smaller than the second argument");
+                return Err("First argument must be smaller than the second argument".to_string());
             }
 
-            Value::Iter(Rc::new(RefCell::new(
-                ((**min as i64)..(**max as i64))
-                    .map(|v| Value::Number(Box::new(v as f64)))
-                    .collect::<Vec<Value>>(),
-            )))
+            Ok(range(*min, Some(*max), 1))
         } else {
-            panic!("Must pass a number to range function")
+            Err("Must pass an integer to range function".to_string())
         }
     } else {
-        panic!("Must pass a number to range function")
+        Err("Must pass an integer to range function".to_string())
     }
 }
 
-pub fn range_min_max_skip(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
-    // arity is Some(2)
+pub fn range_min_max_skip(
+    arguments: &mut [Value],
+    _output: &mut dyn Write,
+) -> Result<Value, String> {
+    // arity is Some(3)
     let min = &arguments[0];
     let max = &arguments[1];
     let skip = &arguments[2];
 
-    if let Value::Number(min) = min {
-        if min.is_sign_negative() {
-            panic!("Must pass a positive number to range function");
+    if let Value::Int(min) = min {
+        if *min < 0 {
+            return Err("Must pass a positive number to range function".to_string());
         }
 
-        if let Value::Number(max) = max {
+        if let Value::Int(max) = max {
             if min >= max {
-                panic!("First argument must be smaller than the second argument");
+                return Err("First argument must be smaller than the second argument".to_string());
             }
 
-            if let Value::Number(skip) = skip {
-                Value::Iter(Rc::new(RefCell::new(
-                    ((**min as i64)..(**max as i64))
-                        .step_by((**skip as i64) as usize)
-                        .map(|v| Value::Number(Box::new(v as f64)))
-                        .collect::<Vec<Value>>(),
-                )))
+            if let Value::Int(skip) = skip {
+                if *skip < 1 {
+                    return Err("Must pass a positive skip to range function".to_string());
+                }
+
+                Ok(range(*min, Some(*max), *skip))
             } else {
-                panic!("Must pass a number to range function")
+                Err("Must pass an integer to range function".to_string())
             }
         } else {
-            panic!("Must pass a number to range function")
+            Err("Must pass an integer to range function".to_string())
         }
     } else {
-        panic!("Must pass a number to range function")
+        Err("Must pass an integer to range function".to_string())
     }
 }
 
-pub fn len(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn range_from(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(1)
-    Value::Number(Box::new(match &arguments[0] {
-        Value::List(list) => list.borrow().len() as f64,
-        Value::String(string) => string.len() as f64,
-        _ => panic!("Must pass either a list or a string to len function"),
+    match &arguments[0] {
+        Value::Int(start) => Ok(range(*start, None, 1)),
+        _ => Err("Must pass an integer to range function".to_string()),
+    }
+}
+
+/// Reads an argument of an iterator adaptor, which may be a list, a string
+/// or an iter.
+fn iterable(value: &Value, name: &str) -> Result<Iter, String> {
+    Iter::over(value).ok_or(format!(
+        "Must pass a list, string or iter to {} function",
+        name
+    ))
+}
+
+fn function(value: &Value, name: &str) -> Result<Value, String> {
+    match value {
+        Value::BuiltIn(_) | Value::Function(_) | Value::Closure(_) => Ok(value.clone()),
+        _ => Err(format!("Must pass a function to {} function", name)),
+    }
+}
+
+fn count(value: &Value, name: &str) -> Result<usize, String> {
+    match value {
+        Value::Int(count) => usize::try_from(*count)
+            .map_err(|_| format!("Must pass a positive number to {} function", name)),
+        _ => Err(format!("Must pass an integer to {} function", name)),
+    }
+}
+
+pub fn map(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Map {
+        source: iterable(&arguments[0], "map")?,
+        function: function(&arguments[1], "map")?,
+    })))
+}
+
+pub fn filter(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Filter {
+        source: iterable(&arguments[0], "filter")?,
+        predicate: function(&arguments[1], "filter")?,
+    })))
+}
+
+pub fn take(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Take {
+        source: iterable(&arguments[0], "take")?,
+        remaining: count(&arguments[1], "take")?,
+    })))
+}
+
+pub fn skip(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Skip {
+        source: iterable(&arguments[0], "skip")?,
+        remaining: count(&arguments[1], "skip")?,
+    })))
+}
+
+pub fn zip(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Zip {
+        left: iterable(&arguments[0], "zip")?,
+        right: iterable(&arguments[1], "zip")?,
+    })))
+}
+
+pub fn enumerate(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(Value::Iter(Iter::new(Enumerate {
+        source: iterable(&arguments[0], "enumerate")?,
+        index: 0,
+    })))
+}
+
+pub fn chain(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    Ok(Value::Iter(Iter::new(Chain {
+        first: Some(iterable(&arguments[0], "chain")?),
+        second: iterable(&arguments[1], "chain")?,
+    })))
+}
+
+pub fn len(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(Value::Int(match &arguments[0] {
+        Value::List(list) => list.borrow().len() as i64,
+        Value::String(string) => string.len() as i64,
+        _ => return Err("Must pass either a list or a string to len function".to_string()),
     }))
 }
 
-pub fn push(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn push(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(2)
     let value = arguments[1].clone();
 
     match &mut arguments[0] {
         Value::List(list) => {
             list.borrow_mut().push(value);
-            Value::List(list.clone())
+            Ok(Value::List(list.clone()))
         }
-        _ => panic!("Must pass either a list to push function"),
+        _ => Err("Must pass either a list to push function".to_string()),
     }
 }
 
-pub fn to_number(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn to_number(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(1)
 
-    Value::Number(Box::new(match &arguments[0] {
-        Value::String(string) => string.parse().expect("Could not parse string"),
-        _ => panic!("Must pass a string to to_number function"),
-    }))
+    match &arguments[0] {
+        Value::String(string) => parse_number(string),
+        _ => Err("Must pass a string to to_number function".to_string()),
+    }
+}
+
+/// An int, a bigint when it is too large for one, or a float.
+fn parse_number(string: &str) -> Result<Value, String> {
+    match (string.parse(), string.parse()) {
+        (Ok(number), _) => Ok(Value::Int(number)),
+        (_, Ok(number)) => Ok(Value::BigInt(Box::new(number))),
+        _ => string
+            .parse()
+            .map(Value::Float)
+            .map_err(|_| format!("Could not parse string '{}'", string)),
+    }
 }
 
-pub fn type_of(_interpreter: &mut Interpreter, arguments: &mut Vec<Value>) -> Value {
+pub fn to_bigint(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
     // arity is Some(1)
 
-    Value::String(Box::new(match &arguments[0] {
-        Value::Number(_) => "number".to_string(),
-        Value::String(_) => "string".to_string(),
-        Value::Boolean(_) => "boolean".to_string(),
-        Value::BuiltIn(_) => "builtin function".to_string(),
-        Value::Function(_) => "function".to_string(),
-        Value::List(_) => "list".to_string(),
-        Value::Iter(_) => "iter".to_string(),
-        Value::Record(_) => "record".to_string(),
-        Value::Nil => "nil".to_string(),
-    }))
+    let number = match &arguments[0] {
+        Value::String(string) => string
+            .parse()
+            .map_err(|_| format!("Could not parse string '{}'", string))?,
+        value => value
+            .as_bigint()
+            .ok_or("Must pass a string or an integer to to_bigint function".to_string())?,
+    };
+    Ok(Value::BigInt(Box::new(number)))
+}
+
+pub fn to_decimal(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+
+    let number = match &arguments[0] {
+        Value::String(string) => string.parse()?,
+        // Goes through the shortest text that reads back as the same float,
+        // so `to_decimal(0.1)` is `0.1` rather than its binary expansion.
+        Value::Float(number) => number
+            .to_string()
+            .parse()
+            .map_err(|_| format!("Cannot convert {} to a decimal", number))?,
+        value => value
+            .as_decimal()
+            .ok_or("Must pass a string or a number to to_decimal function".to_string())?,
+    };
+    Ok(Value::Decimal(Box::new(number)))
+}
+
+/// Reads the `places` and `mode` arguments of `round` and `divide`.
+fn rounding(places: &Value, mode: &Value) -> Result<(u32, Rounding), String> {
+    let places = match places {
+        Value::Int(places) => u32::try_from(*places)
+            .map_err(|_| "Must pass a non-negative number of places".to_string())?,
+        _ => return Err("Must pass an integer number of places".to_string()),
+    };
+    let rounding = match mode {
+        Value::String(mode) => Rounding::parse(mode).ok_or_else(|| {
+            let names = Rounding::NAMES.map(|(name, _)| name).join(", ");
+            format!(
+                "Unknown rounding mode '{}', expected one of {}",
+                mode, names
+            )
+        })?,
+        _ => return Err("Must pass the rounding mode as a string".to_string()),
+    };
+    Ok((places, rounding))
+}
+
+fn decimal_argument(value: &Value) -> Result<Decimal, String> {
+    value
+        .as_decimal()
+        .ok_or(format!("Expected a decimal, got {}", value.type_name()))
+}
+
+pub fn round(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(3)
+    let number = decimal_argument(&arguments[0])?;
+    let (places, rounding) = rounding(&arguments[1], &arguments[2])?;
+
+    Ok(Value::Decimal(Box::new(number.round(places, rounding))))
+}
+
+pub fn divide(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(4)
+    let left = decimal_argument(&arguments[0])?;
+    let right = decimal_argument(&arguments[1])?;
+    let (places, rounding) = rounding(&arguments[2], &arguments[3])?;
+
+    left.divide(&right, places, rounding)
+        .map(|quotient| Value::Decimal(Box::new(quotient)))
+        .ok_or("Division by zero".to_string())
+}
+
+pub fn type_of(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+
+    Ok(Value::String(Box::new(
+        arguments[0].type_name().to_string(),
+    )))
+}
+
+pub fn error(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+
+    match (&arguments[0], &arguments[1]) {
+        (Value::String(kind), Value::String(message)) => Ok(Value::Error(Box::new(
+            ErrorValue::new(kind.as_str(), message.as_str()),
+        ))),
+        _ => Err("Must pass a kind and a message as strings to error function".to_string()),
+    }
+}
+
+pub fn ok(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(Value::Result(Box::new(Ok(arguments[0].clone()))))
+}
+
+pub fn err(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(Value::Result(Box::new(Err(arguments[0].clone()))))
+}
+
+pub fn some(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(Value::Option(Some(Box::new(arguments[0].clone()))))
+}
+
+/// What a safe builtin returns: its value in an `ok`, or its failure as an
+/// `err` holding the message.
+fn result(result: Result<Value, String>) -> Value {
+    Value::Result(Box::new(
+        result.map_err(|message| Value::String(Box::new(message))),
+    ))
+}
+
+fn not_wrapped(function: &str, value: &Value) -> String {
+    format!(
+        "Must pass a result or an option to {} function, not {}",
+        function,
+        value.type_name()
+    )
+}
+
+pub fn unwrap(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    match arguments[0].unwrapped() {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(failed)) => Err(format!("Called unwrap on {}", failed)),
+        None => Err(not_wrapped("unwrap", &arguments[0])),
+    }
+}
+
+pub fn unwrap_or(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    match arguments[0].unwrapped() {
+        Some(Ok(value)) => Ok(value),
+        Some(Err(_)) => Ok(arguments[1].clone()),
+        None => Err(not_wrapped("unwrap_or", &arguments[0])),
+    }
+}
+
+pub fn is_ok(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    match &arguments[0] {
+        Value::Result(result) => Ok(Value::Boolean(result.is_ok())),
+        _ => Err("Must pass a result to is_ok function".to_string()),
+    }
+}
+
+pub fn is_some(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    match &arguments[0] {
+        Value::Option(option) => Ok(Value::Boolean(option.is_some())),
+        _ => Err("Must pass an option to is_some function".to_string()),
+    }
+}
+
+/// `to_number`, with a string that isn't a number giving an `err`.
+pub fn try_to_number(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    match &arguments[0] {
+        Value::String(string) => Ok(result(parse_number(string))),
+        _ => Err("Must pass a string to try_to_number function".to_string()),
+    }
+}
+
+/// An item of a list or a field of a record, or `none` where indexing
+/// would fail.
+pub fn get(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    let value = match (&arguments[0], &arguments[1]) {
+        (Value::List(list), Value::Int(index)) => usize::try_from(*index)
+            .ok()
+            .and_then(|index| list.borrow().get(index).cloned()),
+        (Value::Record(record), Value::String(key)) => record.borrow().get(key.as_str()).cloned(),
+        _ => {
+            return Err(
+                "Must pass a list and an int, or a record and a string, to get function"
+                    .to_string(),
+            )
+        }
+    };
+    Ok(Value::Option(value.map(Box::new)))
+}
+
+fn string_argument<'a>(value: &'a Value, name: &str) -> Result<&'a str, String> {
+    match value {
+        Value::String(string) => Ok(string),
+        _ => Err(format!(
+            "Expected the {} as a string, got {}",
+            name,
+            value.type_name()
+        )),
+    }
+}
+
+fn read(path: &str) -> Result<Value, String> {
+    std::fs::read_to_string(path)
+        .map(|contents| Value::String(Box::new(contents)))
+        .map_err(|error| format!("Could not read '{}': {}", path, error))
+}
+
+fn write(path: &str, contents: &str) -> Result<Value, String> {
+    std::fs::write(path, contents)
+        .map(|_| Value::Nil)
+        .map_err(|error| format!("Could not write '{}': {}", path, error))
+}
+
+pub fn read_file(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    read(string_argument(&arguments[0], "path")?)
+}
+
+pub fn write_file(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    let path = string_argument(&arguments[0], "path")?;
+    write(path, string_argument(&arguments[1], "contents")?)
+}
+
+pub fn try_read_file(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(1)
+    Ok(result(read(string_argument(&arguments[0], "path")?)))
+}
+
+pub fn try_write_file(arguments: &mut [Value], _output: &mut dyn Write) -> Result<Value, String> {
+    // arity is Some(2)
+    let path = string_argument(&arguments[0], "path")?;
+    Ok(result(write(
+        path,
+        string_argument(&arguments[1], "contents")?,
+    )))
 }
@@ -0,0 +1,340 @@
+//! Conversions between jive values and Rust types, so host functions can be
+//! written as ordinary closures:
+//!
+//! ```
+//! let mut engine = jive::Engine::new();
+//! engine.register("add", |a: f64, b: f64| a + b);
+//!
+//! assert_eq!(engine.eval("add(1, 2)").unwrap(), jive::Value::Float(3.0));
+//! ```
+
+use std::{cell::RefCell, collections::HashMap, fmt, io::Write, rc::Rc};
+
+use super::{callable::BuiltIn, value::Value};
+use crate::visitors::type_checker::types::{Signature, Type};
+
+/// A Rust type that can be read out of a jive value.
+pub trait FromValue: Sized {
+    /// Fails with a description of what was expected and what was found.
+    fn from_value(value: &Value) -> Result<Self, String>;
+
+    /// The type the type checker expects for arguments of this type.
+    fn static_type() -> Type;
+}
+
+/// A Rust type that can be turned into a jive value.
+pub trait IntoValue {
+    fn into_value(self) -> Value;
+
+    /// The type the type checker gives values of this type.
+    fn static_type() -> Type;
+}
+
+/// What a host function may return: a value, or a `Result` whose error
+/// becomes a runtime error in the calling program.
+pub trait IntoReturn {
+    fn into_return(self) -> Result<Value, String>;
+
+    fn static_type() -> Type;
+}
+
+/// A closure that can be registered as a native function. Implemented for
+/// closures of up to six `FromValue` arguments returning an `IntoReturn`.
+pub trait HostFunction<Arguments> {
+    fn into_builtin(self, name: &str) -> BuiltIn;
+}
+
+fn expected(expected: &str, value: &Value) -> String {
+    format!("expected {}, got {}", expected, value.type_name())
+}
+
+impl FromValue for Value {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        Ok(value.clone())
+    }
+
+    fn static_type() -> Type {
+        Type::Unknown
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Int(number) => Ok(*number),
+            _ => Err(expected("int", value)),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::Int
+    }
+}
+
+/// Accepts ints too, converting them.
+impl FromValue for f64 {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        value.as_float().ok_or_else(|| expected("number", value))
+    }
+
+    fn static_type() -> Type {
+        Type::Float
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Boolean(boolean) => Ok(*boolean),
+            _ => Err(expected("boolean", value)),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::Boolean
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::String(string) => Ok(string.to_string()),
+            _ => Err(expected("string", value)),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::String
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::List(list) => list
+                .borrow()
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    T::from_value(item).map_err(|error| format!("item {}: {}", index, error))
+                })
+                .collect(),
+            _ => Err(expected("list", value)),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::List
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Record(record) => record
+                .borrow()
+                .iter()
+                .map(|(key, item)| {
+                    T::from_value(item)
+                        .map(|item| (key.clone(), item))
+                        .map_err(|error| format!("field {}: {}", key, error))
+                })
+                .collect(),
+            _ => Err(expected("record", value)),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::Record
+    }
+}
+
+/// `nil` is `None`; anything else must convert to `T`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: &Value) -> Result<Self, String> {
+        match value {
+            Value::Nil => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::Unknown
+    }
+}
+
+impl IntoValue for Value {
+    fn into_value(self) -> Value {
+        self
+    }
+
+    fn static_type() -> Type {
+        Type::Unknown
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Value {
+        Value::Nil
+    }
+
+    fn static_type() -> Type {
+        Type::Nil
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Value {
+        Value::Int(self)
+    }
+
+    fn static_type() -> Type {
+        Type::Int
+    }
+}
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+
+    fn static_type() -> Type {
+        Type::Float
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Value {
+        Value::Boolean(self)
+    }
+
+    fn static_type() -> Type {
+        Type::Boolean
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Value {
+        Value::String(Box::new(self))
+    }
+
+    fn static_type() -> Type {
+        Type::String
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Value {
+        Value::String(Box::new(self.to_string()))
+    }
+
+    fn static_type() -> Type {
+        Type::String
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Value {
+        Value::List(Rc::new(RefCell::new(
+            self.into_iter().map(IntoValue::into_value).collect(),
+        )))
+    }
+
+    fn static_type() -> Type {
+        Type::List
+    }
+}
+
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Value {
+        Value::Record(Rc::new(RefCell::new(
+            self.into_iter()
+                .map(|(key, value)| (key, value.into_value()))
+                .collect(),
+        )))
+    }
+
+    fn static_type() -> Type {
+        Type::Record
+    }
+}
+
+/// `None` is `nil`.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Value {
+        match self {
+            Some(value) => value.into_value(),
+            None => Value::Nil,
+        }
+    }
+
+    fn static_type() -> Type {
+        Type::Unknown
+    }
+}
+
+impl<T: IntoValue> IntoReturn for T {
+    fn into_return(self) -> Result<Value, String> {
+        Ok(self.into_value())
+    }
+
+    fn static_type() -> Type {
+        <T as IntoValue>::static_type()
+    }
+}
+
+impl<T: IntoValue, E: fmt::Display> IntoReturn for Result<T, E> {
+    fn into_return(self) -> Result<Value, String> {
+        self.map(IntoValue::into_value)
+            .map_err(|error| error.to_string())
+    }
+
+    fn static_type() -> Type {
+        <T as IntoValue>::static_type()
+    }
+}
+
+macro_rules! host_function {
+    ($($parameter:ident $argument:ident),*) => {
+        impl<F, R, $($parameter),*> HostFunction<($($parameter,)*)> for F
+        where
+            F: Fn($($parameter),*) -> R + 'static,
+            R: IntoReturn,
+            $($parameter: FromValue,)*
+        {
+            // The zero argument version uses neither `name` nor `arguments`.
+            #[allow(unused_variables, unused_mut)]
+            fn into_builtin(self, name: &str) -> BuiltIn {
+                let signature = Signature {
+                    parameters: vec![$(<$parameter as FromValue>::static_type()),*],
+                    returns: R::static_type(),
+                };
+                let arity = signature.parameters.len();
+                let name = name.to_string();
+
+                let function = move |arguments: &mut [Value], _output: &mut dyn Write| {
+                    // The caller has already checked the arity.
+                    let mut arguments = arguments.iter().enumerate();
+                    $(
+                        let (index, value) = arguments.next().expect("Arity was checked");
+                        let $argument = <$parameter as FromValue>::from_value(value).map_err(
+                            |error| format!("Argument {} of {}: {}", index + 1, name, error),
+                        )?;
+                    )*
+
+                    (self)($($argument),*).into_return()
+                };
+
+                BuiltIn::new(Some(arity), function).with_signature(signature)
+            }
+        }
+    };
+}
+
+host_function!();
+host_function!(A a);
+host_function!(A a, B b);
+host_function!(A a, B b, C c);
+host_function!(A a, B b, C c, D d);
+host_function!(A a, B b, C c, D d, E e);
+host_function!(A a, B b, C c, D d, E e, G g);
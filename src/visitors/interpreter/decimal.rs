@@ -0,0 +1,319 @@
+//! Exact decimal numbers for the `12.50d` literals: an arbitrary-precision
+//! integer mantissa with a count of digits after the point.
+
+use std::{cmp::Ordering, fmt, str::FromStr};
+
+use num_bigint::{BigInt, Sign};
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
+/// How many digits after the point `/` keeps before rounding.
+pub const DIVISION_PLACES: u32 = 28;
+
+#[derive(Debug, Clone)]
+pub struct Decimal {
+    mantissa: BigInt,
+    scale: u32,
+}
+
+/// How `round` and `divide` settle the digits they drop.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Rounding {
+    /// To the nearest, ties to the even neighbour. What `/` uses.
+    HalfEven,
+    /// To the nearest, ties away from zero.
+    HalfUp,
+    /// To the nearest, ties towards zero.
+    HalfDown,
+    /// Away from zero.
+    Up,
+    /// Towards zero.
+    Down,
+    Ceiling,
+    Floor,
+}
+
+impl Rounding {
+    pub const NAMES: [(&'static str, Rounding); 7] = [
+        ("half_even", Rounding::HalfEven),
+        ("half_up", Rounding::HalfUp),
+        ("half_down", Rounding::HalfDown),
+        ("up", Rounding::Up),
+        ("down", Rounding::Down),
+        ("ceiling", Rounding::Ceiling),
+        ("floor", Rounding::Floor),
+    ];
+
+    pub fn parse(name: &str) -> Option<Rounding> {
+        Self::NAMES
+            .iter()
+            .find(|(candidate, _)| *candidate == name)
+            .map(|(_, rounding)| *rounding)
+    }
+}
+
+fn power_of_ten(exponent: u32) -> BigInt {
+    BigInt::from(10).pow(exponent)
+}
+
+/// `numerator / denominator` as an integer, with the remainder settled by
+/// `rounding`. The denominator must not be zero.
+fn divide_rounded(numerator: &BigInt, denominator: &BigInt, rounding: Rounding) -> BigInt {
+    let (quotient, remainder) = numerator.div_rem(denominator);
+    if remainder.is_zero() {
+        return quotient;
+    }
+
+    let negative = numerator.is_negative() != denominator.is_negative();
+    let half = (remainder.abs() * BigInt::from(2)).cmp(&denominator.abs());
+    let away = match rounding {
+        Rounding::HalfEven => {
+            half == Ordering::Greater || (half == Ordering::Equal && quotient.is_odd())
+        }
+        Rounding::HalfUp => half != Ordering::Less,
+        Rounding::HalfDown => half == Ordering::Greater,
+        Rounding::Up => true,
+        Rounding::Down => false,
+        Rounding::Ceiling => !negative,
+        Rounding::Floor => negative,
+    };
+
+    match (away, negative) {
+        (false, _) => quotient,
+        (true, false) => quotient + 1,
+        (true, true) => quotient - 1,
+    }
+}
+
+impl Decimal {
+    pub fn new(mantissa: BigInt, scale: u32) -> Self {
+        Decimal { mantissa, scale }
+    }
+
+    /// How many digits there are after the point.
+    pub fn scale(&self) -> u32 {
+        self.scale
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.mantissa.is_zero()
+    }
+
+    /// The mantissa as if the number had `scale` digits after the point,
+    /// which must be at least as many as it has.
+    fn rescaled(&self, scale: u32) -> BigInt {
+        &self.mantissa * power_of_ten(scale - self.scale)
+    }
+
+    /// Both mantissas at the larger of the two scales.
+    fn aligned(&self, other: &Decimal) -> (BigInt, BigInt, u32) {
+        let scale = self.scale.max(other.scale);
+        (self.rescaled(scale), other.rescaled(scale), scale)
+    }
+
+    /// Drops zeros at the end of the fraction, keeping at least `scale`
+    /// digits after the point.
+    fn trimmed(mut self, scale: u32) -> Decimal {
+        let ten = BigInt::from(10);
+        while self.scale > scale && (&self.mantissa % &ten).is_zero() {
+            self.mantissa /= &ten;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    /// `self / other` to `places` digits after the point, or `None` when
+    /// dividing by zero.
+    pub fn divide(&self, other: &Decimal, places: u32, rounding: Rounding) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let numerator = &self.mantissa * power_of_ten(places + other.scale);
+        let denominator = &other.mantissa * power_of_ten(self.scale);
+        Some(Decimal::new(
+            divide_rounded(&numerator, &denominator, rounding),
+            places,
+        ))
+    }
+
+    /// `/`: exact where the quotient ends within `DIVISION_PLACES` digits,
+    /// otherwise rounded half to even.
+    pub fn quotient(&self, other: &Decimal) -> Option<Decimal> {
+        let scale = self.scale.max(other.scale);
+        self.divide(other, DIVISION_PLACES.max(scale), Rounding::HalfEven)
+            .map(|quotient| quotient.trimmed(scale))
+    }
+
+    /// `self` with `places` digits after the point.
+    pub fn round(&self, places: u32, rounding: Rounding) -> Decimal {
+        if places >= self.scale {
+            return Decimal::new(self.rescaled(places), places);
+        }
+
+        let divisor = power_of_ten(self.scale - places);
+        Decimal::new(divide_rounded(&self.mantissa, &divisor, rounding), places)
+    }
+
+    /// `//`, or `None` when dividing by zero.
+    pub fn floor_div(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (left, right, _) = self.aligned(other);
+        Some(Decimal::new(left.div_floor(&right), 0))
+    }
+
+    /// `%`, floored like it is for ints, or `None` when dividing by zero.
+    pub fn rem(&self, other: &Decimal) -> Option<Decimal> {
+        if other.is_zero() {
+            return None;
+        }
+
+        let (left, right, scale) = self.aligned(other);
+        Some(Decimal::new(left.mod_floor(&right), scale))
+    }
+
+    /// `**` with an int exponent, or `None` when raising zero to a
+    /// negative power.
+    pub fn pow(&self, exponent: i64) -> Option<Decimal> {
+        let magnitude = u32::try_from(exponent.unsigned_abs()).ok()?;
+        let power = Decimal::new(self.mantissa.pow(magnitude), self.scale * magnitude);
+        if exponent >= 0 {
+            return Some(power);
+        }
+
+        Decimal::from(BigInt::one()).quotient(&power)
+    }
+
+    pub fn to_f64(&self) -> Option<f64> {
+        self.to_string().parse().ok()
+    }
+}
+
+impl From<BigInt> for Decimal {
+    fn from(integer: BigInt) -> Self {
+        Decimal::new(integer, 0)
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(integer: i64) -> Self {
+        Decimal::new(BigInt::from(integer), 0)
+    }
+}
+
+/// Parses digits with an optional sign, fraction and exponent, such as
+/// `-12.50` or `1.5e3`.
+impl FromStr for Decimal {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Could not parse decimal '{}'", source);
+
+        let (number, exponent) = match source.split_once(['e', 'E']) {
+            Some((number, exponent)) => (number, exponent.parse::<i64>().map_err(|_| invalid())?),
+            None => (source, 0),
+        };
+        let (sign, number) = match number.strip_prefix('-') {
+            Some(number) => ("-", number),
+            None => ("", number.strip_prefix('+').unwrap_or(number)),
+        };
+        let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+
+        let digits = format!("{}{}", whole, fraction);
+        if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+            return Err(invalid());
+        }
+
+        let mantissa: BigInt = format!("{}{}", sign, digits)
+            .parse()
+            .map_err(|_| invalid())?;
+        let scale = fraction.len() as i64 - exponent;
+        match u32::try_from(scale) {
+            Ok(scale) => Ok(Decimal::new(mantissa, scale)),
+            Err(_) if scale < 0 => {
+                let shift = u32::try_from(-scale).map_err(|_| invalid())?;
+                Ok(Decimal::new(mantissa * power_of_ten(shift), 0))
+            }
+            Err(_) => Err(invalid()),
+        }
+    }
+}
+
+impl std::ops::Add for Decimal {
+    type Output = Decimal;
+
+    fn add(self, other: Self) -> Self::Output {
+        let (left, right, scale) = self.aligned(&other);
+        Decimal::new(left + right, scale)
+    }
+}
+
+impl std::ops::Sub for Decimal {
+    type Output = Decimal;
+
+    fn sub(self, other: Self) -> Self::Output {
+        let (left, right, scale) = self.aligned(&other);
+        Decimal::new(left - right, scale)
+    }
+}
+
+impl std::ops::Mul for Decimal {
+    type Output = Decimal;
+
+    fn mul(self, other: Self) -> Self::Output {
+        Decimal::new(self.mantissa * other.mantissa, self.scale + other.scale)
+    }
+}
+
+impl std::ops::Neg for Decimal {
+    type Output = Decimal;
+
+    fn neg(self) -> Self::Output {
+        Decimal::new(-self.mantissa, self.scale)
+    }
+}
+
+/// Compares values, so `12.5` equals `12.50`.
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (left, right, _) = self.aligned(other);
+        left.cmp(&right)
+    }
+}
+
+/// Keeps every digit after the point, so `12.50d` prints `12.50`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.mantissa.magnitude().to_string();
+        let sign = if self.mantissa.sign() == Sign::Minus {
+            "-"
+        } else {
+            ""
+        };
+        let scale = self.scale as usize;
+        if scale == 0 {
+            return write!(f, "{}{}", sign, digits);
+        }
+
+        let digits = format!("{:0>width$}", digits, width = scale + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - scale);
+        write!(f, "{}{}.{}", sign, whole, fraction)
+    }
+}
@@ -0,0 +1,78 @@
+//! Error values, which `catch` binds and `throw` raises. A failed builtin,
+//! a bad operand or a `throw` all surface as one.
+
+use std::fmt;
+
+use crate::diagnostic::{Diagnostic, Span};
+
+use super::value::Value;
+
+/// The kind given to errors raised by the runtime rather than by `throw`.
+pub const RUNTIME: &str = "runtime";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorValue {
+    pub kind: String,
+    pub message: String,
+    /// Where the error was raised. A line of 0 means it hasn't been thrown.
+    pub span: Span,
+}
+
+impl ErrorValue {
+    pub fn new(kind: impl Into<String>, message: impl Into<String>) -> Self {
+        ErrorValue {
+            kind: kind.into(),
+            message: message.into(),
+            span: Span::default(),
+        }
+    }
+
+    /// What a `catch` receives for a failed statement.
+    pub fn caught(diagnostic: &Diagnostic) -> Self {
+        ErrorValue {
+            kind: diagnostic.kind.as_deref().unwrap_or(RUNTIME).to_string(),
+            message: diagnostic.message.clone(),
+            span: diagnostic.span,
+        }
+    }
+
+    /// `error.kind`, `error.message`, `error.line` and `error.column`.
+    pub fn field(&self, name: &str) -> Option<Value> {
+        match name {
+            "kind" => Some(Value::String(Box::new(self.kind.clone()))),
+            "message" => Some(Value::String(Box::new(self.message.clone()))),
+            "line" => Some(Value::Int(self.span.line as i64)),
+            "column" => Some(Value::Int(self.span.column as i64)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ErrorValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+/// The diagnostic `throw value` fails with at `span`. Strings become errors
+/// of kind "error", and an error that was caught keeps its original location
+/// when thrown again.
+pub fn throw(value: Value, span: Span) -> Diagnostic {
+    let error = match value {
+        Value::Error(error) => *error,
+        Value::String(message) => ErrorValue::new("error", *message),
+        value => {
+            return Diagnostic::error(format!(
+                "Can only throw an error or a string, not {}",
+                value.type_name()
+            ))
+            .at(span)
+        }
+    };
+
+    let diagnostic = Diagnostic::error(error.message).with_kind(error.kind);
+    match error.span.line {
+        0 => diagnostic.at(span),
+        _ => diagnostic.at(error.span),
+    }
+}
@@ -0,0 +1,454 @@
+//! Generator functions for the tree-walking interpreter. Rust's call stack
+//! can't be paused, so a generator's body is run by a second executor that
+//! records where it stopped as a stack of cursors, one for each statement
+//! that encloses the `yield`, and walks back down them on the next resume.
+
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    diagnostic::Diagnostic,
+    parser::{
+        accept::Accept,
+        statement::{Block, For, IfStatement, Statement, Try, WhileStatement, With},
+    },
+    shape,
+    visitors::environment::Scope,
+};
+
+use super::{
+    error::{self, ErrorValue},
+    iter::Iter,
+    value::Value,
+    Flow, Interpreter,
+};
+
+/// A generator function's body, its scope holding the arguments, and where
+/// it stopped.
+pub struct TreeGenerator {
+    body: Statement,
+    scope: Rc<RefCell<Scope<Value>>>,
+    /// Innermost statement first. Empty before the first resume.
+    cursors: Vec<Cursor>,
+}
+
+impl TreeGenerator {
+    pub fn new(body: Statement, scope: Rc<RefCell<Scope<Value>>>) -> Self {
+        TreeGenerator {
+            body,
+            scope,
+            cursors: vec![],
+        }
+    }
+}
+
+/// Where a statement enclosing the `yield` had got to.
+enum Cursor {
+    /// At the `yield` itself, which carries on with the next statement.
+    Yield,
+    /// At a `yield` that returns instead, to close the generator.
+    Close,
+    /// At the statement `index` of a block, counted in the order `hoisted`
+    /// runs them.
+    Block {
+        index: usize,
+        scope: Rc<RefCell<Scope<Value>>>,
+    },
+    /// In the then branch of an `if`, or its else branch when false.
+    Branch(bool),
+    /// In a `while` body, so the condition is not checked on the way back in.
+    While,
+    /// In a `for` body, with the scope holding the loop variable.
+    For {
+        iter: Iter,
+        scope: Rc<RefCell<Scope<Value>>>,
+    },
+    With {
+        scope: Rc<RefCell<Scope<Value>>>,
+    },
+    Try(TryStage),
+}
+
+/// Which clause of a `try` the yield is in.
+enum TryStage {
+    Body,
+    /// In the catch clause, with the scope holding the error.
+    Catch(Rc<RefCell<Scope<Value>>>),
+    /// In the finally clause, holding what the body or catch clause did so
+    /// it can carry on afterwards, and the value of a `?` returning through
+    /// the `try`.
+    Finally(Result<Step, Diagnostic>, Option<Value>),
+}
+
+/// How far a statement got before handing control back.
+enum Step {
+    Finished(Value, Flow),
+    Yielded(Value),
+}
+
+impl Step {
+    /// `Finished` with a normal flow, which is what `while` and `if` leave
+    /// behind.
+    fn done() -> Self {
+        Step::Finished(Value::Nil, Flow::Normal)
+    }
+}
+
+impl Interpreter {
+    /// Runs `generator` until its next `yield`, or `None` when it finishes.
+    pub(crate) fn resume_generator(
+        &mut self,
+        generator: &mut TreeGenerator,
+    ) -> Result<Option<Value>, Diagnostic> {
+        let mut cursors = std::mem::take(&mut generator.cursors);
+
+        let previous = self.scope.replace(generator.scope.clone());
+        let result =
+            self.nested(|interpreter| interpreter.resume_statement(&generator.body, &mut cursors));
+        self.scope = previous;
+
+        match result {
+            Ok(Step::Yielded(value)) => {
+                generator.cursors = cursors;
+                Ok(Some(value))
+            }
+            Ok(Step::Finished(..)) => Ok(None),
+            // Nothing receives a generator's return value, so a failed `?`
+            // is thrown to whoever is iterating instead.
+            Err(diagnostic) => match self.propagating.take() {
+                Some(failed) => Err(error::throw(
+                    Value::String(Box::new(format!(
+                        "{} propagated out of a generator",
+                        failed
+                    ))),
+                    diagnostic.span,
+                )),
+                None => Err(diagnostic),
+            },
+        }
+    }
+
+    /// Finishes `generator` from its `yield` as a `return` would, running
+    /// the finally clauses around it. One that hasn't started has none.
+    pub(crate) fn close_generator(
+        &mut self,
+        generator: &mut TreeGenerator,
+    ) -> Result<(), Diagnostic> {
+        match generator.cursors.first_mut() {
+            Some(cursor) => *cursor = Cursor::Close,
+            None => return Ok(()),
+        }
+
+        match self.resume_generator(generator)? {
+            Some(_) => Err(Diagnostic::error("Generator yielded while being closed")),
+            None => Ok(()),
+        }
+    }
+
+    /// Runs `statement`, starting where `cursors` say it stopped if there
+    /// are any left. On a yield, each enclosing statement pushes its cursor
+    /// after the inner ones.
+    fn resume_statement(
+        &mut self,
+        statement: &Statement,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        match statement {
+            Statement::Yield(yield_statement) => {
+                match cursors.pop() {
+                    Some(Cursor::Close) => return Ok(Step::Finished(Value::Nil, Flow::Return)),
+                    Some(_) => return Ok(Step::done()),
+                    None => {}
+                }
+
+                let (value, _) = yield_statement.value.accept(self)?;
+                cursors.push(Cursor::Yield);
+                Ok(Step::Yielded(value))
+            }
+            Statement::Block(block) => self.resume_block(block, cursors),
+            Statement::IfStatement(if_statement) => self.resume_if(if_statement, cursors),
+            Statement::WhileStatement(while_statement) => {
+                self.resume_while(while_statement, cursors)
+            }
+            Statement::For(for_statement) => self.resume_for(for_statement, cursors),
+            Statement::With(with_statement) => self.resume_with(with_statement, cursors),
+            Statement::Try(try_statement) => self.resume_try(try_statement, cursors),
+            _ => {
+                let (value, flow) = statement.accept(self)?;
+                Ok(Step::Finished(value, flow))
+            }
+        }
+    }
+
+    /// Runs `statement` with `scope` as the current scope.
+    fn resume_in(
+        &mut self,
+        scope: Rc<RefCell<Scope<Value>>>,
+        statement: &Statement,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let previous = self.scope.replace(scope);
+        let result = self.resume_statement(statement, cursors);
+        self.scope = previous;
+        result
+    }
+
+    fn resume_block(
+        &mut self,
+        block: &Block,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let (start, scope) = match cursors.pop() {
+            Some(Cursor::Block { index, scope }) => (index, scope),
+            _ => (0, Rc::new(RefCell::new(Scope::new(self.scope.clone())))),
+        };
+
+        let previous = self.scope.replace(scope.clone());
+        let mut result = Ok(Step::done());
+        for (index, statement) in Self::hoisted(block).enumerate().skip(start) {
+            result = self.resume_statement(statement, cursors);
+            match result {
+                Ok(Step::Finished(_, Flow::Normal)) => {}
+                Ok(Step::Yielded(_)) => {
+                    cursors.push(Cursor::Block { index, scope });
+                    break;
+                }
+                _ => break,
+            }
+        }
+        self.scope = previous;
+
+        match result? {
+            Step::Finished(_, Flow::Normal) => Ok(Step::done()),
+            step => Ok(step),
+        }
+    }
+
+    fn resume_if(
+        &mut self,
+        if_statement: &IfStatement,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let then = match cursors.pop() {
+            Some(Cursor::Branch(then)) => then,
+            _ => {
+                let (condition, _) = if_statement.condition.accept(self)?;
+                condition == Value::Boolean(true)
+            }
+        };
+
+        let branch = match then {
+            true => &if_statement.then_branch,
+            false => match &if_statement.else_branch {
+                Some(else_branch) => else_branch,
+                None => return Ok(Step::done()),
+            },
+        };
+
+        match self.resume_statement(branch, cursors)? {
+            Step::Yielded(value) => {
+                cursors.push(Cursor::Branch(then));
+                Ok(Step::Yielded(value))
+            }
+            Step::Finished(value, flow) if flow != Flow::Normal => Ok(Step::Finished(value, flow)),
+            Step::Finished(..) => Ok(Step::done()),
+        }
+    }
+
+    fn resume_while(
+        &mut self,
+        while_statement: &WhileStatement,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let mut resuming = matches!(cursors.last(), Some(Cursor::While));
+        if resuming {
+            cursors.pop();
+        }
+
+        let label = &while_statement.label;
+        loop {
+            if !resuming {
+                let (condition, _) = while_statement.condition.accept(self)?;
+                if condition == Value::Boolean(false) {
+                    break;
+                }
+            }
+            resuming = false;
+
+            match self.resume_statement(&while_statement.body, cursors)? {
+                Step::Yielded(value) => {
+                    cursors.push(Cursor::While);
+                    return Ok(Step::Yielded(value));
+                }
+                Step::Finished(_, Flow::Break(target)) if Flow::targets(&target, label) => break,
+                Step::Finished(_, Flow::Continue(target)) if Flow::targets(&target, label) => {}
+                Step::Finished(_, Flow::Normal) => {}
+                step => return Ok(step),
+            }
+        }
+
+        Ok(Step::done())
+    }
+
+    fn resume_for(
+        &mut self,
+        for_statement: &For,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let (iter, resumed) = match cursors.pop() {
+            Some(Cursor::For { iter, scope }) => (iter, Some(scope)),
+            _ => match for_statement.iter.accept(self)? {
+                (Value::Iter(iter), _) => (iter, None),
+                _ => {
+                    return Err(Diagnostic::error("Must use an Iter in the 'for' statement")
+                        .at(for_statement.iter.span()))
+                }
+            },
+        };
+
+        let result = self.resume_iterate(for_statement, &iter, resumed, cursors);
+        if let Ok(Step::Yielded(_)) = result {
+            return result;
+        }
+
+        self.close_loop(&iter, for_statement, result)
+    }
+
+    /// Runs a `for` loop's body over what is left of `iter`, starting in
+    /// the `resumed` iteration's scope if it had yielded.
+    fn resume_iterate(
+        &mut self,
+        for_statement: &For,
+        iter: &Iter,
+        mut resumed: Option<Rc<RefCell<Scope<Value>>>>,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let label = &for_statement.label;
+        loop {
+            let scope = match resumed.take() {
+                Some(scope) => scope,
+                None => {
+                    let value = iter
+                        .next(self)
+                        .map_err(|diagnostic| diagnostic.or_at(for_statement.iter.span()))?;
+                    let value = match value {
+                        Some(value) => value,
+                        None => break,
+                    };
+                    let mut scope = Scope::new(self.scope.clone());
+                    let values = shape::destructure(&for_statement.pattern, value)?;
+                    for (slot, value) in values.into_iter().enumerate() {
+                        scope.declare(slot, value);
+                    }
+                    Rc::new(RefCell::new(scope))
+                }
+            };
+
+            match self.resume_in(scope.clone(), &for_statement.body, cursors)? {
+                Step::Yielded(value) => {
+                    cursors.push(Cursor::For {
+                        iter: iter.clone(),
+                        scope,
+                    });
+                    return Ok(Step::Yielded(value));
+                }
+                Step::Finished(_, Flow::Break(target)) if Flow::targets(&target, label) => break,
+                Step::Finished(_, Flow::Continue(target)) if Flow::targets(&target, label) => {}
+                Step::Finished(_, Flow::Normal) => {}
+                step => return Ok(step),
+            }
+        }
+
+        Ok(Step::done())
+    }
+
+    fn resume_with(
+        &mut self,
+        with_statement: &With,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let scope = match cursors.pop() {
+            Some(Cursor::With { scope }) => scope,
+            _ => {
+                let (value, _) = with_statement.value.accept(self)?;
+                let mut scope = Scope::new(self.scope.clone());
+                scope.declare(0, value);
+                Rc::new(RefCell::new(scope))
+            }
+        };
+
+        match self.resume_in(scope.clone(), &with_statement.body, cursors)? {
+            Step::Yielded(value) => {
+                cursors.push(Cursor::With { scope });
+                Ok(Step::Yielded(value))
+            }
+            Step::Finished(value, flow) if flow != Flow::Normal => Ok(Step::Finished(value, flow)),
+            Step::Finished(..) => Ok(Step::done()),
+        }
+    }
+
+    fn resume_try(
+        &mut self,
+        try_statement: &Try,
+        cursors: &mut Vec<Cursor>,
+    ) -> Result<Step, Diagnostic> {
+        let mut stage = match cursors.pop() {
+            Some(Cursor::Try(stage)) => stage,
+            _ => TryStage::Body,
+        };
+
+        loop {
+            stage = match stage {
+                TryStage::Body => {
+                    let result = self.resume_statement(&try_statement.body, cursors);
+                    let unwinding = self.propagating.take();
+                    match result {
+                        Ok(Step::Yielded(value)) => {
+                            cursors.push(Cursor::Try(TryStage::Body));
+                            return Ok(Step::Yielded(value));
+                        }
+                        Err(diagnostic) if unwinding.is_none() && try_statement.catch.is_some() => {
+                            let error = Value::Error(Box::new(ErrorValue::caught(&diagnostic)));
+                            let mut scope = Scope::new(self.scope.clone());
+                            scope.declare(0, error);
+                            TryStage::Catch(Rc::new(RefCell::new(scope)))
+                        }
+                        result => TryStage::Finally(result, unwinding),
+                    }
+                }
+                TryStage::Catch(scope) => {
+                    let catch = try_statement
+                        .catch
+                        .as_ref()
+                        .expect("Caught without a catch");
+                    let result = self.resume_in(scope.clone(), &catch.body, cursors);
+                    let unwinding = self.propagating.take();
+                    match result {
+                        Ok(Step::Yielded(value)) => {
+                            cursors.push(Cursor::Try(TryStage::Catch(scope)));
+                            return Ok(Step::Yielded(value));
+                        }
+                        result => TryStage::Finally(result, unwinding),
+                    }
+                }
+                TryStage::Finally(result, unwinding) => {
+                    if let Some(finally) = &try_statement.finally {
+                        match self.resume_statement(finally, cursors)? {
+                            Step::Yielded(value) => {
+                                cursors.push(Cursor::Try(TryStage::Finally(result, unwinding)));
+                                return Ok(Step::Yielded(value));
+                            }
+                            Step::Finished(_, Flow::Normal) => {}
+                            step => return Ok(step),
+                        }
+                    }
+
+                    self.propagating = unwinding;
+                    return match result? {
+                        Step::Finished(_, Flow::Normal) => Ok(Step::done()),
+                        step => Ok(step),
+                    };
+                }
+            }
+        }
+    }
+}
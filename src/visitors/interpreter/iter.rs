@@ -0,0 +1,337 @@
+//! Lazy iterators. A `for` loop pulls one item at a time, so ranges never
+//! build a list and can go on forever.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use crate::{compiler::vm::VmGenerator, diagnostic::Diagnostic};
+
+use super::{generator::TreeGenerator, value::Value};
+
+/// Calls a jive function on behalf of an iterator, for adaptors such as
+/// `map`. Implemented by both the `Interpreter` and the bytecode `Vm`.
+pub trait Caller {
+    fn call(&mut self, callee: &Value, arguments: Vec<Value>) -> Result<Value, Diagnostic>;
+
+    /// Runs a generator's body until its next `yield`, returning `None` once
+    /// the body finishes.
+    fn resume(&mut self, generator: &mut Suspended) -> Result<Option<Value>, Diagnostic>;
+
+    /// Finishes a paused generator as if its `yield` were a `return`, which
+    /// runs the finally clauses around it.
+    fn close(&mut self, generator: &mut Suspended) -> Result<(), Diagnostic>;
+}
+
+/// A source of values, advanced one at a time.
+pub trait Iterate {
+    /// The next value, or `None` once there are no more.
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic>;
+
+    /// Stops early, letting the generators behind the iterator clean up.
+    fn close(&mut self, _caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        Ok(())
+    }
+}
+
+/// A shared handle to an iterator. Copies advance the same iterator.
+#[derive(Clone)]
+pub struct Iter(Rc<RefCell<dyn Iterate>>);
+
+impl Iter {
+    pub fn new(iterate: impl Iterate + 'static) -> Self {
+        Iter(Rc::new(RefCell::new(iterate)))
+    }
+
+    /// Iterates over a list, the characters of a string, or an existing iter.
+    pub fn over(value: &Value) -> Option<Self> {
+        match value {
+            Value::Iter(iter) => Some(iter.clone()),
+            Value::List(list) => Some(Iter::new(ListIter {
+                list: list.clone(),
+                position: 0,
+            })),
+            Value::String(string) => Some(Iter::new(Chars {
+                string: string.to_string(),
+                position: 0,
+            })),
+            _ => None,
+        }
+    }
+
+    pub fn next(&self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let mut iterate = self
+            .0
+            .try_borrow_mut()
+            .map_err(|_| Diagnostic::error("Iterator is already being advanced"))?;
+        iterate.next(caller)
+    }
+
+    /// Closes the iterator once a `for` loop is done with it, unless another
+    /// handle still holds it and may carry on iterating. This one must be
+    /// the loop's own.
+    pub fn close(&self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        if Rc::strong_count(&self.0) > 1 {
+            return Ok(());
+        }
+
+        match self.0.try_borrow_mut() {
+            Ok(mut iterate) => iterate.close(caller),
+            Err(_) => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for Iter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<iter>")
+    }
+}
+
+/// Ints from `next` up to but not including `end`, or forever without one.
+/// `next` is `None` once stepping on from the last int overflowed.
+pub struct Range {
+    pub next: Option<i64>,
+    pub end: Option<i64>,
+    pub step: i64,
+}
+
+impl Iterate for Range {
+    fn next(&mut self, _caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let value = match (self.next, self.end) {
+            (Some(next), Some(end)) if next >= end => return Ok(None),
+            (Some(next), _) => next,
+            // Every int below `end` has been visited.
+            (None, Some(_)) => return Ok(None),
+            (None, None) => return Err(Diagnostic::error("Integer overflow")),
+        };
+
+        self.next = value.checked_add(self.step);
+        Ok(Some(Value::Int(value)))
+    }
+}
+
+/// Reads the list as it goes, so items pushed during a loop are visited.
+pub struct ListIter {
+    list: Rc<RefCell<Vec<Value>>>,
+    position: usize,
+}
+
+impl Iterate for ListIter {
+    fn next(&mut self, _caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let value = self.list.borrow().get(self.position).cloned();
+        self.position += 1;
+        Ok(value)
+    }
+}
+
+pub struct Chars {
+    string: String,
+    position: usize,
+}
+
+impl Iterate for Chars {
+    fn next(&mut self, _caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let c = match self.string[self.position..].chars().next() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+        self.position += c.len_utf8();
+        Ok(Some(Value::String(Box::new(c.to_string()))))
+    }
+}
+
+pub struct Map {
+    pub source: Iter,
+    pub function: Value,
+}
+
+impl Iterate for Map {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        match self.source.next(caller)? {
+            Some(value) => caller.call(&self.function, vec![value]).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        self.source.close(caller)
+    }
+}
+
+pub struct Filter {
+    pub source: Iter,
+    pub predicate: Value,
+}
+
+impl Iterate for Filter {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        while let Some(value) = self.source.next(caller)? {
+            match caller.call(&self.predicate, vec![value.clone()])? {
+                Value::Boolean(true) => return Ok(Some(value)),
+                Value::Boolean(false) => {}
+                _ => return Err(Diagnostic::error("filter function must return a boolean")),
+            }
+        }
+        Ok(None)
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        self.source.close(caller)
+    }
+}
+
+pub struct Take {
+    pub source: Iter,
+    pub remaining: usize,
+}
+
+impl Iterate for Take {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+
+        self.remaining -= 1;
+        self.source.next(caller)
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        self.source.close(caller)
+    }
+}
+
+pub struct Skip {
+    pub source: Iter,
+    pub remaining: usize,
+}
+
+impl Iterate for Skip {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        while self.remaining > 0 {
+            self.remaining -= 1;
+            if self.source.next(caller)?.is_none() {
+                return Ok(None);
+            }
+        }
+
+        self.source.next(caller)
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        self.source.close(caller)
+    }
+}
+
+fn pair(first: Value, second: Value) -> Value {
+    Value::List(Rc::new(RefCell::new(vec![first, second])))
+}
+
+/// Pairs up items as two-item lists, stopping with the shorter iterator.
+pub struct Zip {
+    pub left: Iter,
+    pub right: Iter,
+}
+
+impl Iterate for Zip {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let left = match self.left.next(caller)? {
+            Some(left) => left,
+            None => return Ok(None),
+        };
+        Ok(self.right.next(caller)?.map(|right| pair(left, right)))
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        let left = self.left.close(caller);
+        let right = self.right.close(caller);
+        left.and(right)
+    }
+}
+
+/// Pairs each item with its position, counting from 0.
+pub struct Enumerate {
+    pub source: Iter,
+    pub index: i64,
+}
+
+impl Iterate for Enumerate {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let value = match self.source.next(caller)? {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let index = self.index;
+        self.index += 1;
+        Ok(Some(pair(Value::Int(index), value)))
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        self.source.close(caller)
+    }
+}
+
+pub struct Chain {
+    pub first: Option<Iter>,
+    pub second: Iter,
+}
+
+impl Iterate for Chain {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        if let Some(first) = &self.first {
+            if let Some(value) = first.next(caller)? {
+                return Ok(Some(value));
+            }
+        }
+
+        self.first = None;
+        self.second.next(caller)
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        if let Some(first) = self.first.take() {
+            first.close(caller)?;
+        }
+        self.second.close(caller)
+    }
+}
+
+/// The paused body of a generator function, in the form used by whichever
+/// of the `Interpreter` or `Vm` called it.
+pub enum Suspended {
+    Tree(Box<TreeGenerator>),
+    Vm(Box<VmGenerator>),
+}
+
+/// What calling a generator function returns. Each item runs the body up
+/// to its next `yield`.
+pub struct Generator {
+    /// `None` once the body has finished or failed.
+    state: Option<Suspended>,
+}
+
+impl Generator {
+    pub fn new(state: Suspended) -> Self {
+        Generator { state: Some(state) }
+    }
+}
+
+impl Iterate for Generator {
+    fn next(&mut self, caller: &mut dyn Caller) -> Result<Option<Value>, Diagnostic> {
+        let state = match &mut self.state {
+            Some(state) => state,
+            None => return Ok(None),
+        };
+
+        let result = caller.resume(state);
+        if !matches!(result, Ok(Some(_))) {
+            self.state = None;
+        }
+        result
+    }
+
+    fn close(&mut self, caller: &mut dyn Caller) -> Result<(), Diagnostic> {
+        match self.state.take() {
+            Some(mut state) => caller.close(&mut state),
+            None => Ok(()),
+        }
+    }
+}
@@ -1,304 +1,677 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+};
 
 use crate::{
+    arity::Arity,
+    diagnostic::{Diagnostic, Span},
+    lexer::{parse_bigint, parse_decimal, parse_float, parse_int},
     parser::{
         accept::Accept,
         expression::{
-            And, Assignment, Call, Comparison, Equality, Factor, IfExpression, Index,
-            IndexAssignment, List, MapIndex, MapIndexAssignment, Or, Primary, Record, Term, Unary,
+            And, Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor, IfExpression,
+            Index, IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment,
+            Match, Or, Power, Primary, Propagate, Record, Term, Unary,
         },
         statement::{
-            Block, ExpressionStatement, For, FunctionDeclaration, IfStatement, PrintStatement,
-            Return, Statement, WhileStatement,
+            Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+            PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement,
+            With, Yield,
         },
     },
-    token::TokenType,
+    shape::{self, Shape},
+    token::{Token, TokenType},
 };
 
 pub mod callable;
+pub mod convert;
+pub mod decimal;
+pub mod error;
+pub mod generator;
+pub mod iter;
 pub mod value;
 
-use super::environment::Environment;
-use callable::{BuiltIn, Callable, Function};
+use super::{
+    environment::{Environment, Scope},
+    resolver::Resolution,
+};
+use callable::{Callable, Function, MAX_CALL_DEPTH};
+use error::ErrorValue;
+use iter::{Caller, Iter, Suspended};
 use value::Value;
 
+/// Where `print` statements and the `println` builtin write to.
+pub type Output = Rc<RefCell<dyn Write>>;
+
+/// How control leaves a statement. Anything but `Normal` skips the rest of
+/// the enclosing statements until a function call or loop handles it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Flow {
+    Normal,
+    Return,
+    /// Carries the label named by `break label;`, if any.
+    Break(Option<String>),
+    Continue(Option<String>),
+}
+
+impl Flow {
+    fn loop_control(label: &Option<Token>) -> Option<String> {
+        label.as_ref().map(|label| label.lexeme.clone())
+    }
+
+    /// Whether a `break` or `continue` is aimed at the loop labelled `label`.
+    fn targets(target: &Option<String>, label: &Option<Token>) -> bool {
+        match (target, label) {
+            (None, _) => true,
+            (Some(target), Some(label)) => *target == label.lexeme,
+            (Some(_), None) => false,
+        }
+    }
+}
+
 pub struct Interpreter {
-    environment: Rc<RefCell<Environment<Value>>>,
+    globals: Rc<RefCell<Environment<Value>>>,
+    scope: Option<Rc<RefCell<Scope<Value>>>>,
+    output: Output,
+    /// The `err` or `none` a `?` is returning from the enclosing function.
+    /// It unwinds as an error so the rest of the expression is skipped, and
+    /// is turned back into a `return` by `returned`.
+    propagating: Option<Value>,
+    /// How many function calls and generator resumes are running, for
+    /// `nested` to stop runaway recursion before it overflows the stack.
+    depth: usize,
 }
 
 impl Interpreter {
     pub fn new() -> Self {
         let environment = Rc::new(RefCell::new(Environment::<Value>::new()));
 
-        environment.borrow_mut().declare_global(
-            "clock".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(0), callable::clock))),
-        );
-        environment.borrow_mut().declare_global(
-            "println".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(None, callable::println))),
-        );
-        environment.borrow_mut().declare_global(
-            "input".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(0), callable::input))),
-        );
-        environment.borrow_mut().declare_global(
-            "iter".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(1), callable::iter))),
-        );
-        environment.borrow_mut().declare_global(
-            "range_to".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(1), callable::range_max))),
-        );
-        environment.borrow_mut().declare_global(
-            "range".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(2), callable::range_min_max))),
-        );
-        environment.borrow_mut().declare_global(
-            "range_skip".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(
-                Some(3),
-                callable::range_min_max_skip,
-            ))),
-        );
-        environment.borrow_mut().declare_global(
-            "len".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(1), callable::len))),
-        );
-        environment.borrow_mut().declare_global(
-            "push".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(2), callable::push))),
-        );
-        environment.borrow_mut().declare_global(
-            "to_number".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(1), callable::to_number))),
-        );
-        environment.borrow_mut().declare_global(
-            "type_of".to_string(),
-            Value::BuiltIn(Box::new(BuiltIn::new(Some(1), callable::type_of))),
-        );
+        for (name, builtin) in callable::builtins() {
+            environment
+                .borrow_mut()
+                .declare_global(name.to_string(), Value::BuiltIn(Box::new(builtin)));
+        }
+
+        environment
+            .borrow_mut()
+            .declare_global("args".to_string(), callable::script_args(&[]));
+        environment
+            .borrow_mut()
+            .declare_global("none".to_string(), Value::Option(None));
+
+        Interpreter {
+            globals: environment,
+            scope: None,
+            output: Rc::new(RefCell::new(io::stdout())),
+            propagating: None,
+            depth: 0,
+        }
+    }
+
+    pub fn define_global(&mut self, name: &str, value: Value) {
+        self.globals
+            .borrow_mut()
+            .declare_global(name.to_string(), value);
+    }
+
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().get(name.to_string()).ok()
+    }
 
-        Interpreter { environment }
+    pub fn set_output(&mut self, output: Output) {
+        self.output = output;
     }
 
-    pub fn evaluate(&mut self, statements: &Vec<Statement>) -> Value {
+    pub fn set_args(&mut self, args: &[String]) {
+        self.globals
+            .borrow_mut()
+            .declare_global("args".to_string(), callable::script_args(args));
+    }
+
+    pub fn evaluate(&mut self, statements: &[Statement]) -> Result<Value, Diagnostic> {
         for statement in statements {
-            let (_, ret) = statement.accept(self);
-            if ret {
-                panic!("Return keyword should not be used outside of a function body");
+            let (_, flow) = statement.accept(self)?;
+            if flow == Flow::Return {
+                return Err(Diagnostic::error(
+                    "Return keyword should not be used outside of a function body",
+                )
+                .at(statement.span()));
+            }
+        }
+
+        Ok(Value::Nil)
+    }
+
+    pub fn evaluate_expression(&mut self, expression: &Expression) -> Result<Value, Diagnostic> {
+        let (value, _) = expression.accept(self)?;
+
+        Ok(value)
+    }
+
+    /// The value being returned if `diagnostic` is a `?` unwinding out of a
+    /// function, otherwise the diagnostic itself.
+    pub(crate) fn returned(&mut self, diagnostic: Diagnostic) -> Result<Value, Diagnostic> {
+        self.propagating.take().ok_or(diagnostic)
+    }
+
+    /// Runs `statement` in a fresh scope nested inside `enclosing`, with
+    /// `values` already bound to its first slots.
+    pub(crate) fn execute_in(
+        &mut self,
+        enclosing: Option<Rc<RefCell<Scope<Value>>>>,
+        values: Vec<Value>,
+        statement: &Statement,
+    ) -> <Self as super::Visitor>::Output {
+        self.in_scope(enclosing, values, |interpreter| {
+            statement.accept(interpreter)
+        })
+    }
+
+    /// Calls `run` in a fresh scope nested inside `enclosing`, with `values`
+    /// already bound to its first slots.
+    fn in_scope<T>(
+        &mut self,
+        enclosing: Option<Rc<RefCell<Scope<Value>>>>,
+        values: Vec<Value>,
+        run: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let mut scope = Scope::new(enclosing);
+        for (slot, value) in values.into_iter().enumerate() {
+            scope.declare(slot, value);
+        }
+
+        self.with_scope(Rc::new(RefCell::new(scope)), run)
+    }
+
+    /// Calls `run` one call deeper, or fails once calls are nested
+    /// `MAX_CALL_DEPTH` deep.
+    pub(crate) fn nested<T>(
+        &mut self,
+        run: impl FnOnce(&mut Self) -> Result<T, Diagnostic>,
+    ) -> Result<T, Diagnostic> {
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(Diagnostic::error("Stack overflow"));
+        }
+
+        self.depth += 1;
+        let result = run(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Calls `run` with `scope` as the innermost scope.
+    pub(crate) fn with_scope<T>(
+        &mut self,
+        scope: Rc<RefCell<Scope<Value>>>,
+        run: impl FnOnce(&mut Self) -> T,
+    ) -> T {
+        let previous = self.scope.replace(scope);
+        let result = run(self);
+        self.scope = previous;
+        result
+    }
+
+    fn look_up(&self, identifier: &Token, resolution: Resolution) -> Result<Value, String> {
+        match (resolution, &self.scope) {
+            (Resolution::Local { depth, slot }, Some(scope)) => scope
+                .borrow()
+                .get(depth, slot)
+                .ok_or_else(|| format!("Undefined variable {}", identifier.lexeme)),
+            _ => self.globals.borrow().get(identifier.lexeme.clone()),
+        }
+    }
+
+    fn assign(
+        &mut self,
+        identifier: &Token,
+        resolution: Resolution,
+        value: Value,
+    ) -> Result<(), String> {
+        match (resolution, &self.scope) {
+            (Resolution::Local { depth, slot }, Some(scope)) => {
+                if scope.borrow_mut().assign(depth, slot, value) {
+                    return Ok(());
+                }
+                Err(format!("Undefined variable {}", identifier.lexeme))
+            }
+            _ => self
+                .globals
+                .borrow_mut()
+                .assign(identifier.lexeme.clone(), value),
+        }
+    }
+
+    fn index(
+        list: Value,
+        expression: Value,
+        list_expression: &Expression,
+        index_expression: &Expression,
+    ) -> Result<Value, Diagnostic> {
+        match list {
+            Value::List(indexable) => match expression {
+                Value::Int(number) => {
+                    let indexable = indexable.borrow();
+                    match usize::try_from(number).ok().and_then(|i| indexable.get(i)) {
+                        Some(value) => Ok(value.clone()),
+                        None => Err(Diagnostic::error(format!(
+                            "Index {} out of bounds for list of length {}",
+                            number,
+                            indexable.len()
+                        ))
+                        .at(index_expression.span())),
+                    }
+                }
+                _ => Err(
+                    Diagnostic::error("Must use integer to index into list or string")
+                        .at(index_expression.span()),
+                ),
+            },
+            Value::String(indexable) => match expression {
+                Value::Int(number) => match usize::try_from(number)
+                    .ok()
+                    .and_then(|i| indexable.as_bytes().get(i))
+                {
+                    Some(byte) => Ok(Value::String(Box::new(byte.to_string()))),
+                    None => Err(Diagnostic::error(format!(
+                        "Index {} out of bounds for string of length {}",
+                        number,
+                        indexable.len()
+                    ))
+                    .at(index_expression.span())),
+                },
+                _ => Err(
+                    Diagnostic::error("Must use integer to index into list or string")
+                        .at(index_expression.span()),
+                ),
+            },
+            _ => {
+                Err(Diagnostic::error("Can only index into list or string")
+                    .at(list_expression.span()))
+            }
+        }
+    }
+
+    fn field(map: Value, key: &Token, map_expression: &Expression) -> Result<Value, Diagnostic> {
+        match map {
+            Value::Error(error) => error.field(&key.lexeme).ok_or_else(|| {
+                Diagnostic::error(format!("Errors have no field {}", key.lexeme)).at(key.span())
+            }),
+            Value::Record(record) => match record.borrow().get(&key.lexeme) {
+                Some(value) => Ok(value.clone()),
+                None => Err(Diagnostic::error(format!(
+                    "key {} does not exist in record",
+                    key.lexeme
+                ))
+                .at(key.span())),
+            },
+            _ => Err(Diagnostic::error("Cannot dot index into non record type")
+                .at(map_expression.span())),
+        }
+    }
+
+    /// Combines the target's `current` value with the assigned `value` for a
+    /// compound assignment, or passes `value` through for a plain one.
+    fn compound(
+        operator: &Option<Token>,
+        current: Option<Value>,
+        value: Value,
+        span: Span,
+    ) -> Result<Value, Diagnostic> {
+        let (operator, current) = match (operator, current) {
+            (Some(operator), Some(current)) => (operator, current),
+            _ => return Ok(value),
+        };
+        let value = match operator.token_type {
+            TokenType::Plus => current + value,
+            TokenType::Minus => current - value,
+            TokenType::Star => current * value,
+            TokenType::Slash => current / value,
+            TokenType::Percent => current % value,
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value.map_err(|message| Diagnostic::error(message).at(span))
+    }
+
+    /// A block's statements in the order they run: functions are hoisted so
+    /// siblings can call each other in any order.
+    fn hoisted(block: &Block) -> impl Iterator<Item = &Statement> {
+        let (functions, statements): (Vec<_>, Vec<_>) = block
+            .statements
+            .iter()
+            .partition(|statement| matches!(statement, Statement::FunctionDeclaration(_)));
+        functions.into_iter().chain(statements)
+    }
+
+    fn declare(&mut self, identifier: &Token, resolution: Resolution, value: Value) {
+        match (resolution, &self.scope) {
+            (Resolution::Local { slot, .. }, Some(scope)) => {
+                scope.borrow_mut().declare(slot, value)
             }
+            _ => self
+                .globals
+                .borrow_mut()
+                .declare(identifier.lexeme.clone(), value),
         }
+    }
 
-        Value::Nil
+    /// Closes a `for` loop's iterator once `result` says how the loop ended.
+    /// An error from the loop wins over one from closing, and a `?`
+    /// returning through the loop is set aside while the generators behind
+    /// the iterator clean up.
+    pub(crate) fn close_loop<T>(
+        &mut self,
+        iter: &Iter,
+        for_statement: &For,
+        result: Result<T, Diagnostic>,
+    ) -> Result<T, Diagnostic> {
+        let unwinding = self.propagating.take();
+        let closed = iter
+            .close(self)
+            .map_err(|diagnostic| diagnostic.or_at(for_statement.iter.span()));
+        self.propagating = unwinding;
+
+        let result = result?;
+        closed?;
+
+        Ok(result)
+    }
+
+    /// Runs a `for` loop's body over what is left of `iter`.
+    fn iterate(&mut self, iter: &Iter, for_statement: &For) -> Result<(Value, Flow), Diagnostic> {
+        let label = &for_statement.label;
+        while let Some(value) = iter
+            .next(self)
+            .map_err(|diagnostic| diagnostic.or_at(for_statement.iter.span()))?
+        {
+            let values = shape::destructure(&for_statement.pattern, value)?;
+            match self.execute_in(self.scope.clone(), values, &for_statement.body)? {
+                (_, Flow::Break(target)) if Flow::targets(&target, label) => break,
+                (_, Flow::Continue(target)) if Flow::targets(&target, label) => {}
+                (_, Flow::Normal) => {}
+                result => return Ok(result),
+            }
+        }
+        Ok((Value::Nil, Flow::Normal))
+    }
+}
+
+impl Caller for Interpreter {
+    fn call(&mut self, callee: &Value, mut arguments: Vec<Value>) -> Result<Value, Diagnostic> {
+        match callee {
+            Value::BuiltIn(builtin) => {
+                if let Some(arity) = builtin.arity {
+                    if arguments.len() != arity {
+                        return Err(Diagnostic::error(format!(
+                            "Expected {} arguments but got {}",
+                            arity,
+                            arguments.len()
+                        )));
+                    }
+                }
+                builtin.call(self, &mut arguments)
+            }
+            Value::Function(function) => function.call_with(self, arguments, &[]),
+            _ => Err(Diagnostic::error("Can only call functions")),
+        }
+    }
+
+    fn resume(&mut self, generator: &mut Suspended) -> Result<Option<Value>, Diagnostic> {
+        match generator {
+            Suspended::Tree(generator) => self.resume_generator(generator),
+            Suspended::Vm(_) => Err(Diagnostic::error(
+                "Generator was started by the bytecode VM",
+            )),
+        }
+    }
+
+    fn close(&mut self, generator: &mut Suspended) -> Result<(), Diagnostic> {
+        match generator {
+            Suspended::Tree(generator) => self.close_generator(generator),
+            Suspended::Vm(_) => Err(Diagnostic::error(
+                "Generator was started by the bytecode VM",
+            )),
+        }
     }
 }
 
 impl super::Visitor for Interpreter {
-    type Output = (Value, bool);
+    type Output = Result<(Value, Flow), Diagnostic>;
 
     fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
-        let (value, _) = assignment.value.accept(self);
-        self.environment
-            .borrow_mut()
-            .assign(assignment.identifier.lexeme.clone(), value.clone());
+        let current = match assignment.operator {
+            Some(_) => Some(
+                self.look_up(&assignment.identifier, assignment.resolution.get())
+                    .map_err(|message| {
+                        Diagnostic::error(message).at(assignment.identifier.span())
+                    })?,
+            ),
+            None => None,
+        };
+        let (value, _) = assignment.value.accept(self)?;
+        let value = Self::compound(&assignment.operator, current, value, assignment.span)?;
+        self.assign(
+            &assignment.identifier,
+            assignment.resolution.get(),
+            value.clone(),
+        )
+        .map_err(|message| Diagnostic::error(message).at(assignment.identifier.span()))?;
 
-        (value, false)
+        Ok((value, Flow::Normal))
     }
 
     fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
-        let (left, _) = equality.left.accept(self);
-        let (right, _) = equality.right.accept(self);
+        let (left, _) = equality.left.accept(self)?;
+        let (right, _) = equality.right.accept(self)?;
 
-        (
+        Ok((
             match equality.operator.token_type {
-                TokenType::EqualEqual => Value::Boolean(Box::new(left == right)),
-                TokenType::BangEqual => Value::Boolean(Box::new(left != right)),
-                _ => panic!("Unexpected token type"),
+                TokenType::EqualEqual => Value::Boolean(left == right),
+                TokenType::BangEqual => Value::Boolean(left != right),
+                _ => return Err(Diagnostic::error("Unexpected token type").at(equality.span)),
             },
-            false,
-        )
+            Flow::Normal,
+        ))
     }
 
     fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
-        let (left, _) = comparison.left.accept(self);
-        let (right, _) = comparison.right.accept(self);
+        let (left, _) = comparison.left.accept(self)?;
+        let (right, _) = comparison.right.accept(self)?;
+
+        if left.partial_cmp(&right).is_none() {
+            return Err(Diagnostic::error("Operands must be numbers").at(comparison.span));
+        }
 
-        (
+        Ok((
             match comparison.operator.token_type {
-                TokenType::Greater => Value::Boolean(Box::new(left > right)),
-                TokenType::GreaterEqual => Value::Boolean(Box::new(left >= right)),
-                TokenType::Less => Value::Boolean(Box::new(left < right)),
-                TokenType::LessEqual => Value::Boolean(Box::new(left <= right)),
-                _ => panic!("Unexpected token type"),
+                TokenType::Greater => Value::Boolean(left > right),
+                TokenType::GreaterEqual => Value::Boolean(left >= right),
+                TokenType::Less => Value::Boolean(left < right),
+                TokenType::LessEqual => Value::Boolean(left <= right),
+                _ => return Err(Diagnostic::error("Unexpected token type").at(comparison.span)),
             },
-            false,
-        )
+            Flow::Normal,
+        ))
     }
 
     fn visit_term(&mut self, term: &Term) -> Self::Output {
-        let (left, _) = term.left.accept(self);
-        let (right, _) = term.right.accept(self);
-        (
-            match term.operator.token_type {
-                TokenType::Plus => left + right,
-                TokenType::Minus => left - right,
-                _ => panic!("Unexpected token type"),
-            },
-            false,
-        )
+        let (left, _) = term.left.accept(self)?;
+        let (right, _) = term.right.accept(self)?;
+        let value = match term.operator.token_type {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(term.span))
     }
 
     fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
-        let (left, _) = factor.left.accept(self);
-        let (right, _) = factor.right.accept(self);
-        (
-            match factor.operator.token_type {
-                TokenType::Star => left * right,
-                TokenType::Slash => left / right,
-                _ => panic!("Unexpected token type"),
-            },
-            false,
-        )
+        let (left, _) = factor.left.accept(self)?;
+        let (right, _) = factor.right.accept(self)?;
+        let value = match factor.operator.token_type {
+            TokenType::Star => left * right,
+            TokenType::Slash => left / right,
+            TokenType::Percent => left % right,
+            TokenType::SlashSlash => left.floor_div(right),
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(factor.span))
+    }
+
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        let (left, _) = bitwise.left.accept(self)?;
+        let (right, _) = bitwise.right.accept(self)?;
+        let value = match bitwise.operator.token_type {
+            TokenType::Ampersand => left & right,
+            TokenType::Pipe => left | right,
+            TokenType::Caret => left ^ right,
+            TokenType::LessLess => left << right,
+            TokenType::GreaterGreater => left >> right,
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(bitwise.span))
+    }
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        let (left, _) = power.left.accept(self)?;
+        let (right, _) = power.right.accept(self)?;
+
+        left.pow(right)
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(power.span))
     }
 
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
-        let (value, _) = unary.right.accept(self);
-        (
-            match unary.operator.token_type {
-                TokenType::Minus => -value,
-                TokenType::Bang => {
-                    Value::Boolean(Box::new(value == Value::Boolean(Box::new(false))))
-                }
-                _ => panic!("Unexpected token type"),
-            },
-            false,
-        )
+        let (value, _) = unary.right.accept(self)?;
+        let value = match unary.operator.token_type {
+            TokenType::Minus => -value,
+            TokenType::Tilde => value.bit_not(),
+            TokenType::Bang => Ok(Value::Boolean(value == Value::Boolean(false))),
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(unary.span))
     }
 
     fn visit_primary(&mut self, primary: &Primary) -> Self::Output {
-        (
-            match primary.value.token_type {
-                TokenType::Number => Value::Number(Box::new(primary.value.lexeme.parse().unwrap())),
-                TokenType::Boolean => Value::Boolean(Box::new(primary.value.lexeme == "true")),
-                TokenType::String => Value::String(Box::new(primary.value.lexeme.clone())),
-                TokenType::Identifier => self
-                    .environment
-                    .borrow_mut()
-                    .get(primary.value.lexeme.clone()),
-                TokenType::Nil => Value::Nil,
-                _ => panic!("Unexpected token type"),
-            },
-            false,
-        )
+        let value = match primary.value.token_type {
+            TokenType::Int => parse_int(&primary.value.lexeme)
+                .map(Value::Int)
+                .ok_or_else(|| format!("Invalid number literal {}", primary.value.lexeme)),
+            TokenType::BigInt => parse_bigint(&primary.value.lexeme)
+                .map(|number| Value::BigInt(Box::new(number)))
+                .ok_or_else(|| format!("Invalid number literal {}", primary.value.lexeme)),
+            TokenType::Float => parse_float(&primary.value.lexeme)
+                .map(Value::Float)
+                .ok_or_else(|| format!("Invalid number literal {}", primary.value.lexeme)),
+            TokenType::Decimal => parse_decimal(&primary.value.lexeme)
+                .map(|number| Value::Decimal(Box::new(number)))
+                .ok_or_else(|| format!("Invalid number literal {}", primary.value.lexeme)),
+            TokenType::Boolean => Ok(Value::Boolean(primary.value.lexeme == "true")),
+            TokenType::String => Ok(Value::String(Box::new(primary.value.lexeme.clone()))),
+            TokenType::Identifier => self.look_up(&primary.value, primary.resolution.get()),
+            TokenType::Nil => Ok(Value::Nil),
+            _ => Err("Unexpected token type".to_string()),
+        };
+
+        value
+            .map(|value| (value, Flow::Normal))
+            .map_err(|message| Diagnostic::error(message).at(primary.value.span()))
     }
 
     fn visit_expression_statement(
         &mut self,
         expression_statement: &ExpressionStatement,
     ) -> Self::Output {
-        expression_statement.expression.accept(self);
+        expression_statement.expression.accept(self)?;
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_print_statement(&mut self, print_statement: &PrintStatement) -> Self::Output {
-        let (value, _) = print_statement.expression.accept(self);
-
-        match value {
-            Value::Number(number) => println!("{}", number),
-            Value::Boolean(boolean) => println!("{}", boolean),
-            Value::String(string) => println!("{}", string),
-            Value::BuiltIn(_) => println!("<native funk>"),
-            Value::Function(_) => println!("<funk>"),
-            Value::Record(record) => println!(
-                "{{{}}}",
-                record
-                    .borrow()
-                    .keys()
-                    .map(|k| { format!("{}:{}", k, record.borrow().get(k).unwrap().to_string()) })
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ),
-            Value::Iter(iter) => println!(
-                "Iter [{}]",
-                iter.borrow()
-                    .iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ),
-            Value::List(list) => println!(
-                "[{}]",
-                list.borrow()
-                    .iter()
-                    .map(|v| v.to_string())
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ),
-            Value::Nil => println!("nil"),
-        }
+        let (value, _) = print_statement.expression.accept(self)?;
+
+        writeln!(self.output.borrow_mut(), "{}", value).map_err(|error| {
+            Diagnostic::error(format!("Could not print: {}", error)).at(print_statement.span)
+        })?;
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_variable_declaration(
         &mut self,
-        variable_declaration: &crate::parser::statement::VariableDeclaration,
+        variable_declaration: &VariableDeclaration,
     ) -> Self::Output {
-        if let Some(expression) = &variable_declaration.expression {
-            let (value, _) = expression.accept(self);
-            self.environment
-                .borrow_mut()
-                .declare(variable_declaration.identifier.lexeme.clone(), value);
+        let pattern = &variable_declaration.pattern;
+        let resolution = variable_declaration.resolution.get();
 
-            return (Value::Nil, false);
+        let value = match &variable_declaration.expression {
+            Some(expression) => expression.accept(self)?.0,
+            None => Value::Nil,
+        };
+        let values = shape::destructure(pattern, value)?;
+        for (index, (identifier, value)) in pattern.bindings().into_iter().zip(values).enumerate() {
+            self.declare(identifier, resolution.nth(index), value);
         }
-        self.environment
-            .borrow_mut()
-            .declare(variable_declaration.identifier.lexeme.clone(), Value::Nil);
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_block(&mut self, block: &Block) -> Self::Output {
-        let new_environment = Rc::new(RefCell::new(Environment::new()));
-        new_environment
-            .borrow_mut()
-            .enclose(self.environment.clone());
-        self.environment = new_environment.clone();
-
-        for statement in &block.statements {
-            let (result, ret) = statement.accept(self);
-            if ret {
-                return (result, ret);
+        let new_scope = Rc::new(RefCell::new(Scope::new(self.scope.clone())));
+        let previous = self.scope.replace(new_scope);
+
+        let mut result = Ok((Value::Nil, Flow::Normal));
+        for statement in Self::hoisted(block) {
+            result = statement.accept(self);
+            match result {
+                Ok((_, Flow::Normal)) => {}
+                _ => break,
             }
         }
 
-        self.environment = new_environment.borrow_mut().get_enclosing();
-        (Value::Nil, false)
+        self.scope = previous;
+        match result? {
+            (_, Flow::Normal) => Ok((Value::Nil, Flow::Normal)),
+            result => Ok(result),
+        }
     }
 
     fn visit_if_statement(&mut self, if_statement: &IfStatement) -> Self::Output {
-        let (condition, _) = if_statement.condition.accept(self);
-        if condition == Value::Boolean(Box::new(true)) {
-            let (result, ret) = if_statement.then_branch.accept(self);
-            if ret {
-                return (result, ret);
+        let (condition, _) = if_statement.condition.accept(self)?;
+        if condition == Value::Boolean(true) {
+            let (result, flow) = if_statement.then_branch.accept(self)?;
+            if flow != Flow::Normal {
+                return Ok((result, flow));
             }
-            return (Value::Nil, false);
+            return Ok((Value::Nil, Flow::Normal));
         }
 
         if let Some(else_branch) = &if_statement.else_branch {
-            else_branch.accept(self);
+            return else_branch.accept(self);
         }
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_if_expression(&mut self, if_expression: &IfExpression) -> Self::Output {
-        let (condition, _) = if_expression.condition.accept(self);
+        let (condition, _) = if_expression.condition.accept(self)?;
 
-        if condition == Value::Boolean(Box::new(true)) {
+        if condition == Value::Boolean(true) {
             return if_expression.then_branch.accept(self);
         }
 
@@ -306,20 +679,20 @@ impl super::Visitor for Interpreter {
     }
 
     fn visit_and(&mut self, and: &And) -> Self::Output {
-        let (left, _) = and.left.accept(self);
+        let (left, _) = and.left.accept(self)?;
 
-        if left == Value::Boolean(Box::new(false)) {
-            return (Value::Boolean(Box::new(false)), false);
+        if left == Value::Boolean(false) {
+            return Ok((Value::Boolean(false), Flow::Normal));
         }
 
         and.right.accept(self)
     }
 
     fn visit_or(&mut self, or: &Or) -> Self::Output {
-        let (left, _) = or.left.accept(self);
+        let (left, _) = or.left.accept(self)?;
 
-        if left == Value::Boolean(Box::new(true)) {
-            return (Value::Boolean(Box::new(true)), false);
+        if left == Value::Boolean(true) {
+            return Ok((Value::Boolean(true), Flow::Normal));
         }
 
         or.right.accept(self)
@@ -327,45 +700,61 @@ impl super::Visitor for Interpreter {
 
     fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
         loop {
-            let (condition, _) = while_statement.condition.accept(self);
-            if condition == Value::Boolean(Box::new(false)) {
+            let (condition, _) = while_statement.condition.accept(self)?;
+            if condition == Value::Boolean(false) {
                 break;
             }
 
-            let (result, ret) = while_statement.body.accept(self);
-            if ret {
-                return (result, ret);
+            match while_statement.body.accept(self)? {
+                (_, Flow::Break(target)) if Flow::targets(&target, &while_statement.label) => break,
+                (_, Flow::Continue(target)) if Flow::targets(&target, &while_statement.label) => {}
+                (_, Flow::Normal) => {}
+                result => return Ok(result),
             }
         }
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_call(&mut self, call: &Call) -> Self::Output {
-        let (callee, _) = call.identifier.accept(self);
+        let (callee, _) = call.identifier.accept(self)?;
 
         let mut arguments = Vec::new();
 
         for argument in &call.arguments {
-            let (arg, _) = argument.accept(self);
+            let (arg, _) = argument.value.accept(self)?;
             arguments.push(arg);
         }
+        let names = call.names();
 
         match callee {
+            Value::BuiltIn(_) if !names.is_empty() => {
+                Err(Diagnostic::error("Native functions do not take named arguments").at(call.span))
+            }
             Value::BuiltIn(callable) => {
-                if callable.arity.is_none() {
-                    return (callable.call(self, &mut arguments), false);
+                if let Some(arity) = callable.arity {
+                    if arguments.len() != arity {
+                        return Err(Diagnostic::error(format!(
+                            "Expected {} arguments but got {}",
+                            arity,
+                            arguments.len()
+                        ))
+                        .at(call.span));
+                    }
                 }
 
-                let arity = callable.arity.unwrap();
-                if arguments.len() != arity {
-                    panic!("Expected {} arguments but got {}", arity, arguments.len());
-                }
-
-                return (callable.call(self, &mut arguments), false);
+                let value = callable
+                    .call(self, &mut arguments)
+                    .map_err(|diagnostic| diagnostic.at(call.span))?;
+                Ok((value, Flow::Normal))
             }
-            Value::Function(function) => (function.call(self, &mut arguments), false),
-            _ => panic!("Can only call functions"),
+            Value::Function(function) => {
+                let value = function
+                    .call_with(self, arguments, &names)
+                    .map_err(|diagnostic| diagnostic.or_at(call.span))?;
+                Ok((value, Flow::Normal))
+            }
+            _ => Err(Diagnostic::error("Can only call functions").at(call.identifier.span())),
         }
     }
 
@@ -373,120 +762,134 @@ impl super::Visitor for Interpreter {
         &mut self,
         function_declaration: &FunctionDeclaration,
     ) -> Self::Output {
-        let identifier = function_declaration.identifier.lexeme.clone();
-
         let function = Function {
-            declaration: function_declaration.clone(),
-            arity: function_declaration.parameters.len(),
-            closure: if self.environment.borrow_mut().has_enclosing() {
-                Rc::new(RefCell::new(self.environment.borrow_mut().clone()))
-            } else {
-                Rc::clone(&self.environment)
-            },
+            name: Some(function_declaration.identifier.lexeme.clone()),
+            parameters: function_declaration.parameters.clone(),
+            arity: Arity::new(&function_declaration.parameters),
+            body: function_declaration.body.clone(),
+            closure: self.scope.clone(),
+            generator: function_declaration.generator,
         };
 
-        let value = Value::Function(Box::new(function));
-
-        self.environment.borrow_mut().declare(identifier, value);
+        self.declare(
+            &function_declaration.identifier,
+            function_declaration.resolution.get(),
+            Value::Function(Box::new(function)),
+        );
 
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
         match return_statement.value {
             Some(ref value) => {
-                let (result, _) = value.accept(self);
-                return (result, true);
+                let (result, _) = value.accept(self)?;
+                return Ok((result, Flow::Return));
             }
-            None => (Value::Nil, true),
+            None => Ok((Value::Nil, Flow::Return)),
         }
     }
 
-    fn visit_with_statement(
-        &mut self,
-        with_statement: &crate::parser::statement::With,
-    ) -> Self::Output {
-        let new_environment = Rc::new(RefCell::new(Environment::new()));
-        new_environment
-            .borrow_mut()
-            .enclose(self.environment.clone());
+    /// Only reached outside a generator, whose bodies are run by
+    /// `resume_generator` instead.
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        Err(
+            Diagnostic::error("Can only yield inside a generator function, declared with 'funk*'")
+                .at(yield_statement.span),
+        )
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        let (value, _) = throw_statement.value.accept(self)?;
+
+        Err(error::throw(value, throw_statement.span))
+    }
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        // A `?` returning through the `try` is not caught, but the finally
+        // clause still runs with its value set aside.
+        let mut result = try_statement.body.accept(self);
+        let mut unwinding = self.propagating.take();
+
+        if let (Err(diagnostic), None, Some(catch)) = (&result, &unwinding, &try_statement.catch) {
+            let error = Value::Error(Box::new(ErrorValue::caught(diagnostic)));
+            result = self.execute_in(self.scope.clone(), vec![error], &catch.body);
+            unwinding = self.propagating.take();
+        }
 
-        let identifier = with_statement.identifier.lexeme.clone();
-        let (value, _) = with_statement.value.accept(self);
-        new_environment.borrow_mut().declare(identifier, value);
+        // A `return`, `break` or error in the finally clause replaces
+        // whatever the body was doing.
+        if let Some(finally) = &try_statement.finally {
+            match finally.accept(self)? {
+                (_, Flow::Normal) => {}
+                result => return Ok(result),
+            }
+        }
 
-        self.environment = new_environment.clone();
-        with_statement.body.accept(self);
+        self.propagating = unwinding;
+        match result? {
+            (_, Flow::Normal) => Ok((Value::Nil, Flow::Normal)),
+            result => Ok(result),
+        }
+    }
+
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output {
+        Ok((
+            Value::Nil,
+            Flow::Break(Flow::loop_control(&break_statement.label)),
+        ))
+    }
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output {
+        Ok((
+            Value::Nil,
+            Flow::Continue(Flow::loop_control(&continue_statement.label)),
+        ))
+    }
+
+    fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
+        let (value, _) = with_statement.value.accept(self)?;
+
+        let (result, flow) =
+            self.execute_in(self.scope.clone(), vec![value], &with_statement.body)?;
+        if flow != Flow::Normal {
+            return Ok((result, flow));
+        }
 
-        self.environment = new_environment.borrow_mut().get_enclosing();
-        (Value::Nil, false)
+        Ok((Value::Nil, Flow::Normal))
     }
 
     fn visit_list(&mut self, list: &List) -> Self::Output {
-        (
-            Value::List(Rc::new(RefCell::new(
-                list.values
-                    .iter()
-                    .map(|v| {
-                        let (result, _) = v.accept(self);
-                        result
-                    })
-                    .collect::<Vec<Value>>(),
-            ))),
-            false,
-        )
+        let mut values = Vec::with_capacity(list.values.len());
+        for value in &list.values {
+            let (result, _) = value.accept(self)?;
+            values.push(result);
+        }
+
+        Ok((Value::List(Rc::new(RefCell::new(values))), Flow::Normal))
     }
 
     fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
-        let identifer = for_statement.identifier.lexeme.clone();
-        let (iter, _) = for_statement.iter.accept(self);
-
-        match iter {
-            Value::Iter(iter) => {
-                let iter = iter.borrow();
-                for value in iter.iter() {
-                    let new_environment = Rc::new(RefCell::new(Environment::new()));
-                    new_environment
-                        .borrow_mut()
-                        .enclose(self.environment.clone());
-
-                    new_environment
-                        .borrow_mut()
-                        .declare(identifer.clone(), value.clone());
-
-                    self.environment = new_environment.clone();
-                    for_statement.body.accept(self);
-
-                    self.environment = new_environment.borrow_mut().get_enclosing();
-                }
-                (Value::Nil, false)
+        let (iter, _) = for_statement.iter.accept(self)?;
+
+        let iter = match iter {
+            Value::Iter(iter) => iter,
+            _ => {
+                return Err(Diagnostic::error("Must use an Iter in the 'for' statement")
+                    .at(for_statement.iter.span()))
             }
-            _ => panic!("Must use an Iter in the 'for' statement"),
-        }
+        };
+
+        let result = self.iterate(&iter, for_statement);
+        self.close_loop(&iter, for_statement, result)
     }
 
     fn visit_index(&mut self, index: &Index) -> Self::Output {
-        let (list, _) = index.list.accept(self);
-        let (expression, _) = index.expression.accept(self);
+        let (list, _) = index.list.accept(self)?;
+        let (expression, _) = index.expression.accept(self)?;
+        let value = Self::index(list, expression, &index.list, &index.expression)?;
 
-        match list {
-            Value::List(indexable) => match expression {
-                Value::Number(number) => {
-                    (indexable.borrow()[(*number as i32) as usize].clone(), false)
-                }
-                _ => panic!("Must use number to index into list or string"),
-            },
-            Value::String(indexable) => match expression {
-                Value::Number(number) => (
-                    Value::String(Box::new(
-                        indexable.as_bytes()[(*number as i32) as usize].to_string(),
-                    )),
-                    false,
-                ),
-                _ => panic!("Must use number to index into list or string"),
-            },
-            _ => panic!("Can only index into list or string"),
-        }
+        Ok((value, Flow::Normal))
     }
 
     fn visit_record(&mut self, record: &Record) -> Self::Output {
@@ -494,63 +897,171 @@ impl super::Visitor for Interpreter {
 
         for (key, value) in &record.key_values {
             let new_key = &key.lexeme;
-            let (new_value, _) = value.accept(self);
+            let (new_value, _) = value.accept(self)?;
             map.insert(new_key.to_string(), new_value);
         }
 
-        (Value::Record(Rc::new(RefCell::new(map))), false)
+        Ok((Value::Record(Rc::new(RefCell::new(map))), Flow::Normal))
     }
 
     fn visit_map_index(&mut self, map_index: &MapIndex) -> Self::Output {
-        let name = map_index.key.lexeme.clone();
-        let (map, _) = map_index.map.accept(self);
+        let (map, _) = map_index.map.accept(self)?;
+        let value = Self::field(map, &map_index.key, &map_index.map)?;
 
-        match map {
-            Value::Record(record) => (
-                record
-                    .borrow_mut()
-                    .get(&name)
-                    .expect("key does not exit in record")
-                    .clone(),
-                false,
-            ),
-            _ => panic!("Cannot dot index into non record type"),
-        }
+        Ok((value, Flow::Normal))
     }
 
     fn visit_map_index_assignment(
         &mut self,
         map_index_assignment: &MapIndexAssignment,
     ) -> Self::Output {
-        let (map, _) = map_index_assignment.map.accept(self);
-        let (value, _) = map_index_assignment.value.accept(self);
+        let (map, _) = map_index_assignment.map.accept(self)?;
+        let key = &map_index_assignment.key;
+        let current = match map_index_assignment.operator {
+            Some(_) => Some(Self::field(map.clone(), key, &map_index_assignment.map)?),
+            None => None,
+        };
+        let (value, _) = map_index_assignment.value.accept(self)?;
+        let value = Self::compound(
+            &map_index_assignment.operator,
+            current,
+            value,
+            map_index_assignment.span,
+        )?;
 
         if let Value::Record(record) = map {
+            if !record.borrow().contains_key(&key.lexeme) {
+                return Err(Diagnostic::error(format!(
+                    "key {} does not exist in record",
+                    key.lexeme
+                ))
+                .at(key.span()));
+            }
+
             record
                 .borrow_mut()
-                .insert(map_index_assignment.key.lexeme.clone(), value.clone())
-                .unwrap();
+                .insert(key.lexeme.clone(), value.clone());
 
-            return (value, false);
+            return Ok((value, Flow::Normal));
         }
 
-        panic!("Cannot dot index into no record type");
+        Err(Diagnostic::error("Cannot dot index into no record type")
+            .at(map_index_assignment.map.span()))
     }
 
     fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
-        let (list, _) = index_assignment.list.accept(self);
-        let (expression, _) = index_assignment.expression.accept(self);
-        let (value, _) = index_assignment.value.accept(self);
+        let (list, _) = index_assignment.list.accept(self)?;
+        let (expression, _) = index_assignment.expression.accept(self)?;
+        let current = match index_assignment.operator {
+            Some(_) => Some(Self::index(
+                list.clone(),
+                expression.clone(),
+                &index_assignment.list,
+                &index_assignment.expression,
+            )?),
+            None => None,
+        };
+        let (value, _) = index_assignment.value.accept(self)?;
+        let value = Self::compound(
+            &index_assignment.operator,
+            current,
+            value,
+            index_assignment.span,
+        )?;
 
         if let Value::List(list) = list {
-            if let Value::Number(number) = expression {
-                let number = (*number as i64) as usize;
-                list.borrow_mut()[number] = value.clone();
-                return (value, false);
+            if let Value::Int(number) = expression {
+                let mut list = list.borrow_mut();
+                let length = list.len();
+                return match usize::try_from(number).ok().and_then(|i| list.get_mut(i)) {
+                    Some(slot) => {
+                        *slot = value.clone();
+                        Ok((value, Flow::Normal))
+                    }
+                    None => Err(Diagnostic::error(format!(
+                        "Index {} out of bounds for list of length {}",
+                        number, length
+                    ))
+                    .at(index_assignment.expression.span())),
+                };
+            }
+            return Err(
+                Diagnostic::error("Must use integer to index into list type")
+                    .at(index_assignment.expression.span()),
+            );
+        }
+
+        Err(Diagnostic::error("Must index into list type").at(index_assignment.list.span()))
+    }
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        let function = Function {
+            name: None,
+            parameters: lambda.parameters.clone(),
+            arity: Arity::new(&lambda.parameters),
+            body: lambda.body.clone(),
+            closure: self.scope.clone(),
+            generator: lambda.generator,
+        };
+
+        Ok((Value::Function(Box::new(function)), Flow::Normal))
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        let mut string = interpolation.parts[0].lexeme.clone();
+        for (expression, part) in interpolation
+            .expressions
+            .iter()
+            .zip(&interpolation.parts[1..])
+        {
+            let (value, _) = expression.accept(self)?;
+            string.push_str(&value.to_string());
+            string.push_str(&part.lexeme);
+        }
+
+        Ok((Value::String(Box::new(string)), Flow::Normal))
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        let (value, _) = propagate.expression.accept(self)?;
+
+        match value.unwrapped() {
+            Some(Ok(value)) => Ok((value, Flow::Normal)),
+            Some(Err(failed)) => {
+                self.propagating = Some(failed);
+                Err(Diagnostic::error("Can only use '?' inside a function body").at(propagate.span))
+            }
+            None => Err(Diagnostic::error(format!(
+                "Can only use '?' on a result or an option, not {}",
+                value.type_name()
+            ))
+            .at(propagate.expression.span())),
+        }
+    }
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        let (value, _) = match_expression.value.accept(self)?;
+
+        for arm in &match_expression.arms {
+            let bindings = match Shape::new(&arm.pattern)?.bind(&value) {
+                Some(bindings) => bindings,
+                None => continue,
+            };
+
+            let result = self.in_scope(self.scope.clone(), bindings, |interpreter| {
+                if let Some(guard) = &arm.guard {
+                    let (condition, _) = guard.accept(interpreter)?;
+                    if condition != Value::Boolean(true) {
+                        return Ok(None);
+                    }
+                }
+                arm.body.accept(interpreter).map(Some)
+            })?;
+
+            if let Some(result) = result {
+                return Ok(result);
             }
-            panic!("Must use number to index into list type");
         }
 
-        panic!("Must index into list type");
+        Err(Diagnostic::error(shape::unmatched(&value)).at(match_expression.value.span()))
     }
 }
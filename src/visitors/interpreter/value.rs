@@ -1,72 +1,440 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{ToPrimitive, Zero};
 
 use super::callable::{BuiltIn, Function};
+use super::decimal::Decimal;
+use super::error::ErrorValue;
+use super::iter::Iter;
+use crate::compiler::vm::Closure;
 
 #[derive(Debug, Clone)]
 pub enum Value {
-    Number(Box<f64>),
-    Boolean(Box<bool>),
+    Int(i64),
+    /// An int literal too large for `Int`.
+    BigInt(Box<BigInt>),
+    Float(f64),
+    Decimal(Box<Decimal>),
+    Boolean(bool),
     String(Box<String>),
     BuiltIn(Box<BuiltIn>),
     Function(Box<Function>),
+    Closure(Rc<Closure>),
     List(Rc<RefCell<Vec<Value>>>),
-    Iter(Rc<RefCell<Vec<Value>>>),
+    Iter(Iter),
     Record(Rc<RefCell<HashMap<String, Value>>>),
+    /// What `catch` binds: see `ErrorValue`.
+    Error(Box<ErrorValue>),
+    /// `ok(value)` or `err(error)`, what the `try_` builtins return.
+    Result(Box<Result<Value, Value>>),
+    /// `some(value)` or `none`.
+    Option(Option<Box<Value>>),
     Nil,
+    /// Fills the VM's slot for the named block-local variable until its
+    /// declaration runs. Reading or assigning it is an error, so programs
+    /// never see one.
+    Uninitialized(Box<String>),
+}
+
+/// The operands of an arithmetic operator, promoted to a common kind. Ints
+/// widen to bigints, both widen to decimals or floats, and decimals never
+/// mix with floats so they stay exact.
+enum Operands {
+    Int(i64, i64),
+    BigInt(BigInt, BigInt),
+    Decimal(Decimal, Decimal),
+    Float(f64, f64),
+}
+
+fn promote(left: Value, right: Value) -> Result<Operands, String> {
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => Ok(Operands::Int(left, right)),
+        (Value::Decimal(_), Value::Float(_)) | (Value::Float(_), Value::Decimal(_)) => {
+            Err("Cannot mix decimal and float".to_string())
+        }
+        (left, right) => {
+            if let (Some(left), Some(right)) = (left.as_bigint(), right.as_bigint()) {
+                return Ok(Operands::BigInt(left, right));
+            }
+            if let (Some(left), Some(right)) = (left.as_decimal(), right.as_decimal()) {
+                return Ok(Operands::Decimal(left, right));
+            }
+            match (left.as_float(), right.as_float()) {
+                (Some(left), Some(right)) => Ok(Operands::Float(left, right)),
+                _ => Err("Operands must be numbers".to_string()),
+            }
+        }
+    }
+}
+
+fn overflow(value: Option<i64>) -> Result<i64, String> {
+    value.ok_or("Integer overflow".to_string())
+}
+
+fn nonzero(divisor: i64) -> Result<i64, String> {
+    match divisor {
+        0 => Err("Division by zero".to_string()),
+        _ => Ok(divisor),
+    }
+}
+
+fn nonzero_big(divisor: BigInt) -> Result<BigInt, String> {
+    match divisor.is_zero() {
+        true => Err("Division by zero".to_string()),
+        false => Ok(divisor),
+    }
+}
+
+fn big(number: BigInt) -> Value {
+    Value::BigInt(Box::new(number))
+}
+
+fn decimal(number: Option<Decimal>) -> Result<Value, String> {
+    number
+        .map(|number| Value::Decimal(Box::new(number)))
+        .ok_or("Division by zero".to_string())
 }
 
 impl std::ops::Add for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn add(self, other: Self) -> Self {
-        match (self, other) {
-            (Value::Number(left), Value::Number(right)) => Value::Number(Box::new(*left + *right)),
-            (Value::String(left), Value::String(right)) => Value::String(Box::new(*left + &*right)),
-            _ => panic!("Operands must be numbers"),
+    fn add(self, other: Self) -> Self::Output {
+        if let (Value::String(left), Value::String(right)) = (&self, &other) {
+            return Ok(Value::String(Box::new(left.to_string() + right)));
+        }
+
+        match promote(self, other)? {
+            Operands::Int(left, right) => overflow(left.checked_add(right)).map(Value::Int),
+            Operands::BigInt(left, right) => Ok(big(left + right)),
+            Operands::Decimal(left, right) => decimal(Some(left + right)),
+            Operands::Float(left, right) => Ok(Value::Float(left + right)),
         }
     }
 }
 
 impl std::ops::Sub for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn sub(self, other: Self) -> Self {
-        match (self, other) {
-            (Value::Number(left), Value::Number(right)) => Value::Number(Box::new(*left - *right)),
-            _ => panic!("Operands must be numbers"),
+    fn sub(self, other: Self) -> Self::Output {
+        match promote(self, other)? {
+            Operands::Int(left, right) => overflow(left.checked_sub(right)).map(Value::Int),
+            Operands::BigInt(left, right) => Ok(big(left - right)),
+            Operands::Decimal(left, right) => decimal(Some(left - right)),
+            Operands::Float(left, right) => Ok(Value::Float(left - right)),
         }
     }
 }
 
 impl std::ops::Mul for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn mul(self, other: Self) -> Self {
-        match (self, other) {
-            (Value::Number(left), Value::Number(right)) => Value::Number(Box::new(*left * *right)),
-            _ => panic!("Operands must be numbers"),
+    fn mul(self, other: Self) -> Self::Output {
+        match promote(self, other)? {
+            Operands::Int(left, right) => overflow(left.checked_mul(right)).map(Value::Int),
+            Operands::BigInt(left, right) => Ok(big(left * right)),
+            Operands::Decimal(left, right) => decimal(Some(left * right)),
+            Operands::Float(left, right) => Ok(Value::Float(left * right)),
         }
     }
 }
 
+/// Gives a float for ints and floats, and an exact decimal for decimals;
+/// `//` keeps ints.
 impl std::ops::Div for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn div(self, other: Self) -> Self {
-        match (self, other) {
-            (Value::Number(left), Value::Number(right)) => Value::Number(Box::new(*left / *right)),
-            _ => panic!("Operands must be numbers"),
+    fn div(self, other: Self) -> Self::Output {
+        match promote(self, other)? {
+            Operands::Decimal(left, right) => decimal(left.quotient(&right)),
+            Operands::Int(left, right) => Ok(Value::Float(left as f64 / right as f64)),
+            Operands::BigInt(left, right) => match (left.to_f64(), right.to_f64()) {
+                (Some(left), Some(right)) => Ok(Value::Float(left / right)),
+                _ => Err("Operands must be numbers".to_string()),
+            },
+            Operands::Float(left, right) => Ok(Value::Float(left / right)),
         }
     }
 }
 
+/// Floored, so the result takes the sign of the divisor and
+/// `a == (a // b) * b + a % b`.
+impl std::ops::Rem for Value {
+    type Output = Result<Self, String>;
+
+    fn rem(self, other: Self) -> Self::Output {
+        match promote(self, other)? {
+            Operands::Int(left, right) => {
+                let remainder = left.wrapping_rem(nonzero(right)?);
+                if remainder != 0 && (remainder < 0) != (right < 0) {
+                    return Ok(Value::Int(remainder + right));
+                }
+                Ok(Value::Int(remainder))
+            }
+            Operands::BigInt(left, right) => Ok(big(left.mod_floor(&nonzero_big(right)?))),
+            Operands::Decimal(left, right) => decimal(left.rem(&right)),
+            Operands::Float(left, right) => Ok(Value::Float(left - right * (left / right).floor())),
+        }
+    }
+}
+
+/// Applies a bitwise operator, whose operands must be ints or bigints.
+fn bitwise(
+    left: Value,
+    right: Value,
+    operator: &str,
+    int: impl Fn(i64, i64) -> i64,
+    bigint: impl Fn(BigInt, BigInt) -> BigInt,
+) -> Result<Value, String> {
+    if let (Value::Int(left), Value::Int(right)) = (&left, &right) {
+        return Ok(Value::Int(int(*left, *right)));
+    }
+
+    match (left.as_bigint(), right.as_bigint()) {
+        (Some(left), Some(right)) => Ok(big(bigint(left, right))),
+        _ => Err(format!("Operands of '{}' must be integers", operator)),
+    }
+}
+
+/// Applies a shift, which moves an int or bigint by an int.
+fn shift(
+    left: Value,
+    right: Value,
+    operator: &str,
+    int: impl Fn(i64, u32) -> Option<i64>,
+    bigint: impl Fn(BigInt, usize) -> BigInt,
+) -> Result<Value, String> {
+    let cannot = |right: i64| format!("Cannot shift by {}", right);
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => u32::try_from(right)
+            .ok()
+            .and_then(|right| int(left, right))
+            .map(Value::Int)
+            .ok_or(cannot(right)),
+        (Value::BigInt(left), Value::Int(right)) => usize::try_from(right)
+            .map(|right| big(bigint(*left, right)))
+            .map_err(|_| cannot(right)),
+        _ => Err(format!("Operands of '{}' must be integers", operator)),
+    }
+}
+
+impl std::ops::BitAnd for Value {
+    type Output = Result<Self, String>;
+
+    fn bitand(self, other: Self) -> Self::Output {
+        bitwise(
+            self,
+            other,
+            "&",
+            |left, right| left & right,
+            |left, right| left & right,
+        )
+    }
+}
+
+impl std::ops::BitOr for Value {
+    type Output = Result<Self, String>;
+
+    fn bitor(self, other: Self) -> Self::Output {
+        bitwise(
+            self,
+            other,
+            "|",
+            |left, right| left | right,
+            |left, right| left | right,
+        )
+    }
+}
+
+impl std::ops::BitXor for Value {
+    type Output = Result<Self, String>;
+
+    fn bitxor(self, other: Self) -> Self::Output {
+        bitwise(
+            self,
+            other,
+            "^",
+            |left, right| left ^ right,
+            |left, right| left ^ right,
+        )
+    }
+}
+
+impl std::ops::Shl for Value {
+    type Output = Result<Self, String>;
+
+    fn shl(self, other: Self) -> Self::Output {
+        shift(
+            self,
+            other,
+            "<<",
+            |left, right| left.checked_shl(right),
+            |left, right| left << right,
+        )
+    }
+}
+
+impl std::ops::Shr for Value {
+    type Output = Result<Self, String>;
+
+    fn shr(self, other: Self) -> Self::Output {
+        shift(
+            self,
+            other,
+            ">>",
+            |left, right| left.checked_shr(right),
+            |left, right| left >> right,
+        )
+    }
+}
+
 impl std::ops::Neg for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn neg(self) -> Self {
+    fn neg(self) -> Self::Output {
         match self {
-            Value::Number(right) => Value::Number(Box::new(-*right)),
-            _ => panic!("Unary operator - can only be applied to numbers"),
+            Value::Int(right) => overflow(right.checked_neg()).map(Value::Int),
+            Value::BigInt(right) => Ok(big(-*right)),
+            Value::Float(right) => Ok(Value::Float(-right)),
+            Value::Decimal(right) => decimal(Some(-*right)),
+            _ => Err("Unary operator - can only be applied to numbers".to_string()),
+        }
+    }
+}
+
+impl Value {
+    pub fn is_number(&self) -> bool {
+        matches!(
+            self,
+            Value::Int(_) | Value::BigInt(_) | Value::Float(_) | Value::Decimal(_)
+        )
+    }
+
+    /// The value as a float, if it is an int, bigint or float.
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Int(number) => Some(*number as f64),
+            Value::BigInt(number) => number.to_f64(),
+            Value::Float(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    /// The value as a bigint, if it is an int or bigint.
+    pub fn as_bigint(&self) -> Option<BigInt> {
+        match self {
+            Value::Int(number) => Some(BigInt::from(*number)),
+            Value::BigInt(number) => Some(*number.clone()),
+            _ => None,
+        }
+    }
+
+    /// The value as a decimal, if it is an int, bigint or decimal.
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match self {
+            Value::Decimal(number) => Some(*number.clone()),
+            _ => self.as_bigint().map(Decimal::from),
+        }
+    }
+
+    /// `**`. An int raised to an int stays an int unless the exponent is
+    /// negative, and a decimal can only be raised to an int.
+    pub fn pow(self, other: Self) -> Result<Self, String> {
+        match (self, other) {
+            (Value::Int(base), Value::Int(exponent)) => match u32::try_from(exponent) {
+                Ok(exponent) => overflow(base.checked_pow(exponent)).map(Value::Int),
+                Err(_) => Ok(Value::Float((base as f64).powf(exponent as f64))),
+            },
+            (Value::BigInt(base), Value::Int(exponent)) => match u32::try_from(exponent) {
+                Ok(exponent) => Ok(big(base.pow(exponent))),
+                Err(_) => Ok(Value::Float(
+                    base.to_f64().unwrap_or(f64::NAN).powf(exponent as f64),
+                )),
+            },
+            (Value::Decimal(base), Value::Int(exponent)) => decimal(base.pow(exponent)),
+            (Value::Decimal(_), _) => Err("Decimals can only be raised to an int".to_string()),
+            (left, right) => match promote(left, right)? {
+                Operands::Float(left, right) => Ok(Value::Float(left.powf(right))),
+                Operands::Decimal(..) => Err("Decimals can only be raised to an int".to_string()),
+                _ => Err("Exponent is too large".to_string()),
+            },
+        }
+    }
+
+    /// `~`, the bitwise complement.
+    pub fn bit_not(self) -> Result<Self, String> {
+        match self {
+            Value::Int(number) => Ok(Value::Int(!number)),
+            Value::BigInt(number) => Ok(big(!*number)),
+            _ => Err("Operands of '~' must be integers".to_string()),
+        }
+    }
+
+    /// `//`, which rounds the quotient down.
+    pub fn floor_div(self, other: Self) -> Result<Self, String> {
+        match promote(self, other)? {
+            Operands::Int(left, right) => {
+                let quotient = overflow(left.checked_div(nonzero(right)?))?;
+                if left % right != 0 && (left < 0) != (right < 0) {
+                    return Ok(Value::Int(quotient - 1));
+                }
+                Ok(Value::Int(quotient))
+            }
+            Operands::BigInt(left, right) => Ok(big(left.div_floor(&nonzero_big(right)?))),
+            Operands::Decimal(left, right) => decimal(left.floor_div(&right)),
+            Operands::Float(left, right) => Ok(Value::Float((left / right).floor())),
+        }
+    }
+
+    /// Orders numbers of any kind, or `None` when they can't be compared.
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        if !self.is_number() || !other.is_number() {
+            return None;
+        }
+
+        match promote(self.clone(), other.clone()).ok()? {
+            Operands::Int(left, right) => Some(left.cmp(&right)),
+            Operands::BigInt(left, right) => Some(left.cmp(&right)),
+            Operands::Decimal(left, right) => Some(left.cmp(&right)),
+            Operands::Float(left, right) => left.partial_cmp(&right),
+        }
+    }
+
+    /// What `?` and `unwrap` make of a result or option: the value inside
+    /// an `ok` or `some`, or else the `err` or `none` itself. `None` for any
+    /// other value.
+    pub fn unwrapped(&self) -> Option<Result<Value, Value>> {
+        match self {
+            Value::Result(result) => match &**result {
+                Ok(value) => Some(Ok(value.clone())),
+                Err(_) => Some(Err(self.clone())),
+            },
+            Value::Option(Some(value)) => Some(Ok((**value).clone())),
+            Value::Option(None) => Some(Err(self.clone())),
+            _ => None,
+        }
+    }
+
+    /// What `type_of` calls the value.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::BigInt(_) => "bigint",
+            Value::Float(_) => "float",
+            Value::Decimal(_) => "decimal",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::BuiltIn(_) => "builtin function",
+            Value::Function(_) | Value::Closure(_) => "function",
+            Value::List(_) => "list",
+            Value::Iter(_) => "iter",
+            Value::Record(_) => "record",
+            Value::Error(_) => "error",
+            Value::Result(_) => "result",
+            Value::Option(_) => "option",
+            Value::Nil => "nil",
+            Value::Uninitialized(_) => "uninitialized",
         }
     }
 }
@@ -74,61 +442,69 @@ impl std::ops::Neg for Value {
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Value::Number(left), Value::Number(right)) => left == right,
+            (Value::Int(left), Value::Int(right)) => left == right,
             (Value::Boolean(left), Value::Boolean(right)) => left == right,
             (Value::String(left), Value::String(right)) => left == right,
             (Value::Nil, Value::Nil) => true,
-            _ => false,
+            (Value::Error(left), Value::Error(right)) => left == right,
+            (Value::Result(left), Value::Result(right)) => left == right,
+            (Value::Option(left), Value::Option(right)) => left == right,
+            _ => self.compare(other) == Some(Ordering::Equal),
         }
     }
 }
 
 impl PartialOrd for Value {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
         match (self, other) {
-            (Value::Number(left), Value::Number(right)) => left.partial_cmp(right),
-            _ => panic!("Operands must be numbers"),
+            (Value::Int(left), Value::Int(right)) => left.partial_cmp(right),
+            _ => self.compare(other),
         }
     }
 }
 
 impl std::ops::Not for Value {
-    type Output = Self;
+    type Output = Result<Self, String>;
 
-    fn not(self) -> Self {
+    fn not(self) -> Self::Output {
         match self {
-            Value::Boolean(right) => Value::Boolean(Box::new(!*right)),
-            _ => panic!("Unary operator ! can only be applied to booleans"),
+            Value::Boolean(right) => Ok(Value::Boolean(!right)),
+            _ => Err("Unary operator ! can only be applied to booleans".to_string()),
         }
     }
 }
 
-impl ToString for Value {
-    fn to_string(&self) -> String {
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Value::Number(number) => number.to_string(),
-            Value::Boolean(boolean) => boolean.to_string(),
-            Value::String(string) => *string.clone(),
-            Value::BuiltIn(_) => format!("<native funk>"),
-            Value::Function(_) => format!("<funk>"),
-            Value::Record(record) => format!(
+            Value::Int(number) => write!(f, "{}", number),
+            Value::BigInt(number) => write!(f, "{}", number),
+            Value::Float(number) => write!(f, "{}", number),
+            Value::Decimal(number) => write!(f, "{}", number),
+            Value::Boolean(boolean) => write!(f, "{}", boolean),
+            Value::String(string) => write!(f, "{}", string),
+            Value::BuiltIn(_) => write!(f, "<native funk>"),
+            Value::Function(_) | Value::Closure(_) => write!(f, "<funk>"),
+            Value::Record(record) => write!(
+                f,
                 "{{{}}}",
                 record
                     .borrow()
                     .keys()
-                    .map(|k| { format!("{}:{}", k, record.borrow().get(k).unwrap().to_string()) })
-                    .collect::<Vec<String>>()
-                    .join(", ")
-            ),
-            Value::Iter(iter) => format!(
-                "Iter [{}]",
-                iter.borrow()
-                    .iter()
-                    .map(|v| v.to_string())
+                    .map(|k| { format!("{}:{}", k, record.borrow().get(k).unwrap()) })
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            Value::List(list) => format!(
+            Value::Iter(_) => write!(f, "<iter>"),
+            Value::Error(error) => write!(f, "{}", error),
+            Value::Result(result) => match &**result {
+                Ok(value) => write!(f, "ok({})", value),
+                Err(error) => write!(f, "err({})", error),
+            },
+            Value::Option(Some(value)) => write!(f, "some({})", value),
+            Value::Option(None) => write!(f, "none"),
+            Value::List(list) => write!(
+                f,
                 "[{}]",
                 list.borrow()
                     .iter()
@@ -136,7 +512,8 @@ impl ToString for Value {
                     .collect::<Vec<String>>()
                     .join(", ")
             ),
-            Value::Nil => "nil".to_string(),
+            Value::Nil => write!(f, "nil"),
+            Value::Uninitialized(_) => write!(f, "<uninitialized>"),
         }
     }
 }
@@ -1,17 +1,20 @@
 use crate::parser::{
     expression::{
-        And, Assignment, Call, Comparison, Equality, Factor, IfExpression, Index, IndexAssignment,
-        List, MapIndex, MapIndexAssignment, Or, Primary, Record, Term, Unary,
+        And, Assignment, Bitwise, Call, Comparison, Equality, Factor, IfExpression, Index,
+        IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment, Match, Or,
+        Power, Primary, Propagate, Record, Term, Unary,
     },
     statement::{
-        Block, ExpressionStatement, For, FunctionDeclaration, IfStatement, PrintStatement, Return,
-        VariableDeclaration, WhileStatement, With,
+        Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+        PrintStatement, Return, Throw, Try, VariableDeclaration, WhileStatement, With, Yield,
     },
 };
 
 pub mod ast_printer;
 pub mod environment;
+pub mod formatter;
 pub mod interpreter;
+pub mod resolver;
 pub mod type_checker;
 
 pub trait Visitor {
@@ -27,6 +30,10 @@ pub trait Visitor {
 
     fn visit_factor(&mut self, factor: &Factor) -> Self::Output;
 
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output;
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output;
+
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output;
 
     fn visit_primary(&mut self, primary: &Primary) -> Self::Output;
@@ -64,6 +71,16 @@ pub trait Visitor {
 
     fn visit_return(&mut self, return_statement: &Return) -> Self::Output;
 
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output;
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output;
+
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output;
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output;
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output;
+
     fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output;
 
     fn visit_list(&mut self, list: &List) -> Self::Output;
@@ -82,4 +99,12 @@ pub trait Visitor {
     ) -> Self::Output;
 
     fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output;
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output;
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output;
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output;
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output;
 }
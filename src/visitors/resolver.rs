@@ -0,0 +1,704 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    diagnostic::{Diagnostic, Span},
+    parser::{
+        accept::Accept,
+        expression::{
+            And, Assignment, Bitwise, Call, Comparison, Equality, Factor, IfExpression, Index,
+            IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment, Match, Or,
+            Power, Primary, Propagate, Record, Term, Unary,
+        },
+        parameter::Parameter,
+        pattern::Pattern,
+        statement::{
+            Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+            PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement,
+            With, Yield,
+        },
+    },
+    token::{Token, TokenType},
+};
+
+/// Where a variable lives at runtime, as worked out by the `Resolver`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, PartialOrd)]
+pub enum Resolution {
+    /// Looked up by name in the global environment.
+    #[default]
+    Global,
+    /// In the scope `depth` levels out from the innermost one, at `slot`.
+    Local { depth: usize, slot: usize },
+}
+
+impl Resolution {
+    /// Where the variable declared `offset` places after this one lives, for
+    /// the names a single pattern declares together.
+    pub fn nth(self, offset: usize) -> Resolution {
+        match self {
+            Resolution::Global => Resolution::Global,
+            Resolution::Local { depth, slot } => Resolution::Local {
+                depth,
+                slot: slot + offset,
+            },
+        }
+    }
+}
+
+struct Variable {
+    slot: usize,
+    defined: bool,
+}
+
+#[derive(Default)]
+struct Scope {
+    variables: HashMap<String, Variable>,
+    next_slot: usize,
+}
+
+/// Static pass that binds every variable use to the declaration it refers
+/// to, recording a `Resolution` on the AST for the interpreter. Runtime
+/// scopes must mirror the ones opened here: one per block, function call
+/// (holding the parameters), `with` statement and `for` iteration.
+pub struct Resolver {
+    scopes: Vec<Scope>,
+    globals: HashSet<String>,
+    program_globals: HashSet<String>,
+    function_depth: usize,
+    /// The labels of the loops around the code being resolved, innermost
+    /// last. Functions start with none, as loops don't reach into them.
+    loops: Vec<Option<String>>,
+}
+
+impl Resolver {
+    const BUILTINS: [&'static str; 39] = [
+        "args",
+        "clock",
+        "println",
+        "input",
+        "iter",
+        "range_to",
+        "range",
+        "range_skip",
+        "range_from",
+        "map",
+        "filter",
+        "take",
+        "skip",
+        "zip",
+        "enumerate",
+        "chain",
+        "len",
+        "push",
+        "to_number",
+        "to_bigint",
+        "to_decimal",
+        "round",
+        "divide",
+        "type_of",
+        "error",
+        "ok",
+        "err",
+        "some",
+        "none",
+        "unwrap",
+        "unwrap_or",
+        "is_ok",
+        "is_some",
+        "try_to_number",
+        "get",
+        "read_file",
+        "write_file",
+        "try_read_file",
+        "try_write_file",
+    ];
+
+    pub fn new() -> Self {
+        Resolver {
+            scopes: vec![],
+            globals: Self::BUILTINS.iter().map(|name| name.to_string()).collect(),
+            program_globals: HashSet::new(),
+            function_depth: 0,
+            loops: vec![],
+        }
+    }
+
+    /// Makes a global defined by the host visible to the programs resolved
+    /// after it.
+    pub fn declare_global(&mut self, name: &str) {
+        self.globals.insert(name.to_string());
+    }
+
+    /// Resolves a program. Globals declared by earlier calls stay visible, so
+    /// one resolver can be fed a program piece by piece.
+    pub fn resolve(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        // Function bodies may call functions declared further down the file.
+        self.program_globals = statements
+            .iter()
+            .filter_map(|statement| match statement {
+                Statement::FunctionDeclaration(declaration) => {
+                    Some(declaration.identifier.lexeme.clone())
+                }
+                _ => None,
+            })
+            .collect();
+
+        for statement in statements {
+            statement.accept(self)?;
+        }
+
+        Ok(())
+    }
+
+    fn begin_scope(&mut self) {
+        self.scopes.push(Scope::default());
+    }
+
+    fn end_scope(&mut self) {
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, identifier: &Token) -> Resolution {
+        match self.scopes.last_mut() {
+            Some(scope) => {
+                let slot = scope.next_slot;
+                scope.next_slot += 1;
+                scope.variables.insert(
+                    identifier.lexeme.clone(),
+                    Variable {
+                        slot,
+                        defined: false,
+                    },
+                );
+
+                Resolution::Local { depth: 0, slot }
+            }
+            None => {
+                self.globals.insert(identifier.lexeme.clone());
+                Resolution::Global
+            }
+        }
+    }
+
+    /// Takes a slot in the innermost scope that no name refers to, for a
+    /// value the runtime keeps there.
+    fn reserve(&mut self) {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.next_slot += 1;
+        }
+    }
+
+    fn define(&mut self, identifier: &Token) {
+        if let Some(variable) = self
+            .scopes
+            .last_mut()
+            .and_then(|scope| scope.variables.get_mut(&identifier.lexeme))
+        {
+            variable.defined = true;
+        }
+    }
+
+    fn resolve_variable(&self, identifier: &Token) -> Result<Resolution, Diagnostic> {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Some(variable) = scope.variables.get(&identifier.lexeme) {
+                if depth == 0 && !variable.defined {
+                    return Err(Diagnostic::error(format!(
+                        "Cannot read local variable {} in its own initializer",
+                        identifier.lexeme
+                    ))
+                    .at(identifier.span()));
+                }
+
+                return Ok(Resolution::Local {
+                    depth,
+                    slot: variable.slot,
+                });
+            }
+        }
+
+        let declared = self.globals.contains(&identifier.lexeme)
+            || (self.function_depth > 0 && self.program_globals.contains(&identifier.lexeme));
+
+        if !declared {
+            return Err(
+                Diagnostic::error(format!("Undefined variable {}", identifier.lexeme))
+                    .at(identifier.span()),
+            );
+        }
+
+        Ok(Resolution::Global)
+    }
+
+    /// Resolves a function body in a new scope holding its parameters. Each
+    /// argument takes a slot, and the names that parameters written as
+    /// patterns bind come after them all.
+    fn function(&mut self, parameters: &[Parameter], body: &Statement) -> Result<(), Diagnostic> {
+        self.function_depth += 1;
+        self.begin_scope();
+        let loops = std::mem::take(&mut self.loops);
+
+        let result = self.parameters(parameters).and_then(|_| body.accept(self));
+
+        self.end_scope();
+        self.function_depth -= 1;
+        self.loops = loops;
+        result
+    }
+
+    /// A default can use the parameters before it, but not itself or the
+    /// ones after it.
+    fn parameters(&mut self, parameters: &[Parameter]) -> Result<(), Diagnostic> {
+        let mut seen = HashSet::new();
+        for parameter in parameters
+            .iter()
+            .flat_map(|parameter| parameter.pattern.bindings())
+        {
+            if !seen.insert(&parameter.lexeme) {
+                return Err(
+                    Diagnostic::error(format!("Duplicate parameter {}", parameter.lexeme))
+                        .at(parameter.span()),
+                );
+            }
+        }
+
+        for parameter in parameters {
+            match &parameter.pattern {
+                Pattern::Binding(name) => {
+                    self.declare(name);
+                }
+                _ => self.reserve(),
+            }
+        }
+        for parameter in parameters {
+            if !matches!(parameter.pattern, Pattern::Binding(_)) {
+                self.declare_pattern(&parameter.pattern)?;
+            }
+        }
+        for parameter in parameters {
+            if let Some(default) = &parameter.default {
+                default.accept(self)?;
+            }
+            self.define_pattern(&parameter.pattern);
+        }
+
+        Ok(())
+    }
+
+    /// Declares the names a pattern binds in the innermost scope, in the
+    /// order their values are bound, returning where the first one lives.
+    fn declare_pattern(&mut self, pattern: &Pattern) -> Result<Resolution, Diagnostic> {
+        Self::check_alternatives(pattern)?;
+
+        let mut seen = HashSet::new();
+        let mut first = None;
+        for binding in pattern.bindings() {
+            if !seen.insert(&binding.lexeme) {
+                return Err(Diagnostic::error(format!(
+                    "Duplicate binding {} in pattern",
+                    binding.lexeme
+                ))
+                .at(binding.span()));
+            }
+
+            let resolution = self.declare(binding);
+            first.get_or_insert(resolution);
+        }
+
+        Ok(first.unwrap_or_default())
+    }
+
+    fn define_pattern(&mut self, pattern: &Pattern) {
+        for binding in pattern.bindings() {
+            self.define(binding);
+        }
+    }
+
+    /// Alternatives can't bind, as only one of them provides the values.
+    fn check_alternatives(pattern: &Pattern) -> Result<(), Diagnostic> {
+        match pattern {
+            Pattern::Either { alternatives, .. } => {
+                match alternatives
+                    .iter()
+                    .find_map(|alternative| alternative.bindings().first().copied())
+                {
+                    Some(binding) => Err(Diagnostic::error(
+                        "Alternatives in a pattern cannot bind variables",
+                    )
+                    .at(binding.span())),
+                    None => Ok(()),
+                }
+            }
+            Pattern::List { items, .. } => items.iter().try_for_each(Self::check_alternatives),
+            Pattern::Record { fields, .. } => fields
+                .iter()
+                .try_for_each(|(_, pattern)| Self::check_alternatives(pattern)),
+            Pattern::Variant { pattern, .. } => Self::check_alternatives(pattern),
+            Pattern::Wildcard(_)
+            | Pattern::Literal { .. }
+            | Pattern::Binding(_)
+            | Pattern::None(_) => Ok(()),
+        }
+    }
+
+    fn loop_body(&mut self, label: &Option<Token>, body: &Statement) -> Result<(), Diagnostic> {
+        self.loops
+            .push(label.as_ref().map(|label| label.lexeme.clone()));
+        let result = body.accept(self);
+        self.loops.pop();
+
+        result
+    }
+
+    /// Checks that a `break` or `continue` is inside a loop, and that the
+    /// loop it names encloses it.
+    fn loop_control(
+        &self,
+        keyword: &str,
+        label: &Option<Token>,
+        span: Span,
+    ) -> Result<(), Diagnostic> {
+        if self.loops.is_empty() {
+            return Err(
+                Diagnostic::error(format!("Cannot use '{}' outside of a loop", keyword)).at(span),
+            );
+        }
+
+        if let Some(label) = label {
+            if !self.loops.contains(&Some(label.lexeme.clone())) {
+                return Err(Diagnostic::error(format!(
+                    "No enclosing loop labelled {}",
+                    label.lexeme
+                ))
+                .at(label.span()));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Declares every function in a block up front so that sibling functions
+    /// can call each other regardless of the order they're written in.
+    fn hoist_functions(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Statement::FunctionDeclaration(declaration) = statement {
+                let resolution = self.declare(&declaration.identifier);
+                self.define(&declaration.identifier);
+                declaration.resolution.set(resolution);
+            }
+        }
+    }
+}
+
+impl super::Visitor for Resolver {
+    type Output = Result<(), Diagnostic>;
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        assignment.value.accept(self)?;
+        assignment
+            .resolution
+            .set(self.resolve_variable(&assignment.identifier)?);
+
+        Ok(())
+    }
+
+    fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
+        equality.left.accept(self)?;
+        equality.right.accept(self)
+    }
+
+    fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
+        comparison.left.accept(self)?;
+        comparison.right.accept(self)
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Self::Output {
+        term.left.accept(self)?;
+        term.right.accept(self)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
+        factor.left.accept(self)?;
+        factor.right.accept(self)
+    }
+
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        bitwise.left.accept(self)?;
+        bitwise.right.accept(self)
+    }
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        power.left.accept(self)?;
+        power.right.accept(self)
+    }
+
+    fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
+        unary.right.accept(self)
+    }
+
+    fn visit_primary(&mut self, primary: &Primary) -> Self::Output {
+        if primary.value.token_type == TokenType::Identifier {
+            primary
+                .resolution
+                .set(self.resolve_variable(&primary.value)?);
+        }
+
+        Ok(())
+    }
+
+    fn visit_expression_statement(
+        &mut self,
+        expression_statement: &ExpressionStatement,
+    ) -> Self::Output {
+        expression_statement.expression.accept(self)
+    }
+
+    fn visit_print_statement(&mut self, print_statement: &PrintStatement) -> Self::Output {
+        print_statement.expression.accept(self)
+    }
+
+    fn visit_variable_declaration(
+        &mut self,
+        variable_declaration: &VariableDeclaration,
+    ) -> Self::Output {
+        let pattern = &variable_declaration.pattern;
+
+        if self.scopes.is_empty() {
+            // Globals are late bound, so `make x = x;` may read an earlier x.
+            if let Some(expression) = &variable_declaration.expression {
+                expression.accept(self)?;
+            }
+            variable_declaration
+                .resolution
+                .set(self.declare_pattern(pattern)?);
+            return Ok(());
+        }
+
+        let resolution = self.declare_pattern(pattern)?;
+        if let Some(expression) = &variable_declaration.expression {
+            expression.accept(self)?;
+        }
+        self.define_pattern(pattern);
+        variable_declaration.resolution.set(resolution);
+
+        Ok(())
+    }
+
+    fn visit_block(&mut self, block: &Block) -> Self::Output {
+        self.begin_scope();
+        self.hoist_functions(&block.statements);
+
+        let result = block
+            .statements
+            .iter()
+            .try_for_each(|statement| statement.accept(self));
+
+        self.end_scope();
+        result
+    }
+
+    fn visit_if_statement(&mut self, if_statement: &IfStatement) -> Self::Output {
+        if_statement.condition.accept(self)?;
+        if_statement.then_branch.accept(self)?;
+
+        if let Some(else_branch) = &if_statement.else_branch {
+            else_branch.accept(self)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_if_expression(&mut self, if_expression: &IfExpression) -> Self::Output {
+        if_expression.condition.accept(self)?;
+        if_expression.then_branch.accept(self)?;
+        if_expression.else_branch.accept(self)
+    }
+
+    fn visit_and(&mut self, and: &And) -> Self::Output {
+        and.left.accept(self)?;
+        and.right.accept(self)
+    }
+
+    fn visit_or(&mut self, or: &Or) -> Self::Output {
+        or.left.accept(self)?;
+        or.right.accept(self)
+    }
+
+    fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
+        while_statement.condition.accept(self)?;
+        self.loop_body(&while_statement.label, &while_statement.body)
+    }
+
+    fn visit_call(&mut self, call: &Call) -> Self::Output {
+        call.identifier.accept(self)?;
+
+        for argument in &call.arguments {
+            argument.value.accept(self)?;
+        }
+
+        Ok(())
+    }
+
+    fn visit_function_declaration(
+        &mut self,
+        function_declaration: &FunctionDeclaration,
+    ) -> Self::Output {
+        // Functions inside a block were already declared by `hoist_functions`.
+        if self.scopes.is_empty() {
+            function_declaration
+                .resolution
+                .set(self.declare(&function_declaration.identifier));
+        }
+
+        self.function(&function_declaration.parameters, &function_declaration.body)
+    }
+
+    fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
+        if self.function_depth == 0 {
+            return Err(Diagnostic::error(
+                "Return keyword should not be used outside of a function body",
+            )
+            .at(return_statement.span));
+        }
+
+        match &return_statement.value {
+            Some(value) => value.accept(self),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        yield_statement.value.accept(self)
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        throw_statement.value.accept(self)
+    }
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        try_statement.body.accept(self)?;
+
+        if let Some(catch) = &try_statement.catch {
+            self.begin_scope();
+            self.declare(&catch.identifier);
+            self.define(&catch.identifier);
+            let result = catch.body.accept(self);
+            self.end_scope();
+            result?;
+        }
+
+        match &try_statement.finally {
+            Some(finally) => finally.accept(self),
+            None => Ok(()),
+        }
+    }
+
+    fn visit_break(&mut self, break_statement: &Break) -> Self::Output {
+        self.loop_control("break", &break_statement.label, break_statement.span)
+    }
+
+    fn visit_continue(&mut self, continue_statement: &Continue) -> Self::Output {
+        self.loop_control(
+            "continue",
+            &continue_statement.label,
+            continue_statement.span,
+        )
+    }
+
+    fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
+        with_statement.value.accept(self)?;
+
+        self.begin_scope();
+        self.declare(&with_statement.identifier);
+        self.define(&with_statement.identifier);
+        let result = with_statement.body.accept(self);
+        self.end_scope();
+
+        result
+    }
+
+    fn visit_list(&mut self, list: &List) -> Self::Output {
+        list.values.iter().try_for_each(|value| value.accept(self))
+    }
+
+    fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
+        for_statement.iter.accept(self)?;
+
+        self.begin_scope();
+        let result = self.declare_pattern(&for_statement.pattern).and_then(|_| {
+            self.define_pattern(&for_statement.pattern);
+            self.loop_body(&for_statement.label, &for_statement.body)
+        });
+        self.end_scope();
+
+        result
+    }
+
+    fn visit_index(&mut self, index: &Index) -> Self::Output {
+        index.list.accept(self)?;
+        index.expression.accept(self)
+    }
+
+    fn visit_record(&mut self, record: &Record) -> Self::Output {
+        record
+            .key_values
+            .iter()
+            .try_for_each(|(_, value)| value.accept(self))
+    }
+
+    fn visit_map_index(&mut self, map_index: &MapIndex) -> Self::Output {
+        map_index.map.accept(self)
+    }
+
+    fn visit_map_index_assignment(
+        &mut self,
+        map_index_assignment: &MapIndexAssignment,
+    ) -> Self::Output {
+        map_index_assignment.map.accept(self)?;
+        map_index_assignment.value.accept(self)
+    }
+
+    fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
+        index_assignment.list.accept(self)?;
+        index_assignment.expression.accept(self)?;
+        index_assignment.value.accept(self)
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        self.function(&lambda.parameters, &lambda.body)
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        interpolation
+            .expressions
+            .iter()
+            .try_for_each(|expression| expression.accept(self))
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        if self.function_depth == 0 {
+            return Err(
+                Diagnostic::error("Can only use '?' inside a function body").at(propagate.span)
+            );
+        }
+
+        propagate.expression.accept(self)
+    }
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        match_expression.value.accept(self)?;
+
+        for arm in &match_expression.arms {
+            self.begin_scope();
+            let result = self.declare_pattern(&arm.pattern).and_then(|_| {
+                self.define_pattern(&arm.pattern);
+                if let Some(guard) = &arm.guard {
+                    guard.accept(self)?;
+                }
+                arm.body.accept(self)
+            });
+            self.end_scope();
+            result?;
+        }
+
+        Ok(())
+    }
+}
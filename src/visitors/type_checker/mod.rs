@@ -1,34 +1,51 @@
 use std::{cell::RefCell, rc::Rc};
 
 use crate::{
+    arity::Arity,
+    diagnostic::{Diagnostic, Span},
     parser::{
         accept::Accept,
         expression::{
-            Assignment, Call, Comparison, Equality, Factor, IfExpression, Index, IndexAssignment,
-            List, MapIndex, MapIndexAssignment, Primary, Record, Term, Unary,
+            Arm, Assignment, Bitwise, Call, Comparison, Equality, Expression, Factor, IfExpression,
+            Index, IndexAssignment, Interpolation, Lambda, List, MapIndex, MapIndexAssignment,
+            Match, Power, Primary, Propagate, Record, Term, Unary,
         },
+        parameter::Parameter,
+        pattern::Pattern,
         statement::{
-            Block, ExpressionStatement, For, FunctionDeclaration, IfStatement, PrintStatement,
-            Return, Statement, VariableDeclaration, WhileStatement, With,
+            Block, Break, Continue, ExpressionStatement, For, FunctionDeclaration, IfStatement,
+            PrintStatement, Return, Statement, Throw, Try, VariableDeclaration, WhileStatement,
+            With, Yield,
         },
     },
-    token::TokenType,
+    token::{Token, TokenType},
 };
 
 use super::environment::Environment;
 
+mod patterns;
 pub mod types;
 
-use types::Type;
+use types::{Generic, Signature, Type};
 
 pub struct TypeChecker {
     environment: Rc<RefCell<Environment<Type>>>,
+    /// Whether the innermost function being checked is a generator, so
+    /// `yield` is allowed.
+    generator: bool,
+    /// Problems that don't stop the program from running, such as a `match`
+    /// that misses some values.
+    warnings: Vec<Diagnostic>,
 }
 
 impl TypeChecker {
     pub fn new() -> Self {
         let environment = Rc::new(RefCell::new(Environment::<Type>::new()));
 
+        environment
+            .borrow_mut()
+            .declare_global("args".to_string(), Type::List);
+
         environment
             .borrow_mut()
             .declare_global("clock".to_string(), Type::Function);
@@ -53,6 +70,30 @@ impl TypeChecker {
         environment
             .borrow_mut()
             .declare_global("range_skip".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("range_from".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("map".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("filter".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("take".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("skip".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("zip".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("enumerate".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("chain".to_string(), Type::Function);
         environment
             .borrow_mut()
             .declare_global("len".to_string(), Type::Function);
@@ -62,135 +103,396 @@ impl TypeChecker {
         environment
             .borrow_mut()
             .declare_global("to_number".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("to_bigint".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("to_decimal".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("round".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("divide".to_string(), Type::Function);
         environment
             .borrow_mut()
             .declare_global("type_of".to_string(), Type::Function);
+        environment
+            .borrow_mut()
+            .declare_global("error".to_string(), Type::Function);
+
+        let builtin = |parameters: Vec<Type>, returns: Type| {
+            Type::BuiltIn(Box::new(Signature {
+                parameters,
+                returns,
+            }))
+        };
+        let unknown = || Box::new(Type::Unknown);
+        let string_result = |ok: Type| Type::Result(Box::new(ok), Box::new(Type::String));
+        let wrapped = [
+            ("ok", Type::Generic(Generic::Ok)),
+            ("err", Type::Generic(Generic::Err)),
+            ("some", Type::Generic(Generic::Some)),
+            ("none", Type::Option(unknown())),
+            ("unwrap", Type::Generic(Generic::Unwrap)),
+            ("unwrap_or", Type::Generic(Generic::UnwrapOr)),
+            (
+                "is_ok",
+                builtin(vec![Type::Result(unknown(), unknown())], Type::Boolean),
+            ),
+            (
+                "is_some",
+                builtin(vec![Type::Option(unknown())], Type::Boolean),
+            ),
+            (
+                "try_to_number",
+                builtin(vec![Type::String], string_result(Type::Unknown)),
+            ),
+            (
+                "get",
+                builtin(vec![Type::Unknown, Type::Unknown], Type::Option(unknown())),
+            ),
+            ("read_file", builtin(vec![Type::String], Type::String)),
+            (
+                "write_file",
+                builtin(vec![Type::String, Type::String], Type::Nil),
+            ),
+            (
+                "try_read_file",
+                builtin(vec![Type::String], string_result(Type::String)),
+            ),
+            (
+                "try_write_file",
+                builtin(vec![Type::String, Type::String], string_result(Type::Nil)),
+            ),
+        ];
+        for (name, global_type) in wrapped {
+            environment
+                .borrow_mut()
+                .declare_global(name.to_string(), global_type);
+        }
 
-        TypeChecker { environment }
+        TypeChecker {
+            environment,
+            generator: false,
+            warnings: vec![],
+        }
+    }
+
+    pub fn declare_global(&mut self, name: &str, global_type: Type) {
+        self.environment
+            .borrow_mut()
+            .declare_global(name.to_string(), global_type);
     }
 
-    pub fn check(&mut self, statements: &Vec<Statement>) {
+    pub fn check(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        self.hoist_functions(statements);
+
         for statement in statements {
-            statement.accept(self);
+            statement.accept(self)?;
         }
+
+        Ok(())
     }
-}
 
-impl super::Visitor for TypeChecker {
-    type Output = Type;
+    /// The warnings found since the last call.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        std::mem::take(&mut self.warnings)
+    }
 
-    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
-        let value_type = assignment.value.accept(self);
-        self.environment
+    pub fn check_expression(&mut self, expression: &Expression) -> Result<Type, Diagnostic> {
+        expression.accept(self)
+    }
+
+    /// Checks a function body in a new environment holding its parameters,
+    /// which could be called with anything.
+    fn function(
+        &mut self,
+        parameters: &[Parameter],
+        body: &Statement,
+        generator: bool,
+    ) -> Result<(), Diagnostic> {
+        let new_environment = Rc::new(RefCell::new(Environment::new()));
+        new_environment
             .borrow_mut()
-            .assign(assignment.identifier.lexeme.clone(), value_type.clone());
+            .enclose(self.environment.clone());
+        self.environment = new_environment.clone();
 
-        value_type
-    }
+        for parameter in parameters {
+            if let Some(default) = &parameter.default {
+                default.accept(self)?;
+            }
+            let parameter_type = match parameter.rest {
+                true => Type::List,
+                false => Type::Unknown,
+            };
+            self.declare_pattern(&parameter.pattern, &parameter_type)?;
+        }
 
-    fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
-        let left_type = equality.left.accept(self);
-        let right_type = equality.right.accept(self);
+        let enclosing_generator = std::mem::replace(&mut self.generator, generator);
+        let result = body.accept(self);
+        self.generator = enclosing_generator;
+
+        self.environment = new_environment.borrow_mut().get_enclosing();
+        result?;
 
+        Ok(())
+    }
+
+    /// The type of `left operator right` for the arithmetic and bitwise
+    /// operators. Numbers are promoted to the wider of the two types, and `/`
+    /// gives a float unless a decimal is involved.
+    fn arithmetic(
+        &self,
+        operator: &Token,
+        left_type: Type,
+        right_type: Type,
+        span: Span,
+    ) -> Result<Type, Diagnostic> {
         if left_type == Type::Unknown || right_type == Type::Unknown {
-            return Type::Boolean;
+            return Ok(Type::Unknown);
+        }
+
+        if operator.token_type == TokenType::Plus
+            && left_type == Type::String
+            && right_type == Type::String
+        {
+            return Ok(Type::String);
+        }
+
+        if matches!(operator.token_type, TokenType::Plus | TokenType::Minus)
+            && left_type.is_number() != right_type.is_number()
+        {
+            return Err(Diagnostic::error("Operands must be the same type").at(span));
         }
 
-        if left_type != right_type {
-            panic!("Operands must be of the same type");
+        if !left_type.is_number() || !right_type.is_number() {
+            return Err(Diagnostic::error("Operands must be numbers").at(span));
         }
 
-        return Type::Boolean;
+        let promoted = left_type
+            .promote(&right_type)
+            .map_err(|message| Diagnostic::error(message).at(span))?;
+
+        match operator.token_type {
+            TokenType::Slash if promoted == Type::Decimal => Ok(Type::Decimal),
+            TokenType::Slash => Ok(Type::Float),
+            TokenType::StarStar if promoted == Type::Decimal && right_type != Type::Int => {
+                Err(Diagnostic::error("Decimals can only be raised to an int").at(span))
+            }
+            // A negative exponent makes a float, and its sign isn't known here.
+            TokenType::StarStar if matches!(promoted, Type::Int | Type::BigInt) => {
+                Ok(Type::Unknown)
+            }
+            TokenType::Ampersand
+            | TokenType::Pipe
+            | TokenType::Caret
+            | TokenType::LessLess
+            | TokenType::GreaterGreater => {
+                if !matches!(promoted, Type::Int | Type::BigInt) {
+                    return Err(Diagnostic::error(format!(
+                        "Operands of '{}' must be integers",
+                        operator.lexeme
+                    ))
+                    .at(span));
+                }
+                Ok(promoted)
+            }
+            _ => Ok(promoted),
+        }
     }
 
-    fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
-        let left_type = comparison.left.accept(self);
-        let right_type = comparison.right.accept(self);
+    fn binary(
+        &mut self,
+        left: &Expression,
+        operator: &Token,
+        right: &Expression,
+        span: Span,
+    ) -> Result<Type, Diagnostic> {
+        let left_type = left.accept(self)?;
+        let right_type = right.accept(self)?;
+
+        self.arithmetic(operator, left_type, right_type, span)
+    }
 
-        if left_type == Type::Unknown || right_type == Type::Unknown {
-            return Type::Boolean;
+    /// Declares the names `pattern` binds from a value of `value_type`, once
+    /// it is known that the pattern could fit such a value.
+    fn declare_pattern(&mut self, pattern: &Pattern, value_type: &Type) -> Result<(), Diagnostic> {
+        if !patterns::can_match(pattern, value_type) {
+            return Err(Diagnostic::error(format!(
+                "Pattern can never match a value of type {}",
+                value_type
+            ))
+            .at(pattern.span()));
         }
 
-        if left_type != Type::Number || right_type != Type::Number {
-            panic!("Operands must be numbers");
+        let types = patterns::binding_types(pattern, value_type);
+        for (binding, binding_type) in pattern.bindings().into_iter().zip(types) {
+            self.environment
+                .borrow_mut()
+                .declare(binding.lexeme.clone(), binding_type);
         }
 
-        return Type::Boolean;
+        Ok(())
     }
 
-    fn visit_term(&mut self, term: &Term) -> Self::Output {
-        let left_type = term.left.accept(self);
-        let right_type = term.right.accept(self);
+    /// The type of a `match` arm's body, once its guard has been checked.
+    fn arm(&mut self, arm: &Arm) -> Result<Type, Diagnostic> {
+        if let Some(guard) = &arm.guard {
+            if guard.accept(self)? != Type::Boolean {
+                return Err(Diagnostic::error("Condition must be a boolean").at(guard.span()));
+            }
+        }
+
+        arm.body.accept(self)
+    }
+
+    fn declared(parameters: &[Parameter]) -> Type {
+        Type::Declared(Box::new(Arity::new(parameters)))
+    }
+
+    /// Functions can be called before their declaration within the same
+    /// block, matching the resolver's hoisting.
+    fn hoist_functions(&mut self, statements: &[Statement]) {
+        for statement in statements {
+            if let Statement::FunctionDeclaration(declaration) = statement {
+                self.environment.borrow_mut().declare(
+                    declaration.identifier.lexeme.clone(),
+                    Self::declared(&declaration.parameters),
+                );
+            }
+        }
+    }
+}
+
+impl super::Visitor for TypeChecker {
+    type Output = Result<Type, Diagnostic>;
+
+    fn visit_assignment(&mut self, assignment: &Assignment) -> Self::Output {
+        let mut value_type = assignment.value.accept(self)?;
+        if let Some(operator) = &assignment.operator {
+            let target_type = self
+                .environment
+                .borrow()
+                .get(assignment.identifier.lexeme.clone())
+                .map_err(|message| Diagnostic::error(message).at(assignment.identifier.span()))?;
+            value_type = self.arithmetic(operator, target_type, value_type, assignment.span)?;
+        }
+        self.environment
+            .borrow_mut()
+            .assign(assignment.identifier.lexeme.clone(), value_type.clone())
+            .map_err(|message| Diagnostic::error(message).at(assignment.identifier.span()))?;
+
+        Ok(value_type)
+    }
+
+    fn visit_equality(&mut self, equality: &Equality) -> Self::Output {
+        let left_type = equality.left.accept(self)?;
+        let right_type = equality.right.accept(self)?;
 
         if left_type == Type::Unknown || right_type == Type::Unknown {
-            return Type::Unknown;
+            return Ok(Type::Boolean);
         }
 
-        if left_type == Type::Number {
-            if right_type == Type::Number {
-                return Type::Number;
-            } else {
-                panic!("Operands must be the same type");
-            }
-        } else if left_type == Type::String {
-            if right_type == Type::String {
-                return Type::String;
-            } else {
-                panic!("Operands must be the same type");
-            }
+        let comparable = left_type.accepts(&right_type)
+            || right_type.accepts(&left_type)
+            || (left_type.is_number() && right_type.is_number());
+        if !comparable {
+            return Err(Diagnostic::error("Operands must be of the same type").at(equality.span));
         }
 
-        Type::Number
+        return Ok(Type::Boolean);
     }
 
-    fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
-        let left_type = factor.left.accept(self);
-        let right_type = factor.right.accept(self);
+    fn visit_comparison(&mut self, comparison: &Comparison) -> Self::Output {
+        let left_type = comparison.left.accept(self)?;
+        let right_type = comparison.right.accept(self)?;
 
         if left_type == Type::Unknown || right_type == Type::Unknown {
-            return Type::Unknown;
+            return Ok(Type::Boolean);
         }
 
-        if left_type != Type::Number || right_type != Type::Number {
-            panic!("Operands must be numbers");
+        if !left_type.is_number() || !right_type.is_number() {
+            return Err(Diagnostic::error("Operands must be numbers").at(comparison.span));
         }
 
-        Type::Number
+        return Ok(Type::Boolean);
+    }
+
+    fn visit_term(&mut self, term: &Term) -> Self::Output {
+        self.binary(&term.left, &term.operator, &term.right, term.span)
+    }
+
+    fn visit_factor(&mut self, factor: &Factor) -> Self::Output {
+        self.binary(&factor.left, &factor.operator, &factor.right, factor.span)
+    }
+
+    fn visit_bitwise(&mut self, bitwise: &Bitwise) -> Self::Output {
+        self.binary(
+            &bitwise.left,
+            &bitwise.operator,
+            &bitwise.right,
+            bitwise.span,
+        )
+    }
+
+    fn visit_power(&mut self, power: &Power) -> Self::Output {
+        self.binary(&power.left, &power.operator, &power.right, power.span)
     }
 
     fn visit_unary(&mut self, unary: &Unary) -> Self::Output {
-        let right_type = unary.right.accept(self);
+        let right_type = unary.right.accept(self)?;
         if unary.operator.token_type == TokenType::Minus {
-            if right_type != Type::Number {
-                panic!("Unary operator - can only be applied to numbers");
+            if !right_type.is_number() && right_type != Type::Unknown {
+                return Err(
+                    Diagnostic::error("Unary operator - can only be applied to numbers")
+                        .at(unary.span),
+                );
             } else {
-                return Type::Number;
+                return Ok(right_type);
             }
         }
 
+        if unary.operator.token_type == TokenType::Tilde {
+            return match right_type {
+                Type::Int | Type::BigInt | Type::Unknown => Ok(right_type),
+                _ => Err(
+                    Diagnostic::error("Unary operator ~ can only be applied to integers")
+                        .at(unary.span),
+                ),
+            };
+        }
+
         if unary.operator.token_type == TokenType::Bang {
             if right_type != Type::Boolean {
-                panic!("Unary operator ! can only be applied to booleans");
+                return Err(
+                    Diagnostic::error("Unary operator ! can only be applied to booleans")
+                        .at(unary.span),
+                );
             } else {
-                return Type::Boolean;
+                return Ok(Type::Boolean);
             }
         }
 
-        panic!("Unknown unary operator");
+        Err(Diagnostic::error("Unknown unary operator").at(unary.span))
     }
 
     fn visit_primary(&mut self, primary: &Primary) -> Self::Output {
         match primary.value.token_type {
-            TokenType::Number => Type::Number,
-            TokenType::Boolean => Type::Boolean,
-            TokenType::String => Type::String,
-            TokenType::Nil => Type::Nil,
+            TokenType::Int => Ok(Type::Int),
+            TokenType::BigInt => Ok(Type::BigInt),
+            TokenType::Float => Ok(Type::Float),
+            TokenType::Decimal => Ok(Type::Decimal),
+            TokenType::Boolean => Ok(Type::Boolean),
+            TokenType::String => Ok(Type::String),
+            TokenType::Nil => Ok(Type::Nil),
             TokenType::Identifier => self
                 .environment
-                .borrow_mut()
+                .borrow()
                 .get(primary.value.lexeme.clone())
-                .clone(),
-            _ => panic!("Unexpected token type"),
+                .map_err(|message| Diagnostic::error(message).at(primary.value.span())),
+            _ => Err(Diagnostic::error("Unexpected token type").at(primary.value.span())),
         }
     }
 
@@ -198,35 +500,28 @@ impl super::Visitor for TypeChecker {
         &mut self,
         expression_statement: &ExpressionStatement,
     ) -> Self::Output {
-        expression_statement.expression.accept(self);
+        expression_statement.expression.accept(self)?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_print_statement(&mut self, print_statement: &PrintStatement) -> Self::Output {
-        print_statement.expression.accept(self);
+        print_statement.expression.accept(self)?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_variable_declaration(
         &mut self,
         variable_declaration: &VariableDeclaration,
     ) -> Self::Output {
-        if let Some(expression) = &variable_declaration.expression {
-            let value_type = expression.accept(self);
-            self.environment
-                .borrow_mut()
-                .declare(variable_declaration.identifier.lexeme.clone(), value_type);
-
-            return Type::Nil;
-        }
-
-        self.environment
-            .borrow_mut()
-            .declare(variable_declaration.identifier.lexeme.clone(), Type::Nil);
+        let value_type = match &variable_declaration.expression {
+            Some(expression) => expression.accept(self)?,
+            None => Type::Nil,
+        };
+        self.declare_pattern(&variable_declaration.pattern, &value_type)?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_block(&mut self, block: &Block) -> Self::Output {
@@ -235,112 +530,165 @@ impl super::Visitor for TypeChecker {
             .borrow_mut()
             .enclose(self.environment.clone());
         self.environment = new_environment.clone();
+        self.hoist_functions(&block.statements);
 
-        for statement in &block.statements {
-            statement.accept(self);
-        }
+        let result = block
+            .statements
+            .iter()
+            .try_for_each(|statement| statement.accept(self).map(|_| ()));
 
         self.environment = new_environment.borrow_mut().get_enclosing();
+        result?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_if_statement(&mut self, if_statemnet: &IfStatement) -> Self::Output {
-        let condition_type = if_statemnet.condition.accept(self);
-        if condition_type != Type::Boolean || condition_type == Type::Unknown {
-            panic!("Condition must be a boolean");
+        let condition_type = if_statemnet.condition.accept(self)?;
+        if condition_type != Type::Boolean {
+            return Err(
+                Diagnostic::error("Condition must be a boolean").at(if_statemnet.condition.span())
+            );
         }
 
-        let then_branch_type = if_statemnet.then_branch.accept(self);
+        let then_branch_type = if_statemnet.then_branch.accept(self)?;
 
         if then_branch_type != Type::Nil {
-            panic!(
+            return Err(Diagnostic::error(format!(
                 "Then branch must not return a value, but got {:?}",
                 then_branch_type
-            );
+            ))
+            .at(if_statemnet.then_branch.span()));
         }
 
         if let Some(else_branch) = &if_statemnet.else_branch {
-            let else_branch_type = else_branch.accept(self);
+            let else_branch_type = else_branch.accept(self)?;
 
             if else_branch_type != Type::Nil {
-                panic!(
+                return Err(Diagnostic::error(format!(
                     "Else branch must not return a value, but got {:?}",
                     else_branch_type
-                );
+                ))
+                .at(else_branch.span()));
             }
         }
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_if_expression(&mut self, if_expression: &IfExpression) -> Self::Output {
-        let condition_type = if_expression.condition.accept(self);
+        let condition_type = if_expression.condition.accept(self)?;
 
-        if condition_type != Type::Boolean || condition_type == Type::Unknown {
-            panic!("Condition must be a boolean");
+        if condition_type != Type::Boolean {
+            return Err(
+                Diagnostic::error("Condition must be a boolean").at(if_expression.condition.span())
+            );
         }
 
-        if_expression.then_branch.accept(self);
-        if_expression.else_branch.accept(self);
+        if_expression.then_branch.accept(self)?;
+        if_expression.else_branch.accept(self)?;
 
-        Type::Unknown
+        Ok(Type::Unknown)
     }
 
     fn visit_and(&mut self, and: &crate::parser::expression::And) -> Self::Output {
-        let left_type = and.left.accept(self);
-        let right_type = and.right.accept(self);
+        let left_type = and.left.accept(self)?;
+        let right_type = and.right.accept(self)?;
 
-        if (left_type != Type::Boolean || left_type == Type::Unknown)
-            || (right_type != Type::Boolean || left_type == Type::Unknown)
-        {
-            panic!("Operands must be booleans");
+        if left_type != Type::Boolean || right_type != Type::Boolean {
+            return Err(Diagnostic::error("Operands must be booleans").at(and.span));
         }
 
-        Type::Boolean
+        Ok(Type::Boolean)
     }
 
     fn visit_or(&mut self, or: &crate::parser::expression::Or) -> Self::Output {
-        let left_type = or.left.accept(self);
-        let right_type = or.right.accept(self);
+        let left_type = or.left.accept(self)?;
+        let right_type = or.right.accept(self)?;
 
-        if (left_type != Type::Boolean || left_type == Type::Unknown)
-            || (right_type != Type::Boolean || right_type == Type::Unknown)
-        {
-            panic!("Operands must be booleans");
+        if left_type != Type::Boolean || right_type != Type::Boolean {
+            return Err(Diagnostic::error("Operands must be booleans").at(or.span));
         }
 
-        Type::Boolean
+        Ok(Type::Boolean)
     }
 
     fn visit_while_statement(&mut self, while_statement: &WhileStatement) -> Self::Output {
-        let condition_type = while_statement.condition.accept(self);
-        if condition_type != Type::Boolean || condition_type == Type::Unknown {
-            panic!("Condition must be a boolean");
+        let condition_type = while_statement.condition.accept(self)?;
+        if condition_type != Type::Boolean {
+            return Err(Diagnostic::error("Condition must be a boolean")
+                .at(while_statement.condition.span()));
         }
 
-        let body_type = while_statement.body.accept(self);
+        let body_type = while_statement.body.accept(self)?;
 
         if body_type != Type::Nil {
-            panic!("Body must not return a value, but got {:?}", body_type);
+            return Err(Diagnostic::error(format!(
+                "Body must not return a value, but got {:?}",
+                body_type
+            ))
+            .at(while_statement.body.span()));
         }
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_call(&mut self, call: &Call) -> Self::Output {
-        let callee_type = call.identifier.accept(self);
-        if callee_type != Type::Function && callee_type != Type::Unknown {
-            panic!("Callee must be a function, but got {:?}", callee_type);
-        }
+        let callee_type = call.identifier.accept(self)?;
 
+        let mut argument_types = vec![];
         for argument in &call.arguments {
-            argument.accept(self);
+            argument_types.push(argument.value.accept(self)?);
         }
+        let names = call.names();
 
-        // TODO: figure out the return type of the function
-
-        Type::Unknown
+        match callee_type {
+            Type::BuiltIn(_) | Type::Generic(_) if !names.is_empty() => {
+                Err(Diagnostic::error("Native functions do not take named arguments").at(call.span))
+            }
+            Type::BuiltIn(signature) => {
+                if argument_types.len() != signature.parameters.len() {
+                    return Err(Diagnostic::error(format!(
+                        "Expected {} arguments but got {}",
+                        signature.parameters.len(),
+                        argument_types.len()
+                    ))
+                    .at(call.span));
+                }
+
+                for (index, (argument_type, parameter_type)) in
+                    argument_types.iter().zip(&signature.parameters).enumerate()
+                {
+                    if !parameter_type.accepts(argument_type) {
+                        return Err(Diagnostic::error(format!(
+                            "Argument {} must be a {}, but got {}",
+                            index + 1,
+                            parameter_type,
+                            argument_type
+                        ))
+                        .at(call.arguments[index].value.span()));
+                    }
+                }
+
+                Ok(signature.returns)
+            }
+            Type::Generic(generic) => generic
+                .returns(&argument_types)
+                .map_err(|message| Diagnostic::error(message).at(call.span)),
+            Type::Declared(arity) => {
+                arity
+                    .arrange(argument_types, &names)
+                    .map_err(|message| Diagnostic::error(message).at(call.span))?;
+                Ok(Type::Unknown)
+            }
+            // TODO: figure out the return type of the function
+            Type::Function | Type::Unknown => Ok(Type::Unknown),
+            _ => Err(Diagnostic::error(format!(
+                "Callee must be a function, but got {:?}",
+                callee_type
+            ))
+            .at(call.identifier.span())),
+        }
     }
 
     fn visit_function_declaration(
@@ -349,75 +697,129 @@ impl super::Visitor for TypeChecker {
     ) -> Self::Output {
         self.environment.borrow_mut().declare(
             function_declaration.identifier.lexeme.clone(),
-            Type::Function,
+            Self::declared(&function_declaration.parameters),
         );
 
-        let new_environment = Rc::new(RefCell::new(Environment::new()));
-        new_environment
-            .borrow_mut()
-            .enclose(self.environment.clone());
-        self.environment = new_environment.clone();
+        self.function(
+            &function_declaration.parameters,
+            &function_declaration.body,
+            function_declaration.generator,
+        )?;
+
+        Ok(Type::Nil)
+    }
+
+    fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
+        match &return_statement.value {
+            Some(value) => value.accept(self),
+            None => Ok(Type::Nil),
+        }
+    }
+
+    fn visit_yield(&mut self, yield_statement: &Yield) -> Self::Output {
+        if !self.generator {
+            return Err(Diagnostic::error(
+                "Can only yield inside a generator function, declared with 'funk*'",
+            )
+            .at(yield_statement.span));
+        }
+
+        yield_statement.value.accept(self)?;
+
+        Ok(Type::Nil)
+    }
+
+    fn visit_throw(&mut self, throw_statement: &Throw) -> Self::Output {
+        let value_type = throw_statement.value.accept(self)?;
+
+        if !matches!(value_type, Type::Error | Type::String | Type::Unknown) {
+            return Err(Diagnostic::error(format!(
+                "Can only throw an error or a string, not {}",
+                value_type
+            ))
+            .at(throw_statement.value.span()));
+        }
+
+        Ok(Type::Nil)
+    }
+
+    fn visit_try(&mut self, try_statement: &Try) -> Self::Output {
+        try_statement.body.accept(self)?;
+
+        if let Some(catch) = &try_statement.catch {
+            let new_environment = Rc::new(RefCell::new(Environment::new()));
+            new_environment
+                .borrow_mut()
+                .enclose(self.environment.clone());
+            self.environment = new_environment.clone();
 
-        for parameter in &function_declaration.parameters {
             self.environment
                 .borrow_mut()
-                .declare(parameter.lexeme.clone(), Type::Unknown);
+                .declare(catch.identifier.lexeme.clone(), Type::Error);
+
+            let result = catch.body.accept(self);
+
+            self.environment = new_environment.borrow_mut().get_enclosing();
+            result?;
         }
 
-        function_declaration.body.accept(self);
+        if let Some(finally) = &try_statement.finally {
+            finally.accept(self)?;
+        }
 
-        self.environment = new_environment.borrow_mut().get_enclosing();
+        Ok(Type::Nil)
+    }
 
-        Type::Nil
+    fn visit_break(&mut self, _break_statement: &Break) -> Self::Output {
+        Ok(Type::Nil)
     }
 
-    fn visit_return(&mut self, return_statement: &Return) -> Self::Output {
-        match &return_statement.value {
-            Some(value) => value.accept(self),
-            None => Type::Nil,
-        }
+    fn visit_continue(&mut self, _continue_statement: &Continue) -> Self::Output {
+        Ok(Type::Nil)
     }
 
     fn visit_with_statement(&mut self, with_statement: &With) -> Self::Output {
+        if with_statement.identifier.token_type != TokenType::Identifier {
+            return Err(
+                Diagnostic::error("Must use an identifier in the 'with' statement")
+                    .at(with_statement.identifier.span()),
+            );
+        }
+
+        let value_type = with_statement.value.accept(self)?;
+
         let new_environment = Rc::new(RefCell::new(Environment::new()));
         new_environment
             .borrow_mut()
             .enclose(self.environment.clone());
         self.environment = new_environment.clone();
 
-        if with_statement.identifier.token_type != TokenType::Identifier {
-            panic!("Must use an identifier in the 'with' statement");
-        }
-
-        let value_type = with_statement.value.accept(self);
         self.environment
             .borrow_mut()
             .declare(with_statement.identifier.lexeme.clone(), value_type);
 
-        with_statement.body.accept(self);
+        let result = with_statement.body.accept(self);
 
         self.environment = new_environment.borrow_mut().get_enclosing();
+        result?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_list(&mut self, list: &List) -> Self::Output {
         for value in list.values.iter() {
-            value.accept(self);
+            value.accept(self)?;
         }
 
-        Type::List
+        Ok(Type::List)
     }
 
     fn visit_for_statement(&mut self, for_statement: &For) -> Self::Output {
-        if for_statement.identifier.token_type != TokenType::Identifier {
-            panic!("Must use an identifier in the 'with' statement");
-        }
-
-        let iter_type = for_statement.iter.accept(self);
+        let iter_type = for_statement.iter.accept(self)?;
 
         if iter_type != Type::Unknown {
-            panic!("Must use an Iter in the 'for' statement");
+            return Err(Diagnostic::error("Must use an Iter in the 'for' statement")
+                .at(for_statement.iter.span()));
         }
 
         let new_environment = Rc::new(RefCell::new(Environment::new()));
@@ -426,29 +828,34 @@ impl super::Visitor for TypeChecker {
             .enclose(self.environment.clone());
         self.environment = new_environment.clone();
 
-        self.environment
-            .borrow_mut()
-            .declare(for_statement.identifier.lexeme.clone(), iter_type);
-        for_statement.body.accept(self);
+        let result = self
+            .declare_pattern(&for_statement.pattern, &iter_type)
+            .and_then(|_| for_statement.body.accept(self));
 
         self.environment = new_environment.borrow_mut().get_enclosing();
+        result?;
 
-        Type::Nil
+        Ok(Type::Nil)
     }
 
     fn visit_index(&mut self, index: &Index) -> Self::Output {
-        let list_type = index.list.accept(self);
+        let list_type = index.list.accept(self)?;
 
         if list_type != Type::List && list_type != Type::String && list_type != Type::Unknown {
-            panic!("Must index into either list or string")
+            return Err(
+                Diagnostic::error("Must index into either list or string").at(index.list.span())
+            );
         }
 
-        let expression_type = index.expression.accept(self);
-        if expression_type != Type::Number && expression_type != Type::Unknown {
-            panic!("Must index into list or string with a number")
+        let expression_type = index.expression.accept(self)?;
+        if expression_type != Type::Int && expression_type != Type::Unknown {
+            return Err(
+                Diagnostic::error("Must use integer to index into list or string")
+                    .at(index.expression.span()),
+            );
         }
 
-        Type::Unknown
+        Ok(Type::Unknown)
     }
 
     fn visit_record(&mut self, record: &Record) -> Self::Output {
@@ -456,28 +863,42 @@ impl super::Visitor for TypeChecker {
             let key_type = &key.token_type;
 
             if *key_type != TokenType::Identifier {
-                panic!("key of record must be an identifier");
+                return Err(Diagnostic::error("key of record must be an identifier").at(key.span()));
             }
 
-            value.accept(self);
+            value.accept(self)?;
         }
 
-        Type::Record
+        Ok(Type::Record)
     }
 
     fn visit_map_index(&mut self, map_index: &MapIndex) -> Self::Output {
-        let map_type = map_index.map.accept(self);
+        let map_type = map_index.map.accept(self)?;
         let key = &map_index.key;
 
         if key.token_type != TokenType::Identifier {
-            panic!("Must index into record with an identifer");
+            return Err(
+                Diagnostic::error("Must index into record with an identifer").at(key.span()),
+            );
+        }
+
+        if map_type == Type::Error {
+            return match key.lexeme.as_str() {
+                "kind" | "message" => Ok(Type::String),
+                "line" | "column" => Ok(Type::Int),
+                _ => Err(Diagnostic::error(format!(
+                    "Errors have no field {}, only kind, message, line and column",
+                    key.lexeme
+                ))
+                .at(key.span())),
+            };
         }
 
         if map_type != Type::Record && map_type != Type::Unknown {
-            panic!("Must index into map");
+            return Err(Diagnostic::error("Must index into map").at(map_index.map.span()));
         }
 
-        Type::Unknown
+        Ok(Type::Unknown)
     }
 
     fn visit_map_index_assignment(
@@ -487,30 +908,114 @@ impl super::Visitor for TypeChecker {
         let key = &map_index_assignment.key;
 
         if key.token_type != TokenType::Identifier {
-            panic!("Must index into record with an identifer");
+            return Err(
+                Diagnostic::error("Must index into record with an identifer").at(key.span()),
+            );
         }
 
-        let map_type = map_index_assignment.map.accept(self);
+        let map_type = map_index_assignment.map.accept(self)?;
 
         if map_type != Type::Record && map_type != Type::Unknown {
-            panic!("Must index into map");
+            return Err(
+                Diagnostic::error("Must index into map").at(map_index_assignment.map.span())
+            );
         }
 
-        map_index_assignment.value.accept(self)
+        let value_type = map_index_assignment.value.accept(self)?;
+        // Fields have no static type, so neither does updating one.
+        if map_index_assignment.operator.is_some() {
+            return Ok(Type::Unknown);
+        }
+
+        Ok(value_type)
     }
 
     fn visit_index_assignment(&mut self, index_assignment: &IndexAssignment) -> Self::Output {
-        let list_type = index_assignment.list.accept(self);
-        let expression_type = index_assignment.expression.accept(self);
+        let list_type = index_assignment.list.accept(self)?;
+        let expression_type = index_assignment.expression.accept(self)?;
 
         if list_type != Type::Unknown && list_type != Type::List {
-            panic!("Must index into list or string");
+            return Err(Diagnostic::error("Must index into list or string")
+                .at(index_assignment.list.span()));
+        }
+
+        if expression_type != Type::Int && expression_type != Type::Unknown {
+            return Err(
+                Diagnostic::error("Must use integer to index into list or string")
+                    .at(index_assignment.expression.span()),
+            );
         }
 
-        if expression_type != Type::Number && expression_type != Type::Unknown {
-            panic!("Must use number to index into list or string");
+        let value_type = index_assignment.value.accept(self)?;
+        if index_assignment.operator.is_some() {
+            return Ok(Type::Unknown);
         }
 
-        return index_assignment.value.accept(self);
+        Ok(value_type)
+    }
+
+    fn visit_lambda(&mut self, lambda: &Lambda) -> Self::Output {
+        self.function(&lambda.parameters, &lambda.body, lambda.generator)?;
+
+        Ok(Self::declared(&lambda.parameters))
+    }
+
+    fn visit_interpolation(&mut self, interpolation: &Interpolation) -> Self::Output {
+        for expression in interpolation.expressions.iter() {
+            expression.accept(self)?;
+        }
+
+        Ok(Type::String)
+    }
+
+    fn visit_propagate(&mut self, propagate: &Propagate) -> Self::Output {
+        let expression_type = propagate.expression.accept(self)?;
+
+        expression_type.unwrapped().map_err(|_| {
+            Diagnostic::error(format!(
+                "Can only use '?' on a result or an option, not {}",
+                expression_type
+            ))
+            .at(propagate.expression.span())
+        })
+    }
+
+    fn visit_match(&mut self, match_expression: &Match) -> Self::Output {
+        let value_type = match_expression.value.accept(self)?;
+
+        let mut arm_types = vec![];
+        for arm in &match_expression.arms {
+            let new_environment = Rc::new(RefCell::new(Environment::new()));
+            new_environment
+                .borrow_mut()
+                .enclose(self.environment.clone());
+            self.environment = new_environment.clone();
+
+            let result = self
+                .declare_pattern(&arm.pattern, &value_type)
+                .and_then(|_| self.arm(arm));
+
+            self.environment = new_environment.borrow_mut().get_enclosing();
+            arm_types.push(result?);
+        }
+
+        let unguarded: Vec<&Pattern> = match_expression
+            .arms
+            .iter()
+            .filter(|arm| arm.guard.is_none())
+            .map(|arm| &arm.pattern)
+            .collect();
+        if let Some(missing) = patterns::uncovered(&unguarded, &value_type) {
+            self.warnings.push(
+                Diagnostic::warning(format!("Match does not cover {}", missing))
+                    .at(match_expression.span),
+            );
+        }
+
+        let first = arm_types[0].clone();
+        match arm_types.iter().all(|arm_type| *arm_type == first) {
+            true => Ok(first),
+            false => Ok(Type::Unknown),
+        }
     }
 }
@@ -0,0 +1,145 @@
+//! What the type checker knows about patterns: the types of the names
+//! they bind, which types they can match, and which values a `match` leaves
+//! uncovered.
+
+use crate::{parser::pattern::Pattern, token::TokenType};
+
+use super::types::Type;
+
+/// The types of the names `pattern` binds against a value of `value_type`,
+/// in the same order as `Pattern::bindings`.
+pub fn binding_types(pattern: &Pattern, value_type: &Type) -> Vec<Type> {
+    let mut types = vec![];
+    collect(pattern, value_type, &mut types);
+    types
+}
+
+fn collect(pattern: &Pattern, value_type: &Type, types: &mut Vec<Type>) {
+    match pattern {
+        Pattern::Binding(_) => types.push(value_type.clone()),
+        Pattern::List { items, rest, .. } => {
+            for item in items {
+                collect(item, &Type::Unknown, types);
+            }
+            if rest.as_ref().is_some_and(|rest| rest.name.is_some()) {
+                types.push(Type::List);
+            }
+        }
+        Pattern::Record { fields, .. } => {
+            for (_, field) in fields {
+                collect(field, &Type::Unknown, types);
+            }
+        }
+        Pattern::Variant { name, pattern, .. } => {
+            let inner = match (name.lexeme.as_str(), value_type) {
+                ("ok", Type::Result(ok, _)) => *ok.clone(),
+                ("err", Type::Result(_, err)) => *err.clone(),
+                ("some", Type::Option(some)) => *some.clone(),
+                _ => Type::Unknown,
+            };
+            collect(pattern, &inner, types);
+        }
+        Pattern::Wildcard(_)
+        | Pattern::Literal { .. }
+        | Pattern::None(_)
+        | Pattern::Either { .. } => {}
+    }
+}
+
+/// Whether `pattern` could accept some value of `value_type`.
+pub fn can_match(pattern: &Pattern, value_type: &Type) -> bool {
+    if *value_type == Type::Unknown {
+        return true;
+    }
+
+    match pattern {
+        Pattern::Wildcard(_) | Pattern::Binding(_) => true,
+        Pattern::Literal { value, .. } => match value.token_type {
+            TokenType::Nil => *value_type == Type::Nil,
+            TokenType::Boolean => *value_type == Type::Boolean,
+            TokenType::String => *value_type == Type::String,
+            _ => value_type.is_number(),
+        },
+        Pattern::List { .. } => *value_type == Type::List,
+        Pattern::Record { .. } => matches!(value_type, Type::Record | Type::Error),
+        Pattern::Variant { name, .. } => match name.lexeme.as_str() {
+            "some" => matches!(value_type, Type::Option(_)),
+            _ => matches!(value_type, Type::Result(_, _)),
+        },
+        Pattern::None(_) => matches!(value_type, Type::Option(_)),
+        Pattern::Either { alternatives, .. } => alternatives
+            .iter()
+            .any(|alternative| can_match(alternative, value_type)),
+    }
+}
+
+/// Describes the values of `value_type` that none of `patterns` accept, or
+/// `None` if they cover every value or the type is unknown.
+pub fn uncovered(patterns: &[&Pattern], value_type: &Type) -> Option<String> {
+    let mut alternatives = vec![];
+    for pattern in patterns {
+        flatten(pattern, &mut alternatives);
+    }
+
+    if alternatives.iter().any(|pattern| pattern.is_irrefutable()) {
+        return None;
+    }
+
+    let covers = |covered: fn(&Pattern) -> bool| alternatives.iter().any(|p| covered(p));
+    let is_true = |pattern: &Pattern| is_literal(pattern, "true");
+    let is_false = |pattern: &Pattern| is_literal(pattern, "false");
+    let is_nil = |pattern: &Pattern| is_literal(pattern, "nil");
+    let is_ok = |pattern: &Pattern| is_variant(pattern, "ok");
+    let is_err = |pattern: &Pattern| is_variant(pattern, "err");
+    let is_some = |pattern: &Pattern| is_variant(pattern, "some");
+    let is_none = |pattern: &Pattern| matches!(pattern, Pattern::None(_));
+
+    let needed: Vec<(&str, bool)> = match value_type {
+        Type::Unknown => return None,
+        Type::Boolean => vec![("true", covers(is_true)), ("false", covers(is_false))],
+        Type::Nil => vec![("nil", covers(is_nil))],
+        Type::Result(_, _) => vec![("ok(_)", covers(is_ok)), ("err(_)", covers(is_err))],
+        Type::Option(_) => vec![("some(_)", covers(is_some)), ("none", covers(is_none))],
+        _ => return Some(format!("every {}, add a '_' arm", value_type)),
+    };
+
+    let missing: Vec<&str> = needed
+        .into_iter()
+        .filter(|(_, covered)| !covered)
+        .map(|(name, _)| name)
+        .collect();
+    match missing.is_empty() {
+        true => None,
+        false => Some(missing.join(" or ")),
+    }
+}
+
+fn is_literal(pattern: &Pattern, lexeme: &str) -> bool {
+    matches!(pattern, Pattern::Literal { value, .. } if value.lexeme == lexeme)
+}
+
+/// Whether `pattern` is `name(p)` with a `p` that accepts anything.
+fn is_variant(pattern: &Pattern, name: &str) -> bool {
+    match pattern {
+        Pattern::Variant {
+            name: variant,
+            pattern,
+            ..
+        } => variant.lexeme == name && pattern.is_irrefutable(),
+        _ => false,
+    }
+}
+
+fn flatten<'a>(pattern: &'a Pattern, alternatives: &mut Vec<&'a Pattern>) {
+    match pattern {
+        Pattern::Either {
+            alternatives: inner,
+            ..
+        } => {
+            for alternative in inner {
+                flatten(alternative, alternatives);
+            }
+        }
+        pattern => alternatives.push(pattern),
+    }
+}
@@ -1,6 +1,11 @@
+use crate::{arity::Arity, visitors::interpreter::value::Value};
+
 #[derive(Clone, PartialEq, Debug)]
 pub enum Type {
-    Number,
+    Int,
+    BigInt,
+    Float,
+    Decimal,
     Boolean,
     String,
     Function,
@@ -8,4 +13,166 @@ pub enum Type {
     Nil,
     List,
     Record,
+    Error,
+    /// `ok` and `err` values, with the types of each.
+    Result(Box<Type>, Box<Type>),
+    Option(Box<Type>),
+    /// A native function whose parameter and return types are known.
+    BuiltIn(Box<Signature>),
+    /// A native function whose return type depends on its arguments.
+    Generic(Generic),
+    /// A function declared in the program, whose parameters are known.
+    Declared(Box<Arity>),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Generic {
+    Ok,
+    Err,
+    Some,
+    Unwrap,
+    UnwrapOr,
+}
+
+impl Generic {
+    /// The type a call with arguments of `arguments` returns.
+    pub fn returns(&self, arguments: &[Type]) -> Result<Type, String> {
+        let expected = match self {
+            Generic::UnwrapOr => 2,
+            _ => 1,
+        };
+        if arguments.len() != expected {
+            return Err(format!(
+                "Expected {} arguments but got {}",
+                expected,
+                arguments.len()
+            ));
+        }
+
+        let argument = Box::new(arguments[0].clone());
+        match self {
+            Generic::Ok => Ok(Type::Result(argument, Box::new(Type::Unknown))),
+            Generic::Err => Ok(Type::Result(Box::new(Type::Unknown), argument)),
+            Generic::Some => Ok(Type::Option(argument)),
+            Generic::Unwrap => argument.unwrapped(),
+            Generic::UnwrapOr => match argument.unwrapped()? {
+                Type::Unknown => Ok(arguments[1].clone()),
+                unwrapped => Ok(unwrapped),
+            },
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Signature {
+    pub parameters: Vec<Type>,
+    pub returns: Type,
+}
+
+impl std::fmt::Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::BigInt => write!(f, "bigint"),
+            Type::Float => write!(f, "float"),
+            Type::Decimal => write!(f, "decimal"),
+            Type::Boolean => write!(f, "boolean"),
+            Type::String => write!(f, "string"),
+            Type::Function | Type::BuiltIn(_) | Type::Generic(_) | Type::Declared(_) => {
+                write!(f, "function")
+            }
+            Type::Unknown => write!(f, "unknown"),
+            Type::Nil => write!(f, "nil"),
+            Type::List => write!(f, "list"),
+            Type::Record => write!(f, "record"),
+            Type::Error => write!(f, "error"),
+            Type::Result(ok, err) => write!(f, "result<{}, {}>", ok, err),
+            Type::Option(some) => write!(f, "option<{}>", some),
+        }
+    }
+}
+
+impl Type {
+    /// The static type of a value handed in from outside the program.
+    pub fn of(value: &Value) -> Type {
+        match value {
+            Value::Int(_) => Type::Int,
+            Value::BigInt(_) => Type::BigInt,
+            Value::Float(_) => Type::Float,
+            Value::Decimal(_) => Type::Decimal,
+            Value::Boolean(_) => Type::Boolean,
+            Value::String(_) => Type::String,
+            Value::BuiltIn(builtin) => match &builtin.signature {
+                Some(signature) => Type::BuiltIn(Box::new(signature.clone())),
+                None => Type::Function,
+            },
+            Value::Function(function) => Type::Declared(Box::new(function.arity.clone())),
+            Value::Closure(closure) => Type::Declared(Box::new(
+                closure.program.functions[closure.function].arity.clone(),
+            )),
+            Value::List(_) => Type::List,
+            Value::Record(_) => Type::Record,
+            Value::Error(_) => Type::Error,
+            Value::Result(result) => match &**result {
+                Ok(value) => Type::Result(Box::new(Type::of(value)), Box::new(Type::Unknown)),
+                Err(error) => Type::Result(Box::new(Type::Unknown), Box::new(Type::of(error))),
+            },
+            Value::Option(Some(value)) => Type::Option(Box::new(Type::of(value))),
+            Value::Option(None) => Type::Option(Box::new(Type::Unknown)),
+            Value::Iter(_) | Value::Uninitialized(_) => Type::Unknown,
+            Value::Nil => Type::Nil,
+        }
+    }
+
+    pub fn is_number(&self) -> bool {
+        matches!(self, Type::Int | Type::BigInt | Type::Float | Type::Decimal)
+    }
+
+    /// The type of the value inside a result or option, which is what `?`
+    /// and `unwrap` give.
+    pub fn unwrapped(&self) -> Result<Type, String> {
+        match self {
+            Type::Result(ok, _) => Ok(*ok.clone()),
+            Type::Option(some) => Ok(*some.clone()),
+            Type::Unknown => Ok(Type::Unknown),
+            _ => Err(format!("Expected a result or an option, got {}", self)),
+        }
+    }
+
+    /// Whether a value of type `other` can be passed where this type is
+    /// expected. Ints are promoted to the wider number types, and the types
+    /// inside results and options are compared the same way.
+    pub fn accepts(&self, other: &Type) -> bool {
+        match (self, other) {
+            (Type::Result(ok, err), Type::Result(other_ok, other_err)) => {
+                return ok.accepts(other_ok) && err.accepts(other_err)
+            }
+            (Type::Option(some), Type::Option(other_some)) => return some.accepts(other_some),
+            (Type::Function | Type::Declared(_), Type::Function | Type::Declared(_)) => {
+                return true
+            }
+            _ => {}
+        }
+
+        self == other
+            || *self == Type::Unknown
+            || *other == Type::Unknown
+            || (*self == Type::Float && matches!(other, Type::Int | Type::BigInt))
+            || (*self == Type::BigInt && *other == Type::Int)
+            || (*self == Type::Decimal && matches!(other, Type::Int | Type::BigInt))
+    }
+
+    /// The type two numbers are promoted to by an arithmetic operator, like
+    /// the interpreter does at run time.
+    pub fn promote(&self, other: &Type) -> Result<Type, String> {
+        match (self, other) {
+            (Type::Decimal, Type::Float) | (Type::Float, Type::Decimal) => {
+                Err("Cannot mix decimal and float".to_string())
+            }
+            (Type::Decimal, _) | (_, Type::Decimal) => Ok(Type::Decimal),
+            (Type::Float, _) | (_, Type::Float) => Ok(Type::Float),
+            (Type::BigInt, _) | (_, Type::BigInt) => Ok(Type::BigInt),
+            _ => Ok(Type::Int),
+        }
+    }
 }
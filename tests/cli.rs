@@ -0,0 +1,176 @@
+//! Checks the command line interface: subcommands, inline and stdin input,
+//! script arguments, exit codes and the repl.
+
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+fn jive(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jive"))
+        .args(args)
+        .output()
+        .expect("Could not run jive")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).to_string()
+}
+
+#[test]
+fn runs_inline_code_with_script_args() {
+    for backend in ["interpreter", "vm"] {
+        let output = jive(&[
+            "run",
+            "--backend",
+            backend,
+            "-e",
+            "print len(args); print args[1];",
+            "first",
+            "second",
+        ]);
+
+        assert!(output.status.success(), "{}: run failed", backend);
+        assert_eq!(stdout(&output), "2\nsecond\n", "{}", backend);
+    }
+}
+
+#[test]
+fn reads_the_program_from_stdin() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jive"))
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Could not run jive");
+    child
+        .stdin
+        .take()
+        .expect("Child has a stdin")
+        .write_all(b"print 1 + 2;")
+        .expect("Could not write to jive");
+
+    let output = child.wait_with_output().expect("Could not wait for jive");
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn exit_codes_name_the_kind_of_error() {
+    let cases = [
+        (vec!["-e", "print 1;"], 0),
+        (vec!["-e", "print 1 +;"], 65),
+        (vec!["-e", "print 1 + \"a\";"], 67),
+        (vec!["-e", "print missing;"], 67),
+        (vec!["-e", "print [1][3];"], 70),
+        (vec!["missing.jive"], 66),
+        (vec!["run", "--unknown", "-e", "print 1;"], 64),
+    ];
+
+    for (args, code) in cases {
+        let output = jive(&args);
+        assert_eq!(output.status.code(), Some(code), "jive {}", args.join(" "));
+    }
+}
+
+#[test]
+fn no_typecheck_skips_the_type_checker() {
+    let program = "print 1 == \"one\";";
+
+    assert_eq!(jive(&["check", "-e", program]).status.code(), Some(67));
+
+    let output = jive(&["run", "--no-typecheck", "-e", program]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "false\n");
+}
+
+#[test]
+fn check_prints_nothing() {
+    let output = jive(&["check", "-e", "print 1;"]);
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn fmt_prints_canonical_source() {
+    let output = jive(&[
+        "fmt",
+        "-e",
+        "funk f(a,b){return (a+b)*2;} # twice\nprint f(1,2);",
+    ]);
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "funk f(a, b) {\n    return (a + b) * 2;\n} # twice\nprint f(1, 2);\n"
+    );
+
+    let increments = jive(&["fmt", "-e", "make i = 0; i++; print - -i;"]);
+    assert_eq!(stdout(&increments), "make i = 0;\ni++;\nprint - -i;\n");
+
+    let unformatted = jive(&["fmt", "--check", "-e", "print  1;"]);
+    assert_eq!(unformatted.status.code(), Some(1));
+
+    let formatted = jive(&["fmt", "--check", "-e", "print 1;\n"]);
+    assert!(formatted.status.success());
+}
+
+#[test]
+fn warnings_go_to_stderr_without_failing() {
+    for backend in ["interpreter", "vm"] {
+        let output = jive(&[
+            "run",
+            "--backend",
+            backend,
+            "-e",
+            "print match 1 > 2 { false => \"no\", };",
+        ]);
+
+        assert!(output.status.success(), "{}: run failed", backend);
+        assert_eq!(stdout(&output), "no\n", "{}", backend);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.starts_with("warning: Match does not cover true"),
+            "{}: {}",
+            backend,
+            stderr
+        );
+    }
+}
+
+#[test]
+fn repl_state_survives_errors_until_reset() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jive"))
+        .env("HOME", env!("CARGO_TARGET_TMPDIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Could not run jive");
+    child
+        .stdin
+        .take()
+        .expect("Child has a stdin")
+        .write_all(
+            b"make x = 1;\nfunk add(a,\n  b) {\n  return a + b;\n}\nprint missing;\n\
+              print add(x, 2);\n:ast 1 + 2\n:reset\nprint x;\n",
+        )
+        .expect("Could not write to jive");
+
+    let output = child.wait_with_output().expect("Could not wait for jive");
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("> 3\n> (1 + 2)\n"));
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let errors: Vec<&str> = stderr
+        .lines()
+        .filter(|line| line.starts_with("error:"))
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "error: Undefined variable missing",
+            "error: Undefined variable x"
+        ]
+    );
+}
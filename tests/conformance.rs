@@ -0,0 +1,120 @@
+//! Runs every program in `tests/programs` on both backends and checks that
+//! they agree with each other and with the program's `.out` file, both from
+//! source and after a round trip through a compiled `.jvc` module.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Output},
+};
+
+fn programs() -> Vec<PathBuf> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/programs");
+    let mut programs: Vec<PathBuf> = fs::read_dir(directory)
+        .expect("Could not read tests/programs")
+        .map(|entry| entry.expect("Could not read directory entry").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "jive")
+        })
+        .collect();
+    programs.sort();
+    programs
+}
+
+fn expected_output(program: &Path) -> String {
+    fs::read_to_string(program.with_extension("out"))
+        .unwrap_or_else(|_| panic!("{}: missing .out file", program.display()))
+}
+
+fn run(backend: &str, program: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_jive"))
+        .args(["run", "--backend", backend])
+        .arg(program)
+        .output()
+        .expect("Could not run jive")
+}
+
+#[test]
+fn backends_agree() {
+    let programs = programs();
+    assert!(!programs.is_empty(), "No programs found in tests/programs");
+
+    for program in programs {
+        let interpreter = run("interpreter", &program);
+        let vm = run("vm", &program);
+        let name = program.display();
+
+        assert_eq!(
+            interpreter.status.code(),
+            vm.status.code(),
+            "{}: backends exited differently",
+            name
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&interpreter.stdout),
+            String::from_utf8_lossy(&vm.stdout),
+            "{}: backends printed different output",
+            name
+        );
+
+        assert_eq!(
+            String::from_utf8_lossy(&vm.stdout),
+            expected_output(&program),
+            "{}: unexpected output",
+            name
+        );
+    }
+}
+
+#[test]
+fn compiled_modules_agree() {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR")).join("modules");
+    fs::create_dir_all(&directory).expect("Could not create module directory");
+
+    for program in programs() {
+        let name = program.display();
+        let module = directory
+            .join(program.file_name().expect("Program has a file name"))
+            .with_extension("jvc");
+
+        let compiled = Command::new(env!("CARGO_BIN_EXE_jive"))
+            .arg("compile")
+            .arg(&program)
+            .arg("-o")
+            .arg(&module)
+            .output()
+            .expect("Could not run jive");
+        assert!(compiled.status.success(), "{}: failed to compile", name);
+
+        let disassembled = Command::new(env!("CARGO_BIN_EXE_jive"))
+            .arg("disassemble")
+            .arg(&module)
+            .output()
+            .expect("Could not run jive");
+        assert!(
+            String::from_utf8_lossy(&disassembled.stdout).starts_with("== script"),
+            "{}: failed to disassemble",
+            name
+        );
+
+        let source = run("vm", &program);
+        let loaded = Command::new(env!("CARGO_BIN_EXE_jive"))
+            .arg(&module)
+            .output()
+            .expect("Could not run jive");
+
+        assert_eq!(
+            source.status.code(),
+            loaded.status.code(),
+            "{}: module exited differently",
+            name
+        );
+        assert_eq!(
+            String::from_utf8_lossy(&loaded.stdout),
+            expected_output(&program),
+            "{}: module printed different output",
+            name
+        );
+    }
+}
@@ -0,0 +1,86 @@
+//! Checks how diagnostics point back at the source that caused them.
+
+use jive::{diagnostic::Span, Diagnostic, Engine};
+
+#[test]
+fn render_underlines_the_span() {
+    let source = "make x = 1;\nprint x + \"a\";";
+    let diagnostic = Diagnostic::error("Operands must be the same type")
+        .at(Span::new(18, 25, 2, 7))
+        .in_file("main.jive");
+
+    assert_eq!(
+        diagnostic.render(source),
+        "error: Operands must be the same type\n \
+         --> main.jive:2:7\n  \
+         |\n\
+         2 | print x + \"a\";\n  \
+         |       ^^^^^^^\n"
+    );
+}
+
+#[test]
+fn render_without_a_location_names_the_file() {
+    let diagnostic = Diagnostic::error("Could not read file");
+    assert_eq!(
+        diagnostic.render(""),
+        "error: Could not read file\n --> <input>\n"
+    );
+
+    let warning = Diagnostic::warning("Unused").at(Span::new(0, 0, 12, 0));
+    assert_eq!(
+        warning.render("print 1;"),
+        "warning: Unused\n --> <input>:12\n"
+    );
+}
+
+#[test]
+fn render_keeps_tabs_in_the_padding() {
+    let source = "\tmake @ = 1;";
+    let diagnostic = Diagnostic::error("Unexpected character: @").at(Span::new(6, 7, 1, 7));
+
+    assert!(diagnostic
+        .render(source)
+        .ends_with("1 | \tmake @ = 1;\n  | \t     ^\n"));
+}
+
+#[test]
+fn errors_from_each_phase_carry_a_location() {
+    let mut engine = Engine::new();
+    let location = |engine: &mut Engine, source: &str| {
+        let diagnostic = engine.run(source).unwrap_err().diagnostics[0].clone();
+        (diagnostic.line, diagnostic.column)
+    };
+
+    assert_eq!(location(&mut engine, "make @ = 1;"), (1, 6));
+    assert_eq!(location(&mut engine, "print 1\nprint 2;"), (1, 7));
+    assert_eq!(location(&mut engine, "print 1 + \"a\";"), (1, 7));
+    assert_eq!(location(&mut engine, "\n\nprint [1][5];"), (3, 11));
+}
+
+#[test]
+fn one_parse_reports_every_syntax_error() {
+    let locations = |source: &str| {
+        jive::parse(source)
+            .unwrap_err()
+            .diagnostics
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.column))
+            .collect::<Vec<(u32, u32)>>()
+    };
+
+    assert_eq!(
+        locations("make = 1;\nprint (;\nprint 2;\nmake x 3;"),
+        [(1, 6), (2, 8), (4, 8)]
+    );
+    // A `}` belonging to the broken declaration doesn't cause a second error.
+    assert_eq!(locations("make r = {a: 1};\nprint (;"), [(1, 15), (2, 8)]);
+    assert_eq!(
+        locations("funk f(a = 1, b) { print [a, b]; }\nprint (;"),
+        [(1, 15), (2, 8)]
+    );
+    assert_eq!(
+        locations("{\n  make r = {a: 1};\n}\nprint 1 +;"),
+        [(2, 17), (4, 10)]
+    );
+}
@@ -0,0 +1,466 @@
+//! Embeds jive through the `Engine` facade.
+
+use std::{cell::Cell, collections::HashMap, rc::Rc, thread};
+
+use jive::{Decimal, Engine, ErrorKind, FromValue, Value};
+
+/// Runs `source`, which must fail with `kind`, returning the first message.
+fn error_message(engine: &mut Engine, source: &str, kind: ErrorKind) -> String {
+    let error = engine.run(source).unwrap_err();
+    assert_eq!(error.kind, kind, "{}", source);
+    error.diagnostics[0].message.clone()
+}
+
+#[test]
+fn globals_persist_between_runs() {
+    let mut engine = Engine::new();
+    engine.set_global("start", Value::Int(40));
+
+    engine.run("make total = start;").unwrap();
+    engine.run("total = total + 2;").unwrap();
+
+    assert_eq!(engine.get_global("total"), Some(Value::Int(42)));
+    assert_eq!(engine.get_global("missing"), None);
+}
+
+#[test]
+fn eval_returns_the_trailing_expression() {
+    let mut engine = Engine::new();
+
+    assert_eq!(engine.eval("make x = 3; x * x").unwrap(), Value::Int(9));
+    assert_eq!(engine.eval("make y = 1;").unwrap(), Value::Nil);
+    // Expressions ending in `}` still need the `;` added.
+    assert_eq!(
+        engine.eval("match x { 3 => 1, _ => 2, }").unwrap(),
+        Value::Int(1)
+    );
+    assert!(engine.eval("funk (a) { return a; }").is_ok());
+}
+
+#[test]
+fn host_functions_convert_their_arguments() {
+    let mut engine = Engine::new();
+    engine.register("double", |number: f64| number * 2.0);
+    engine.register("total", |numbers: Vec<f64>| numbers.iter().sum::<f64>());
+    engine.register("greeting", |name: Option<String>| {
+        format!("hello {}", name.unwrap_or("you".to_string()))
+    });
+    engine.register("keys", |record: HashMap<String, bool>| {
+        let mut keys: Vec<String> = record.into_keys().collect();
+        keys.sort();
+        keys
+    });
+
+    assert_eq!(engine.eval("double(21)").unwrap(), Value::Float(42.0));
+    assert_eq!(engine.eval("total([1, 2, 3])").unwrap(), Value::Float(6.0));
+    assert_eq!(
+        engine
+            .eval("greeting(nil) + \", \" + greeting(\"jive\")")
+            .unwrap(),
+        Value::String(Box::new("hello you, hello jive".to_string()))
+    );
+    let keys = engine.eval("keys({b: true, a: false,})").unwrap();
+    assert_eq!(
+        Vec::<String>::from_value(&keys).unwrap(),
+        vec!["a".to_string(), "b".to_string()]
+    );
+}
+
+#[test]
+fn host_function_signatures_are_type_checked() {
+    let mut engine = Engine::new();
+    engine.register("double", |number: f64| number * 2.0);
+
+    let error = engine.eval("double(\"x\")").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Argument 1 must be a float, but got string"
+    );
+
+    let error = engine.eval("double(1, 2)").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Expected 1 arguments but got 2"
+    );
+
+    // The checker can't see through lists, so this fails when it runs.
+    let error = engine.eval("double([\"x\"][0])").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Argument 1 of double: expected number, got string"
+    );
+
+    engine
+        .eval("make doubled = double(2); doubled + 1")
+        .unwrap();
+    assert_eq!(
+        engine.eval("doubled + \"x\"").unwrap_err().kind,
+        ErrorKind::Type
+    );
+}
+
+#[test]
+fn host_functions_can_hold_state_and_fail() {
+    let mut engine = Engine::new();
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    engine.register("tick", move || {
+        counter.set(counter.get() + 1);
+        counter.get() as f64
+    });
+    engine.register("checked_sqrt", |number: f64| {
+        if number < 0.0 {
+            return Err(format!("cannot take the root of {}", number));
+        }
+        Ok(number.sqrt())
+    });
+    engine.register_variadic("count", |arguments| Ok(Value::Int(arguments.len() as i64)));
+
+    assert_eq!(engine.eval("tick(); tick()").unwrap(), Value::Float(2.0));
+    assert_eq!(calls.get(), 2);
+
+    assert_eq!(engine.eval("checked_sqrt(9)").unwrap(), Value::Float(3.0));
+    let error = engine.eval("checked_sqrt(-1)").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(error.diagnostics[0].message, "cannot take the root of -1");
+
+    assert_eq!(engine.eval("count(1, \"a\", nil)").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn output_can_be_captured() {
+    let mut engine = Engine::new();
+    engine.capture_output();
+
+    engine.run("print 1; println(\"a\", 2);").unwrap();
+    assert_eq!(engine.take_output(), "1\na2\n");
+    assert_eq!(engine.take_output(), "");
+}
+
+#[test]
+fn errors_name_the_phase_that_failed() {
+    let mut engine = Engine::new();
+
+    let kind = |engine: &mut Engine, source: &str| engine.run(source).unwrap_err().kind;
+    assert_eq!(kind(&mut engine, "print (;"), ErrorKind::Syntax);
+    assert_eq!(kind(&mut engine, "print missing;"), ErrorKind::Type);
+    assert_eq!(kind(&mut engine, "print [][0];"), ErrorKind::Runtime);
+    // Checked indexes fail the same way they would at runtime.
+    assert_eq!(
+        error_message(&mut engine, "print [1, 2][1.0];", ErrorKind::Type),
+        "Must use integer to index into list or string"
+    );
+
+    engine.capture_output();
+    engine.set_type_checking(false);
+    assert!(engine.run("print 1 == \"1\";").is_ok());
+    assert_eq!(engine.take_output(), "false\n");
+}
+
+#[test]
+fn runaway_recursion_is_caught_within_a_main_thread_stack() {
+    let run = || {
+        let mut engine = Engine::new();
+        engine.capture_output();
+        engine
+            .run("funk f(n) { return f(n + 1); } try { f(0); } catch (e) { print e.message; }")
+            .unwrap();
+        engine.take_output()
+    };
+
+    let output = thread::Builder::new()
+        .stack_size(8 * 1024 * 1024)
+        .spawn(run)
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(output, "Stack overflow\n");
+}
+
+#[test]
+fn malformed_number_literals_are_syntax_errors() {
+    let mut engine = Engine::new();
+
+    assert_eq!(
+        error_message(&mut engine, "0x", ErrorKind::Syntax),
+        "Expected hex digits after 0x"
+    );
+    assert_eq!(
+        error_message(&mut engine, "1_000_", ErrorKind::Syntax),
+        "'_' must be between digits"
+    );
+    assert_eq!(
+        error_message(&mut engine, "0b102", ErrorKind::Syntax),
+        "Invalid digit '2' in binary literal"
+    );
+    assert_eq!(
+        error_message(&mut engine, "2e", ErrorKind::Syntax),
+        "Expected digits in exponent"
+    );
+    assert_eq!(
+        error_message(&mut engine, "12abc", ErrorKind::Syntax),
+        "Invalid character 'a' in number literal"
+    );
+    assert_eq!(
+        error_message(&mut engine, "1e400", ErrorKind::Syntax),
+        "Float literal out of range"
+    );
+
+    assert_eq!(engine.eval("0xff + 0b1 + 1_0").unwrap(), Value::Int(266));
+    assert_eq!(engine.eval("1e-400").unwrap(), Value::Float(0.0));
+}
+
+#[test]
+fn decimals_and_bigints_are_exact() {
+    let mut engine = Engine::new();
+
+    let cents: Decimal = "0.30".parse().unwrap();
+    assert_eq!(
+        engine.eval("0.10d + 0.20d").unwrap(),
+        Value::Decimal(Box::new(cents))
+    );
+    assert_eq!(engine.eval("0.10d + 0.20d").unwrap().to_string(), "0.30");
+
+    let big = engine.eval("18446744073709551615 + 1").unwrap();
+    assert_eq!(big.type_name(), "bigint");
+    assert_eq!(big.to_string(), "18446744073709551616");
+
+    let error = engine.eval("1.5d + [2.5][0]").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(error.diagnostics[0].message, "Cannot mix decimal and float");
+}
+
+#[test]
+fn yield_is_only_allowed_in_generators() {
+    let mut engine = Engine::new();
+
+    let expected = "Can only yield inside a generator function, declared with 'funk*'";
+    assert_eq!(
+        error_message(&mut engine, "funk f() { yield 1; }", ErrorKind::Type),
+        expected
+    );
+    assert_eq!(
+        error_message(
+            &mut engine,
+            "funk* f() { make g = funk() { yield 1; }; }",
+            ErrorKind::Type
+        ),
+        expected
+    );
+
+    engine
+        .run("funk* g(n) { yield n; yield n + 1; } make total = 0; for n in g(1) { total += n; }")
+        .unwrap();
+    assert_eq!(engine.eval("total").unwrap(), Value::Int(3));
+}
+
+#[test]
+fn uncaught_throws_are_runtime_errors() {
+    let mut engine = Engine::new();
+
+    let error = engine
+        .run("throw error(\"parse\", \"unexpected end\");")
+        .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(error.diagnostics[0].message, "unexpected end");
+    assert_eq!(error.diagnostics[0].kind.as_deref(), Some("parse"));
+
+    let error = engine.run("throw 3;").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Can only throw an error or a string, not int"
+    );
+
+    engine
+        .run("make kind = nil; try { [][0]; } catch (e) { kind = e.kind; }")
+        .unwrap();
+    assert_eq!(
+        engine.eval("kind").unwrap(),
+        Value::String(Box::new("runtime".to_string()))
+    );
+}
+
+#[test]
+fn results_and_options_are_typed_by_their_contents() {
+    let mut engine = Engine::new();
+
+    assert_eq!(
+        error_message(&mut engine, "print unwrap(ok(1)) + \"a\";", ErrorKind::Type),
+        "Operands must be the same type"
+    );
+    assert_eq!(
+        error_message(&mut engine, "funk f() { return 1?; }", ErrorKind::Type),
+        "Can only use '?' on a result or an option, not int"
+    );
+    assert_eq!(
+        error_message(&mut engine, "print some(1)?;", ErrorKind::Type),
+        "Can only use '?' inside a function body"
+    );
+    assert_eq!(
+        error_message(&mut engine, "print ok(1) == some(1);", ErrorKind::Type),
+        "Operands must be of the same type"
+    );
+
+    engine
+        .run("funk f(x) { return some(x?); } make found = f(some(1)) == some(1);")
+        .unwrap();
+    assert_eq!(engine.eval("found").unwrap(), Value::Boolean(true));
+}
+
+#[test]
+fn files_round_trip_through_the_safe_builtins() {
+    let mut engine = Engine::new();
+    let path = std::env::temp_dir().join(format!("jive-results-{}.txt", std::process::id()));
+    engine.set_global("path", path.to_string_lossy().to_string());
+
+    engine
+        .run("make written = try_write_file(path, \"hello\"); make read = try_read_file(path);")
+        .unwrap();
+    assert_eq!(engine.eval("written").unwrap().to_string(), "ok(nil)");
+    assert_eq!(engine.eval("read").unwrap().to_string(), "ok(hello)");
+    assert_eq!(engine.eval("read_file(path)").unwrap().to_string(), "hello");
+
+    std::fs::remove_file(&path).unwrap();
+    assert!(engine.run("read_file(path);").is_err());
+}
+
+#[test]
+fn matches_warn_about_values_they_miss() {
+    let mut engine = Engine::new();
+    let warnings = |engine: &mut Engine, source: &str| {
+        engine.check(source).unwrap();
+        engine
+            .take_warnings()
+            .into_iter()
+            .map(|warning| warning.message)
+            .collect::<Vec<String>>()
+    };
+
+    assert_eq!(
+        warnings(&mut engine, "print match 1 > 2 { true => 1, };"),
+        ["Match does not cover false"]
+    );
+    assert_eq!(
+        warnings(&mut engine, "print match some(1) { some(1) => 1, };"),
+        ["Match does not cover some(_) or none"]
+    );
+    assert_eq!(
+        warnings(&mut engine, "print match \"a\" { \"a\" => 1, };"),
+        ["Match does not cover every string, add a '_' arm"]
+    );
+    assert!(warnings(
+        &mut engine,
+        "print match ok(1) { ok(_) => 1, err(_) => 2, };"
+    )
+    .is_empty());
+    assert!(warnings(&mut engine, "print match 1 { n if n > 0 => n, _ => 0, };").is_empty());
+    assert!(warnings(&mut engine, "funk f(x) { return match x { 1 => 2, }; }").is_empty());
+}
+
+#[test]
+fn unmatched_values_are_runtime_errors() {
+    let mut engine = Engine::new();
+
+    let error = engine
+        .run("print match [1, 2] { [] => 0, [x] => x, };")
+        .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "No match arm accepts the list [1, 2]"
+    );
+
+    let error = engine
+        .run("print match 1 { [] => 0, _ => 1, };")
+        .unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Pattern can never match a value of type int"
+    );
+}
+
+#[test]
+fn destructuring_checks_the_shape_of_values() {
+    let mut engine = Engine::new();
+
+    engine
+        .run("funk first([head, ..rest]) { return head; }")
+        .unwrap();
+    let error = engine.run("print first([]);").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Runtime);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Cannot destructure the list []"
+    );
+
+    let error = engine.run("make {x, y} = \"point\";").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(
+        error.diagnostics[0].message,
+        "Pattern can never match a value of type string"
+    );
+
+    let error = engine.run("funk f([a, b], {a}) { return a; }").unwrap_err();
+    assert_eq!(error.kind, ErrorKind::Type);
+    assert_eq!(error.diagnostics[0].message, "Duplicate parameter a");
+}
+
+#[test]
+fn calls_are_checked_against_the_parameters() {
+    let mut engine = Engine::new();
+    engine
+        .run("funk pad(text, width = 8) { return text; }")
+        .unwrap();
+
+    assert_eq!(
+        error_message(&mut engine, "pad(\"a\", 1, 2);", ErrorKind::Type),
+        "Expected 1 to 2 arguments but got 3"
+    );
+    assert_eq!(
+        error_message(&mut engine, "pad(\"a\", size: 1);", ErrorKind::Type),
+        "No parameter named size"
+    );
+    assert_eq!(
+        error_message(&mut engine, "pad(width: 1);", ErrorKind::Type),
+        "Missing argument text"
+    );
+    assert_eq!(
+        error_message(&mut engine, "pad(\"a\", text: \"b\");", ErrorKind::Type),
+        "Argument text was passed twice"
+    );
+
+    engine.run("make call = |f| f(1);").unwrap();
+    assert_eq!(
+        error_message(&mut engine, "call(|a, b| a);", ErrorKind::Runtime),
+        "Expected 2 arguments but got 1"
+    );
+
+    engine.run("make named = |f| f(text: \"a\");").unwrap();
+    assert_eq!(engine.eval("named(pad)").unwrap().to_string(), "a");
+    assert_eq!(
+        error_message(&mut engine, "named(|value| value);", ErrorKind::Runtime),
+        "No parameter named text"
+    );
+    assert_eq!(
+        error_message(&mut engine, "named(len);", ErrorKind::Runtime),
+        "Native functions do not take named arguments"
+    );
+
+    assert_eq!(
+        error_message(&mut engine, "pad(width: 1, \"a\");", ErrorKind::Syntax),
+        "Positional arguments must come before named ones"
+    );
+    assert_eq!(
+        error_message(&mut engine, "funk f(a = 1, b) {}", ErrorKind::Syntax),
+        "A parameter without a default cannot follow one with a default"
+    );
+    assert_eq!(
+        error_message(&mut engine, "funk f(...a, b) {}", ErrorKind::Syntax),
+        "A rest parameter must be the last parameter"
+    );
+}
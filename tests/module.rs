@@ -0,0 +1,131 @@
+//! Checks that corrupted `.jvc` modules are rejected when they are loaded,
+//! rather than crashing the VM that runs them.
+
+use std::{fs, path::Path, process::Command};
+
+use jive::{
+    compiler::{
+        chunk::{Instruction, Program},
+        module, Compiler,
+    },
+    visitors::resolver::Resolver,
+};
+
+fn compile(source: &str) -> Program {
+    let statements = jive::parse(source).unwrap();
+    Resolver::new().resolve(&statements).unwrap();
+    Compiler::new().compile(&statements).unwrap()
+}
+
+/// Loads `program` after replacing instruction `at` of its function
+/// `function` with `instruction`, returning the error it was rejected with.
+fn corrupted(program: &Program, function: usize, at: usize, instruction: Instruction) -> String {
+    let mut program = program.clone();
+    program.functions[function].chunk.code[at] = instruction;
+
+    match Program::from_bytes(&program.to_bytes()) {
+        Ok(_) => panic!("{:?} at {} was accepted", instruction, at),
+        Err(diagnostic) => diagnostic.message,
+    }
+}
+
+#[test]
+fn operands_the_frame_cannot_hold_are_rejected() {
+    // f's code is `GetLocal 1, Return, Nil, Return`.
+    let program = compile("funk f(a) { return a; } print f(1);");
+    assert!(Program::from_bytes(&program.to_bytes()).is_ok());
+
+    let cases = [
+        (Instruction::GetLocal(37634), "f: invalid slot 37634 at 0"),
+        (Instruction::SetLocal(2), "f: invalid slot 2 at 0"),
+        (Instruction::GetUpvalue(0), "f: invalid upvalue 0 at 0"),
+        (Instruction::Duplicate(3), "f: stack underflow at 0"),
+        (Instruction::Unwind(3), "f: invalid height 3 at 0"),
+        (Instruction::TryEnd, "f: no handler to remove at 0"),
+        (
+            Instruction::TryStart(3),
+            "f: return with a handler installed at 1",
+        ),
+    ];
+    for (instruction, expected) in cases {
+        let message = corrupted(&program, 1, 0, instruction);
+        assert!(
+            message.ends_with(expected),
+            "{:?}: {}",
+            instruction,
+            message
+        );
+    }
+
+    let message = corrupted(&program, 0, 3, Instruction::Pop);
+    assert!(
+        message.ends_with("script: stack underflow at 4"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn closures_must_capture_what_their_maker_has() {
+    let program =
+        compile("funk outer(a) { funk inner() { return a; } return inner; } print outer(1)();");
+    let inner = program
+        .functions
+        .iter()
+        .position(|function| function.name == "inner")
+        .unwrap();
+
+    let mut captures_missing = program.clone();
+    captures_missing.functions[inner].captures[0].index = 9;
+    let message = Program::from_bytes(&captures_missing.to_bytes())
+        .unwrap_err()
+        .message;
+    assert!(
+        message.ends_with("outer: invalid slot 9 at 1"),
+        "{}",
+        message
+    );
+
+    let mut captures_upvalue = program.clone();
+    captures_upvalue.functions[inner].captures[0].is_local = false;
+    let message = Program::from_bytes(&captures_upvalue.to_bytes())
+        .unwrap_err()
+        .message;
+    assert!(
+        message.ends_with("outer: invalid upvalue 1 at 1"),
+        "{}",
+        message
+    );
+}
+
+#[test]
+fn modules_from_other_versions_are_rejected() {
+    let mut bytes = compile("print [1, 2][0];").to_bytes();
+    bytes[4..6].copy_from_slice(&(module::VERSION - 1).to_le_bytes());
+
+    let message = Program::from_bytes(&bytes).unwrap_err().message;
+    assert_eq!(
+        message,
+        format!(
+            "Unsupported module version {} (expected {})",
+            module::VERSION - 1,
+            module::VERSION
+        )
+    );
+}
+
+#[test]
+fn running_a_corrupted_module_is_a_syntax_error() {
+    let mut program = compile("funk f(a) { return a; } print f(1);");
+    program.functions[1].chunk.code[0] = Instruction::GetLocal(37634);
+
+    let module = Path::new(env!("CARGO_TARGET_TMPDIR")).join("corrupted.jvc");
+    fs::write(&module, program.to_bytes()).expect("Could not write module");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_jive"))
+        .arg(&module)
+        .output()
+        .expect("Could not run jive");
+    assert_eq!(output.status.code(), Some(65));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error: Corrupt module"));
+}
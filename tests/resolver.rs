@@ -0,0 +1,72 @@
+//! Checks that variables are bound to the declarations they lexically refer to.
+
+use jive::{visitors::resolver::Resolver, Engine};
+
+fn resolve(source: &str) -> Result<(), (String, u32, u32)> {
+    let statements = jive::parse(source).unwrap();
+    Resolver::new()
+        .resolve(&statements)
+        .map_err(|diagnostic| (diagnostic.message, diagnostic.line, diagnostic.column))
+}
+
+#[test]
+fn locals_cannot_read_themselves_while_initialized() {
+    assert_eq!(
+        resolve("{ make a = 1; { make a = a; } }"),
+        Err((
+            "Cannot read local variable a in its own initializer".to_string(),
+            1,
+            26
+        ))
+    );
+    // Shadowing a global is no different.
+    assert_eq!(
+        resolve("make a = 1; { make a = a; }"),
+        Err((
+            "Cannot read local variable a in its own initializer".to_string(),
+            1,
+            24
+        ))
+    );
+    assert_eq!(resolve("make a = 1; { make b = a; }"), Ok(()));
+}
+
+#[test]
+fn undefined_and_duplicate_names_are_errors() {
+    assert_eq!(
+        resolve("funk f() { print g; }"),
+        Err(("Undefined variable g".to_string(), 1, 18))
+    );
+    assert_eq!(
+        resolve("funk f(a, a) { return a; }"),
+        Err(("Duplicate parameter a".to_string(), 1, 11))
+    );
+    // Functions may call ones declared further down the file.
+    assert_eq!(
+        resolve("funk f() { return g(); } funk g() { return 1; }"),
+        Ok(())
+    );
+}
+
+#[test]
+fn closures_capture_the_variable_in_scope_where_they_are_declared() {
+    let mut engine = Engine::new();
+    engine.capture_output();
+
+    engine
+        .run(
+            "make a = \"global\"; { funk show() { print a; } show(); make a = \"local\"; show(); }",
+        )
+        .unwrap();
+    assert_eq!(engine.take_output(), "global\nglobal\n");
+
+    engine
+        .run(
+            "funk counter() { make count = 0; funk next() { count = count + 1; return count; } return next; }
+             make first = counter(); make second = counter();
+             first(); first(); second();
+             print first(); print second();",
+        )
+        .unwrap();
+    assert_eq!(engine.take_output(), "3\n2\n");
+}
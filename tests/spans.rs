@@ -0,0 +1,96 @@
+//! Checks the lines, columns and byte spans the lexer and parser record.
+
+use jive::{
+    diagnostic::Span,
+    lexer::Lexer,
+    parser::{expression::Expression, statement::Statement},
+    token::TokenType,
+};
+
+fn tokens(source: &str) -> Vec<(TokenType, u32, u32, usize, usize)> {
+    Lexer::new(source.to_string())
+        .lex()
+        .unwrap()
+        .into_iter()
+        .map(|token| {
+            (
+                token.token_type,
+                token.line,
+                token.column,
+                token.start,
+                token.end,
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn tokens_start_on_line_one() {
+    assert_eq!(
+        tokens("make x = 10;"),
+        [
+            (TokenType::Make, 1, 1, 0, 4),
+            (TokenType::Identifier, 1, 6, 5, 6),
+            (TokenType::Equal, 1, 8, 7, 8),
+            (TokenType::Int, 1, 10, 9, 11),
+            (TokenType::Semicolon, 1, 12, 11, 12),
+            (TokenType::EOF, 1, 13, 12, 12),
+        ]
+    );
+}
+
+#[test]
+fn columns_restart_after_each_newline() {
+    assert_eq!(
+        tokens("print 1;\n  x >= \"é\";"),
+        [
+            (TokenType::Print, 1, 1, 0, 5),
+            (TokenType::Int, 1, 7, 6, 7),
+            (TokenType::Semicolon, 1, 8, 7, 8),
+            (TokenType::Identifier, 2, 3, 11, 12),
+            (TokenType::GreaterEqual, 2, 5, 13, 15),
+            (TokenType::String, 2, 8, 16, 20),
+            (TokenType::Semicolon, 2, 11, 20, 21),
+            (TokenType::EOF, 2, 12, 21, 21),
+        ]
+    );
+}
+
+#[test]
+fn multi_line_strings_keep_their_starting_position() {
+    let tokens = tokens("\"a\nb\" x");
+    assert_eq!(tokens[0], (TokenType::String, 1, 1, 0, 5));
+    assert_eq!(tokens[1], (TokenType::Identifier, 2, 4, 6, 7));
+}
+
+#[test]
+fn expressions_span_their_operands() {
+    let statements = jive::parse("print 1 +\n  foo(2);").unwrap();
+    let print = match &statements[0] {
+        Statement::PrintStatement(print) => print,
+        statement => panic!("Expected a print statement, got {:?}", statement),
+    };
+    assert_eq!(print.span, Span::new(0, 19, 1, 1));
+
+    let term = match &print.expression {
+        Expression::Term(term) => term,
+        expression => panic!("Expected a term, got {:?}", expression),
+    };
+    assert_eq!(term.span, Span::new(6, 18, 1, 7));
+    assert_eq!(term.left.span(), Span::new(6, 7, 1, 7));
+    assert_eq!(term.right.span(), Span::new(12, 18, 2, 3));
+}
+
+#[test]
+fn statements_span_from_keyword_to_terminator() {
+    let statements = jive::parse("make a = [1, 2];\n{\n  a;\n}").unwrap();
+
+    assert_eq!(statements[0].span(), Span::new(0, 16, 1, 1));
+    assert_eq!(statements[1].span(), Span::new(17, 25, 2, 1));
+
+    let block = match &statements[1] {
+        Statement::Block(block) => block,
+        statement => panic!("Expected a block, got {:?}", statement),
+    };
+    assert_eq!(block.statements[0].span(), Span::new(21, 23, 3, 3));
+}